@@ -1,14 +1,21 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
-use ic_canister::{generate_exports, generate_idl, state_getter, update, Canister, Idl, PreUpdate};
+use ic_canister::{
+    generate_exports, generate_idl, query, state_getter, update, Canister, Idl, PreUpdate,
+};
 use ic_exports::candid::Principal;
 #[cfg(feature = "debug-logs")]
 use ic_exports::ic_cdk;
 use ic_metrics::Interval;
 
+use crate::bid_history::{BidHistory, BidHistoryPage};
 use crate::error::{AuctionError, Result};
-use crate::state::{AuctionInfo, AuctionState, BiddingInfo};
+use crate::events::{AuctionEvent, AuctionEventLog};
+use crate::refunds::{spawn_refunds, CycleRefund};
+use crate::state::{
+    AuctionInfo, AuctionParams, AuctionParamsChange, AuctionParamsUpdate, AuctionState, BiddingInfo,
+};
 
 pub trait Auction: Canister + Sized {
     #[state_getter]
@@ -17,7 +24,7 @@ pub trait Auction: Canister + Sized {
     fn canister_pre_update(&self, method_name: &str, _method_type: ic_canister::MethodType) {
         if method_name == "run_auction" {
             #[cfg(feature = "debug-logs")]
-            if !self.auction_state().borrow().bidding_state.is_auction_due() {
+            if !self.auction_state().borrow().is_auction_due() {
                 ic_cdk::println!("Too early to begin auction");
             }
         } else if let Err(_auction_error) = self.run_auction() {
@@ -26,6 +33,12 @@ pub trait Auction: Canister + Sized {
         }
     }
 
+    /// Called after an auction round is completed successfully and recorded in the history.
+    ///
+    /// Can be used to disburse the round proceeds, e.g. with the `ProceedsDisburser` of the
+    /// `payments` feature.
+    fn on_auction_completed(&self, _info: &AuctionInfo) {}
+
     // TODO [CPROD-1056]: Remove default implementation as the
     // user may forget to overwrite it
     fn disburse_rewards(&self) -> Result<AuctionInfo> {
@@ -38,7 +51,12 @@ pub trait Auction: Canister + Sized {
     /// since the last auction is less than the set period, [AuctionError::TooEarly] will be returned.
     ///
     /// The auction will distribute the accumulated fees in proportion to the user cycle bids, and
-    /// then will update the fee ratio until the next auction.
+    /// then will update the fee ratio until the next auction. If the number of winners is limited
+    /// by the `max_winners` parameter, the outbid bids are excluded from the distribution and
+    /// refunded to the bidders.
+    ///
+    /// In the [Dutch auction mode](crate::state::AuctionMode::Dutch) the auction can be run as
+    /// soon as the lot is sold, and the only bid is the winner's one.
     #[update(trait = true)]
     fn run_auction(&self) -> Result<AuctionInfo> {
         let auction_state = self.auction_state();
@@ -47,7 +65,7 @@ pub trait Auction: Canister + Sized {
             return Err(AuctionError::NoBids);
         }
 
-        if !auction_state.borrow().bidding_state.is_auction_due() {
+        if !auction_state.borrow().is_auction_due() {
             return Err(AuctionError::TooEarlyToBeginAuction(
                 auction_state
                     .borrow()
@@ -56,14 +74,21 @@ pub trait Auction: Canister + Sized {
             ));
         }
 
+        let refunds = auction_state.borrow_mut().take_outbid_bids();
+        let winners = auction_state.borrow().bidding_state.bids.clone();
         let result = self.disburse_rewards();
 
         auction_state.borrow_mut().reset_bidding_state();
+        spawn_refunds(auction_state.clone(), refunds);
 
         if let Ok(result) = result.clone() {
-            auction_state.borrow_mut().history.push(result);
+            auction_state.borrow_mut().history.push(result.clone());
+            auction_state.borrow().emit_round_closed(&result, &winners);
+            self.on_auction_completed(&result);
         }
 
+        auction_state.borrow().emit_round_started();
+
         result
     }
 
@@ -135,6 +160,90 @@ pub trait Auction: Canister + Sized {
         Ok(())
     }
 
+    /// Schedules the change of the auction parameters. The changes take effect when the next
+    /// round starts, i.e. after the current auction is run.
+    ///
+    /// Only the owner is allowed to call this method.
+    #[update(trait = true)]
+    fn update_auction_params(&self, update: AuctionParamsUpdate) -> Result<()> {
+        self.auction_state()
+            .borrow_mut()
+            .authorize_owner()?
+            .update_params(update)
+    }
+
+    /// Returns the current auction parameters and the changes scheduled for the next round.
+    #[query(trait = true)]
+    fn get_auction_params(&self) -> AuctionParams {
+        self.auction_state().borrow().auction_params()
+    }
+
+    /// Returns the history of the auction parameters change requests, oldest first.
+    #[query(trait = true)]
+    fn get_auction_params_history(&self) -> Vec<AuctionParamsChange> {
+        self.auction_state().borrow().params_history().to_vec()
+    }
+
+    /// Restarts the refunds of the outbid bids that failed before. Returns the number of restarted
+    /// refunds.
+    ///
+    /// Only the owner is allowed to call this method.
+    #[update(trait = true)]
+    fn retry_failed_refunds(&self) -> Result<usize> {
+        let auction_state = self.auction_state();
+        let refunds = auction_state
+            .borrow_mut()
+            .authorize_owner()?
+            .take_failed_refunds();
+        let count = refunds.len();
+        spawn_refunds(auction_state, refunds);
+
+        Ok(count)
+    }
+
+    /// Returns the refunds of the outbid bids that failed and can be retried.
+    #[query(trait = true)]
+    fn get_failed_refunds(&self) -> Vec<CycleRefund> {
+        self.auction_state().borrow().failed_refunds().to_vec()
+    }
+
+    /// Returns at most `limit` auction events starting from the event with the `start` id, oldest
+    /// first.
+    ///
+    /// The events are recorded only if the log storage is initialized with
+    /// [`AuctionEventLog::init`].
+    #[query(trait = true)]
+    fn get_auction_events(&self, start: u64, limit: u64) -> Vec<AuctionEvent> {
+        AuctionEventLog.events(start, limit)
+    }
+
+    /// Sets the canister the auction events are sent to, or removes it if `None` is given. See
+    /// the [`events`](crate::events) module docs for the subscriber interface.
+    ///
+    /// Only the owner is allowed to call this method.
+    #[update(trait = true)]
+    fn set_event_subscriber(&self, subscriber: Option<Principal>) -> Result<()> {
+        self.auction_state()
+            .borrow_mut()
+            .authorize_owner()?
+            .set_event_subscriber(subscriber);
+        Ok(())
+    }
+
+    /// Returns at most `limit` bid records, newest first, skipping the first `offset` of them. If
+    /// `bidder` is given, only the bids of this principal are returned.
+    ///
+    /// The bids are recorded only if the history storage is initialized with [`BidHistory::init`].
+    #[query(trait = true)]
+    fn get_bid_history(
+        &self,
+        bidder: Option<Principal>,
+        offset: u64,
+        limit: u64,
+    ) -> BidHistoryPage {
+        BidHistory.page(bidder, offset, limit)
+    }
+
     // Important: This function *must* be defined to be the
     // last one in the trait because it depends on the order
     // of expansion of update/query(trait = true) methods.
@@ -0,0 +1,90 @@
+//! Persistent history of the cycle bids.
+//!
+//! To record the history, initialize the storage with [`BidHistory::init`] in both `init` and
+//! `post_upgrade` methods of the canister. The recorded bids can be queried with the
+//! [`Auction::get_bid_history`](crate::api::Auction::get_bid_history) method.
+
+use ic_exports::candid::{CandidType, Deserialize, Principal};
+use ic_stable_structures::stable_structures::DefaultMemoryImpl;
+use ic_stable_structures::{RecordLog, VirtualMemory};
+
+use crate::state::{Cycles, Timestamp};
+
+/// Maximum number of records returned by one [`BidHistory::page`] request.
+pub const MAX_BID_HISTORY_PAGE_SIZE: u64 = 100;
+
+thread_local! {
+    static BID_STORAGE: RecordLog<BidRecord> = const { RecordLog::new() };
+}
+
+/// Record of an accepted cycle bid.
+#[derive(CandidType, Debug, Clone, Deserialize, PartialEq, Eq)]
+pub struct BidRecord {
+    /// Principal the bid is made for.
+    pub bidder: Principal,
+
+    /// Amount of accepted cycles.
+    pub amount: Cycles,
+
+    /// Time when the bid was accepted.
+    pub timestamp: Timestamp,
+
+    /// Id of the auction the bid participates in.
+    pub auction_id: usize,
+}
+
+/// Page of the bid history.
+#[derive(CandidType, Debug, Clone, Deserialize, PartialEq, Eq)]
+pub struct BidHistoryPage {
+    /// Total number of records matching the request.
+    pub total: u64,
+
+    /// Records of the page, newest first.
+    pub records: Vec<BidRecord>,
+}
+
+/// History of the cycle bids stored in stable memory.
+///
+/// If the storage is not initialized with [`BidHistory::init`], no records are stored.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BidHistory;
+
+impl BidHistory {
+    /// Initializes the history storage in the given memories, loading the records already stored
+    /// there.
+    pub fn init(
+        index_memory: VirtualMemory<DefaultMemoryImpl>,
+        data_memory: VirtualMemory<DefaultMemoryImpl>,
+    ) -> ic_stable_structures::Result<Self> {
+        BID_STORAGE.with(|log| log.init(index_memory, data_memory))?;
+        Ok(Self)
+    }
+
+    /// Number of records in the history.
+    pub fn len(&self) -> u64 {
+        BID_STORAGE.with(|log| log.len())
+    }
+
+    /// Returns `true` if the history has no records.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns at most `limit` records, newest first, skipping the first `offset` of them.
+    ///
+    /// If `bidder` is given, only the bids of this principal are returned. Note that filtering
+    /// requires scanning the whole history.
+    ///
+    /// The `limit` is capped by [`MAX_BID_HISTORY_PAGE_SIZE`].
+    pub fn page(&self, bidder: Option<Principal>, offset: u64, limit: u64) -> BidHistoryPage {
+        let limit = limit.min(MAX_BID_HISTORY_PAGE_SIZE);
+        let filter = bidder.map(|bidder| move |record: &BidRecord| record.bidder == bidder);
+        let (total, records) = BID_STORAGE.with(|log| log.page(offset, limit, filter));
+
+        BidHistoryPage { total, records }
+    }
+
+    pub(crate) fn append(&self, record: BidRecord) {
+        BID_STORAGE.with(|log| log.append(record));
+    }
+}
@@ -17,6 +17,12 @@ pub enum AuctionError {
 
     #[error("the principal {0} is not an auction controller")]
     Unauthorized(String),
+
+    #[error("the lot of the current Dutch auction round is already sold")]
+    LotAlreadySold,
+
+    #[error("invalid auction parameters: {0}")]
+    InvalidParameters(String),
 }
 
 pub type Result<T> = std::result::Result<T, AuctionError>;
@@ -0,0 +1,118 @@
+//! Log of the auction events.
+//!
+//! To record the events, initialize the storage with [`AuctionEventLog::init`] in both `init` and
+//! `post_upgrade` methods of the canister. The recorded events can be queried with the
+//! [`Auction::get_auction_events`](crate::api::Auction::get_auction_events) method.
+//!
+//! If an event subscriber is set with
+//! [`Auction::set_event_subscriber`](crate::api::Auction::set_event_subscriber), every event is
+//! also sent to the `on_auction_event : (AuctionEvent) -> ()` method of the subscriber canister
+//! with a one-way call.
+
+use ic_canister::virtual_canister_notify;
+use ic_exports::candid::{CandidType, Deserialize, Principal};
+use ic_exports::ic_kit::ic;
+use ic_stable_structures::stable_structures::DefaultMemoryImpl;
+use ic_stable_structures::{RecordLog, VirtualMemory};
+
+use crate::state::{AuctionInfo, Cycles, Timestamp};
+
+/// Maximum number of events returned by one [`AuctionEventLog::events`] request.
+pub const MAX_EVENTS_PAGE_SIZE: u64 = 100;
+
+/// Method of the subscriber canister the events are sent to.
+pub const SUBSCRIBER_METHOD: &str = "on_auction_event";
+
+thread_local! {
+    static EVENT_STORAGE: RecordLog<AuctionEvent> = const { RecordLog::new() };
+}
+
+/// Kind of an auction event.
+#[derive(CandidType, Debug, Clone, Deserialize, PartialEq)]
+pub enum AuctionEventKind {
+    /// New bidding round is started.
+    RoundStarted { fee_ratio: f64 },
+
+    /// Cycles bid is accepted.
+    BidAccepted { bidder: Principal, amount: Cycles },
+
+    /// Auction of the round is held.
+    RoundClosed { info: AuctionInfo },
+
+    /// The bidder took part in the held auction with the given amount of cycles.
+    Winner { bidder: Principal, cycles: Cycles },
+}
+
+/// Auction event.
+#[derive(CandidType, Debug, Clone, Deserialize, PartialEq)]
+pub struct AuctionEvent {
+    /// Index of the event in the log. If the log is not initialized, the index is always 0.
+    pub id: u64,
+
+    /// Time of the event.
+    pub timestamp: Timestamp,
+
+    /// Id of the auction the event relates to.
+    pub auction_id: usize,
+
+    /// Event details.
+    pub kind: AuctionEventKind,
+}
+
+/// Log of the auction events stored in stable memory.
+///
+/// If the storage is not initialized with [`AuctionEventLog::init`], no events are stored, but
+/// they are still sent to the subscriber.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AuctionEventLog;
+
+impl AuctionEventLog {
+    /// Initializes the log storage in the given memories, loading the events already stored
+    /// there.
+    pub fn init(
+        index_memory: VirtualMemory<DefaultMemoryImpl>,
+        data_memory: VirtualMemory<DefaultMemoryImpl>,
+    ) -> ic_stable_structures::Result<Self> {
+        EVENT_STORAGE.with(|log| log.init(index_memory, data_memory))?;
+        Ok(Self)
+    }
+
+    /// Number of events in the log.
+    pub fn len(&self) -> u64 {
+        EVENT_STORAGE.with(|log| log.len())
+    }
+
+    /// Returns `true` if the log has no events.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns at most `limit` events starting from the event with the `start` id, oldest first.
+    ///
+    /// The `limit` is capped by [`MAX_EVENTS_PAGE_SIZE`].
+    pub fn events(&self, start: u64, limit: u64) -> Vec<AuctionEvent> {
+        let limit = limit.min(MAX_EVENTS_PAGE_SIZE);
+        EVENT_STORAGE.with(|log| log.range(start, limit))
+    }
+
+    /// Records the event and sends it to the `subscriber`, if given.
+    pub(crate) fn emit(
+        &self,
+        auction_id: usize,
+        kind: AuctionEventKind,
+        subscriber: Option<Principal>,
+    ) {
+        let event = AuctionEvent {
+            id: self.len(),
+            timestamp: ic::time(),
+            auction_id,
+            kind,
+        };
+        EVENT_STORAGE.with(|log| log.append(event.clone()));
+
+        if let Some(subscriber) = subscriber {
+            // Notifications are best effort: the subscriber can catch up with the log.
+            let _ = virtual_canister_notify!(subscriber, SUBSCRIBER_METHOD, (event,), ());
+        }
+    }
+}
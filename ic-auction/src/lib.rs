@@ -1,3 +1,8 @@
 pub mod api;
+pub mod bid_history;
 pub mod error;
+pub mod events;
+#[cfg(feature = "payments")]
+pub mod proceeds;
+pub mod refunds;
 pub mod state;
@@ -0,0 +1,103 @@
+//! Disbursement of the auction proceeds through the [`TokenTerminal`] of `ic-payments`.
+//!
+//! The [`ProceedsDisburser`] transfers the proceeds of an auction round from the canister main
+//! account to the beneficiary account. Transfers with unknown result are saved to the recovery
+//! list of the terminal, and can be recovered automatically with
+//! [`start_recovery_timer`](ic_payments::start_recovery_timer).
+//!
+//! The disbursement is not started by the auction itself: the amount of the proceeds depends on
+//! the canister token economics, so the canister computes it and starts the disbursement from
+//! [`Auction::on_auction_completed`](crate::api::Auction::on_auction_completed).
+//!
+//! ```ignore
+//! impl Auction for MyCanister {
+//!     fn on_auction_completed(&self, info: &AuctionInfo) {
+//!         // The proceeds computation is specific to the canister.
+//!         let proceeds = self.owner_fees_since(info.first_transaction_id);
+//!         DISBURSER.with(|disburser| disburser.spawn(info, proceeds));
+//!     }
+//!     ...
+//! }
+//! ```
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use ic_exports::candid::Nat;
+use ic_exports::ic_cdk;
+use ic_exports::ic_kit::ic;
+use ic_exports::icrc_types::icrc1::account::Account;
+use ic_payments::recovery_list::RecoveryList;
+use ic_payments::{Balances, Operation, PaymentError, TokenTerminal, Transfer};
+use log::error;
+
+use crate::state::AuctionInfo;
+
+/// Number of retries of a disbursement transfer before it's moved to the recovery list.
+const DISBURSEMENT_RETRIES: usize = 3;
+
+/// Transfers the auction round proceeds to the beneficiary account.
+pub struct ProceedsDisburser<B: Balances, R: RecoveryList> {
+    terminal: Rc<RefCell<TokenTerminal<B, R>>>,
+    beneficiary: Account,
+}
+
+impl<B: Balances + 'static, R: RecoveryList + 'static> ProceedsDisburser<B, R> {
+    /// Creates a disburser sending the proceeds to the `beneficiary` through the `terminal`.
+    pub fn new(terminal: Rc<RefCell<TokenTerminal<B, R>>>, beneficiary: Account) -> Self {
+        Self {
+            terminal,
+            beneficiary,
+        }
+    }
+
+    /// Account the proceeds are sent to.
+    pub fn beneficiary(&self) -> Account {
+        self.beneficiary
+    }
+
+    /// Transfers `amount` tokens of the auction round proceeds to the beneficiary and returns the
+    /// token transaction id. The transfer fee is paid by the canister on top of the amount. The
+    /// auction id is used as the transfer memo.
+    ///
+    /// The terminal is not borrowed during the ledger calls, so it can be used by other messages
+    /// while the disbursement is in progress.
+    pub async fn disburse(&self, info: &AuctionInfo, amount: Nat) -> Result<Nat, PaymentError> {
+        let transfer = {
+            let terminal = self.terminal.borrow();
+            let transfer = Transfer::new(
+                terminal.token_config(),
+                ic::id(),
+                self.beneficiary,
+                None,
+                amount,
+            )
+            .with_operation(Operation::None)
+            .with_memo((info.auction_id as u64).into());
+            let amount = transfer.amount.clone() + transfer.effective_fee();
+            Transfer { amount, ..transfer }
+        };
+
+        TokenTerminal::<B, R>::transfer_shared(&self.terminal, transfer, DISBURSEMENT_RETRIES).await
+    }
+
+    /// Starts the disbursement of the auction round proceeds in the background.
+    ///
+    /// Errors are only logged, as the transfers that can be retried are handled by the terminal
+    /// recovery.
+    pub fn spawn(&self, info: &AuctionInfo, amount: Nat) {
+        let disburser = Self {
+            terminal: self.terminal.clone(),
+            beneficiary: self.beneficiary,
+        };
+        let info = info.clone();
+        ic_cdk::spawn(async move {
+            if let Err(error) = disburser.disburse(&info, amount).await {
+                error!(
+                    "failed to disburse proceeds of auction {}: {error}",
+                    info.auction_id
+                );
+            }
+        });
+    }
+}
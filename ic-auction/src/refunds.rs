@@ -0,0 +1,66 @@
+//! Refunds of the cycles bid by the outbid participants.
+//!
+//! If the number of auction winners is limited with the `max_winners` auction parameter, the bids
+//! that don't make it into the winners are refunded at the end of the round with the
+//! `deposit_cycles` management canister method. Failed refunds are stored in the
+//! [`AuctionState`] and can be retried with the
+//! [`Auction::retry_failed_refunds`](crate::api::Auction::retry_failed_refunds) method.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use ic_exports::candid::{CandidType, Deserialize, Principal};
+use ic_exports::ic_kit::ic;
+use ic_exports::ic_kit::interfaces::management::{DepositCycles, WithCanisterId};
+use ic_exports::ic_kit::interfaces::Method;
+
+use crate::state::{AuctionState, Cycles};
+
+/// Refund of an outbid cycles bid.
+#[derive(CandidType, Debug, Clone, Deserialize, PartialEq, Eq)]
+pub struct CycleRefund {
+    /// Principal the cycles are returned to.
+    pub bidder: Principal,
+
+    /// Amount of refunded cycles.
+    pub amount: Cycles,
+
+    /// Id of the auction the bid was made for.
+    pub auction_id: usize,
+
+    /// Error of the last refund attempt, if any.
+    pub last_error: Option<String>,
+}
+
+/// Starts the given refunds in the background. Failed refunds are added to the failed refunds
+/// list of the `state`.
+pub fn spawn_refunds(state: Rc<RefCell<AuctionState>>, refunds: Vec<CycleRefund>) {
+    if refunds.is_empty() {
+        return;
+    }
+
+    ic::spawn(async move {
+        for refund in refunds {
+            if let Err(refund) = refund_cycles(refund).await {
+                state.borrow_mut().add_failed_refund(refund);
+            }
+        }
+    });
+}
+
+async fn refund_cycles(refund: CycleRefund) -> Result<(), CycleRefund> {
+    let args = WithCanisterId {
+        canister_id: refund.bidder,
+    };
+    let result = DepositCycles::perform_with_payment(
+        Principal::management_canister(),
+        (args,),
+        refund.amount,
+    )
+    .await;
+
+    result.map_err(|(code, message)| CycleRefund {
+        last_error: Some(format!("{code:?}: {message}")),
+        ..refund
+    })
+}
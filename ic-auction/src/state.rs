@@ -6,7 +6,10 @@ use ic_helpers::tokens::Tokens128;
 use ic_metrics::Interval;
 use ic_storage::IcStorage;
 
+use crate::bid_history::{BidHistory, BidRecord};
 use crate::error::{AuctionError, Result};
+use crate::events::{AuctionEventKind, AuctionEventLog};
+use crate::refunds::CycleRefund;
 
 // Minimum bidding amount is required, for every update call costs cycles, and we want bidding
 // to add cycles rather then to decrease them. 1M is chosen as one ingress call costs 590K cycles.
@@ -48,6 +51,165 @@ pub struct BiddingInfo {
 
     /// The amount of cycles the caller bid for the upcoming auction.
     pub caller_cycles: Cycles,
+
+    /// Current price of the lot in the [Dutch auction mode](AuctionMode::Dutch), or `None` in the
+    /// standard mode or if the lot is already sold.
+    pub current_price: Option<Cycles>,
+}
+
+/// Strategy of the auction rounds.
+#[derive(CandidType, Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+pub enum AuctionMode {
+    /// All bids made during the auction period share the rewards in proportion to the bid cycles.
+    Standard,
+
+    /// The price of the round rewards in cycles declines over time until the first bidder accepts
+    /// it. The winner gets all the rewards, and the auction can be run right after the lot is
+    /// sold.
+    Dutch(DutchAuctionConfig),
+}
+
+/// Configuration of the Dutch auction mode.
+#[derive(CandidType, Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+pub struct DutchAuctionConfig {
+    /// Price of the lot at the start of the round.
+    pub start_price: Cycles,
+
+    /// Minimum price of the lot, reached after the `decline_period`.
+    pub floor_price: Cycles,
+
+    /// Time in nanoseconds the price linearly declines from the start price to the floor price.
+    pub decline_period: Timestamp,
+}
+
+impl DutchAuctionConfig {
+    /// Price of the lot after `elapsed` nanoseconds since the round start.
+    pub fn price_at(&self, elapsed: Timestamp) -> Cycles {
+        if elapsed >= self.decline_period || self.start_price <= self.floor_price {
+            return self.floor_price;
+        }
+
+        let decline = (self.start_price - self.floor_price) as u128 * elapsed as u128
+            / self.decline_period as u128;
+        self.start_price - decline as Cycles
+    }
+
+    /// Checks the configuration. The floor price must not be below the `min_bid`, otherwise the
+    /// lot could not be bought once the price declines below it.
+    fn validate(&self, min_bid: Cycles) -> Result<()> {
+        if self.decline_period == 0 {
+            return Err(AuctionError::InvalidParameters(
+                "price decline period cannot be zero".into(),
+            ));
+        }
+
+        if self.floor_price > self.start_price {
+            return Err(AuctionError::InvalidParameters(
+                "floor price cannot be greater than the start price".into(),
+            ));
+        }
+
+        if self.floor_price < min_bid {
+            return Err(AuctionError::InvalidParameters(format!(
+                "floor price cannot be less than the minimum bid {min_bid}"
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// How the fee ratio of the next round is set.
+#[derive(CandidType, Debug, Clone, Copy, Deserialize, PartialEq)]
+pub enum FeeRatioPolicy {
+    /// The ratio is calculated from the canister cycles balance and the `min_cycles` value.
+    Automatic,
+
+    /// The ratio is set to the given value between 0.0 and 1.0.
+    Fixed(f64),
+}
+
+/// Change of the auction parameters requested by the controller. Parameters set to `None` are
+/// left unchanged.
+///
+/// The changes take effect when the next round starts, i.e. after the current auction is run.
+#[derive(CandidType, Debug, Clone, Default, Deserialize, PartialEq)]
+pub struct AuctionParamsUpdate {
+    /// Period between the auctions, in nanoseconds.
+    pub auction_period: Option<Timestamp>,
+
+    /// Minimum amount of cycles in one bid.
+    pub min_bid: Option<Cycles>,
+
+    /// Fee ratio policy.
+    pub fee_ratio: Option<FeeRatioPolicy>,
+
+    /// Auction mode.
+    pub mode: Option<AuctionMode>,
+
+    /// Maximum number of winners of a round in the standard mode. The bids of the outbid
+    /// participants are refunded at the end of the round. The value of 0 removes the limit.
+    pub max_winners: Option<u64>,
+}
+
+impl AuctionParamsUpdate {
+    /// Checks the update. The `min_bid` and `mode` values are used for the parameters which are
+    /// not changed by the update.
+    fn validate(&self, min_bid: Cycles, mode: AuctionMode) -> Result<()> {
+        if self.auction_period == Some(0) {
+            return Err(AuctionError::InvalidParameters(
+                "auction period cannot be zero".into(),
+            ));
+        }
+
+        if let AuctionMode::Dutch(config) = self.mode.unwrap_or(mode) {
+            config.validate(self.min_bid.unwrap_or(min_bid))?;
+        }
+
+        if let Some(FeeRatioPolicy::Fixed(ratio)) = self.fee_ratio {
+            if !(0.0..=1.0).contains(&ratio) {
+                return Err(AuctionError::InvalidParameters(format!(
+                    "fee ratio {ratio} is not between 0.0 and 1.0"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn merge(self, newer: AuctionParamsUpdate) -> Self {
+        Self {
+            auction_period: newer.auction_period.or(self.auction_period),
+            min_bid: newer.min_bid.or(self.min_bid),
+            fee_ratio: newer.fee_ratio.or(self.fee_ratio),
+            mode: newer.mode.or(self.mode),
+            max_winners: newer.max_winners.or(self.max_winners),
+        }
+    }
+}
+
+/// Record of an auction parameters change request.
+#[derive(CandidType, Debug, Clone, Deserialize, PartialEq)]
+pub struct AuctionParamsChange {
+    /// Controller that requested the change.
+    pub changed_by: Principal,
+
+    /// Time of the request.
+    pub timestamp: Timestamp,
+
+    /// Requested change.
+    pub update: AuctionParamsUpdate,
+}
+
+/// Current auction parameters and the changes scheduled for the next round.
+#[derive(CandidType, Debug, Clone, Deserialize, PartialEq)]
+pub struct AuctionParams {
+    pub auction_period: Timestamp,
+    pub min_bid: Cycles,
+    pub fee_ratio: FeeRatioPolicy,
+    pub mode: AuctionMode,
+    pub max_winners: Option<u64>,
+    pub pending: Option<AuctionParamsUpdate>,
 }
 
 //------------------------------------------------------------------------------
@@ -99,6 +261,15 @@ pub struct AuctionState {
     pub history: Vec<AuctionInfo>,
     pub controller: Principal,
     min_cycles: Cycles,
+    // The fields below are optional, so the state saved before they were added can be restored.
+    min_bid: Option<Cycles>,
+    fee_ratio_policy: Option<FeeRatioPolicy>,
+    pending_params: Option<AuctionParamsUpdate>,
+    params_history: Option<Vec<AuctionParamsChange>>,
+    mode: Option<AuctionMode>,
+    max_winners: Option<u64>,
+    failed_refunds: Option<Vec<CycleRefund>>,
+    event_subscriber: Option<Principal>,
 }
 
 impl Default for AuctionState {
@@ -108,6 +279,14 @@ impl Default for AuctionState {
             bidding_state: BiddingState::default(),
             history: Vec::new(),
             min_cycles: MIN_BIDDING_AMOUNT,
+            min_bid: None,
+            fee_ratio_policy: None,
+            pending_params: None,
+            params_history: None,
+            mode: None,
+            max_winners: None,
+            failed_refunds: None,
+            event_subscriber: None,
         }
     }
 }
@@ -120,8 +299,46 @@ impl AuctionState {
                 auction_period: auction_period.nanos(),
                 ..Default::default()
             },
-            history: Vec::new(),
-            min_cycles: MIN_BIDDING_AMOUNT,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the auction mode. Use [`AuctionParamsUpdate::mode`] to change the mode of a running
+    /// auction.
+    pub fn with_mode(self, mode: AuctionMode) -> Result<Self> {
+        if let AuctionMode::Dutch(config) = &mode {
+            config.validate(self.min_bid())?;
+        }
+
+        Ok(Self {
+            mode: Some(mode),
+            ..self
+        })
+    }
+
+    /// Current auction mode.
+    pub fn mode(&self) -> AuctionMode {
+        self.mode.unwrap_or(AuctionMode::Standard)
+    }
+
+    /// Returns `true` if the auction can be run. In the Dutch mode the auction is due as soon as
+    /// the lot is sold.
+    pub fn is_auction_due(&self) -> bool {
+        match self.mode() {
+            AuctionMode::Standard => self.bidding_state.is_auction_due(),
+            AuctionMode::Dutch(_) => !self.bidding_state.bids.is_empty(),
+        }
+    }
+
+    /// Current price of the lot in the Dutch auction mode. Returns `None` in the standard mode or
+    /// if the lot is already sold.
+    pub fn current_price(&self) -> Option<Cycles> {
+        match self.mode() {
+            AuctionMode::Dutch(config) if self.bidding_state.bids.is_empty() => {
+                let elapsed = ic::time().saturating_sub(self.bidding_state.last_auction);
+                Some(config.price_at(elapsed))
+            }
+            _ => None,
         }
     }
 
@@ -137,6 +354,7 @@ impl AuctionState {
     }
 
     pub fn reset_bidding_state(&mut self) {
+        self.apply_pending_params();
         self.bidding_state = BiddingState {
             fee_ratio: self.get_fee_ratio(),
             auction_period: self.bidding_state.auction_period,
@@ -145,7 +363,37 @@ impl AuctionState {
         };
     }
 
+    fn apply_pending_params(&mut self) {
+        let Some(update) = self.pending_params.take() else {
+            return;
+        };
+
+        if let Some(auction_period) = update.auction_period {
+            self.bidding_state.auction_period = auction_period;
+        }
+
+        if let Some(min_bid) = update.min_bid {
+            self.min_bid = Some(min_bid);
+        }
+
+        if let Some(fee_ratio) = update.fee_ratio {
+            self.fee_ratio_policy = Some(fee_ratio);
+        }
+
+        if let Some(mode) = update.mode {
+            self.mode = Some(mode);
+        }
+
+        if let Some(max_winners) = update.max_winners {
+            self.max_winners = Some(max_winners).filter(|&v| v > 0);
+        }
+    }
+
     fn get_fee_ratio(&self) -> f64 {
+        if let Some(FeeRatioPolicy::Fixed(ratio)) = self.fee_ratio_policy {
+            return ratio;
+        }
+
         let min_cycles = self.min_cycles as f64;
         let current_cycles = ic::balance128() as f64;
         if min_cycles == 0.0 {
@@ -162,8 +410,20 @@ impl AuctionState {
     }
 
     pub fn bid_cycles(&mut self, bidder: Principal) -> Result<Cycles> {
-        let amount = ic::msg_cycles_available();
-        if amount < MIN_BIDDING_AMOUNT {
+        let amount = match self.mode() {
+            AuctionMode::Standard => ic::msg_cycles_available(),
+            AuctionMode::Dutch(_) => {
+                let price = self.current_price().ok_or(AuctionError::LotAlreadySold)?;
+                if ic::msg_cycles_available() < price {
+                    return Err(AuctionError::BiddingTooSmall);
+                }
+
+                // Cycles above the price are refunded to the bidder.
+                price
+            }
+        };
+
+        if amount < self.min_bid() {
             return Err(AuctionError::BiddingTooSmall);
         }
 
@@ -171,6 +431,20 @@ impl AuctionState {
         self.bidding_state.cycles_since_auction += amount_accepted;
         *self.bidding_state.bids.entry(bidder).or_insert(0) += amount_accepted;
 
+        BidHistory.append(BidRecord {
+            bidder,
+            amount: amount_accepted,
+            timestamp: ic::time(),
+            auction_id: self.history.len(),
+        });
+        self.emit(
+            self.history.len(),
+            AuctionEventKind::BidAccepted {
+                bidder,
+                amount: amount_accepted,
+            },
+        );
+
         Ok(amount_accepted)
     }
 
@@ -186,6 +460,7 @@ impl AuctionState {
                 .get(&ic::caller())
                 .cloned()
                 .unwrap_or(0),
+            current_price: self.current_price(),
         }
     }
 
@@ -199,6 +474,121 @@ impl AuctionState {
     pub fn min_cycles(&self) -> Cycles {
         self.min_cycles
     }
+
+    /// Minimum amount of cycles in one bid.
+    pub fn min_bid(&self) -> Cycles {
+        self.min_bid.unwrap_or(MIN_BIDDING_AMOUNT)
+    }
+
+    pub fn auction_params(&self) -> AuctionParams {
+        AuctionParams {
+            auction_period: self.bidding_state.auction_period,
+            min_bid: self.min_bid(),
+            fee_ratio: self.fee_ratio_policy.unwrap_or(FeeRatioPolicy::Automatic),
+            mode: self.mode(),
+            max_winners: self.max_winners,
+            pending: self.pending_params.clone(),
+        }
+    }
+
+    /// Removes the bids that don't make it into the `max_winners` largest bids of the round, and
+    /// returns the refunds of the removed bids. Bids of the same amount are ordered by principal.
+    pub fn take_outbid_bids(&mut self) -> Vec<CycleRefund> {
+        let Some(max_winners) = self.max_winners else {
+            return vec![];
+        };
+
+        if self.mode() != AuctionMode::Standard
+            || self.bidding_state.bids.len() as u64 <= max_winners
+        {
+            return vec![];
+        }
+
+        let mut bids: Vec<_> = self
+            .bidding_state
+            .bids
+            .iter()
+            .map(|(bidder, amount)| (*bidder, *amount))
+            .collect();
+        bids.sort_by(|(a_bidder, a_amount), (b_bidder, b_amount)| {
+            b_amount.cmp(a_amount).then(a_bidder.cmp(b_bidder))
+        });
+
+        let auction_id = self.history.len();
+        bids.into_iter()
+            .skip(max_winners as usize)
+            .map(|(bidder, amount)| {
+                self.bidding_state.bids.remove(&bidder);
+                self.bidding_state.cycles_since_auction -= amount;
+                CycleRefund {
+                    bidder,
+                    amount,
+                    auction_id,
+                    last_error: None,
+                }
+            })
+            .collect()
+    }
+
+    /// Canister the auction events are sent to.
+    pub fn event_subscriber(&self) -> Option<Principal> {
+        self.event_subscriber
+    }
+
+    /// Emits the events of the held auction: the round closing and the participation of each of
+    /// the `winners`.
+    pub fn emit_round_closed(&self, info: &AuctionInfo, winners: &HashMap<Principal, Cycles>) {
+        self.emit(
+            info.auction_id,
+            AuctionEventKind::RoundClosed { info: info.clone() },
+        );
+
+        let mut winners: Vec<_> = winners.iter().collect();
+        winners.sort();
+        for (bidder, cycles) in winners {
+            self.emit(
+                info.auction_id,
+                AuctionEventKind::Winner {
+                    bidder: *bidder,
+                    cycles: *cycles,
+                },
+            );
+        }
+    }
+
+    /// Emits the event of a new bidding round start.
+    pub fn emit_round_started(&self) {
+        self.emit(
+            self.history.len(),
+            AuctionEventKind::RoundStarted {
+                fee_ratio: self.bidding_state.fee_ratio,
+            },
+        );
+    }
+
+    fn emit(&self, auction_id: usize, kind: AuctionEventKind) {
+        AuctionEventLog.emit(auction_id, kind, self.event_subscriber);
+    }
+
+    /// Refunds that failed and can be retried.
+    pub fn failed_refunds(&self) -> &[CycleRefund] {
+        self.failed_refunds.as_deref().unwrap_or_default()
+    }
+
+    pub fn add_failed_refund(&mut self, refund: CycleRefund) {
+        self.failed_refunds
+            .get_or_insert_with(Vec::new)
+            .push(refund);
+    }
+
+    pub fn take_failed_refunds(&mut self) -> Vec<CycleRefund> {
+        self.failed_refunds.take().unwrap_or_default()
+    }
+
+    /// History of the auction parameters change requests, oldest first.
+    pub fn params_history(&self) -> &[AuctionParamsChange] {
+        self.params_history.as_deref().unwrap_or_default()
+    }
 }
 
 /// A wrapper that helps us separate owner/caller methods with a
@@ -223,4 +613,300 @@ impl Authorized<Controller<'_>> {
     pub fn set_controller(&mut self, controller: Principal) {
         self.auth.state.controller = controller;
     }
+
+    pub fn set_event_subscriber(&mut self, subscriber: Option<Principal>) {
+        self.auth.state.event_subscriber = subscriber;
+    }
+
+    /// Schedules the change of the auction parameters for the next round. Changes requested
+    /// before the next round starts are merged, with the later values taking precedence.
+    pub fn update_params(&mut self, update: AuctionParamsUpdate) -> Result<()> {
+        let state = &mut *self.auth.state;
+        let pending = state
+            .pending_params
+            .clone()
+            .unwrap_or_default()
+            .merge(update.clone());
+        pending.validate(state.min_bid(), state.mode())?;
+
+        state
+            .params_history
+            .get_or_insert_with(Vec::new)
+            .push(AuctionParamsChange {
+                changed_by: ic::caller(),
+                timestamp: ic::time(),
+                update: update.clone(),
+            });
+        state.pending_params = Some(pending);
+
+        Ok(())
+    }
+
+    /// Takes the failed refunds to retry them.
+    pub fn take_failed_refunds(&mut self) -> Vec<CycleRefund> {
+        self.auth.state.take_failed_refunds()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ic_exports::ic_kit::mock_principals::{alice, bob, john, xtc};
+    use ic_exports::ic_kit::MockContext;
+
+    use super::*;
+
+    fn dutch(start_price: Cycles, floor_price: Cycles) -> DutchAuctionConfig {
+        DutchAuctionConfig {
+            start_price,
+            floor_price,
+            decline_period: 1_000,
+        }
+    }
+
+    fn owner_state() -> AuctionState {
+        MockContext::new().with_caller(alice()).inject();
+        AuctionState::new(Interval::PerDay, alice())
+    }
+
+    fn is_invalid<T>(result: Result<T>) -> bool {
+        matches!(result, Err(AuctionError::InvalidParameters(_)))
+    }
+
+    #[test]
+    fn params_update_is_validated() {
+        let update = AuctionParamsUpdate {
+            auction_period: Some(0),
+            ..Default::default()
+        };
+        assert!(is_invalid(
+            update.validate(MIN_BIDDING_AMOUNT, AuctionMode::Standard)
+        ));
+
+        let update = AuctionParamsUpdate {
+            fee_ratio: Some(FeeRatioPolicy::Fixed(1.5)),
+            ..Default::default()
+        };
+        assert!(is_invalid(
+            update.validate(MIN_BIDDING_AMOUNT, AuctionMode::Standard)
+        ));
+
+        let update = AuctionParamsUpdate {
+            min_bid: Some(3_000_000),
+            fee_ratio: Some(FeeRatioPolicy::Fixed(0.5)),
+            ..Default::default()
+        };
+        assert!(update
+            .validate(MIN_BIDDING_AMOUNT, AuctionMode::Standard)
+            .is_ok());
+        assert!(is_invalid(update.validate(
+            MIN_BIDDING_AMOUNT,
+            AuctionMode::Dutch(dutch(5_000_000, 2_000_000))
+        )));
+    }
+
+    #[test]
+    fn price_declines_linearly_to_floor() {
+        let config = dutch(5_000_000, 2_000_000);
+        assert_eq!(config.price_at(0), 5_000_000);
+        assert_eq!(config.price_at(500), 3_500_000);
+        assert_eq!(config.price_at(1_000), 2_000_000);
+        assert_eq!(config.price_at(u64::MAX), 2_000_000);
+
+        assert_eq!(dutch(2_000_000, 2_000_000).price_at(0), 2_000_000);
+    }
+
+    #[test]
+    fn dutch_config_is_validated() {
+        assert!(dutch(5_000_000, 2_000_000).validate(1_000_000).is_ok());
+        assert!(dutch(5_000_000, 2_000_000).validate(2_000_000).is_ok());
+        assert!(is_invalid(dutch(5_000_000, 2_000_000).validate(3_000_000)));
+        assert!(is_invalid(dutch(1_000_000, 2_000_000).validate(0)));
+
+        let config = DutchAuctionConfig {
+            decline_period: 0,
+            ..dutch(5_000_000, 2_000_000)
+        };
+        assert!(is_invalid(config.validate(0)));
+    }
+
+    #[test]
+    fn floor_below_min_bid_is_rejected() {
+        let state = owner_state();
+        assert!(is_invalid(state.with_mode(AuctionMode::Dutch(dutch(
+            5_000_000,
+            MIN_BIDDING_AMOUNT - 1
+        )))));
+
+        let mut state = owner_state()
+            .with_mode(AuctionMode::Dutch(dutch(5_000_000, 2_000_000)))
+            .unwrap();
+        let mut owner = state.authorize_owner().unwrap();
+        let result = owner.update_params(AuctionParamsUpdate {
+            min_bid: Some(3_000_000),
+            ..Default::default()
+        });
+        assert!(is_invalid(result));
+
+        let result = owner.update_params(AuctionParamsUpdate {
+            mode: Some(AuctionMode::Dutch(dutch(5_000_000, 500_000))),
+            ..Default::default()
+        });
+        assert!(is_invalid(result));
+        assert_eq!(state.auction_params().pending, None);
+
+        state
+            .authorize_owner()
+            .unwrap()
+            .update_params(AuctionParamsUpdate {
+                min_bid: Some(3_000_000),
+                mode: Some(AuctionMode::Standard),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(state.params_history().len(), 1);
+    }
+
+    #[test]
+    fn dutch_lot_is_sold_at_floor_price() {
+        let context = MockContext::new()
+            .with_caller(alice())
+            .with_msg_cycles(3_000_000)
+            .inject();
+        let mut state = AuctionState::new(Interval::PerDay, alice())
+            .with_mode(AuctionMode::Dutch(dutch(5_000_000, 2_000_000)))
+            .unwrap();
+        context.add_time(2_000);
+
+        assert_eq!(state.current_price(), Some(2_000_000));
+        assert_eq!(state.bid_cycles(bob()), Ok(2_000_000));
+        assert_eq!(state.bid_cycles(john()), Err(AuctionError::LotAlreadySold));
+    }
+
+    #[test]
+    fn invalid_params_update_is_not_scheduled() {
+        let mut state = owner_state();
+        let result = state
+            .authorize_owner()
+            .unwrap()
+            .update_params(AuctionParamsUpdate {
+                auction_period: Some(0),
+                ..Default::default()
+            });
+
+        assert!(is_invalid(result));
+        assert_eq!(state.auction_params().pending, None);
+        assert!(state.params_history().is_empty());
+    }
+
+    #[test]
+    fn params_updates_are_merged() {
+        let older = AuctionParamsUpdate {
+            auction_period: Some(10),
+            min_bid: Some(2_000_000),
+            max_winners: Some(3),
+            ..Default::default()
+        };
+        let newer = AuctionParamsUpdate {
+            min_bid: Some(5_000_000),
+            fee_ratio: Some(FeeRatioPolicy::Automatic),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            older.merge(newer),
+            AuctionParamsUpdate {
+                auction_period: Some(10),
+                min_bid: Some(5_000_000),
+                fee_ratio: Some(FeeRatioPolicy::Automatic),
+                mode: None,
+                max_winners: Some(3),
+            }
+        );
+    }
+
+    #[test]
+    fn pending_params_are_applied() {
+        let mut state = owner_state();
+        state.max_winners = Some(3);
+        state.pending_params = Some(AuctionParamsUpdate {
+            auction_period: Some(10),
+            min_bid: Some(2_000_000),
+            fee_ratio: Some(FeeRatioPolicy::Fixed(0.5)),
+            mode: None,
+            max_winners: Some(0),
+        });
+
+        state.apply_pending_params();
+
+        assert_eq!(
+            state.auction_params(),
+            AuctionParams {
+                auction_period: 10,
+                min_bid: 2_000_000,
+                fee_ratio: FeeRatioPolicy::Fixed(0.5),
+                mode: AuctionMode::Standard,
+                max_winners: None,
+                pending: None,
+            }
+        );
+
+        let params = state.auction_params();
+        state.apply_pending_params();
+        assert_eq!(state.auction_params(), params);
+    }
+
+    #[test]
+    fn outbid_bids_are_taken() {
+        let mut state = owner_state();
+        for (bidder, amount) in [(alice(), 300), (bob(), 100), (john(), 300), (xtc(), 200)] {
+            state.bidding_state.bids.insert(bidder, amount);
+            state.bidding_state.cycles_since_auction += amount;
+        }
+        assert!(state.take_outbid_bids().is_empty());
+
+        state.max_winners = Some(2);
+        let mut refunds: Vec<_> = state
+            .take_outbid_bids()
+            .into_iter()
+            .map(|refund| (refund.bidder, refund.amount))
+            .collect();
+        refunds.sort();
+
+        let mut expected = vec![(bob(), 100), (xtc(), 200)];
+        expected.sort();
+        assert_eq!(refunds, expected);
+        assert_eq!(state.bidding_state.bids.len(), 2);
+        assert_eq!(state.bidding_state.cycles_since_auction, 600);
+    }
+
+    #[test]
+    fn equal_outbid_bids_are_ordered_by_principal() {
+        let mut state = owner_state();
+        state.max_winners = Some(1);
+        for bidder in [alice(), bob()] {
+            state.bidding_state.bids.insert(bidder, 100);
+        }
+        state.bidding_state.cycles_since_auction = 200;
+
+        let refunds = state.take_outbid_bids();
+        assert_eq!(refunds.len(), 1);
+        assert_eq!(refunds[0].bidder, alice().max(bob()));
+    }
+
+    #[test]
+    fn retrying_refunds_requires_owner() {
+        let mut state = owner_state();
+        MockContext::new().with_caller(bob()).inject();
+        assert!(matches!(
+            state.authorize_owner(),
+            Err(AuctionError::Unauthorized(_))
+        ));
+
+        MockContext::new().with_caller(alice()).inject();
+        assert!(state
+            .authorize_owner()
+            .unwrap()
+            .take_failed_refunds()
+            .is_empty());
+    }
 }
@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
 use candid::utils::ArgumentEncoder;
@@ -36,6 +37,22 @@ impl PocketIcClient {
         }
     }
 
+    /// Creates clients calling the canisters of the environment as `caller`, keyed by the
+    /// canister names.
+    pub fn from_environment(
+        environment: &PocketIcEnvironment,
+        caller: Principal,
+    ) -> BTreeMap<String, Self> {
+        environment
+            .canisters
+            .iter()
+            .map(|(name, canister)| {
+                let client = Self::from_client(environment.client.clone(), *canister, caller);
+                (name.clone(), client)
+            })
+            .collect()
+    }
+
     /// Returns the PocketIC client for the canister.
     pub fn client(&self) -> &PocketIc {
         self.client
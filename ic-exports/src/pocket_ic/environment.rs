@@ -0,0 +1,141 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use candid::Principal;
+use log::*;
+use pocket_ic::nonblocking::PocketIc;
+
+use super::init_pocket_ic;
+
+/// Cycles added to every deployed canister by default.
+pub const DEFAULT_CANISTER_CYCLES: u128 = 10_u128.pow(14);
+
+/// A canister deployed by the [`PocketIcEnvironmentBuilder`].
+#[derive(Debug, Clone)]
+pub struct CanisterDeployment {
+    /// Name used to look up the canister in the environment.
+    pub name: String,
+    /// Wasm module of the canister, optionally gzipped.
+    pub wasm: Vec<u8>,
+    /// Candid encoded init arguments.
+    pub init_args: Vec<u8>,
+    /// Cycles added to the canister before the installation.
+    pub cycles: u128,
+}
+
+impl CanisterDeployment {
+    /// Creates a new deployment of the `wasm` module installed with the candid encoded
+    /// `init_args`.
+    pub fn new(name: impl Into<String>, wasm: Vec<u8>, init_args: Vec<u8>) -> Self {
+        Self {
+            name: name.into(),
+            wasm,
+            init_args,
+            cycles: DEFAULT_CANISTER_CYCLES,
+        }
+    }
+
+    /// Sets the cycles added to the canister before the installation.
+    pub fn with_cycles(mut self, cycles: u128) -> Self {
+        self.cycles = cycles;
+        self
+    }
+}
+
+/// Builder of a PocketIC instance with the NNS, II and application subnets and a list of deployed
+/// canisters.
+///
+/// ```ignore
+/// let env = PocketIcEnvironmentBuilder::new()
+///     .with_controller(alice())
+///     .with_canister(CanisterDeployment::new("token", token_wasm, Encode!(&token_init)?))
+///     .with_canister(CanisterDeployment::new("auction", auction_wasm, Encode!(&())?))
+///     .build()
+///     .await;
+///
+/// let token = env.canister("token");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PocketIcEnvironmentBuilder {
+    controller: Option<Principal>,
+    canisters: Vec<CanisterDeployment>,
+}
+
+impl PocketIcEnvironmentBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the principal creating and controlling the canisters. Default is the anonymous
+    /// principal.
+    pub fn with_controller(mut self, controller: Principal) -> Self {
+        self.controller = Some(controller);
+        self
+    }
+
+    /// Adds a canister to be deployed. The canisters are deployed in the order they are added.
+    pub fn with_canister(mut self, canister: CanisterDeployment) -> Self {
+        self.canisters.push(canister);
+        self
+    }
+
+    /// Starts the PocketIC instance and deploys the canisters.
+    ///
+    /// Panics if the names of the canisters are not unique.
+    pub async fn build(self) -> PocketIcEnvironment {
+        let client = init_pocket_ic().await.build_async().await;
+
+        let mut canisters = BTreeMap::new();
+        for deployment in self.canisters {
+            let canister = client
+                .create_canister_with_settings(self.controller, None)
+                .await;
+            client.add_cycles(canister, deployment.cycles).await;
+            client
+                .install_canister(
+                    canister,
+                    deployment.wasm,
+                    deployment.init_args,
+                    self.controller,
+                )
+                .await;
+            info!("canister {} deployed at {canister}", deployment.name);
+
+            if canisters
+                .insert(deployment.name.clone(), canister)
+                .is_some()
+            {
+                panic!("canister name {} is used more than once", deployment.name);
+            }
+        }
+
+        PocketIcEnvironment {
+            client: Arc::new(client),
+            controller: self.controller.unwrap_or_else(Principal::anonymous),
+            canisters,
+        }
+    }
+}
+
+/// A PocketIC instance with the canisters deployed by the [`PocketIcEnvironmentBuilder`].
+#[derive(Clone)]
+pub struct PocketIcEnvironment {
+    /// The PocketIC client.
+    pub client: Arc<PocketIc>,
+    /// The controller of the deployed canisters.
+    pub controller: Principal,
+    /// The deployed canisters keyed by name.
+    pub canisters: BTreeMap<String, Principal>,
+}
+
+impl PocketIcEnvironment {
+    /// Returns the principal of the canister deployed with the given name.
+    ///
+    /// Panics if there is no canister with this name.
+    pub fn canister(&self, name: &str) -> Principal {
+        *self
+            .canisters
+            .get(name)
+            .unwrap_or_else(|| panic!("canister {name} is not deployed"))
+    }
+}
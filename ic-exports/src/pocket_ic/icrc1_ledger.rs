@@ -0,0 +1,245 @@
+use std::env;
+use std::sync::Arc;
+
+use candid::utils::ArgumentEncoder;
+use candid::{CandidType, Nat, Principal};
+use pocket_ic::nonblocking::PocketIc;
+use pocket_ic::WasmResult;
+use serde::de::DeserializeOwned;
+
+use super::{CanisterDeployment, PocketIcEnvironment, PocketIcEnvironmentBuilder};
+use crate::icrc_types::icrc1::account::Account;
+use crate::icrc_types::icrc1::transfer::{TransferArg, TransferError};
+use crate::icrc_types::icrc1_ledger::{ArchiveOptions, FeatureFlags, InitArgs, LedgerArgument};
+use crate::icrc_types::icrc2::allowance::{Allowance, AllowanceArgs};
+use crate::icrc_types::icrc2::approve::{ApproveArgs, ApproveError};
+use crate::icrc_types::icrc2::transfer_from::{TransferFromArgs, TransferFromError};
+
+/// Environment variable with the path of the reference ICRC-1 ledger wasm module.
+pub const ICRC1_LEDGER_WASM: &str = "ICRC1_LEDGER_WASM";
+
+/// Default name of the ledger canister in the environment.
+pub const ICRC1_LEDGER_NAME: &str = "icrc1_ledger";
+
+/// Deployment of the reference ICRC-1 ledger with the ICRC-2 extension enabled.
+///
+/// By default the ledger wasm module is read from the file set in the `ICRC1_LEDGER_WASM`
+/// environment variable. The module can be downloaded from the dfinity IC releases
+/// (`ic-icrc1-ledger.wasm.gz`).
+///
+/// ```ignore
+/// let (env, ledger) = Icrc1LedgerFixture::new(minting_account)
+///     .with_transfer_fee(10_000)
+///     .with_initial_balance(alice().into(), 1_000_000)
+///     .deploy()
+///     .await;
+///
+/// assert_eq!(ledger.balance_of(alice().into()).await, 1_000_000);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Icrc1LedgerFixture {
+    wasm: Option<Vec<u8>>,
+    minting_account: Account,
+    transfer_fee: Nat,
+    initial_balances: Vec<(Account, Nat)>,
+    token_name: String,
+    token_symbol: String,
+    decimals: Option<u8>,
+}
+
+impl Icrc1LedgerFixture {
+    /// Creates a ledger with the given minting account, no fee and no initial balances.
+    pub fn new(minting_account: Account) -> Self {
+        Self {
+            wasm: None,
+            minting_account,
+            transfer_fee: Nat::from(0u64),
+            initial_balances: vec![],
+            token_name: "Test Token".to_string(),
+            token_symbol: "TST".to_string(),
+            decimals: None,
+        }
+    }
+
+    /// Sets the ledger wasm module, optionally gzipped.
+    pub fn with_wasm(mut self, wasm: Vec<u8>) -> Self {
+        self.wasm = Some(wasm);
+        self
+    }
+
+    /// Sets the transfer fee of the ledger.
+    pub fn with_transfer_fee(mut self, transfer_fee: impl Into<Nat>) -> Self {
+        self.transfer_fee = transfer_fee.into();
+        self
+    }
+
+    /// Adds the initial balance of the `account`.
+    pub fn with_initial_balance(mut self, account: Account, amount: impl Into<Nat>) -> Self {
+        self.initial_balances.push((account, amount.into()));
+        self
+    }
+
+    /// Sets the name, the symbol and the decimals of the token.
+    pub fn with_token(mut self, name: &str, symbol: &str, decimals: u8) -> Self {
+        self.token_name = name.to_string();
+        self.token_symbol = symbol.to_string();
+        self.decimals = Some(decimals);
+        self
+    }
+
+    /// Returns the init arguments of the ledger.
+    pub fn init_args(&self) -> LedgerArgument {
+        LedgerArgument::Init(InitArgs {
+            minting_account: self.minting_account,
+            fee_collector_account: None,
+            initial_balances: self.initial_balances.clone(),
+            transfer_fee: self.transfer_fee.clone(),
+            decimals: self.decimals,
+            token_name: self.token_name.clone(),
+            token_symbol: self.token_symbol.clone(),
+            metadata: vec![],
+            archive_options: ArchiveOptions {
+                trigger_threshold: 2000,
+                num_blocks_to_archive: 1000,
+                node_max_memory_size_bytes: None,
+                max_message_size_bytes: None,
+                controller_id: self.minting_account.owner,
+                cycles_for_archive_creation: None,
+                max_transactions_per_response: None,
+            },
+            max_memo_length: None,
+            feature_flags: Some(FeatureFlags { icrc2: true }),
+            maximum_number_of_accounts: None,
+            accounts_overflow_trim_quantity: None,
+        })
+    }
+
+    /// Returns the deployment of the ledger with the given canister name, to be added to a
+    /// [`PocketIcEnvironmentBuilder`].
+    ///
+    /// Panics if the wasm module is not set and cannot be read from the `ICRC1_LEDGER_WASM` file.
+    pub fn deployment(self, name: impl Into<String>) -> CanisterDeployment {
+        let init_args =
+            candid::encode_args((self.init_args(),)).expect("ledger init args should be encoded");
+        let wasm = self.wasm.unwrap_or_else(load_icrc1_ledger_wasm);
+
+        CanisterDeployment::new(name, wasm, init_args)
+    }
+
+    /// Deploys the ledger in a new environment. Returns the environment and the client of the
+    /// ledger calling it as the environment controller.
+    pub async fn deploy(self) -> (PocketIcEnvironment, Icrc1LedgerClient) {
+        let env = PocketIcEnvironmentBuilder::new()
+            .with_canister(self.deployment(ICRC1_LEDGER_NAME))
+            .build()
+            .await;
+        let client = Icrc1LedgerClient::from_environment(&env, ICRC1_LEDGER_NAME, env.controller);
+
+        (env, client)
+    }
+}
+
+fn load_icrc1_ledger_wasm() -> Vec<u8> {
+    let path = env::var(ICRC1_LEDGER_WASM)
+        .unwrap_or_else(|_| panic!("{ICRC1_LEDGER_WASM} environment variable should be set"));
+    std::fs::read(&path).unwrap_or_else(|e| panic!("ledger wasm {path} should be readable: {e}"))
+}
+
+/// Client of an ICRC-1 ledger deployed in PocketIC.
+///
+/// The methods panic if the call is rejected, and return the result of the ledger otherwise.
+#[derive(Clone)]
+pub struct Icrc1LedgerClient {
+    client: Arc<PocketIc>,
+    /// Principal of the ledger canister.
+    pub ledger: Principal,
+    /// Principal the calls are made with.
+    pub caller: Principal,
+}
+
+impl Icrc1LedgerClient {
+    pub fn new(client: Arc<PocketIc>, ledger: Principal, caller: Principal) -> Self {
+        Self {
+            client,
+            ledger,
+            caller,
+        }
+    }
+
+    /// Creates a client of the ledger deployed with the given name in the environment.
+    pub fn from_environment(env: &PocketIcEnvironment, name: &str, caller: Principal) -> Self {
+        Self::new(env.client.clone(), env.canister(name), caller)
+    }
+
+    /// Returns a client of the same ledger calling it as `caller`.
+    pub fn with_caller(&self, caller: Principal) -> Self {
+        Self::new(self.client.clone(), self.ledger, caller)
+    }
+
+    pub async fn balance_of(&self, account: Account) -> Nat {
+        self.query("icrc1_balance_of", (account,)).await
+    }
+
+    pub async fn fee(&self) -> Nat {
+        self.query("icrc1_fee", ()).await
+    }
+
+    pub async fn total_supply(&self) -> Nat {
+        self.query("icrc1_total_supply", ()).await
+    }
+
+    pub async fn transfer(&self, args: TransferArg) -> Result<Nat, TransferError> {
+        self.update("icrc1_transfer", (args,)).await
+    }
+
+    pub async fn approve(&self, args: ApproveArgs) -> Result<Nat, ApproveError> {
+        self.update("icrc2_approve", (args,)).await
+    }
+
+    pub async fn allowance(&self, args: AllowanceArgs) -> Allowance {
+        self.query("icrc2_allowance", (args,)).await
+    }
+
+    pub async fn transfer_from(&self, args: TransferFromArgs) -> Result<Nat, TransferFromError> {
+        self.update("icrc2_transfer_from", (args,)).await
+    }
+
+    async fn query<T, R>(&self, method: &str, args: T) -> R
+    where
+        T: ArgumentEncoder,
+        R: DeserializeOwned + CandidType,
+    {
+        let args = candid::encode_args(args).expect("ledger call args should be encoded");
+        let result = self
+            .client
+            .query_call(self.ledger, self.caller, method, args)
+            .await;
+        decode_reply(method, result)
+    }
+
+    async fn update<T, R>(&self, method: &str, args: T) -> R
+    where
+        T: ArgumentEncoder,
+        R: DeserializeOwned + CandidType,
+    {
+        let args = candid::encode_args(args).expect("ledger call args should be encoded");
+        let result = self
+            .client
+            .update_call(self.ledger, self.caller, method, args)
+            .await;
+        decode_reply(method, result)
+    }
+}
+
+fn decode_reply<R, E>(method: &str, result: Result<WasmResult, E>) -> R
+where
+    R: DeserializeOwned + CandidType,
+    E: std::fmt::Debug,
+{
+    match result {
+        Ok(WasmResult::Reply(reply)) => candid::decode_one(&reply)
+            .unwrap_or_else(|e| panic!("reply of ledger {method} should be decoded: {e}")),
+        Ok(WasmResult::Reject(e)) => panic!("ledger {method} call rejected: {e}"),
+        Err(e) => panic!("ledger {method} call failed: {e:?}"),
+    }
+}
@@ -9,6 +9,26 @@ pub use pocket_ic::nonblocking::*;
 pub use pocket_ic::{common, CallError, ErrorCode, PocketIcBuilder, UserError, WasmResult};
 use tokio::sync::OnceCell;
 
+mod environment;
+#[cfg(feature = "icrc")]
+mod icrc1_ledger;
+mod snapshot;
+mod time;
+mod upgrade;
+mod wasm;
+
+pub use environment::{CanisterDeployment, PocketIcEnvironment, PocketIcEnvironmentBuilder};
+#[cfg(feature = "icrc")]
+pub use icrc1_ledger::{
+    Icrc1LedgerClient, Icrc1LedgerFixture, ICRC1_LEDGER_NAME, ICRC1_LEDGER_WASM,
+};
+pub use snapshot::{StateChange, StateSnapshot};
+pub use time::{
+    advance_time_and_tick, assert_timer_executed, assert_timer_not_executed, TIMER_ROUNDS,
+};
+pub use upgrade::UpgradeTest;
+pub use wasm::WorkspaceWasm;
+
 const POCKET_IC_SERVER_VERSION: &str = "7.0.0";
 const POCKET_IC_BIN: &str = "POCKET_IC_BIN";
 
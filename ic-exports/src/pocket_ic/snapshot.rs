@@ -0,0 +1,192 @@
+use std::fmt::Debug;
+
+use candid::{CandidType, Principal};
+use pocket_ic::nonblocking::PocketIc;
+use pocket_ic::WasmResult;
+use serde::de::DeserializeOwned;
+
+/// Snapshot of the state of a canister, captured to check the changes made by an operation.
+///
+/// ```ignore
+/// let before = StateSnapshot::query::<DebugState>(&client, canister, "debug_state").await;
+/// transfer(&client, alice(), bob(), 100).await;
+/// let after = StateSnapshot::query::<DebugState>(&client, canister, "debug_state").await;
+///
+/// assert_eq!(before.diff(&after).len(), 4, "{:#?}", before.diff(&after));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateSnapshot {
+    /// Lines of the pretty printed state returned by a query.
+    Query(Vec<String>),
+    /// Content of the canister stable memory.
+    StableMemory(Vec<u8>),
+}
+
+/// A difference between two [`StateSnapshot`]s.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateChange {
+    /// A line of the state present only before the operation.
+    Removed(String),
+    /// A line of the state present only after the operation.
+    Added(String),
+    /// A range of the stable memory changed by the operation.
+    StableMemory { offset: usize, len: usize },
+}
+
+impl StateSnapshot {
+    /// Captures the state returned by the `method` query of the canister, called with no
+    /// arguments. The state is decoded as `R` and pretty printed with its `Debug` implementation.
+    ///
+    /// Panics if the query fails.
+    pub async fn query<R>(client: &PocketIc, canister: Principal, method: &str) -> Self
+    where
+        R: DeserializeOwned + CandidType + Debug,
+    {
+        let args = candid::encode_args(()).expect("empty args should be encoded");
+        let reply = match client
+            .query_call(canister, Principal::anonymous(), method, args)
+            .await
+        {
+            Ok(WasmResult::Reply(reply)) => reply,
+            Ok(WasmResult::Reject(e)) => panic!("state query {method} rejected: {e}"),
+            Err(e) => panic!("state query {method} failed: {e:?}"),
+        };
+        let state: R = candid::decode_one(&reply)
+            .unwrap_or_else(|e| panic!("state returned by {method} should be decoded: {e}"));
+
+        Self::Query(format!("{state:#?}").lines().map(str::to_string).collect())
+    }
+
+    /// Captures the stable memory of the canister.
+    pub async fn stable_memory(client: &PocketIc, canister: Principal) -> Self {
+        Self::StableMemory(client.get_stable_memory(canister).await)
+    }
+
+    /// Returns the changes from this snapshot to the `after` snapshot.
+    ///
+    /// Panics if the snapshots are captured from different sources.
+    pub fn diff(&self, after: &Self) -> Vec<StateChange> {
+        match (self, after) {
+            (Self::Query(before), Self::Query(after)) => diff_lines(before, after),
+            (Self::StableMemory(before), Self::StableMemory(after)) => diff_bytes(before, after),
+            _ => panic!("snapshots captured from different sources cannot be compared"),
+        }
+    }
+
+    /// Panics with the list of changes if the `after` snapshot differs from this one.
+    pub fn assert_unchanged(&self, after: &Self) {
+        let changes = self.diff(after);
+        if !changes.is_empty() {
+            panic!("state has changed: {changes:#?}");
+        }
+    }
+}
+
+/// Returns the lines removed from `before` and added in `after`, using their longest common
+/// subsequence.
+fn diff_lines(before: &[String], after: &[String]) -> Vec<StateChange> {
+    // common[i][j] is the length of the longest common subsequence of before[i..] and after[j..]
+    let mut common = vec![vec![0usize; after.len() + 1]; before.len() + 1];
+    for i in (0..before.len()).rev() {
+        for j in (0..after.len()).rev() {
+            common[i][j] = if before[i] == after[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut changes = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < before.len() || j < after.len() {
+        if i < before.len() && j < after.len() && before[i] == after[j] {
+            i += 1;
+            j += 1;
+        } else if j == after.len() || (i < before.len() && common[i + 1][j] >= common[i][j + 1]) {
+            changes.push(StateChange::Removed(before[i].clone()));
+            i += 1;
+        } else {
+            changes.push(StateChange::Added(after[j].clone()));
+            j += 1;
+        }
+    }
+
+    changes
+}
+
+/// Returns the ranges of bytes that differ between `before` and `after`. The bytes beyond the end
+/// of the shorter memory are compared to zero.
+fn diff_bytes(before: &[u8], after: &[u8]) -> Vec<StateChange> {
+    let byte = |memory: &[u8], offset: usize| memory.get(offset).copied().unwrap_or_default();
+
+    let mut changes = vec![];
+    let mut changed_from = None;
+    for offset in 0..before.len().max(after.len()) {
+        match (byte(before, offset) != byte(after, offset), changed_from) {
+            (true, None) => changed_from = Some(offset),
+            (false, Some(start)) => {
+                changes.push(StateChange::StableMemory {
+                    offset: start,
+                    len: offset - start,
+                });
+                changed_from = None;
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(start) = changed_from {
+        changes.push(StateChange::StableMemory {
+            offset: start,
+            len: before.len().max(after.len()) - start,
+        });
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn lines(lines: &[&str]) -> StateSnapshot {
+        StateSnapshot::Query(lines.iter().map(|line| line.to_string()).collect())
+    }
+
+    #[test]
+    fn should_diff_query_snapshots() {
+        let before = lines(&["a", "b", "c", "d"]);
+        let after = lines(&["a", "c", "e", "d", "f"]);
+
+        assert_eq!(
+            before.diff(&after),
+            vec![
+                StateChange::Removed("b".to_string()),
+                StateChange::Added("e".to_string()),
+                StateChange::Added("f".to_string()),
+            ]
+        );
+        before.assert_unchanged(&before.clone());
+    }
+
+    #[test]
+    fn should_diff_stable_memory_snapshots() {
+        let before = StateSnapshot::StableMemory(vec![1, 2, 3, 4, 5]);
+        let after = StateSnapshot::StableMemory(vec![1, 0, 0, 4, 5, 0, 7]);
+
+        assert_eq!(
+            before.diff(&after),
+            vec![
+                StateChange::StableMemory { offset: 1, len: 2 },
+                StateChange::StableMemory { offset: 6, len: 1 },
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "state has changed")]
+    fn should_panic_if_state_changed() {
+        lines(&["a"]).assert_unchanged(&lines(&["b"]));
+    }
+}
@@ -0,0 +1,59 @@
+use std::future::Future;
+use std::time::Duration;
+
+use pocket_ic::nonblocking::PocketIc;
+
+/// Number of rounds executed after advancing the time. A timer is executed in the round after its
+/// deadline, and the calls it makes need some more rounds to complete.
+pub const TIMER_ROUNDS: usize = 10;
+
+/// Advances the PocketIC time by `duration` and executes [`TIMER_ROUNDS`] rounds, so the timers
+/// with a deadline in the elapsed period are executed.
+pub async fn advance_time_and_tick(client: &PocketIc, duration: Duration) {
+    client.advance_time(duration).await;
+    for _ in 0..TIMER_ROUNDS {
+        client.tick().await;
+    }
+}
+
+/// Advances the PocketIC time by `duration` and executes rounds until `executed` returns true.
+///
+/// Panics if `executed` still returns false after [`TIMER_ROUNDS`] rounds.
+///
+/// ```ignore
+/// assert_timer_executed(&client, Duration::from_secs(60), || async {
+///     get_counter(&client, canister).await == 1
+/// })
+/// .await;
+/// ```
+pub async fn assert_timer_executed<F, Fut>(client: &PocketIc, duration: Duration, executed: F)
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = bool>,
+{
+    client.advance_time(duration).await;
+    for _ in 0..TIMER_ROUNDS {
+        client.tick().await;
+        if executed().await {
+            return;
+        }
+    }
+
+    panic!("timer was not executed in {TIMER_ROUNDS} rounds after advancing time by {duration:?}");
+}
+
+/// Advances the PocketIC time by `duration`, executes [`TIMER_ROUNDS`] rounds and checks that
+/// `executed` returns false.
+///
+/// Panics if `executed` returns true.
+pub async fn assert_timer_not_executed<F, Fut>(client: &PocketIc, duration: Duration, executed: F)
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = bool>,
+{
+    advance_time_and_tick(client, duration).await;
+
+    if executed().await {
+        panic!("timer was executed after advancing time by {duration:?}");
+    }
+}
@@ -0,0 +1,85 @@
+use std::future::Future;
+
+use log::*;
+
+use super::{PocketIcEnvironment, PocketIcEnvironmentBuilder};
+
+/// Harness testing the upgrade of a canister from an old to a new wasm module.
+///
+/// The environment is deployed with the old wasm of the canister, the state is populated through
+/// its API, then the canister is upgraded to the new wasm and the state is checked by the
+/// assertions.
+///
+/// ```ignore
+/// let environment = PocketIcEnvironmentBuilder::new()
+///     .with_canister(CanisterDeployment::new("token", old_wasm, Encode!(&init)?));
+///
+/// UpgradeTest::new(environment, "token", new_wasm)
+///     .run(
+///         |env| async move { mint(&env, alice(), 100).await },
+///         |env| async move { assert_eq!(balance_of(&env, alice()).await, 100) },
+///     )
+///     .await;
+/// ```
+#[derive(Debug, Clone)]
+pub struct UpgradeTest {
+    environment: PocketIcEnvironmentBuilder,
+    canister: String,
+    new_wasm: Vec<u8>,
+    upgrade_args: Vec<u8>,
+}
+
+impl UpgradeTest {
+    /// Creates a test upgrading the `canister` deployed by the `environment` to the `new_wasm`
+    /// module.
+    pub fn new(
+        environment: PocketIcEnvironmentBuilder,
+        canister: impl Into<String>,
+        new_wasm: Vec<u8>,
+    ) -> Self {
+        Self {
+            environment,
+            canister: canister.into(),
+            new_wasm,
+            upgrade_args: candid::encode_args(()).expect("empty args should be encoded"),
+        }
+    }
+
+    /// Sets the candid encoded arguments of the upgrade. Default is no arguments.
+    pub fn with_upgrade_args(mut self, upgrade_args: Vec<u8>) -> Self {
+        self.upgrade_args = upgrade_args;
+        self
+    }
+
+    /// Deploys the environment, calls `populate`, upgrades the canister and calls `assert`.
+    /// Returns the environment with the upgraded canister.
+    ///
+    /// Panics if the upgrade fails.
+    pub async fn run<P, PFut, A, AFut>(self, populate: P, assert: A) -> PocketIcEnvironment
+    where
+        P: FnOnce(PocketIcEnvironment) -> PFut,
+        PFut: Future<Output = ()>,
+        A: FnOnce(PocketIcEnvironment) -> AFut,
+        AFut: Future<Output = ()>,
+    {
+        let env = self.environment.build().await;
+        let canister = env.canister(&self.canister);
+
+        populate(env.clone()).await;
+
+        env.client
+            .upgrade_canister(
+                canister,
+                self.new_wasm,
+                self.upgrade_args,
+                Some(env.controller),
+            )
+            .await
+            .unwrap_or_else(|e| panic!("upgrade of canister {} failed: {e:?}", self.canister));
+        info!("canister {} upgraded", self.canister);
+
+        assert(env.clone()).await;
+
+        env
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::{env, fs};
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::*;
+
+const WASM_TARGET: &str = "wasm32-unknown-unknown";
+
+/// Builds the wasm module of a workspace member for the integration tests.
+///
+/// ```ignore
+/// let wasm = WorkspaceWasm::new("dummy_scheduler_canister")
+///     .with_features(&["export-api"])
+///     .build();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct WorkspaceWasm {
+    package: String,
+    features: Vec<String>,
+}
+
+impl WorkspaceWasm {
+    /// Creates a build of the workspace member with the given package name.
+    pub fn new(package: impl Into<String>) -> Self {
+        Self {
+            package: package.into(),
+            features: vec![],
+        }
+    }
+
+    /// Enables the given features of the package.
+    pub fn with_features(mut self, features: &[&str]) -> Self {
+        self.features
+            .extend(features.iter().map(|feature| feature.to_string()));
+        self
+    }
+
+    /// Builds the package with `cargo build --target wasm32-unknown-unknown --release` and
+    /// returns the gzipped wasm module.
+    ///
+    /// The result is cached, so every package and features combination is built once per test
+    /// binary. Builds are executed one at a time, since builds of the same package with
+    /// different features write the same artifact.
+    ///
+    /// Panics if the build fails. A failed build doesn't affect the other builds, and is
+    /// executed again if requested again.
+    pub fn build(&self) -> Vec<u8> {
+        static CACHE: OnceLock<Mutex<HashMap<WorkspaceWasm, Vec<u8>>>> = OnceLock::new();
+        static BUILD: Mutex<()> = Mutex::new(());
+
+        let cache = CACHE.get_or_init(Default::default);
+        let cached = || {
+            cache
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .get(self)
+                .cloned()
+        };
+
+        if let Some(wasm) = cached() {
+            return wasm;
+        }
+
+        // The cache is not locked during the build, so the other tests can read the modules
+        // already built. The build lock is only poisoned by a failed build, which leaves no
+        // state behind.
+        let _build = BUILD.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(wasm) = cached() {
+            return wasm;
+        }
+
+        let wasm = self.build_uncached();
+        cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(self.clone(), wasm.clone());
+
+        wasm
+    }
+
+    fn build_uncached(&self) -> Vec<u8> {
+        info!(
+            "building wasm of {} with features {:?}",
+            self.package, self.features
+        );
+
+        let mut command = Command::new(cargo());
+        command.args(["build", "--target", WASM_TARGET, "--release", "-p"]);
+        command.arg(&self.package);
+        if !self.features.is_empty() {
+            command.arg("--features").arg(self.features.join(","));
+        }
+
+        let status = command.status().expect("cargo should be executed");
+        if !status.success() {
+            panic!("wasm build of {} failed with {status}", self.package);
+        }
+
+        let artifact = target_dir()
+            .join(WASM_TARGET)
+            .join("release")
+            .join(format!("{}.wasm", self.package.replace('-', "_")));
+        let wasm = fs::read(&artifact)
+            .unwrap_or_else(|e| panic!("wasm artifact {artifact:?} should be readable: {e}"));
+
+        gzip(&wasm)
+    }
+}
+
+fn cargo() -> String {
+    env::var("CARGO").unwrap_or_else(|_| "cargo".to_string())
+}
+
+/// Returns the target directory of the workspace the tests are executed from.
+fn target_dir() -> PathBuf {
+    if let Ok(target_dir) = env::var("CARGO_TARGET_DIR") {
+        return target_dir.into();
+    }
+
+    let output = Command::new(cargo())
+        .args(["locate-project", "--workspace", "--message-format", "plain"])
+        .output()
+        .expect("cargo should be executed");
+    let manifest = String::from_utf8(output.stdout).expect("manifest path should be utf-8");
+
+    PathBuf::from(manifest.trim())
+        .parent()
+        .expect("workspace manifest should have a parent directory")
+        .join("target")
+}
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(data)
+        .expect("wasm module should be compressed");
+    encoder.finish().expect("wasm module should be compressed")
+}
@@ -2,7 +2,7 @@ use std::cell::RefCell;
 use std::rc::Rc;
 
 use candid::Principal;
-use ic_canister::{generate_idl, init, post_upgrade, Canister, Idl, PreUpdate};
+use ic_canister::{generate_idl, init, post_upgrade, pre_upgrade, Canister, Idl, PreUpdate};
 use ic_exports::ic_cdk;
 use ic_exports::ic_kit::ic;
 use ic_log::canister::inspect::logger_canister_inspect;
@@ -52,12 +52,22 @@ impl LoggerCanister {
         });
     }
 
+    #[pre_upgrade]
+    pub fn pre_upgrade(&self) {
+        MEMORY_MANAGER.with(|mm| {
+            self.log_state()
+                .borrow()
+                .pre_upgrade(mm.get(MemoryId::new(2)))
+                .expect("error saving the log records");
+        });
+    }
+
     #[post_upgrade]
     pub fn post_upgrade(&self) {
         MEMORY_MANAGER.with(|mm| {
             self.log_state()
                 .borrow_mut()
-                .reload(mm.get(MemoryId::new(1)))
+                .post_upgrade(mm.get(MemoryId::new(1)), mm.get(MemoryId::new(2)))
                 .expect("error configuring the logger");
         });
     }
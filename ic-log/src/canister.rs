@@ -8,9 +8,14 @@ use ic_canister::{
 use ic_exports::ic_kit::ic;
 
 pub use crate::canister::state::LogState;
-use crate::did::{LogCanisterError, LogCanisterSettings, LoggerPermission, Pagination};
+use crate::did::{
+    LogCanisterError, LogCanisterSettings, LogDirective, LoggerPermission, Pagination,
+};
 use crate::writer::Logs;
 
+mod filter;
+#[cfg(feature = "http")]
+pub mod http;
 pub mod inspect;
 mod state;
 
@@ -30,9 +35,15 @@ mod state;
 /// to them.
 ///
 /// * `Read` permission allows a principal to get the logs with `ic_logs` method.
+/// * `Filter` permission allows changing the logger filter with `set_logger_filter` method.
+/// * `Clear` permission allows removing the in-memory logs with `clear_logs` method.
 /// * `Configure` permission allows changing the logger configuration and manager logger permissions.
-///   If a principal has `Configure` permission, `Read` permission is also assumed for that
-///   principal.
+///   If a principal has `Configure` permission, all the other permissions are also assumed for
+///   that principal.
+///
+/// The permissions can be given at the canister initialization with
+/// [`LogCanisterSettings::acl`] and managed at runtime with `add_logger_permission` and
+/// `remove_logger_permission` methods.
 ///
 /// # Configuration and ways to get logs
 ///
@@ -52,7 +63,8 @@ mod state;
 ///    memory. To limit the size of the memory that can be dedicated to the logs, configure
 ///    max number of entries to store and max size of a single entry. This method cannot store
 ///    logs from operations that trapped, and the logs storage is reset when the canister is
-///    upgraded.
+///    upgraded, unless the records are saved with [`LogState::pre_upgrade`] and restored with
+///    [`LogState::post_upgrade`].
 pub trait LogCanister: Canister + PreUpdate {
     /// State of the logger. Usually the implementation of this method would look like:
     ///
@@ -89,7 +101,7 @@ pub trait LogCanister: Canister + PreUpdate {
 
     /// Sets the logger filter string.
     ///
-    /// To call this method, the caller must have [`LoggerPermission::Configure`] permission.
+    /// To call this method, the caller must have [`LoggerPermission::Filter`] permission.
     ///
     /// To turn off logging for the canister, use `filter == "off"`.
     ///
@@ -99,7 +111,7 @@ pub trait LogCanister: Canister + PreUpdate {
     ///
     /// # Traps
     ///
-    /// Traps if the caller doesn't have [`LoggerPermission::Configure`] permission of if the
+    /// Traps if the caller doesn't have [`LoggerPermission::Filter`] permission of if the
     /// logger state is not initialized.
     #[update(trait = true)]
     fn set_logger_filter(&mut self, filter: String) -> Result<(), LogCanisterError> {
@@ -120,6 +132,76 @@ pub trait LogCanister: Canister + PreUpdate {
         }
     }
 
+    /// Returns the directives of the current logger filter.
+    #[query(trait = true)]
+    fn get_logger_filter_directives(&self) -> Vec<LogDirective> {
+        self.log_state().borrow().get_filter_directives()
+    }
+
+    /// Sets the max `level` of the records logged by the `module`. If the `module` is `None`,
+    /// sets the default level for all modules. Other directives of the filter are kept.
+    ///
+    /// To call this method, the caller must have [`LoggerPermission::Filter`] permission.
+    ///
+    /// # Errors
+    ///
+    /// * [`LogCanisterError::InvalidConfiguration`] if the `level` is invalid.
+    ///
+    /// # Traps
+    ///
+    /// Traps if the caller doesn't have [`LoggerPermission::Filter`] permission of if the
+    /// logger state is not initialized.
+    #[update(trait = true)]
+    fn set_logger_module_filter(
+        &mut self,
+        module: Option<String>,
+        level: String,
+    ) -> Result<(), LogCanisterError> {
+        match self
+            .log_state()
+            .borrow_mut()
+            .set_module_filter(ic::caller(), module, level)
+        {
+            err @ Err(LogCanisterError::InvalidConfiguration(_)) => err,
+            result => {
+                result.expect("failed to update configuration");
+                Ok(())
+            }
+        }
+    }
+
+    /// Removes the filter directive of the `module`, so the default level applies to it.
+    ///
+    /// To call this method, the caller must have [`LoggerPermission::Filter`] permission.
+    ///
+    /// # Traps
+    ///
+    /// Traps if the caller doesn't have [`LoggerPermission::Filter`] permission of if the
+    /// logger state is not initialized.
+    #[update(trait = true)]
+    fn remove_logger_module_filter(&mut self, module: String) {
+        self.log_state()
+            .borrow_mut()
+            .remove_module_filter(ic::caller(), module)
+            .expect("failed to update configuration");
+    }
+
+    /// Resets the logger filter to the default value.
+    ///
+    /// To call this method, the caller must have [`LoggerPermission::Filter`] permission.
+    ///
+    /// # Traps
+    ///
+    /// Traps if the caller doesn't have [`LoggerPermission::Filter`] permission of if the
+    /// logger state is not initialized.
+    #[update(trait = true)]
+    fn reset_logger_filter(&mut self) {
+        self.log_state()
+            .borrow_mut()
+            .reset_logger_filter(ic::caller())
+            .expect("failed to update configuration");
+    }
+
     /// Updates the maximum number of log entries stored in the canister memory.
     ///
     /// To call this method, the caller must have [`LoggerPermission::Configure`] permission.
@@ -136,6 +218,41 @@ pub trait LogCanister: Canister + PreUpdate {
             .expect("failed to update configuration");
     }
 
+    /// Removes all the log entries stored in the canister memory. The ids of the log entries
+    /// written afterward continue from the last removed one.
+    ///
+    /// To call this method, the caller must have [`LoggerPermission::Clear`] permission.
+    ///
+    /// # Traps
+    ///
+    /// Traps if the caller doesn't have [`LoggerPermission::Clear`] permission of if the
+    /// logger state is not initialized.
+    #[update(trait = true)]
+    fn clear_logs(&mut self) {
+        self.log_state()
+            .borrow()
+            .clear_logs(ic::caller())
+            .expect("failed to clear logs");
+    }
+
+    /// Returns the access control list of the logger.
+    ///
+    /// To call this method, the caller must have [`LoggerPermission::Configure`] permission.
+    ///
+    /// # Traps
+    ///
+    /// Traps if the caller doesn't have [`LoggerPermission::Configure`] permission of if the
+    /// logger state is not initialized.
+    #[query(trait = true)]
+    fn get_logger_acl(&self) -> Vec<(Principal, LoggerPermission)> {
+        self.log_state()
+            .borrow()
+            .get_acl(ic::caller())
+            .expect("failed to get logger acl")
+            .into_iter()
+            .collect()
+    }
+
     /// Returns the current logger settings.
     #[query(trait = true)]
     fn get_logger_settings(&self) -> LogCanisterSettings {
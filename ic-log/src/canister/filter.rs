@@ -0,0 +1,147 @@
+use std::fmt;
+use std::str::FromStr;
+
+use log::LevelFilter;
+
+use crate::did::{LogCanisterError, LogDirective};
+
+/// Logger filter string split into directives, so they can be changed one by one.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct FilterDirectives {
+    directives: Vec<LogDirective>,
+    /// Message filter after the `/` in the filter string.
+    regex: Option<String>,
+}
+
+impl FilterDirectives {
+    /// Splits the filter string in the `RUST_LOG` format into directives.
+    pub fn parse(filter: &str) -> Self {
+        let (directives, regex) = match filter.split_once('/') {
+            Some((directives, regex)) => (directives, Some(regex.to_string())),
+            None => (filter, None),
+        };
+
+        let directives = directives
+            .split(',')
+            .map(str::trim)
+            .filter(|directive| !directive.is_empty())
+            .map(|directive| match directive.split_once('=') {
+                Some((module, level)) => LogDirective {
+                    module: Some(module.trim().to_string()),
+                    level: level.trim().to_lowercase(),
+                },
+                None => match LevelFilter::from_str(directive) {
+                    Ok(level) => LogDirective {
+                        module: None,
+                        level: level.to_string().to_lowercase(),
+                    },
+                    Err(_) => LogDirective {
+                        module: Some(directive.to_string()),
+                        level: LevelFilter::Trace.to_string().to_lowercase(),
+                    },
+                },
+            })
+            .collect();
+
+        Self { directives, regex }
+    }
+
+    pub fn directives(&self) -> &[LogDirective] {
+        &self.directives
+    }
+
+    /// Sets the level of the `module`, or the default level if the `module` is `None`.
+    pub fn set(&mut self, module: Option<String>, level: &str) -> Result<(), LogCanisterError> {
+        let level = LevelFilter::from_str(level)
+            .map_err(|_| LogCanisterError::InvalidConfiguration(format!("invalid level: {level}")))?
+            .to_string()
+            .to_lowercase();
+
+        match self
+            .directives
+            .iter_mut()
+            .find(|directive| directive.module == module)
+        {
+            Some(directive) => directive.level = level,
+            None => self.directives.push(LogDirective { module, level }),
+        }
+
+        Ok(())
+    }
+
+    /// Removes the directive of the `module`. Returns false if there is no such directive.
+    pub fn remove(&mut self, module: &str) -> bool {
+        let len = self.directives.len();
+        self.directives
+            .retain(|directive| directive.module.as_deref() != Some(module));
+        self.directives.len() != len
+    }
+}
+
+impl fmt::Display for FilterDirectives {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, directive) in self.directives.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+
+            match &directive.module {
+                Some(module) => write!(f, "{module}={}", directive.level)?,
+                None => write!(f, "{}", directive.level)?,
+            }
+        }
+
+        if let Some(regex) = &self.regex {
+            write!(f, "/{regex}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn directive(module: Option<&str>, level: &str) -> LogDirective {
+        LogDirective {
+            module: module.map(ToString::to_string),
+            level: level.to_string(),
+        }
+    }
+
+    #[test]
+    fn should_parse_filter() {
+        let filter = FilterDirectives::parse("debug, crate1::mod1=error,crate2/foo.*");
+        assert_eq!(
+            filter.directives(),
+            &[
+                directive(None, "debug"),
+                directive(Some("crate1::mod1"), "error"),
+                directive(Some("crate2"), "trace"),
+            ]
+        );
+        assert_eq!(
+            filter.to_string(),
+            "debug,crate1::mod1=error,crate2=trace/foo.*"
+        );
+    }
+
+    #[test]
+    fn should_update_directives() {
+        let mut filter = FilterDirectives::parse("info,crate1=warn");
+        filter.set(Some("crate1".to_string()), "DEBUG").unwrap();
+        filter.set(Some("crate2".to_string()), "error").unwrap();
+        filter.set(None, "off").unwrap();
+        assert_eq!(filter.to_string(), "off,crate1=debug,crate2=error");
+
+        assert!(filter.remove("crate1"));
+        assert!(!filter.remove("crate1"));
+        assert_eq!(filter.to_string(), "off,crate2=error");
+
+        assert!(matches!(
+            filter.set(None, "loud"),
+            Err(LogCanisterError::InvalidConfiguration(_))
+        ));
+    }
+}
@@ -0,0 +1,337 @@
+//! Serving the in-memory logs with the canister `http_request` query.
+//!
+//! Call [`http_request`] from the `http_request` query of your canister to make the logs
+//! available at the `/logs` path, e.g. `https://<canister_id>.raw.icp0.io/logs?count=50&level=warn`.
+//!
+//! Supported query parameters:
+//!
+//! * `offset` - id of the first log entry to return. If not set, the latest entries are returned.
+//! * `count` - max number of log entries to return. Defaults to `100`, capped at `1000`.
+//! * `level` - the most verbose level to return, e.g. `warn` returns only warnings and errors.
+//!   The entries are filtered by the level they were written with before `offset` and `count`
+//!   are applied. Compressed entries don't keep their level, so they are not returned.
+//!
+//! The requests are authorized the same way as the `ic_logs` method, so the caller must have
+//! [`LoggerPermission::Read`] permission. Requests coming through the HTTP gateway are sent by
+//! the anonymous principal, so to allow reading the logs from a browser, grant the `Read`
+//! permission to [`Principal::anonymous`].
+
+use std::str::FromStr;
+
+use candid::{CandidType, Principal};
+use ic_exports::ic_kit::ic;
+use ic_storage::IcStorage;
+use log::Level;
+use serde::Deserialize;
+use serde_bytes::ByteBuf;
+
+use crate::canister::LogState;
+use crate::did::{LogCanisterError, LoggerPermission, Pagination};
+use crate::writer::{InMemoryWriter, Logs};
+
+/// Path the logs are served at.
+pub const LOGS_PATH: &str = "/logs";
+
+const DEFAULT_COUNT: usize = 100;
+const MAX_COUNT: usize = 1000;
+
+/// Request of the `http_request` query.
+#[derive(Debug, Clone, CandidType, Deserialize)]
+pub struct HttpRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: ByteBuf,
+}
+
+/// Response of the `http_request` query.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Deserialize)]
+pub struct HttpResponse {
+    pub status_code: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: ByteBuf,
+}
+
+impl HttpResponse {
+    fn text(status_code: u16, body: String) -> Self {
+        Self {
+            status_code,
+            headers: vec![(
+                "content-type".to_string(),
+                "text/plain; charset=utf-8".to_string(),
+            )],
+            body: ByteBuf::from(body.into_bytes()),
+        }
+    }
+}
+
+/// Implementation of the `http_request` query serving the logs. Call this method from the
+/// `http_request` query of your canister.
+pub fn http_request(request: &HttpRequest) -> HttpResponse {
+    serve_logs(&LogState::get().borrow(), ic::caller(), request)
+}
+
+/// Returns the logs of the `state` as an HTTP response to the `request`.
+pub fn serve_logs(state: &LogState, caller: Principal, request: &HttpRequest) -> HttpResponse {
+    let (path, query) = request
+        .url
+        .split_once('?')
+        .unwrap_or((request.url.as_str(), ""));
+
+    if path != LOGS_PATH {
+        return HttpResponse::text(404, "not found".to_string());
+    }
+
+    if !request.method.eq_ignore_ascii_case("GET") {
+        return HttpResponse::text(405, "method not allowed".to_string());
+    }
+
+    let params = match LogsQuery::parse(query) {
+        Ok(params) => params,
+        Err(message) => return HttpResponse::text(400, message),
+    };
+
+    match params.take_logs(state, caller) {
+        Ok(logs) => {
+            let mut response = HttpResponse::text(200, render(&logs));
+            response.headers.push((
+                "x-all-logs-count".to_string(),
+                logs.all_logs_count.to_string(),
+            ));
+            response
+        }
+        Err(LogCanisterError::NotAuthorized) => HttpResponse::text(403, "forbidden".to_string()),
+        Err(err) => HttpResponse::text(503, format!("{err:?}")),
+    }
+}
+
+fn render(logs: &Logs) -> String {
+    let mut body = String::new();
+    for log in &logs.logs {
+        body.push_str(&log.log);
+        if !log.log.ends_with('\n') {
+            body.push('\n');
+        }
+    }
+
+    body
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct LogsQuery {
+    offset: Option<usize>,
+    count: usize,
+    level: Option<Level>,
+}
+
+impl LogsQuery {
+    fn parse(query: &str) -> Result<Self, String> {
+        let mut params = Self {
+            offset: None,
+            count: DEFAULT_COUNT,
+            level: None,
+        };
+
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            match name {
+                "offset" => {
+                    params.offset = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("invalid offset: {value}"))?,
+                    )
+                }
+                "count" => {
+                    let count: usize = value
+                        .parse()
+                        .map_err(|_| format!("invalid count: {value}"))?;
+                    params.count = count.min(MAX_COUNT);
+                }
+                "level" => {
+                    params.level = Some(
+                        Level::from_str(value).map_err(|_| format!("invalid level: {value}"))?,
+                    )
+                }
+                _ => return Err(format!("unknown parameter: {name}")),
+            }
+        }
+
+        Ok(params)
+    }
+
+    fn take_logs(&self, state: &LogState, caller: Principal) -> Result<Logs, LogCanisterError> {
+        if let Some(max_level) = self.level {
+            state.check_permission(caller, LoggerPermission::Read)?;
+            return Ok(InMemoryWriter::take_records_up_to_level(
+                max_level,
+                self.count,
+                self.offset,
+            ));
+        }
+
+        let offset = match self.offset {
+            Some(offset) => offset,
+            None => {
+                let latest = Pagination {
+                    offset: 0,
+                    count: 0,
+                };
+                let all_logs_count = state.get_logs(caller, latest)?.all_logs_count;
+                all_logs_count.saturating_sub(self.count)
+            }
+        };
+
+        state.get_logs(
+            caller,
+            Pagination {
+                offset,
+                count: self.count,
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ic_stable_structures::stable_structures::DefaultMemoryImpl;
+    use ic_stable_structures::{IcMemoryManager, MemoryId};
+
+    use super::*;
+    use crate::did::LogCanisterSettings;
+    use crate::formatter::buffer::Buffer;
+    use crate::writer::{InMemoryWriter, Writer};
+
+    fn admin() -> Principal {
+        Principal::from_slice(&[1; 20])
+    }
+
+    fn test_state() -> LogState {
+        let memory_manager = IcMemoryManager::init(DefaultMemoryImpl::default());
+        let mut state = LogState::default();
+        state
+            .init(
+                admin(),
+                memory_manager.get(MemoryId::new(1)),
+                LogCanisterSettings::default(),
+            )
+            .unwrap();
+
+        InMemoryWriter::init_buffer(16, 1024);
+        let writer = InMemoryWriter {};
+        let levels = [Level::Error, Level::Warn, Level::Info, Level::Debug];
+        for (i, level) in levels.into_iter().enumerate() {
+            let mut buf = Buffer::from(format!(
+                "[2024-01-01T00:00:00Z {level:<5} target] message {i}\n"
+            ));
+            buf.set_level(Some(level));
+            writer.print(&buf).unwrap();
+        }
+
+        state
+    }
+
+    fn get(url: &str) -> HttpRequest {
+        HttpRequest {
+            method: "GET".to_string(),
+            url: url.to_string(),
+            headers: vec![],
+            body: ByteBuf::new(),
+        }
+    }
+
+    fn body(response: &HttpResponse) -> String {
+        String::from_utf8(response.body.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn should_serve_latest_logs() {
+        let state = test_state();
+        let response = serve_logs(&state, admin(), &get("/logs?count=2"));
+        assert_eq!(response.status_code, 200);
+        assert_eq!(
+            body(&response),
+            "[2024-01-01T00:00:00Z INFO  target] message 2\n\
+             [2024-01-01T00:00:00Z DEBUG target] message 3\n"
+        );
+        assert!(response
+            .headers
+            .contains(&("x-all-logs-count".to_string(), "4".to_string())));
+    }
+
+    #[test]
+    fn should_paginate_and_filter_logs() {
+        let state = test_state();
+        let response = serve_logs(&state, admin(), &get("/logs?offset=1&level=warn"));
+        assert_eq!(
+            body(&response),
+            "[2024-01-01T00:00:00Z WARN  target] message 1\n"
+        );
+
+        let response = serve_logs(&state, admin(), &get("/logs?level=error&count=1"));
+        assert_eq!(
+            body(&response),
+            "[2024-01-01T00:00:00Z ERROR target] message 0\n"
+        );
+    }
+
+    #[test]
+    fn should_filter_logs_without_level_in_format() {
+        let state = test_state();
+        let mut buf = Buffer::from("custom format");
+        buf.set_level(Some(Level::Warn));
+        InMemoryWriter {}.print(&buf).unwrap();
+
+        let response = serve_logs(&state, admin(), &get("/logs?level=warn&count=2"));
+        assert_eq!(
+            body(&response),
+            "[2024-01-01T00:00:00Z WARN  target] message 1\n\
+             custom format\n"
+        );
+    }
+
+    #[test]
+    fn should_reject_invalid_requests() {
+        let state = test_state();
+        assert_eq!(
+            serve_logs(&state, admin(), &get("/metrics")).status_code,
+            404
+        );
+        assert_eq!(
+            serve_logs(&state, admin(), &get("/logs?count=many")).status_code,
+            400
+        );
+        assert_eq!(
+            serve_logs(&state, admin(), &get("/logs?level=loud")).status_code,
+            400
+        );
+        assert_eq!(
+            serve_logs(&state, Principal::anonymous(), &get("/logs")).status_code,
+            403
+        );
+
+        let mut request = get("/logs");
+        request.method = "POST".to_string();
+        assert_eq!(serve_logs(&state, admin(), &request).status_code, 405);
+    }
+
+    #[test]
+    fn should_parse_query() {
+        assert_eq!(
+            LogsQuery::parse("offset=5&count=5000&level=INFO"),
+            Ok(LogsQuery {
+                offset: Some(5),
+                count: MAX_COUNT,
+                level: Some(Level::Info),
+            })
+        );
+        assert_eq!(
+            LogsQuery::parse(""),
+            Ok(LogsQuery {
+                offset: None,
+                count: DEFAULT_COUNT,
+                level: None,
+            })
+        );
+    }
+}
@@ -20,9 +20,13 @@ pub fn logger_canister_inspect() {
     match method.as_str() {
         "ic_logs" => state.check_permission(caller, LoggerPermission::Read),
         "set_logger_filter"
-        | "set_logger_in_memory_records"
-        | "add_logger_permission"
-        | "remove_logger_permission" => state.check_permission(caller, LoggerPermission::Configure),
+        | "set_logger_module_filter"
+        | "remove_logger_module_filter"
+        | "reset_logger_filter" => state.check_permission(caller, LoggerPermission::Filter),
+        "clear_logs" => state.check_permission(caller, LoggerPermission::Clear),
+        "set_logger_in_memory_records" | "add_logger_permission" | "remove_logger_permission" => {
+            state.check_permission(caller, LoggerPermission::Configure)
+        }
         _ => Ok(()),
     }
     .expect("inspect check failed");
@@ -5,8 +5,11 @@ use ic_stable_structures::stable_structures::DefaultMemoryImpl;
 use ic_stable_structures::{Bound, CellStructure, StableCell, Storable, VirtualMemory};
 use ic_storage::IcStorage;
 
-use crate::did::{LogCanisterError, LogCanisterSettings, LoggerAcl, LoggerPermission, Pagination};
-use crate::writer::{InMemoryWriter, Logs};
+use crate::canister::filter::FilterDirectives;
+use crate::did::{
+    LogCanisterError, LogCanisterSettings, LogDirective, LoggerAcl, LoggerPermission, Pagination,
+};
+use crate::writer::{ExportedRecords, InMemoryWriter, Logs};
 use crate::{take_memory_records, LogSettingsV2, LoggerConfig};
 
 /// State of the logger canister.
@@ -84,7 +87,7 @@ impl LogState {
         caller: Principal,
         filter_value: String,
     ) -> Result<(), LogCanisterError> {
-        self.check_permission(caller, LoggerPermission::Configure)?;
+        self.check_permission(caller, LoggerPermission::Filter)?;
 
         // This operation must be the first one as it is the only one that may return error.
         // It is not guaranteed that the caller of this function will revert the canister state
@@ -107,6 +110,49 @@ impl LogState {
         Ok(())
     }
 
+    /// Returns the directives of the current logger filter.
+    pub fn get_filter_directives(&self) -> Vec<LogDirective> {
+        let filter = self.get_settings().log_filter.unwrap_or_default();
+        FilterDirectives::parse(&filter).directives().to_vec()
+    }
+
+    /// Set the max level of the `module`, or the default level if the `module` is `None`.
+    pub fn set_module_filter(
+        &mut self,
+        caller: Principal,
+        module: Option<String>,
+        level: String,
+    ) -> Result<(), LogCanisterError> {
+        self.check_permission(caller, LoggerPermission::Filter)?;
+
+        let mut filter =
+            FilterDirectives::parse(&self.get_settings().log_filter.unwrap_or_default());
+        filter.set(module, &level)?;
+        self.set_logger_filter(caller, filter.to_string())
+    }
+
+    /// Remove the filter directive of the `module`.
+    pub fn remove_module_filter(
+        &mut self,
+        caller: Principal,
+        module: String,
+    ) -> Result<(), LogCanisterError> {
+        self.check_permission(caller, LoggerPermission::Filter)?;
+
+        let mut filter =
+            FilterDirectives::parse(&self.get_settings().log_filter.unwrap_or_default());
+        if !filter.remove(&module) {
+            return Ok(());
+        }
+
+        self.set_logger_filter(caller, filter.to_string())
+    }
+
+    /// Reset the logger filter to the default value.
+    pub fn reset_logger_filter(&mut self, caller: Principal) -> Result<(), LogCanisterError> {
+        self.set_logger_filter(caller, LogSettingsV2::default().log_filter)
+    }
+
     /// Set `in_memory_records` settings.
     pub fn set_in_memory_records(
         &mut self,
@@ -133,6 +179,16 @@ impl LogState {
         Ok(take_memory_records(page.count, page.offset))
     }
 
+    /// Remove all the logs from the memory.
+    pub fn clear_logs(&self, caller: Principal) -> Result<(), LogCanisterError> {
+        self.check_permission(caller, LoggerPermission::Clear)?;
+        InMemoryWriter::clear_records();
+
+        log::info!("In-memory logs cleared by {caller}");
+
+        Ok(())
+    }
+
     /// Reloads the configuration of the logger from the stable memory and initializes the logger.
     ///
     /// This method should be called from `#[post_upgrade]` method.
@@ -159,6 +215,46 @@ impl LogState {
         Ok(())
     }
 
+    /// Saves the in-memory log records to the `records_memory`, so they can be restored after
+    /// the upgrade with [`LogState::post_upgrade`].
+    ///
+    /// This method should be called from `#[pre_upgrade]` method.
+    pub fn pre_upgrade(
+        &self,
+        records_memory: VirtualMemory<DefaultMemoryImpl>,
+    ) -> Result<(), LogCanisterError> {
+        let exported = InMemoryWriter::export_records();
+        let mut cell = StableCell::new(records_memory, StorableLogRecords::default())
+            .map_err(|_| LogCanisterError::InvalidMemory)?;
+        cell.set(StorableLogRecords(exported))
+            .map_err(|_| LogCanisterError::InvalidMemory)
+    }
+
+    /// Reloads the logger with [`LogState::reload`] and restores the in-memory log records
+    /// saved by [`LogState::pre_upgrade`] to the `records_memory`.
+    ///
+    /// This method should be called from `#[post_upgrade]` method instead of
+    /// [`LogState::reload`].
+    pub fn post_upgrade(
+        &mut self,
+        settings_memory: VirtualMemory<DefaultMemoryImpl>,
+        records_memory: VirtualMemory<DefaultMemoryImpl>,
+    ) -> Result<(), LogCanisterError> {
+        self.reload(settings_memory)?;
+
+        let mut cell = StableCell::new(records_memory, StorableLogRecords::default())
+            .map_err(|_| LogCanisterError::InvalidMemory)?;
+        let StorableLogRecords(exported) = cell.get().clone();
+
+        // The records must not be restored twice if the next upgrade skips `pre_upgrade`.
+        cell.set(StorableLogRecords::default())
+            .map_err(|_| LogCanisterError::InvalidMemory)?;
+
+        InMemoryWriter::import_records(exported);
+
+        Ok(())
+    }
+
     /// Add permission for the `to` principal.
     pub fn add_permission(
         &mut self,
@@ -197,6 +293,12 @@ impl LogState {
         Ok(())
     }
 
+    /// Returns the access control list of the logger.
+    pub fn get_acl(&self, caller: Principal) -> Result<LoggerAcl, LogCanisterError> {
+        self.check_permission(caller, LoggerPermission::Configure)?;
+        Ok(self.acl())
+    }
+
     pub fn acl(&self) -> LoggerAcl {
         self.settings
             .as_ref()
@@ -232,13 +334,9 @@ impl LogState {
         let settings = cell.get();
         let acl = &settings.1;
 
-        let allowed = match logger_permission {
-            LoggerPermission::Read => {
-                acl.contains(&(caller, LoggerPermission::Read))
-                    || (acl.contains(&(caller, LoggerPermission::Configure)))
-            }
-            LoggerPermission::Configure => acl.contains(&(caller, LoggerPermission::Configure)),
-        };
+        // `Configure` permission grants all the other permissions.
+        let allowed = acl.contains(&(caller, logger_permission))
+            || acl.contains(&(caller, LoggerPermission::Configure));
 
         if allowed {
             Ok(())
@@ -264,11 +362,28 @@ impl Storable for StorableLogSettings {
     const BOUND: Bound = Bound::Unbounded;
 }
 
+/// In-memory log records saved during an upgrade.
+#[derive(Debug, Default, Clone)]
+struct StorableLogRecords(ExportedRecords);
+
+impl Storable for StorableLogRecords {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::from(Encode!(&self.0).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Self(Decode!(&bytes, ExportedRecords).unwrap())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
 #[cfg(test)]
 mod tests {
     use ic_stable_structures::{IcMemoryManager, MemoryId};
 
     use super::*;
+    use crate::writer::Writer;
 
     thread_local! {
         static MEMORY_MANAGER: IcMemoryManager<DefaultMemoryImpl> = IcMemoryManager::init(DefaultMemoryImpl::default());
@@ -290,6 +405,10 @@ mod tests {
         MEMORY_MANAGER.with(|manager| manager.get(MemoryId::new(2)))
     }
 
+    fn test_records_memory() -> VirtualMemory<DefaultMemoryImpl> {
+        MEMORY_MANAGER.with(|manager| manager.get(MemoryId::new(3)))
+    }
+
     fn test_settings() -> LogSettingsV2 {
         LogSettingsV2 {
             enable_console: true,
@@ -596,6 +715,133 @@ mod tests {
         );
     }
 
+    #[test]
+    fn set_logger_filter_allowed_with_filter_permission() {
+        let mut state = test_state();
+        state
+            .add_permission(admin(), user(), LoggerPermission::Filter)
+            .unwrap();
+        assert!(state.set_logger_filter(user(), "info".into()).is_ok());
+        assert_eq!(
+            state.set_in_memory_records(user(), 10),
+            Err(LogCanisterError::NotAuthorized)
+        );
+        assert_eq!(
+            state.get_logs(
+                user(),
+                Pagination {
+                    offset: 0,
+                    count: 10
+                }
+            ),
+            Err(LogCanisterError::NotAuthorized)
+        );
+    }
+
+    #[test]
+    fn clear_logs_checks_permissions() {
+        let mut state = test_state();
+        assert_eq!(
+            state.clear_logs(reader()),
+            Err(LogCanisterError::NotAuthorized)
+        );
+        assert!(state.clear_logs(admin()).is_ok());
+
+        state
+            .add_permission(admin(), user(), LoggerPermission::Clear)
+            .unwrap();
+        assert!(state.clear_logs(user()).is_ok());
+        assert_eq!(
+            state.set_logger_filter(user(), "info".into()),
+            Err(LogCanisterError::NotAuthorized)
+        );
+    }
+
+    #[test]
+    fn clear_logs_removes_records() {
+        let state = test_state();
+        InMemoryWriter::init_buffer(10, 1024);
+        InMemoryWriter {}.print(&"log".into()).unwrap();
+
+        state.clear_logs(admin()).unwrap();
+        let logs = InMemoryWriter::take_records(10, 0);
+        assert_eq!(logs.all_logs_count, 1);
+        assert!(logs.logs.is_empty());
+    }
+
+    #[test]
+    fn get_acl_checks_permissions() {
+        let state = test_state();
+        assert_eq!(state.get_acl(admin()), Ok(test_acl()));
+        assert_eq!(
+            state.get_acl(reader()),
+            Err(LogCanisterError::NotAuthorized)
+        );
+    }
+
+    #[test]
+    fn module_filter_directives_can_be_changed() {
+        let mut state = test_state();
+        state
+            .set_logger_filter(admin(), "info,crate1=warn".into())
+            .unwrap();
+
+        state
+            .set_module_filter(admin(), Some("crate2".into()), "debug".into())
+            .unwrap();
+        state
+            .set_module_filter(admin(), Some("crate1".into()), "error".into())
+            .unwrap();
+        assert_eq!(
+            state.get_settings().log_filter.unwrap(),
+            "info,crate1=error,crate2=debug"
+        );
+
+        state
+            .remove_module_filter(admin(), "crate1".into())
+            .unwrap();
+        assert_eq!(
+            state.get_filter_directives(),
+            vec![
+                LogDirective {
+                    module: None,
+                    level: "info".into(),
+                },
+                LogDirective {
+                    module: Some("crate2".into()),
+                    level: "debug".into(),
+                },
+            ]
+        );
+
+        state.reset_logger_filter(admin()).unwrap();
+        assert_eq!(
+            state.get_settings().log_filter,
+            Some(LogSettingsV2::default().log_filter)
+        );
+    }
+
+    #[test]
+    fn module_filter_checks_caller_and_level() {
+        let mut state = test_state();
+        assert_eq!(
+            state.set_module_filter(reader(), None, "info".into()),
+            Err(LogCanisterError::NotAuthorized)
+        );
+        assert_eq!(
+            state.remove_module_filter(reader(), "crate1".into()),
+            Err(LogCanisterError::NotAuthorized)
+        );
+        assert_eq!(
+            state.reset_logger_filter(reader()),
+            Err(LogCanisterError::NotAuthorized)
+        );
+        assert!(matches!(
+            state.set_module_filter(admin(), None, "loud".into()),
+            Err(LogCanisterError::InvalidConfiguration(_))
+        ));
+    }
+
     #[test]
     fn set_logger_filter_updates_stored_settings() {
         let mut state = test_state();
@@ -706,4 +952,46 @@ mod tests {
 
         assert_eq!(state.get_settings(), settings);
     }
+
+    #[test]
+    fn post_upgrade_restores_records() {
+        let mut state = test_state();
+        InMemoryWriter::init_buffer(10, 1024);
+        let writer = InMemoryWriter {};
+        writer.print(&"before upgrade".into()).unwrap();
+
+        state.pre_upgrade(test_records_memory()).unwrap();
+
+        // Simulate canister upgrade
+        state.logger_config = None;
+        state.settings = None;
+        InMemoryWriter::init_buffer(10, 1024);
+
+        state
+            .post_upgrade(test_memory(), test_records_memory())
+            .unwrap();
+        writer.print(&"after upgrade".into()).unwrap();
+
+        let logs = state
+            .get_logs(
+                admin(),
+                Pagination {
+                    offset: 0,
+                    count: 10,
+                },
+            )
+            .unwrap();
+        assert_eq!(logs.all_logs_count, 2);
+        assert_eq!(logs.logs[0].log, "before upgrade");
+        assert_eq!(logs.logs[1].log, "after upgrade");
+
+        // The saved records are restored only once.
+        state.logger_config = None;
+        state.settings = None;
+        InMemoryWriter::init_buffer(10, 1024);
+        state
+            .post_upgrade(test_memory(), test_records_memory())
+            .unwrap();
+        assert_eq!(InMemoryWriter::take_records(10, 0).all_logs_count, 0);
+    }
 }
@@ -0,0 +1,163 @@
+//! Compressed storage for the records evicted from the in-memory log buffers.
+
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+
+/// Archive of the log records compressed in segments.
+///
+/// Records are collected uncompressed until there are `segment_records` of them, and then
+/// compressed into a segment. When there are more than `max_segments` segments, the oldest
+/// one is dropped.
+pub(crate) struct LogArchive {
+    segment_records: usize,
+    max_segments: usize,
+    pending: Vec<(usize, String)>,
+    segments: VecDeque<Segment>,
+}
+
+struct Segment {
+    first_offset: usize,
+    last_offset: usize,
+    data: Vec<u8>,
+}
+
+impl LogArchive {
+    pub fn new(segment_records: usize, max_segments: usize) -> Self {
+        Self {
+            segment_records: segment_records.max(1),
+            max_segments,
+            pending: vec![],
+            segments: VecDeque::new(),
+        }
+    }
+
+    /// Adds the record to the archive.
+    pub fn push(&mut self, offset: usize, log: String) {
+        self.pending.push((offset, log));
+        if self.pending.len() >= self.segment_records {
+            self.compress_pending();
+        }
+    }
+
+    /// Returns the archived records with the offset not less than `from_offset`, sorted by offset.
+    pub fn records(&self, from_offset: usize) -> Vec<(usize, String)> {
+        let mut records: Vec<_> = self
+            .segments
+            .iter()
+            .filter(|segment| segment.last_offset >= from_offset)
+            .flat_map(Segment::decompress)
+            .chain(self.pending.iter().cloned())
+            .filter(|(offset, _)| *offset >= from_offset)
+            .collect();
+        records.sort_unstable_by_key(|(offset, _)| *offset);
+        records
+    }
+
+    /// Returns the `segment_records` and `max_segments` settings of the archive.
+    pub fn settings(&self) -> (usize, usize) {
+        (self.segment_records, self.max_segments)
+    }
+
+    /// Size of the compressed data in bytes.
+    pub fn compressed_size(&self) -> usize {
+        self.segments.iter().map(|segment| segment.data.len()).sum()
+    }
+
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        self.segments.clear();
+    }
+
+    fn compress_pending(&mut self) {
+        let records = std::mem::take(&mut self.pending);
+        if self.max_segments == 0 || records.is_empty() {
+            return;
+        }
+
+        if self.segments.len() >= self.max_segments {
+            self.segments.pop_front();
+        }
+
+        self.segments.push_back(Segment::compress(&records));
+    }
+}
+
+impl Segment {
+    fn compress(records: &[(usize, String)]) -> Self {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::fast());
+        for (offset, log) in records {
+            // Writing to a vector doesn't fail.
+            let _ = encoder.write_all(&(*offset as u64).to_le_bytes());
+            let _ = encoder.write_all(&(log.len() as u32).to_le_bytes());
+            let _ = encoder.write_all(log.as_bytes());
+        }
+
+        Self {
+            first_offset: records.iter().map(|(offset, _)| *offset).min().unwrap_or(0),
+            last_offset: records.iter().map(|(offset, _)| *offset).max().unwrap_or(0),
+            data: encoder.finish().unwrap_or_default(),
+        }
+    }
+
+    fn decompress(&self) -> Vec<(usize, String)> {
+        let mut bytes = vec![];
+        if DeflateDecoder::new(self.data.as_slice())
+            .read_to_end(&mut bytes)
+            .is_err()
+        {
+            return vec![];
+        }
+
+        let mut records = vec![];
+        let mut rest = bytes.as_slice();
+        while rest.len() >= 12 {
+            let offset = u64::from_le_bytes(rest[..8].try_into().expect("8 bytes")) as usize;
+            let len = u32::from_le_bytes(rest[8..12].try_into().expect("4 bytes")) as usize;
+            let Some(log) = rest.get(12..12 + len) else {
+                break;
+            };
+
+            debug_assert!(offset >= self.first_offset && offset <= self.last_offset);
+            records.push((offset, String::from_utf8_lossy(log).into_owned()));
+            rest = &rest[12 + len..];
+        }
+
+        records
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_compress_and_restore_records() {
+        let mut archive = LogArchive::new(4, 2);
+        for offset in 0..10 {
+            archive.push(offset, format!("[INFO ] record number {offset}\n"));
+        }
+
+        // 2 segments of 4 records and 2 pending records
+        assert!(archive.compressed_size() > 0);
+        let records = archive.records(0);
+        assert_eq!(records.len(), 10);
+        assert_eq!(records[3], (3, "[INFO ] record number 3\n".to_string()));
+
+        // The oldest segment is dropped
+        for offset in 10..12 {
+            archive.push(offset, format!("{offset}"));
+        }
+        let records = archive.records(0);
+        assert_eq!(records.first().unwrap().0, 4);
+        assert_eq!(records.len(), 8);
+
+        assert_eq!(archive.records(10).len(), 2);
+
+        archive.clear();
+        assert!(archive.records(0).is_empty());
+    }
+}
@@ -0,0 +1,236 @@
+//! Context of the message being executed by the canister.
+//!
+//! The context is appended by the default log format to every record emitted while it is set,
+//! so the records of interleaved messages can be told apart. The context is scoped: it is set by
+//! a guard at the start of the canister method and removed when the guard is dropped, so
+//! queries, timers and other messages don't inherit the context of a previous message:
+//!
+//! ```ignore
+//! #[update]
+//! fn transfer(&self, to: Principal, amount: u64) {
+//!     let _context = ic_log::context::log_context("transfer", ic::caller());
+//!     ...
+//! }
+//! ```
+//!
+//! The guard must not be held across an `.await`, since other messages are executed while the
+//! method is suspended. The async methods wrap their body with [`with_log_context`] instead,
+//! which sets the context every time the method is resumed and removes it when it's suspended:
+//!
+//! ```ignore
+//! #[update]
+//! async fn withdraw(&self, amount: u64) -> Result<u64> {
+//!     ic_log::context::with_log_context("withdraw", ic::caller(), async move {
+//!         ...
+//!     })
+//!     .await
+//! }
+//! ```
+//!
+//! `PreUpdate::pre_update` cannot be used to set the context, since it is not called for the
+//! queries and it has no way to remove the context at the end of the message.
+
+use std::cell::{Cell, RefCell};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use candid::Principal;
+
+thread_local! {
+    static LOG_CONTEXT: RefCell<Option<LogContext>> = const { RefCell::new(None) };
+    static NEXT_MESSAGE_ID: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Context of the message being executed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogContext {
+    /// Name of the canister method.
+    pub method: String,
+    /// Caller of the method.
+    pub caller: Principal,
+    /// Id of the message, unique within the canister instance.
+    pub message_id: u64,
+}
+
+impl LogContext {
+    fn new(method: &str, caller: Principal) -> Self {
+        let message_id = NEXT_MESSAGE_ID.with(|id| {
+            let message_id = id.get();
+            id.set(message_id.wrapping_add(1));
+            message_id
+        });
+
+        Self {
+            method: method.to_string(),
+            caller,
+            message_id,
+        }
+    }
+
+    /// Returns the context fields as key-value pairs.
+    pub fn fields(&self) -> [(&'static str, String); 3] {
+        [
+            ("method", self.method.clone()),
+            ("caller", self.caller.to_text()),
+            ("message_id", self.message_id.to_string()),
+        ]
+    }
+}
+
+/// Sets the context of the current message for the lifetime of the returned guard.
+///
+/// The previous context, usually none, is restored when the guard is dropped. The guard must not
+/// be held across an `.await`, use [`with_log_context`] for the async code.
+pub fn log_context(method: &str, caller: Principal) -> LogContextGuard {
+    enter(LogContext::new(method, caller))
+}
+
+/// Sets the context of the `future` every time it is polled, and restores the previous context
+/// when it returns from the poll.
+///
+/// All the records emitted by the future, also after it is resumed from an `.await`, share the
+/// same context and message id.
+pub fn with_log_context<F: Future>(
+    method: &str,
+    caller: Principal,
+    future: F,
+) -> WithLogContext<F> {
+    WithLogContext {
+        context: LogContext::new(method, caller),
+        future: Box::pin(future),
+    }
+}
+
+fn enter(context: LogContext) -> LogContextGuard {
+    let previous = LOG_CONTEXT.with(|current| current.replace(Some(context)));
+    LogContextGuard { previous }
+}
+
+/// Returns the context of the current message.
+pub fn current_log_context() -> Option<LogContext> {
+    LOG_CONTEXT.with(|current| current.borrow().clone())
+}
+
+/// Guard returned by [`log_context`], restores the previous context on drop.
+#[must_use = "the context is reset when the guard is dropped"]
+pub struct LogContextGuard {
+    previous: Option<LogContext>,
+}
+
+impl Drop for LogContextGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        LOG_CONTEXT.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+/// Future returned by [`with_log_context`].
+#[must_use = "futures do nothing unless polled"]
+pub struct WithLogContext<F> {
+    context: LogContext,
+    future: Pin<Box<F>>,
+}
+
+impl<F: Future> Future for WithLogContext<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let _guard = enter(this.context.clone());
+        this.future.as_mut().poll(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::{RawWaker, RawWakerVTable, Waker};
+
+    use super::*;
+
+    fn noop_waker() -> Waker {
+        fn clone(_: *const ()) -> RawWaker {
+            RawWaker::new(std::ptr::null(), &VTABLE)
+        }
+        fn noop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+
+        unsafe { Waker::from_raw(RawWaker::new(std::ptr::null(), &VTABLE)) }
+    }
+
+    /// Future suspended once before returning the context it is resumed with.
+    struct ResumeOnce {
+        suspended: bool,
+    }
+
+    impl Future for ResumeOnce {
+        type Output = Option<LogContext>;
+
+        fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+            if self.suspended {
+                Poll::Ready(current_log_context())
+            } else {
+                self.suspended = true;
+                Poll::Pending
+            }
+        }
+    }
+
+    #[test]
+    fn guard_restores_previous_context() {
+        let caller = Principal::management_canister();
+        {
+            let _update = log_context("update", caller);
+            let message_id = current_log_context().unwrap().message_id;
+
+            {
+                let _timer = log_context("timer", Principal::anonymous());
+                let context = current_log_context().unwrap();
+                assert_eq!(context.method, "timer");
+                assert_eq!(context.caller, Principal::anonymous());
+                assert!(context.message_id > message_id);
+            }
+
+            let context = current_log_context().unwrap();
+            assert_eq!(context.method, "update");
+            assert_eq!(context.message_id, message_id);
+        }
+
+        assert!(current_log_context().is_none());
+    }
+
+    #[test]
+    fn next_message_does_not_inherit_context() {
+        {
+            let _update = log_context("update", Principal::anonymous());
+        }
+
+        // A query or a timer executed after the update doesn't set a context
+        assert!(current_log_context().is_none());
+    }
+
+    #[test]
+    fn async_context_is_restored_after_await() {
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let mut update = Box::pin(with_log_context("update", Principal::anonymous(), async {
+            ResumeOnce { suspended: false }.await
+        }));
+        assert!(update.as_mut().poll(&mut cx).is_pending());
+
+        // Messages executed while the update is suspended don't see its context
+        assert!(current_log_context().is_none());
+        let timer_id = {
+            let _timer = log_context("timer", Principal::management_canister());
+            current_log_context().unwrap().message_id
+        };
+
+        let Poll::Ready(Some(context)) = update.as_mut().poll(&mut cx) else {
+            panic!("update should be resumed with its context");
+        };
+        assert_eq!(context.method, "update");
+        assert!(context.message_id < timer_id);
+        assert!(current_log_context().is_none());
+    }
+}
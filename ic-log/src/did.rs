@@ -51,10 +51,23 @@ pub enum LoggerPermission {
     Read,
     /// Allows the caller to get the logs and change the configuration of the canister.
     Configure,
+    /// Allows the caller to change the logger filter.
+    Filter,
+    /// Allows the caller to clear the in-memory logs.
+    Clear,
 }
 
 pub type LoggerAcl = HashSet<(Principal, LoggerPermission)>;
 
+/// A directive of the logger filter.
+#[derive(Debug, Clone, CandidType, Deserialize, PartialEq, Eq)]
+pub struct LogDirective {
+    /// Module the directive applies to. If `None`, the directive sets the default level.
+    pub module: Option<String>,
+    /// Max level of the records to log, e.g. `debug` or `off`.
+    pub level: String,
+}
+
 /// Log settings to initialize the logger
 #[derive(Default, Debug, Clone, CandidType, Deserialize, PartialEq, Eq)]
 pub struct LogCanisterSettings {
@@ -1,16 +1,26 @@
 use std::io;
 
+use log::Level;
+
 // A buffer to store log formatted data
 #[derive(Default)]
-pub struct Buffer(Vec<u8>);
+pub struct Buffer {
+    bytes: Vec<u8>,
+    level: Option<Level>,
+    target: String,
+    fields: Vec<(String, String)>,
+}
 
 impl Buffer {
     pub fn clear(&mut self) {
-        self.0.clear();
+        self.bytes.clear();
+        self.level = None;
+        self.target.clear();
+        self.fields.clear();
     }
 
     pub fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.0.extend(buf);
+        self.bytes.extend(buf);
         Ok(buf.len())
     }
 
@@ -19,18 +29,51 @@ impl Buffer {
     }
 
     pub fn bytes(&self) -> &[u8] {
-        &self.0
+        &self.bytes
+    }
+
+    /// Level of the formatted record.
+    pub fn level(&self) -> Option<Level> {
+        self.level
+    }
+
+    /// Target of the formatted record.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// Key-value fields of the formatted record.
+    pub fn fields(&self) -> &[(String, String)] {
+        &self.fields
+    }
+
+    pub(crate) fn add_field(&mut self, key: String, value: String) {
+        self.fields.push((key, value));
+    }
+
+    pub(crate) fn set_level(&mut self, level: Option<Level>) {
+        self.level = level;
+    }
+
+    pub(crate) fn set_target(&mut self, target: &str) {
+        self.target.clear();
+        self.target.push_str(target);
     }
 }
 
 impl From<String> for Buffer {
     fn from(value: String) -> Self {
-        Buffer(value.into_bytes())
+        Buffer {
+            bytes: value.into_bytes(),
+            level: None,
+            target: String::new(),
+            fields: vec![],
+        }
     }
 }
 
 impl From<&str> for Buffer {
     fn from(value: &str) -> Self {
-        Buffer(value.as_bytes().to_owned())
+        Buffer::from(value.to_owned())
     }
 }
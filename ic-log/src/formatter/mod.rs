@@ -34,10 +34,12 @@ use std::{fmt, io};
 
 pub mod buffer;
 mod humantime;
+use log::kv::{self, Key, Value, VisitSource};
 use log::Record;
 
 use self::buffer::Buffer;
 use self::humantime::Rfc3339Timestamp;
+use crate::context::current_log_context;
 use crate::writer::Writer;
 
 /// A formatter to write logs into.
@@ -63,6 +65,30 @@ impl Formatter {
     pub(crate) fn clear(&mut self) {
         self.buf.borrow_mut().clear()
     }
+
+    /// Stores the metadata of the `record` for the writers.
+    pub(crate) fn set_metadata(&mut self, record: &Record) {
+        let mut buf = self.buf.borrow_mut();
+        buf.set_level(Some(record.level()));
+        buf.set_target(record.target());
+
+        struct FieldCollector<'a>(&'a mut Buffer);
+
+        impl<'kvs> VisitSource<'kvs> for FieldCollector<'_> {
+            fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+                self.0.add_field(key.to_string(), value.to_string());
+                Ok(())
+            }
+        }
+
+        let _ = record.key_values().visit(&mut FieldCollector(&mut buf));
+
+        if let Some(context) = current_log_context() {
+            for (key, value) in context.fields() {
+                buf.add_field(key.to_string(), value);
+            }
+        }
+    }
 }
 
 impl Write for Formatter {
@@ -81,7 +107,8 @@ impl fmt::Debug for Formatter {
     }
 }
 
-pub(crate) type FormatFn = Box<dyn Fn(&mut Formatter, &Record) -> io::Result<()> + Sync + Send>;
+/// Function that formats a log record.
+pub type FormatFn = Box<dyn Fn(&mut Formatter, &Record) -> io::Result<()> + Sync + Send>;
 
 pub(crate) struct Builder {
     pub timestamp: bool,
@@ -228,6 +255,38 @@ impl DefaultFormat<'_> {
         }
     }
 
+    /// Writes the key-value fields of the record as ` key=value` pairs.
+    fn write_key_values(&mut self, record: &Record) -> io::Result<()> {
+        struct KeyValueWriter<'a>(&'a mut Formatter);
+
+        impl<'kvs> VisitSource<'kvs> for KeyValueWriter<'_> {
+            fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+                write!(self.0, " {key}={value}")
+                    .map_err(|_| kv::Error::msg("failed to write key-value field"))
+            }
+        }
+
+        record
+            .key_values()
+            .visit(&mut KeyValueWriter(self.formatter))
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string()))?;
+
+        self.write_context()
+    }
+
+    /// Writes the context of the current message, if it is set.
+    fn write_context(&mut self) -> io::Result<()> {
+        match current_log_context() {
+            Some(context) => {
+                for (key, value) in context.fields() {
+                    write!(self.formatter, " {key}={value}")?;
+                }
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
     fn finish_header(&mut self) -> io::Result<()> {
         if self.written_header_value {
             let close_brace = self.subtle_style("]");
@@ -240,7 +299,11 @@ impl DefaultFormat<'_> {
     fn write_args(&mut self, record: &Record) -> io::Result<()> {
         match self.indent {
             // Fast path for no indentation
-            None => write!(self.formatter, "{}{}", record.args(), self.suffix),
+            None => {
+                write!(self.formatter, "{}", record.args())?;
+                self.write_key_values(record)?;
+                write!(self.formatter, "{}", self.suffix)
+            }
 
             Some(indent_count) => {
                 // Create a wrapper around the buffer only if we have to actually indent the message
@@ -284,6 +347,7 @@ impl DefaultFormat<'_> {
                     write!(wrapper, "{}", record.args())?;
                 }
 
+                self.write_key_values(record)?;
                 write!(self.formatter, "{}", self.suffix)?;
 
                 Ok(())
@@ -325,6 +389,42 @@ mod tests {
         write_target("", fmt)
     }
 
+    #[test]
+    fn format_key_values() {
+        let mut f = Formatter::default();
+        let buf = f.buf.clone();
+        let fields = [("request_id", 42)];
+        let record = Record::builder()
+            .args(format_args!("log message"))
+            .level(Level::Info)
+            .key_values(&fields)
+            .build();
+
+        f.set_metadata(&record);
+        DefaultFormat {
+            timestamp: false,
+            module_path: false,
+            target: false,
+            level: true,
+            written_header_value: false,
+            indent: Some(4),
+            suffix: "\n",
+            formatter: &mut f,
+        }
+        .write(&record)
+        .unwrap();
+
+        let buf = buf.borrow();
+        assert_eq!(
+            String::from_utf8(buf.bytes().to_vec()).unwrap(),
+            "[INFO ] log message request_id=42\n"
+        );
+        assert_eq!(
+            buf.fields(),
+            &[("request_id".to_string(), "42".to_string())]
+        );
+    }
+
     #[test]
     fn format_with_header() {
         let mut f = Formatter::default();
@@ -510,4 +610,30 @@ mod tests {
 
         assert_eq!("[INFO  test::path] log\nmessage\n", written);
     }
+
+    #[test]
+    fn format_with_context() {
+        let mut f = Formatter::default();
+
+        let context = crate::context::log_context("transfer", candid::Principal::anonymous());
+        let written = write(DefaultFormat {
+            timestamp: false,
+            module_path: false,
+            target: false,
+            level: true,
+            written_header_value: false,
+            indent: None,
+            suffix: "\n",
+            formatter: &mut f,
+        });
+        let message_id = crate::context::current_log_context().unwrap().message_id;
+        drop(context);
+
+        assert_eq!(
+            format!(
+                "[INFO ] log\nmessage method=transfer caller=2vxsx-fae message_id={message_id}\n"
+            ),
+            written
+        );
+    }
 }
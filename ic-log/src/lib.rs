@@ -5,12 +5,15 @@
 //! enable), which simplifies adding logging configuration to your canister.
 
 use env_filter::{Filter, ParseError};
-use formatter::FormatFn;
-use writer::{ConsoleWriter, InMemoryWriter, Logs, MultiWriter, Writer};
+pub use formatter::{FormatFn, Formatter};
+use writer::{ConsoleWriter, CounterWriter, InMemoryWriter, Logs, MultiWriter, Route, Writer};
 
 #[cfg(feature = "canister")]
 pub mod canister;
 
+#[cfg(feature = "compression")]
+mod compression;
+pub mod context;
 pub mod did;
 mod formatter;
 mod platform;
@@ -18,6 +21,7 @@ mod settings;
 pub mod writer;
 
 use std::cell::RefCell;
+use std::io;
 use std::sync::Arc;
 
 use arc_swap::{ArcSwap, ArcSwapAny};
@@ -26,7 +30,6 @@ use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
 pub use settings::{LogSettings, LogSettingsV2};
 
 use crate::did::LogCanisterError;
-use crate::formatter::Formatter;
 
 /// The logger.
 ///
@@ -119,6 +122,30 @@ impl Builder {
         self
     }
 
+    /// Sets the format function for formatting the log output.
+    ///
+    /// This function is called on each record logged into this logger. It overrides all the
+    /// default format switches, such as [`Builder::format_level`].
+    ///
+    /// # Examples
+    ///
+    /// Use a custom format to write only the log message:
+    ///
+    /// ```
+    /// use std::io::Write;
+    /// use ic_log::Builder;
+    ///
+    /// let builder = Builder::new()
+    ///     .format(|buf, record| writeln!(buf, "{}", record.args()));
+    /// ```
+    pub fn format<F>(mut self, format: F) -> Self
+    where
+        F: Fn(&mut Formatter, &Record) -> io::Result<()> + Sync + Send + 'static,
+    {
+        self.format.custom_format = Some(Box::new(format));
+        self
+    }
+
     /// Adds a directive to the filter for a specific module.
     ///
     /// # Examples
@@ -200,6 +227,27 @@ impl Builder {
         self
     }
 
+    /// Append a new writer that receives only the records passing the `route`.
+    ///
+    /// # Examples
+    ///
+    /// Print only errors to the console:
+    ///
+    /// ```
+    /// use ic_log::writer::{ConsoleWriter, Route};
+    /// use ic_log::Builder;
+    /// use log::LevelFilter;
+    ///
+    /// let builder = Builder::new().add_routed_writer(
+    ///     Route::all().max_level(LevelFilter::Error),
+    ///     Box::new(ConsoleWriter {}),
+    /// );
+    /// ```
+    pub fn add_routed_writer(mut self, route: Route, writer: Box<dyn Writer>) -> Self {
+        self.writer.add_routed(route, writer);
+        self
+    }
+
     /// Initializes the global logger with the built logger.
     ///
     /// This should be called early in the execution of a Rust program. Any log
@@ -225,11 +273,12 @@ impl Builder {
     pub fn build(mut self) -> (Logger, LoggerConfig) {
         let filter = Arc::new(ArcSwap::from_pointee(self.filter.build()));
 
-        let writer: Box<dyn Writer> = if self.writer.writers.len() == 1 {
-            self.writer.writers.remove(0)
-        } else {
-            Box::new(self.writer)
-        };
+        let writer: Box<dyn Writer> =
+            if self.writer.writers.len() == 1 && self.writer.writers[0].0 == Route::all() {
+                self.writer.writers.remove(0).1
+            } else {
+                Box::new(self.writer)
+            };
 
         (
             Logger {
@@ -300,6 +349,7 @@ impl Log for Logger {
             }
 
             let print = |formatter: &mut Formatter, record: &Record| {
+                formatter.set_metadata(record);
                 let _ = (self.format)(formatter, record)
                     .and_then(|_| formatter.print(self.writer.as_ref()));
 
@@ -367,7 +417,9 @@ pub fn init_log(settings: &LogSettingsV2) -> Result<LoggerConfig, LogCanisterErr
     }
 
     writer::InMemoryWriter::init_buffer(settings.in_memory_records, settings.max_record_length);
-    builder = builder.add_writer(Box::new(InMemoryWriter {}));
+    builder = builder
+        .add_writer(Box::new(InMemoryWriter {}))
+        .add_writer(Box::new(CounterWriter {}));
 
     let config = builder.try_init()?;
 
@@ -375,6 +427,8 @@ pub fn init_log(settings: &LogSettingsV2) -> Result<LoggerConfig, LogCanisterErr
 }
 
 /// Take the log memory records for the circular buffer.
+///
+/// See [`InMemoryWriter::take_records`] for details about the record offsets.
 pub fn take_memory_records(max_count: usize, from_offset: usize) -> Logs {
     writer::InMemoryWriter::take_records(max_count, from_offset)
 }
@@ -382,9 +436,45 @@ pub fn take_memory_records(max_count: usize, from_offset: usize) -> Logs {
 #[cfg(test)]
 mod tests {
 
+    use std::io::Write;
+    use std::sync::Mutex;
+
     use log::*;
 
     use super::*;
+    use crate::formatter::buffer::Buffer;
+
+    #[derive(Default, Clone)]
+    struct TestWriter(Arc<Mutex<Vec<String>>>);
+
+    impl Writer for TestWriter {
+        fn print(&self, buf: &Buffer) -> std::io::Result<()> {
+            self.0
+                .lock()
+                .unwrap()
+                .push(String::from_utf8_lossy(buf.bytes()).to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn custom_format_is_used() {
+        let writer = TestWriter::default();
+        let (logger, _) = Builder::default()
+            .filter_level(LevelFilter::Info)
+            .format(|buf, record| write!(buf, "{}|{}", record.level(), record.args()))
+            .add_writer(Box::new(writer.clone()))
+            .build();
+
+        logger.log(
+            &Record::builder()
+                .args(format_args!("custom message"))
+                .level(Level::Warn)
+                .build(),
+        );
+
+        assert_eq!(*writer.0.lock().unwrap(), vec!["WARN|custom message"]);
+    }
 
     #[test]
     fn update_filter_at_runtime() {
@@ -1,7 +1,9 @@
 use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
 use candid::CandidType;
+use log::{Level, LevelFilter};
 use ringbuffer::{AllocRingBuffer, RingBuffer};
 use serde::{Deserialize, Serialize};
 
@@ -13,23 +15,92 @@ pub trait Writer: Send + Sync {
     fn print(&self, buf: &Buffer) -> std::io::Result<()>;
 }
 
-/// Writer implementation that prints the given data to the console
+/// Selects the records passed to a writer of the [`MultiWriter`].
+///
+/// # Examples
+///
+/// Route only warnings and errors of the `audit` target and its submodules:
+///
+/// ```
+/// use ic_log::writer::Route;
+/// use log::LevelFilter;
+///
+/// let route = Route::all().max_level(LevelFilter::Warn).target("audit");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route {
+    max_level: LevelFilter,
+    target: Option<String>,
+}
+
+impl Route {
+    /// Route that passes all the records.
+    pub fn all() -> Self {
+        Self {
+            max_level: LevelFilter::Trace,
+            target: None,
+        }
+    }
+
+    /// Passes only the records with the `level` or more severe.
+    pub fn max_level(mut self, level: LevelFilter) -> Self {
+        self.max_level = level;
+        self
+    }
+
+    /// Passes only the records of the `target` or its submodules.
+    pub fn target(mut self, target: &str) -> Self {
+        self.target = Some(target.to_string());
+        self
+    }
+
+    /// Checks if the record written to the `buf` passes the route. Records without metadata
+    /// pass the level check.
+    pub fn matches(&self, buf: &Buffer) -> bool {
+        let level_matches = buf.level().map_or(true, |level| level <= self.max_level);
+        let target_matches = match &self.target {
+            Some(target) => buf
+                .target()
+                .strip_prefix(target.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with("::")),
+            None => true,
+        };
+
+        level_matches && target_matches
+    }
+}
+
+impl Default for Route {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+/// Writer implementation that passes the given data to the added writers, optionally routing
+/// the records with [`Route`]s.
 #[derive(Default)]
 pub struct MultiWriter {
-    pub(crate) writers: Vec<Box<dyn Writer>>,
+    pub(crate) writers: Vec<(Route, Box<dyn Writer>)>,
 }
 
 impl MultiWriter {
     /// Add a new writer
     pub fn add(&mut self, writer: Box<dyn Writer>) {
-        self.writers.push(writer)
+        self.add_routed(Route::all(), writer)
+    }
+
+    /// Add a new writer that receives only the records passing the `route`.
+    pub fn add_routed(&mut self, route: Route, writer: Box<dyn Writer>) {
+        self.writers.push((route, writer))
     }
 }
 
 impl Writer for MultiWriter {
     fn print(&self, buf: &Buffer) -> std::io::Result<()> {
-        for writer in &self.writers {
-            writer.print(buf)?;
+        for (route, writer) in &self.writers {
+            if route.matches(buf) {
+                writer.print(buf)?;
+            }
         }
         Ok(())
     }
@@ -45,18 +116,278 @@ impl Writer for ConsoleWriter {
     }
 }
 
+/// Number of records written with a level and a target.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct LogCounter {
+    /// Level of the records, e.g. `ERROR`.
+    pub level: String,
+    /// Target of the records.
+    pub target: String,
+    /// Number of the records.
+    pub count: u64,
+}
+
+thread_local! {
+    /// Number of records by level and target.
+    static LOG_COUNTERS: RefCell<BTreeMap<Level, BTreeMap<String, u64>>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+/// Writer that counts the written records per level and target.
+///
+/// The counters make it possible to monitor the number of errors and warnings, e.g. with
+/// the `log-counters` feature of `ic-metrics`, without exporting the logs.
+pub struct CounterWriter {}
+
+impl CounterWriter {
+    /// Returns the number of records written with every level and target.
+    pub fn counters() -> Vec<LogCounter> {
+        LOG_COUNTERS.with(|counters| {
+            counters
+                .borrow()
+                .iter()
+                .flat_map(|(level, targets)| {
+                    targets.iter().map(|(target, count)| LogCounter {
+                        level: level.to_string(),
+                        target: target.clone(),
+                        count: *count,
+                    })
+                })
+                .collect()
+        })
+    }
+
+    /// Returns the number of records written with the `level`.
+    pub fn level_count(level: Level) -> u64 {
+        LOG_COUNTERS.with(|counters| {
+            counters
+                .borrow()
+                .get(&level)
+                .map(|targets| targets.values().sum())
+                .unwrap_or_default()
+        })
+    }
+
+    /// Resets all the counters.
+    pub fn reset() {
+        LOG_COUNTERS.with(|counters| counters.borrow_mut().clear());
+    }
+}
+
+impl Writer for CounterWriter {
+    fn print(&self, buf: &Buffer) -> std::io::Result<()> {
+        let Some(level) = buf.level() else {
+            return Ok(());
+        };
+
+        LOG_COUNTERS.with(|counters| {
+            let mut counters = counters.borrow_mut();
+            let targets = counters.entry(level).or_default();
+            // The target is allocated only for the first record of the target.
+            match targets.get_mut(buf.target()) {
+                Some(count) => *count += 1,
+                None => {
+                    targets.insert(buf.target().to_string(), 1);
+                }
+            }
+        });
+
+        Ok(())
+    }
+}
+
 const INIT_LOG_CAPACITY: usize = 128;
 
-type LogRecordsBuffer = AllocRingBuffer<String>;
+type LogRecordsBuffer = AllocRingBuffer<StoredRecord>;
 thread_local! {
-    static LOG_RECORDS: RefCell<(usize, LogRecordsBuffer)> =
-        RefCell::new((0, LogRecordsBuffer::new(INIT_LOG_CAPACITY)));
+    static LOG_RECORDS: RefCell<LogRecords> = RefCell::new(LogRecords::new(INIT_LOG_CAPACITY));
     static IS_ENABLED: AtomicBool = const { AtomicBool::new(false) };
     static MAX_RECORD_LENGTH: AtomicUsize = const { AtomicUsize::new(0) };
 }
 
+#[derive(Debug, Clone)]
+struct StoredRecord {
+    offset: usize,
+    level: Option<Level>,
+    fields: Vec<(String, String)>,
+    log: String,
+}
+
+/// Records stored by the [`InMemoryWriter`].
+struct LogRecords {
+    /// Total number of written records, used as the offset of the next record.
+    count: usize,
+    /// Records of the levels without a dedicated buffer.
+    shared: LogRecordsBuffer,
+    /// Dedicated buffers of the levels with configured capacity.
+    levels: Vec<(Level, LogRecordsBuffer)>,
+    /// Compressed records evicted from the buffers.
+    #[cfg(feature = "compression")]
+    archive: Option<crate::compression::LogArchive>,
+}
+
+impl LogRecords {
+    fn new(capacity: usize) -> Self {
+        Self {
+            count: 0,
+            shared: LogRecordsBuffer::new(capacity.max(1)),
+            levels: vec![],
+            #[cfg(feature = "compression")]
+            archive: None,
+        }
+    }
+
+    fn push(&mut self, level: Option<Level>, fields: Vec<(String, String)>, log: String) {
+        let record = StoredRecord {
+            offset: self.count,
+            level,
+            fields,
+            log,
+        };
+        self.count += 1;
+        self.store(record);
+    }
+
+    /// Stores the record in the buffer of its level, archiving the evicted record if
+    /// the compression is enabled.
+    fn store(&mut self, record: StoredRecord) {
+        let buffer = self.buffer_mut(record.level);
+        let evicted = if buffer.is_full() {
+            buffer.dequeue()
+        } else {
+            None
+        };
+        buffer.push(record);
+
+        #[cfg(feature = "compression")]
+        if let (Some(evicted), Some(archive)) = (evicted, self.archive.as_mut()) {
+            archive.push(evicted.offset, evicted.log);
+        }
+        #[cfg(not(feature = "compression"))]
+        drop(evicted);
+    }
+
+    fn buffer_mut(&mut self, level: Option<Level>) -> &mut LogRecordsBuffer {
+        match self
+            .levels
+            .iter_mut()
+            .find(|(buffer_level, _)| Some(*buffer_level) == level)
+        {
+            Some((_, buffer)) => buffer,
+            None => &mut self.shared,
+        }
+    }
+
+    /// Returns the stored records sorted by offset.
+    fn sorted(&self) -> Vec<&StoredRecord> {
+        let mut records: Vec<_> = self
+            .shared
+            .iter()
+            .chain(self.levels.iter().flat_map(|(_, buffer)| buffer.iter()))
+            .collect();
+        if !self.levels.is_empty() {
+            records.sort_unstable_by_key(|record| record.offset);
+        }
+
+        records
+    }
+
+    /// Returns the stored records of the `max_level` and the less verbose levels sorted by
+    /// offset. The dedicated buffers of the more verbose levels are not read.
+    fn sorted_up_to_level(&self, max_level: Level) -> Vec<&StoredRecord> {
+        let mut records: Vec<_> = self
+            .shared
+            .iter()
+            .filter(|record| record.level.is_some_and(|level| level <= max_level))
+            .chain(
+                self.levels
+                    .iter()
+                    .filter(|(level, _)| *level <= max_level)
+                    .flat_map(|(_, buffer)| buffer.iter()),
+            )
+            .collect();
+        records.sort_unstable_by_key(|record| record.offset);
+        records
+    }
+
+    fn level_capacities(&self) -> Vec<(Level, usize)> {
+        self.levels
+            .iter()
+            .map(|(level, buffer)| (*level, buffer.capacity()))
+            .collect()
+    }
+
+    /// Recreates the buffers with the given capacities keeping the latest records.
+    fn rebuild(&mut self, shared_capacity: usize, level_capacities: Vec<(Level, usize)>) {
+        let records: Vec<StoredRecord> = self.sorted().into_iter().cloned().collect();
+        self.shared = LogRecordsBuffer::new(shared_capacity.max(1));
+        self.levels = level_capacities
+            .into_iter()
+            .map(|(level, capacity)| (level, LogRecordsBuffer::new(capacity.max(1))))
+            .collect();
+
+        for record in records {
+            self.store(record);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.shared.clear();
+        for (_, buffer) in &mut self.levels {
+            buffer.clear();
+        }
+
+        #[cfg(feature = "compression")]
+        if let Some(archive) = &mut self.archive {
+            archive.clear();
+        }
+    }
+
+    /// Returns the stored and the archived records sorted by offset.
+    fn all_sorted(&self) -> Vec<StoredRecord> {
+        let mut records = self.archived(0);
+        records.extend(self.sorted().into_iter().cloned());
+        records.sort_unstable_by_key(|record| record.offset);
+        records
+    }
+
+    /// Returns the `segment_records` and `max_segments` settings of the archive, if the
+    /// compression is enabled.
+    fn compression(&self) -> Option<(usize, usize)> {
+        #[cfg(feature = "compression")]
+        if let Some(archive) = &self.archive {
+            return Some(archive.settings());
+        }
+
+        None
+    }
+
+    /// Returns the archived records with the offset not less than `from_offset`.
+    fn archived(&self, from_offset: usize) -> Vec<StoredRecord> {
+        #[cfg(feature = "compression")]
+        if let Some(archive) = &self.archive {
+            return archive
+                .records(from_offset)
+                .into_iter()
+                .map(|(offset, log)| StoredRecord {
+                    offset,
+                    level: None,
+                    fields: vec![],
+                    log,
+                })
+                .collect();
+        }
+
+        let _ = from_offset;
+        vec![]
+    }
+}
+
 /// Writer that stores strings in a thread_local memory circular buffer.
 /// Note: it can be optimized to reduce the number of memory allocations.
+///
+/// By default, the records of all levels share one buffer. To prevent important records from
+/// being evicted by verbose output, a level can be given a dedicated buffer with
+/// [`InMemoryWriter::set_level_capacity`].
 pub struct InMemoryWriter {}
 
 #[derive(Debug, Default, PartialEq, Eq, CandidType, Serialize, Deserialize)]
@@ -67,68 +398,185 @@ pub struct Logs {
     pub all_logs_count: usize,
 }
 
+impl Logs {
+    /// Returns the offset to request the records following the ones in this response.
+    pub fn next_offset(&self) -> usize {
+        self.logs
+            .last()
+            .map(|log| log.offset + 1)
+            .unwrap_or(self.all_logs_count)
+    }
+}
+
 #[derive(Debug, Default, PartialEq, Eq, CandidType, Serialize, Deserialize)]
 pub struct Log {
     /// the log text
     pub log: String,
-    /// the offset of the log
+    /// the offset of the log, a unique id of the record that increases monotonically
     pub offset: usize,
 }
 
+/// Records and configuration of the [`InMemoryWriter`] buffers, exported with
+/// [`InMemoryWriter::export_records`] to be restored after an upgrade.
+#[derive(Debug, Default, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct ExportedRecords {
+    /// Total number of written records.
+    pub all_logs_count: u64,
+    /// Stored and archived records, from the oldest to the newest.
+    pub records: Vec<ExportedRecord>,
+    /// Capacities of the dedicated level buffers, see [`InMemoryWriter::set_level_capacity`].
+    pub level_capacities: Vec<(String, u64)>,
+    /// `segment_records` and `max_segments` settings of the compression, if enabled.
+    pub compression: Option<(u64, u64)>,
+}
+
+/// A record exported with [`InMemoryWriter::export_records`].
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Serialize, Deserialize)]
+pub struct ExportedRecord {
+    pub offset: u64,
+    /// Level of the record. Archived records don't keep their level.
+    pub level: Option<String>,
+    /// Key-value fields of the record. Archived records don't keep their fields.
+    pub fields: Vec<(String, String)>,
+    pub log: String,
+}
+
+impl From<&StoredRecord> for ExportedRecord {
+    fn from(record: &StoredRecord) -> Self {
+        Self {
+            offset: record.offset as u64,
+            level: record.level.map(|level| level.to_string()),
+            fields: record.fields.clone(),
+            log: record.log.clone(),
+        }
+    }
+}
+
+impl From<ExportedRecord> for StoredRecord {
+    fn from(record: ExportedRecord) -> Self {
+        Self {
+            offset: record.offset as usize,
+            level: record.level.and_then(|level| level.parse().ok()),
+            fields: record.fields,
+            log: record.log,
+        }
+    }
+}
+
 impl InMemoryWriter {
     pub fn init_buffer(capacity: usize, max_record_length: usize) {
         MAX_RECORD_LENGTH.with(|v| v.store(max_record_length, Ordering::Relaxed));
         LOG_RECORDS.with(|records| {
+            *records.borrow_mut() = LogRecords::new(capacity);
             if capacity > 0 {
-                *records.borrow_mut() = (0, LogRecordsBuffer::new(capacity));
                 Self::enable()
             } else {
-                *records.borrow_mut() = (0, LogRecordsBuffer::new(1));
                 Self::disable()
             }
         });
     }
 
+    /// Returns at most `max_count` records starting from the record with the `from_offset` id.
+    ///
+    /// Every record gets a monotonically increasing id (returned as [`Log::offset`]) that is
+    /// not affected by the buffer wrapping around, so the records can be read incrementally by
+    /// passing [`Logs::next_offset`] of the previous response as `from_offset`. If the record with
+    /// the `from_offset` id was already evicted from the buffer, the records are returned starting
+    /// from the oldest stored one.
     pub fn take_records(max_count: usize, from_offset: usize) -> Logs {
+        Self::take_filtered_records(max_count, from_offset, |_| true)
+    }
+
+    /// Returns at most `max_count` records with the `key` field equal to the `value`, starting
+    /// from the record with the `from_offset` id.
+    ///
+    /// The fields are added to the records with the key-value syntax of the `log` macros, e.g.
+    /// `log::info!(request_id = 42; "message")`.
+    pub fn take_records_with_field(
+        key: &str,
+        value: &str,
+        max_count: usize,
+        from_offset: usize,
+    ) -> Logs {
+        Self::take_filtered_records(max_count, from_offset, |record| {
+            record
+                .fields
+                .iter()
+                .any(|(field_key, field_value)| field_key == key && field_value == value)
+        })
+    }
+
+    /// Returns at most `max_count` records of the `max_level` and the less verbose levels,
+    /// starting from the record with the `from_offset` id. If `from_offset` is `None`, the latest
+    /// records of the levels are returned.
+    ///
+    /// The records are selected by the level they were written with, so the level doesn't have
+    /// to be present in the record format. Archived records don't keep their level, so they are
+    /// not returned.
+    pub fn take_records_up_to_level(
+        max_level: Level,
+        max_count: usize,
+        from_offset: Option<usize>,
+    ) -> Logs {
         if !Self::is_enabled() {
             return Logs::default();
         }
 
         LOG_RECORDS.with(|records| {
-            let records = records.borrow_mut();
-            let all_logs_count = records.0;
+            let records = records.borrow();
+            let matching = records.sorted_up_to_level(max_level);
+            let start = match from_offset {
+                Some(from_offset) => matching.partition_point(|record| record.offset < from_offset),
+                None => matching.len().saturating_sub(max_count),
+            };
+
+            let logs = matching[start..]
+                .iter()
+                .take(max_count)
+                .map(|record| Log {
+                    log: record.log.clone(),
+                    offset: record.offset,
+                })
+                .collect();
 
-            if (from_offset >= all_logs_count) || all_logs_count == 0 {
-                Logs {
-                    all_logs_count,
-                    logs: vec![],
-                }
-            } else {
-                let mut result = Vec::with_capacity(max_count);
-
-                let from_offset = if (from_offset + records.1.len()) < all_logs_count {
-                    0
-                } else if all_logs_count > records.1.len() {
-                    from_offset - (all_logs_count % records.1.capacity())
-                } else {
-                    from_offset
-                };
-
-                let first_index = from_offset % records.1.capacity();
-                let mut offset = all_logs_count + first_index - records.1.len();
-
-                for log in records.1.iter().skip(first_index).take(max_count) {
-                    result.push(Log {
-                        log: log.clone(),
-                        offset,
-                    });
-                    offset += 1;
-                }
+            Logs {
+                logs,
+                all_logs_count: records.count,
+            }
+        })
+    }
 
-                Logs {
-                    all_logs_count,
-                    logs: result,
-                }
+    fn take_filtered_records(
+        max_count: usize,
+        from_offset: usize,
+        filter: impl Fn(&StoredRecord) -> bool,
+    ) -> Logs {
+        if !Self::is_enabled() {
+            return Logs::default();
+        }
+
+        LOG_RECORDS.with(|records| {
+            let records = records.borrow();
+            let archived = records.archived(from_offset);
+            let mut stored = records.sorted();
+            if !archived.is_empty() {
+                stored.extend(&archived);
+                stored.sort_unstable_by_key(|record| record.offset);
+            }
+
+            let logs = stored
+                .into_iter()
+                .filter(|record| record.offset >= from_offset && filter(record))
+                .take(max_count)
+                .map(|record| Log {
+                    log: record.log.clone(),
+                    offset: record.offset,
+                })
+                .collect();
+
+            Logs {
+                logs,
+                all_logs_count: records.count,
             }
         })
     }
@@ -145,24 +593,155 @@ impl InMemoryWriter {
         IS_ENABLED.with(|v| v.load(Ordering::Relaxed))
     }
 
+    /// Returns the total number of records written to the buffer, the stored and archived
+    /// records with their levels and fields, and the configuration of the level buffers and the
+    /// compression.
+    pub fn export_records() -> ExportedRecords {
+        LOG_RECORDS.with(|records| {
+            let records = records.borrow();
+            ExportedRecords {
+                all_logs_count: records.count as u64,
+                records: records.all_sorted().iter().map(Into::into).collect(),
+                level_capacities: records
+                    .level_capacities()
+                    .into_iter()
+                    .map(|(level, capacity)| (level.to_string(), capacity as u64))
+                    .collect(),
+                compression: records
+                    .compression()
+                    .map(|(segment_records, max_segments)| {
+                        (segment_records as u64, max_segments as u64)
+                    }),
+            }
+        })
+    }
+
+    /// Restores the records and the configuration previously returned by
+    /// [`InMemoryWriter::export_records`].
+    ///
+    /// The exported level buffers and compression settings replace the current ones. The
+    /// restored records are placed before the records already stored in the buffer, and the
+    /// offsets of the stored records are shifted by `all_logs_count`. If the buffer is disabled,
+    /// the records are dropped.
+    pub fn import_records(exported: ExportedRecords) {
+        if !Self::is_enabled() {
+            return;
+        }
+
+        #[cfg(feature = "compression")]
+        match exported.compression {
+            Some((segment_records, max_segments)) => {
+                Self::enable_compression(segment_records as usize, max_segments as usize)
+            }
+            None => Self::disable_compression(),
+        }
+
+        LOG_RECORDS.with(|buffer| {
+            let mut buffer = buffer.borrow_mut();
+            let current: Vec<StoredRecord> = buffer.sorted().into_iter().cloned().collect();
+            buffer.clear();
+
+            let shared_capacity = buffer.shared.capacity();
+            let level_capacities = exported
+                .level_capacities
+                .iter()
+                .filter_map(|(level, capacity)| Some((level.parse().ok()?, *capacity as usize)))
+                .collect();
+            buffer.rebuild(shared_capacity, level_capacities);
+
+            for record in exported.records {
+                buffer.store(record.into());
+            }
+
+            let all_logs_count = exported.all_logs_count as usize;
+            for mut record in current {
+                record.offset += all_logs_count;
+                buffer.store(record);
+            }
+
+            buffer.count += all_logs_count;
+        });
+    }
+
+    /// Removes all the records from the buffer. The offsets of the records written later
+    /// continue from the current total number of records.
+    pub fn clear_records() {
+        LOG_RECORDS.with(|records| records.borrow_mut().clear());
+    }
+
+    /// Enables compression of the records evicted from the buffers.
+    ///
+    /// The evicted records are compressed in segments of `segment_records` records and at most
+    /// `max_segments` segments are kept, so the history retained by the writer is extended by
+    /// up to `segment_records * max_segments` records. The compressed records are returned by
+    /// [`InMemoryWriter::take_records`] transparently, but they don't keep their key-value fields.
+    ///
+    /// The compression is disabled by [`InMemoryWriter::init_buffer`].
+    #[cfg(feature = "compression")]
+    pub fn enable_compression(segment_records: usize, max_segments: usize) {
+        LOG_RECORDS.with(|records| {
+            records.borrow_mut().archive = Some(crate::compression::LogArchive::new(
+                segment_records,
+                max_segments,
+            ));
+        });
+    }
+
+    /// Disables compression and drops the compressed records.
+    #[cfg(feature = "compression")]
+    pub fn disable_compression() {
+        LOG_RECORDS.with(|records| records.borrow_mut().archive = None);
+    }
+
+    /// Size of the compressed records in bytes.
+    #[cfg(feature = "compression")]
+    pub fn compressed_size() -> usize {
+        LOG_RECORDS.with(|records| {
+            records
+                .borrow()
+                .archive
+                .as_ref()
+                .map(|archive| archive.compressed_size())
+                .unwrap_or_default()
+        })
+    }
+
     pub fn change_capacity(capacity: usize) {
         LOG_RECORDS.with(|records| {
-            let all_logs_count = records.borrow().0;
+            let mut records = records.borrow_mut();
             if capacity > 0 {
-                let logs = Self::take_records(capacity, all_logs_count.saturating_sub(capacity));
-                let mut buffer = LogRecordsBuffer::new(capacity);
-                for log in logs.logs {
-                    buffer.push(log.log);
-                }
-
-                *records.borrow_mut() = (all_logs_count, buffer);
+                let level_capacities = records.level_capacities();
+                records.rebuild(capacity, level_capacities);
                 Self::enable()
             } else {
-                *records.borrow_mut() = (all_logs_count, LogRecordsBuffer::new(1));
+                let all_logs_count = records.count;
+                *records = LogRecords::new(1);
+                records.count = all_logs_count;
                 Self::disable()
             }
         });
     }
+
+    /// Sets the capacity of the dedicated buffer for the records of the `level`.
+    ///
+    /// With `Some` capacity, the records of the `level` are stored in a separate buffer and are
+    /// not evicted by the records of other levels. The capacity is at least one record. With
+    /// `None`, the records of the `level` are stored in the shared buffer.
+    ///
+    /// The dedicated buffers are reset by [`InMemoryWriter::init_buffer`].
+    pub fn set_level_capacity(level: Level, capacity: Option<usize>) {
+        LOG_RECORDS.with(|records| {
+            let mut records = records.borrow_mut();
+            let shared_capacity = records.shared.capacity();
+            let mut level_capacities = records.level_capacities();
+            level_capacities.retain(|(buffer_level, _)| *buffer_level != level);
+            if let Some(capacity) = capacity {
+                level_capacities.push((level, capacity));
+            }
+
+            records.rebuild(shared_capacity, level_capacities);
+        });
+    }
 }
 
 impl Writer for InMemoryWriter {
@@ -174,9 +753,9 @@ impl Writer for InMemoryWriter {
         let max_length = MAX_RECORD_LENGTH.with(|v| v.load(Ordering::Relaxed));
 
         LOG_RECORDS.with(|records| {
-            let mut borrow = records.borrow_mut();
-            borrow.0 += 1;
-            borrow.1.push(
+            records.borrow_mut().push(
+                buf.level(),
+                buf.fields().to_vec(),
                 String::from_utf8_lossy(&buf.bytes()[0..max_length.min(buf.bytes().len())])
                     .to_string(),
             );
@@ -208,20 +787,22 @@ pub mod tests {
         LOG_RECORDS.with(|records| {
             assert!(records
                 .borrow()
-                .1
+                .shared
                 .iter()
+                .map(|record| &record.log)
                 .eq(["some data".to_string()].iter()));
-            assert_eq!(records.borrow().0, 1);
+            assert_eq!(records.borrow().count, 1);
         });
 
         writer.print(&"some more data".into()).unwrap();
         LOG_RECORDS.with(|records| {
-            assert!(records.borrow().1.iter().eq([
-                "some data".to_string(),
-                "some more data".to_string()
-            ]
-            .iter()));
-            assert_eq!(records.borrow().0, 2);
+            assert!(records
+                .borrow()
+                .shared
+                .iter()
+                .map(|record| &record.log)
+                .eq(["some data".to_string(), "some more data".to_string()].iter()));
+            assert_eq!(records.borrow().count, 2);
         });
     }
 
@@ -794,9 +1375,9 @@ pub mod tests {
         LOG_RECORDS.with(|records| {
             assert!(records
                 .borrow()
-                .1
+                .shared
                 .iter()
-                .cloned()
+                .map(|record| record.log.clone())
                 .eq((2..(LOG_RECORDS_MAX_COUNT + 2)).map(|i| format!("{i}"))));
         });
     }
@@ -890,6 +1471,413 @@ pub mod tests {
         }
     }
 
+    #[test]
+    fn import_records_restores_offsets() {
+        clear_memory_records();
+        let writer = InMemoryWriter {};
+        for i in 0..5 {
+            writer.print(&format!("{i}").into()).unwrap();
+        }
+
+        let exported = InMemoryWriter::export_records();
+        assert_eq!(exported.all_logs_count, 5);
+        assert_eq!(exported.records.len(), 5);
+
+        clear_memory_records();
+        writer.print(&"5".into()).unwrap();
+        InMemoryWriter::import_records(exported);
+
+        let logs = InMemoryWriter::take_records(20, 0);
+        assert_eq!(logs.all_logs_count, 6);
+        for (i, log) in logs.logs.iter().enumerate() {
+            assert_eq!(log.log, format!("{i}"));
+            assert_eq!(log.offset, i);
+        }
+    }
+
+    #[test]
+    fn import_records_keeps_newest_records() {
+        clear_memory_records();
+        let records = (0..LOG_RECORDS_MAX_COUNT * 2)
+            .map(|i| ExportedRecord {
+                offset: i as u64,
+                level: None,
+                fields: vec![],
+                log: format!("{i}"),
+            })
+            .collect();
+        InMemoryWriter::import_records(ExportedRecords {
+            all_logs_count: (LOG_RECORDS_MAX_COUNT * 2) as u64,
+            records,
+            ..Default::default()
+        });
+
+        let logs = InMemoryWriter::take_records(20, 0);
+        assert_eq!(logs.all_logs_count, LOG_RECORDS_MAX_COUNT * 2);
+        assert_eq!(logs.logs.len(), LOG_RECORDS_MAX_COUNT);
+        assert_eq!(logs.logs[0].log, format!("{LOG_RECORDS_MAX_COUNT}"));
+        assert_eq!(logs.logs[0].offset, LOG_RECORDS_MAX_COUNT);
+    }
+
+    #[test]
+    fn clear_records_preserves_offsets() {
+        clear_memory_records();
+        let writer = InMemoryWriter {};
+        writer.print(&"0".into()).unwrap();
+        writer.print(&"1".into()).unwrap();
+
+        InMemoryWriter::clear_records();
+        let logs = InMemoryWriter::take_records(10, 0);
+        assert_eq!(logs.all_logs_count, 2);
+        assert!(logs.logs.is_empty());
+
+        writer.print(&"2".into()).unwrap();
+        let logs = InMemoryWriter::take_records(10, 0);
+        assert_eq!(
+            logs.logs,
+            vec![Log {
+                log: "2".to_string(),
+                offset: 2,
+            }]
+        );
+    }
+
+    #[test]
+    fn take_records_resumes_after_wrap() {
+        clear_memory_records();
+        let writer = InMemoryWriter {};
+        let mut next_offset = 0;
+        let mut received = vec![];
+
+        for i in 0..LOG_RECORDS_MAX_COUNT * 3 {
+            writer.print(&format!("{i}").into()).unwrap();
+            if i % 5 == 0 {
+                let logs = InMemoryWriter::take_records(3, next_offset);
+                next_offset = logs.next_offset();
+                received.extend(logs.logs);
+            }
+        }
+
+        for window in received.windows(2) {
+            assert!(window[0].offset < window[1].offset);
+        }
+        for log in &received {
+            assert_eq!(log.log, log.offset.to_string());
+        }
+    }
+
+    #[test]
+    fn take_records_after_clear() {
+        clear_memory_records();
+        let writer = InMemoryWriter {};
+        for i in 0..3 {
+            writer.print(&format!("{i}").into()).unwrap();
+        }
+        InMemoryWriter::clear_records();
+        writer.print(&"3".into()).unwrap();
+
+        let logs = InMemoryWriter::take_records(10, 2);
+        assert_eq!(
+            logs.logs,
+            vec![Log {
+                log: "3".to_string(),
+                offset: 3,
+            }]
+        );
+        assert_eq!(logs.next_offset(), 4);
+        assert_eq!(InMemoryWriter::take_records(10, 4).next_offset(), 4);
+    }
+
+    fn print_with_level(writer: &InMemoryWriter, level: Level, log: &str) {
+        let mut buf = Buffer::from(log);
+        buf.set_level(Some(level));
+        writer.print(&buf).unwrap();
+    }
+
+    #[test]
+    fn level_buffer_keeps_records_of_level() {
+        clear_memory_records();
+        InMemoryWriter::set_level_capacity(Level::Error, Some(2));
+        let writer = InMemoryWriter {};
+
+        print_with_level(&writer, Level::Error, "error 0");
+        print_with_level(&writer, Level::Error, "error 1");
+        for i in 0..LOG_RECORDS_MAX_COUNT * 2 {
+            print_with_level(&writer, Level::Debug, &format!("debug {i}"));
+        }
+        print_with_level(&writer, Level::Error, "error 2");
+
+        let logs = InMemoryWriter::take_records(100, 0);
+        assert_eq!(logs.all_logs_count, LOG_RECORDS_MAX_COUNT * 2 + 3);
+        assert_eq!(logs.logs.len(), LOG_RECORDS_MAX_COUNT + 2);
+        assert_eq!(logs.logs[0].log, "error 1");
+        assert_eq!(logs.logs[0].offset, 1);
+        assert_eq!(logs.logs[1].log, format!("debug {LOG_RECORDS_MAX_COUNT}"));
+        assert_eq!(logs.logs.last().unwrap().log, "error 2");
+
+        for window in logs.logs.windows(2) {
+            assert!(window[0].offset < window[1].offset);
+        }
+    }
+
+    #[test]
+    fn take_records_up_to_level_filters_before_paging() {
+        clear_memory_records();
+        InMemoryWriter::set_level_capacity(Level::Error, Some(2));
+        let writer = InMemoryWriter {};
+
+        print_with_level(&writer, Level::Error, "error 0");
+        print_with_level(&writer, Level::Warn, "warn 1");
+        for i in 2..7 {
+            print_with_level(&writer, Level::Debug, &format!("debug {i}"));
+        }
+        print_with_level(&writer, Level::Error, "error 7");
+        writer.print(&"no level".into()).unwrap();
+
+        let logs = InMemoryWriter::take_records_up_to_level(Level::Error, 10, None);
+        let records: Vec<_> = logs.logs.iter().map(|log| log.log.as_str()).collect();
+        assert_eq!(records, vec!["error 0", "error 7"]);
+        assert_eq!(logs.all_logs_count, 9);
+
+        let logs = InMemoryWriter::take_records_up_to_level(Level::Warn, 1, None);
+        assert_eq!(logs.logs[0].log, "error 7");
+
+        let logs = InMemoryWriter::take_records_up_to_level(Level::Warn, 2, Some(0));
+        let records: Vec<_> = logs.logs.iter().map(|log| log.log.as_str()).collect();
+        assert_eq!(records, vec!["error 0", "warn 1"]);
+
+        let logs = InMemoryWriter::take_records_up_to_level(Level::Warn, 10, Some(2));
+        assert_eq!(logs.logs[0].offset, 7);
+        assert_eq!(logs.next_offset(), 8);
+    }
+
+    #[test]
+    fn set_level_capacity_moves_records() {
+        clear_memory_records();
+        let writer = InMemoryWriter {};
+        print_with_level(&writer, Level::Warn, "warn");
+        print_with_level(&writer, Level::Info, "info");
+
+        InMemoryWriter::set_level_capacity(Level::Warn, Some(4));
+        InMemoryWriter::change_capacity(1);
+        let logs = InMemoryWriter::take_records(10, 0);
+        assert_eq!(
+            logs.logs,
+            vec![
+                Log {
+                    log: "warn".to_string(),
+                    offset: 0,
+                },
+                Log {
+                    log: "info".to_string(),
+                    offset: 1,
+                }
+            ]
+        );
+
+        InMemoryWriter::set_level_capacity(Level::Warn, None);
+        let logs = InMemoryWriter::take_records(10, 0);
+        assert_eq!(logs.logs.len(), 1);
+        assert_eq!(logs.logs[0].log, "info");
+    }
+
+    #[test]
+    fn import_records_restores_levels_fields_and_level_buffers() {
+        clear_memory_records();
+        InMemoryWriter::set_level_capacity(Level::Error, Some(2));
+        let writer = InMemoryWriter {};
+        print_with_level(&writer, Level::Error, "error");
+        let mut buf = Buffer::from("info");
+        buf.set_level(Some(Level::Info));
+        buf.add_field("request_id".to_string(), "1".to_string());
+        writer.print(&buf).unwrap();
+
+        let exported = InMemoryWriter::export_records();
+        assert_eq!(exported.level_capacities, vec![("ERROR".to_string(), 2)]);
+        assert_eq!(exported.records[0].level.as_deref(), Some("ERROR"));
+        assert_eq!(exported.records[1].fields.len(), 1);
+
+        // The upgrade resets the buffers
+        clear_memory_records();
+        InMemoryWriter::import_records(exported);
+
+        let logs = InMemoryWriter::take_records_with_field("request_id", "1", 10, 0);
+        assert_eq!(logs.logs.len(), 1);
+        assert_eq!(logs.logs[0].offset, 1);
+
+        // The restored error is kept in the dedicated buffer
+        for i in 0..LOG_RECORDS_MAX_COUNT * 2 {
+            print_with_level(&writer, Level::Debug, &format!("debug {i}"));
+        }
+        let logs = InMemoryWriter::take_records(100, 0);
+        assert_eq!(logs.logs[0].log, "error");
+        assert_eq!(logs.logs[0].offset, 0);
+    }
+
+    #[test]
+    fn counter_writer_counts_records() {
+        CounterWriter::reset();
+        let writer = CounterWriter {};
+        for (level, target) in [
+            (Level::Error, "canister"),
+            (Level::Error, "canister"),
+            (Level::Error, "storage"),
+            (Level::Info, "canister"),
+        ] {
+            let mut buf = Buffer::from("log");
+            buf.set_level(Some(level));
+            buf.set_target(target);
+            writer.print(&buf).unwrap();
+        }
+        writer.print(&"no metadata".into()).unwrap();
+
+        assert_eq!(CounterWriter::level_count(Level::Error), 3);
+        assert_eq!(CounterWriter::level_count(Level::Info), 1);
+        assert_eq!(CounterWriter::level_count(Level::Warn), 0);
+        assert_eq!(
+            CounterWriter::counters(),
+            vec![
+                LogCounter {
+                    level: "ERROR".to_string(),
+                    target: "canister".to_string(),
+                    count: 2,
+                },
+                LogCounter {
+                    level: "ERROR".to_string(),
+                    target: "storage".to_string(),
+                    count: 1,
+                },
+                LogCounter {
+                    level: "INFO".to_string(),
+                    target: "canister".to_string(),
+                    count: 1,
+                },
+            ]
+        );
+
+        CounterWriter::reset();
+        assert!(CounterWriter::counters().is_empty());
+    }
+
+    #[test]
+    fn take_records_with_field_filters_records() {
+        clear_memory_records();
+        let writer = InMemoryWriter {};
+        for (i, request_id) in ["1", "2", "1"].iter().enumerate() {
+            let mut buf = Buffer::from(format!("{i}"));
+            buf.add_field("request_id".to_string(), request_id.to_string());
+            writer.print(&buf).unwrap();
+        }
+        writer.print(&"3".into()).unwrap();
+
+        let logs = InMemoryWriter::take_records_with_field("request_id", "1", 10, 0);
+        assert_eq!(logs.all_logs_count, 4);
+        assert_eq!(
+            logs.logs,
+            vec![
+                Log {
+                    log: "0".to_string(),
+                    offset: 0,
+                },
+                Log {
+                    log: "2".to_string(),
+                    offset: 2,
+                },
+            ]
+        );
+
+        let logs = InMemoryWriter::take_records_with_field("request_id", "1", 10, 1);
+        assert_eq!(logs.logs.len(), 1);
+        assert!(
+            InMemoryWriter::take_records_with_field("caller", "1", 10, 0)
+                .logs
+                .is_empty()
+        );
+    }
+
+    #[derive(Default, Clone)]
+    struct CollectingWriter(std::sync::Arc<std::sync::Mutex<Vec<String>>>);
+
+    impl Writer for CollectingWriter {
+        fn print(&self, buf: &Buffer) -> std::io::Result<()> {
+            self.0
+                .lock()
+                .unwrap()
+                .push(String::from_utf8_lossy(buf.bytes()).to_string());
+            Ok(())
+        }
+    }
+
+    fn record(level: Level, target: &str, log: &str) -> Buffer {
+        let mut buf = Buffer::from(log);
+        buf.set_level(Some(level));
+        buf.set_target(target);
+        buf
+    }
+
+    #[test]
+    fn multi_writer_routes_records() {
+        let console = CollectingWriter::default();
+        let memory = CollectingWriter::default();
+        let audit = CollectingWriter::default();
+
+        let mut writer = MultiWriter::default();
+        writer.add_routed(
+            Route::all().max_level(LevelFilter::Error),
+            Box::new(console.clone()),
+        );
+        writer.add(Box::new(memory.clone()));
+        writer.add_routed(Route::all().target("audit"), Box::new(audit.clone()));
+
+        writer
+            .print(&record(Level::Error, "canister", "error"))
+            .unwrap();
+        writer
+            .print(&record(Level::Debug, "canister", "debug"))
+            .unwrap();
+        writer
+            .print(&record(Level::Info, "audit::transfers", "transfer"))
+            .unwrap();
+        writer
+            .print(&record(Level::Info, "auditor", "not audit"))
+            .unwrap();
+
+        assert_eq!(*console.0.lock().unwrap(), vec!["error"]);
+        assert_eq!(
+            *memory.0.lock().unwrap(),
+            vec!["error", "debug", "transfer", "not audit"]
+        );
+        assert_eq!(*audit.0.lock().unwrap(), vec!["transfer"]);
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn compressed_records_are_returned() {
+        clear_memory_records();
+        InMemoryWriter::enable_compression(4, 4);
+        let writer = InMemoryWriter {};
+        for i in 0..LOG_RECORDS_MAX_COUNT * 3 {
+            writer.print(&format!("{i}").into()).unwrap();
+        }
+
+        assert!(InMemoryWriter::compressed_size() > 0);
+        let logs = InMemoryWriter::take_records(100, 0);
+        assert_eq!(logs.all_logs_count, LOG_RECORDS_MAX_COUNT * 3);
+        assert_eq!(logs.logs.len(), LOG_RECORDS_MAX_COUNT * 3);
+        for (i, log) in logs.logs.iter().enumerate() {
+            assert_eq!(log.offset, i);
+            assert_eq!(log.log, format!("{i}"));
+        }
+
+        let logs = InMemoryWriter::take_records(2, 5);
+        assert_eq!(logs.logs[0].offset, 5);
+        assert_eq!(logs.logs[1].offset, 6);
+
+        InMemoryWriter::disable_compression();
+        let logs = InMemoryWriter::take_records(100, 0);
+        assert_eq!(logs.logs.len(), LOG_RECORDS_MAX_COUNT);
+    }
+
     #[test]
     fn max_record_length_is_respected() {
         clear_memory_records();
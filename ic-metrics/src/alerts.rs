@@ -0,0 +1,204 @@
+//! Alert rules evaluated on every metrics snapshot.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use ic_exports::candid::{CandidType, Deserialize};
+
+use crate::MetricsData;
+
+thread_local! {
+    static ALERT_RULES: RefCell<Vec<AlertRule>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Condition of an alert rule.
+#[derive(Debug, Clone, CandidType, Deserialize, PartialEq, Eq)]
+pub enum AlertCondition {
+    /// Cycles balance is below the value.
+    CyclesBelow(u128),
+    /// Heap memory size in bytes is above the value.
+    HeapMemoryAbove(u64),
+    /// Stable memory size in pages is above the value.
+    StableMemoryAbove(u64),
+    /// Number of error log records written since the previous snapshot is above the value.
+    ErrorLogsAbove(u64),
+}
+
+impl AlertCondition {
+    fn is_met(&self, previous: Option<&MetricsData>, current: &MetricsData) -> bool {
+        match self {
+            AlertCondition::CyclesBelow(cycles) => current.cycles < *cycles,
+            AlertCondition::HeapMemoryAbove(size) => current.heap_memory_size > *size,
+            AlertCondition::StableMemoryAbove(size) => current.stable_memory_size > *size,
+            AlertCondition::ErrorLogsAbove(count) => {
+                let previous_errors = previous.map(|p| p.log_errors_total).unwrap_or_default();
+                current.log_errors_total.saturating_sub(previous_errors) > *count
+            }
+        }
+    }
+}
+
+/// Alert raised by a rule.
+#[derive(Debug, Clone, CandidType, Deserialize)]
+pub struct Alert {
+    pub rule: String,
+    pub condition: AlertCondition,
+    pub metrics: MetricsData,
+}
+
+type AlertCallback = Rc<dyn Fn(&Alert)>;
+
+struct AlertRule {
+    name: String,
+    condition: AlertCondition,
+    callback: AlertCallback,
+    active: bool,
+}
+
+/// Registry of the alert rules.
+pub struct Alerts {}
+
+impl Alerts {
+    /// Adds an alert rule with the `name`, replacing the rule with the same name.
+    ///
+    /// The `callback` is called when the `condition` becomes met on a metrics snapshot. It is not
+    /// called again until the condition stops being met. The callback can e.g. notify a monitoring
+    /// canister:
+    ///
+    /// ```ignore
+    /// Alerts::add_rule("low_cycles", AlertCondition::CyclesBelow(1_000_000_000_000), |alert| {
+    ///     let _ = canister_notify!(monitor.report_alert(alert.clone()), ());
+    /// });
+    /// ```
+    pub fn add_rule(name: &str, condition: AlertCondition, callback: impl Fn(&Alert) + 'static) {
+        let rule = AlertRule {
+            name: name.to_string(),
+            condition,
+            callback: Rc::new(callback),
+            active: false,
+        };
+
+        ALERT_RULES.with(|rules| {
+            let mut rules = rules.borrow_mut();
+            rules.retain(|rule| rule.name != name);
+            rules.push(rule);
+        });
+    }
+
+    /// Removes the alert rule with the `name`.
+    pub fn remove_rule(name: &str) {
+        ALERT_RULES.with(|rules| rules.borrow_mut().retain(|rule| rule.name != name));
+    }
+
+    /// Returns the names and conditions of the alert rules.
+    pub fn rules() -> Vec<(String, AlertCondition)> {
+        ALERT_RULES.with(|rules| {
+            rules
+                .borrow()
+                .iter()
+                .map(|rule| (rule.name.clone(), rule.condition.clone()))
+                .collect()
+        })
+    }
+
+    /// Returns the names of the rules whose conditions are currently met.
+    pub fn active() -> Vec<String> {
+        ALERT_RULES.with(|rules| {
+            rules
+                .borrow()
+                .iter()
+                .filter(|rule| rule.active)
+                .map(|rule| rule.name.clone())
+                .collect()
+        })
+    }
+}
+
+/// Evaluates the alert rules on the `current` snapshot and calls the callbacks of the raised alerts.
+pub(crate) fn evaluate(previous: Option<&MetricsData>, current: &MetricsData) {
+    let raised: Vec<(Alert, AlertCallback)> = ALERT_RULES.with(|rules| {
+        let mut rules = rules.borrow_mut();
+        let mut raised = vec![];
+        for rule in rules.iter_mut() {
+            let is_met = rule.condition.is_met(previous, current);
+            if is_met && !rule.active {
+                let alert = Alert {
+                    rule: rule.name.clone(),
+                    condition: rule.condition.clone(),
+                    metrics: current.clone(),
+                };
+                raised.push((alert, rule.callback.clone()));
+            }
+            rule.active = is_met;
+        }
+        raised
+    });
+
+    // The callbacks are called after the rules are released, so they can change the rules.
+    for (alert, callback) in raised {
+        callback(&alert);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    fn snapshot(cycles: u128, log_errors_total: u64) -> MetricsData {
+        MetricsData {
+            cycles,
+            log_errors_total,
+            ..Default::default()
+        }
+    }
+
+    thread_local! {
+        static RAISED: Cell<u32> = const { Cell::new(0) };
+    }
+
+    fn count_alert(_alert: &Alert) {
+        RAISED.with(|raised| raised.set(raised.get() + 1));
+    }
+
+    #[test]
+    fn should_check_thresholds() {
+        let current = snapshot(100, 5);
+        assert!(AlertCondition::CyclesBelow(101).is_met(None, &current));
+        assert!(!AlertCondition::CyclesBelow(100).is_met(None, &current));
+        assert!(AlertCondition::ErrorLogsAbove(4).is_met(None, &current));
+        assert!(!AlertCondition::ErrorLogsAbove(4).is_met(Some(&snapshot(100, 2)), &current));
+        assert!(!AlertCondition::HeapMemoryAbove(0).is_met(None, &current));
+        assert!(!AlertCondition::StableMemoryAbove(0).is_met(None, &current));
+    }
+
+    #[test]
+    fn should_raise_alert_once_while_condition_is_met() {
+        Alerts::add_rule("low_cycles", AlertCondition::CyclesBelow(100), count_alert);
+
+        evaluate(None, &snapshot(50, 0));
+        evaluate(None, &snapshot(60, 0));
+        assert_eq!(RAISED.with(Cell::get), 1);
+        assert_eq!(Alerts::active(), vec!["low_cycles".to_string()]);
+
+        evaluate(None, &snapshot(200, 0));
+        assert!(Alerts::active().is_empty());
+
+        evaluate(None, &snapshot(50, 0));
+        assert_eq!(RAISED.with(Cell::get), 2);
+    }
+
+    #[test]
+    fn should_replace_and_remove_rules() {
+        Alerts::add_rule("rule", AlertCondition::CyclesBelow(100), count_alert);
+        Alerts::add_rule("rule", AlertCondition::ErrorLogsAbove(1), count_alert);
+        assert_eq!(
+            Alerts::rules(),
+            vec![("rule".to_string(), AlertCondition::ErrorLogsAbove(1))]
+        );
+
+        Alerts::remove_rule("rule");
+        assert!(Alerts::rules().is_empty());
+    }
+}
@@ -0,0 +1,89 @@
+//! Runtime configuration of the metrics collection.
+
+use ic_exports::candid::{CandidType, Deserialize};
+
+use crate::{Interval, RetentionTier};
+
+/// Configuration of the metrics history.
+#[derive(Debug, Clone, CandidType, Deserialize)]
+pub struct MetricsConfig {
+    pub interval: Interval,
+    pub history_length_nanos: u64,
+    pub retention: Vec<RetentionTier>,
+}
+
+impl MetricsConfig {
+    /// Checks that the snapshot interval is not zero.
+    pub(crate) fn validate_interval(interval: Interval) -> Result<(), MetricsError> {
+        if interval.nanos() == 0 {
+            return Err(MetricsError::InvalidConfiguration(
+                "interval must not be zero".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks that the history length is not zero.
+    pub(crate) fn validate_history_length(history_length_nanos: u64) -> Result<(), MetricsError> {
+        if history_length_nanos == 0 {
+            return Err(MetricsError::InvalidConfiguration(
+                "history length must not be zero".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks that the intervals of the retention tiers are not zero.
+    pub(crate) fn validate_retention(tiers: &[RetentionTier]) -> Result<(), MetricsError> {
+        if tiers.iter().any(|tier| tier.interval.nanos() == 0) {
+            return Err(MetricsError::InvalidConfiguration(
+                "retention interval must not be zero".into(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Error returned by the metrics endpoints.
+#[derive(Debug, Clone, CandidType, Deserialize, Eq, PartialEq)]
+pub enum MetricsError {
+    /// The caller does not have permission to execute this method.
+    NotAuthorized,
+    /// Invalid metrics configuration.
+    InvalidConfiguration(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Aggregation;
+
+    fn is_invalid(result: Result<(), MetricsError>) -> bool {
+        matches!(result, Err(MetricsError::InvalidConfiguration(_)))
+    }
+
+    #[test]
+    fn should_reject_zero_interval() {
+        assert!(is_invalid(MetricsConfig::validate_interval(
+            Interval::from_secs(0)
+        )));
+        assert_eq!(MetricsConfig::validate_interval(Interval::PerHour), Ok(()));
+    }
+
+    #[test]
+    fn should_reject_zero_history_length() {
+        assert!(is_invalid(MetricsConfig::validate_history_length(0)));
+        assert_eq!(MetricsConfig::validate_history_length(1), Ok(()));
+    }
+
+    #[test]
+    fn should_reject_zero_retention_interval() {
+        let tier = |seconds| RetentionTier::new(0, Interval::from_secs(seconds), Aggregation::Max);
+        assert!(is_invalid(MetricsConfig::validate_retention(&[
+            tier(60),
+            tier(0)
+        ])));
+        assert_eq!(MetricsConfig::validate_retention(&[tier(60)]), Ok(()));
+        assert_eq!(MetricsConfig::validate_retention(&[]), Ok(()));
+    }
+}
@@ -0,0 +1,74 @@
+//! Cycles consumption rate and runway projection.
+
+use crate::{MetricsData, MetricsMap};
+
+const NANOS_PER_DAY: u128 = 24 * 60 * 60 * 1_000_000_000;
+
+/// Returns the number of cycles burned per day, derived from the latest snapshot
+/// taken before `current_ts`.
+///
+/// Returns zero if there is no such snapshot or the balance has grown since, e.g. after a top-up.
+pub(crate) fn burn_rate_per_day(
+    metrics: &MetricsMap<MetricsData>,
+    current_ts: u64,
+    current_cycles: u128,
+) -> u128 {
+    let Some((ts, snapshot)) = metrics.map.range(..current_ts).next_back() else {
+        return 0;
+    };
+
+    let elapsed = (current_ts - ts) as u128;
+    snapshot.cycles.saturating_sub(current_cycles) * NANOS_PER_DAY / elapsed
+}
+
+/// Returns the number of seconds until the balance reaches the `freezing_threshold_cycles`
+/// with the given burn rate, or `None` if no cycles are burned.
+pub(crate) fn runway_seconds(
+    cycles: u128,
+    freezing_threshold_cycles: u128,
+    burn_rate_per_day: u128,
+) -> Option<u64> {
+    if burn_rate_per_day == 0 {
+        return None;
+    }
+
+    let available = cycles.saturating_sub(freezing_threshold_cycles);
+    let seconds = available * (NANOS_PER_DAY / 1_000_000_000) / burn_rate_per_day;
+    Some(seconds.try_into().unwrap_or(u64::MAX))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: u64 = NANOS_PER_DAY as u64;
+
+    fn metrics(snapshots: &[(u64, u128)]) -> MetricsMap<MetricsData> {
+        let mut metrics = MetricsMap::default();
+        for (ts, cycles) in snapshots {
+            metrics.map.insert(
+                *ts,
+                MetricsData {
+                    cycles: *cycles,
+                    ..Default::default()
+                },
+            );
+        }
+        metrics
+    }
+
+    #[test]
+    fn should_compute_burn_rate_from_latest_snapshot() {
+        let metrics = metrics(&[(0, 10_000), (DAY, 5_000)]);
+        assert_eq!(burn_rate_per_day(&metrics, DAY + DAY / 2, 4_000), 2_000);
+        assert_eq!(burn_rate_per_day(&metrics, 2 * DAY, 6_000), 0);
+        assert_eq!(burn_rate_per_day(&MetricsMap::default(), DAY, 100), 0);
+    }
+
+    #[test]
+    fn should_project_runway() {
+        assert_eq!(runway_seconds(3_000, 1_000, 2_000), Some(24 * 60 * 60));
+        assert_eq!(runway_seconds(500, 1_000, 2_000), Some(0));
+        assert_eq!(runway_seconds(3_000, 1_000, 0), None);
+    }
+}
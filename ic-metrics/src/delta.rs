@@ -0,0 +1,114 @@
+//! Differences between consecutive metric snapshots.
+
+use ic_exports::candid::{CandidType, Deserialize};
+
+use crate::MetricsData;
+
+/// Difference between two consecutive metric snapshots.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MetricsDelta {
+    /// Timestamp of the earlier snapshot.
+    pub from_ts: u64,
+    /// Timestamp of the later snapshot.
+    pub to_ts: u64,
+    /// Change of the cycles balance, positive after a top-up.
+    pub cycles_delta: i128,
+    /// Number of cycles burned per second, zero if the balance has grown.
+    pub cycles_burned_per_second: u128,
+    /// Change of the heap memory size in bytes.
+    pub heap_memory_delta: i64,
+    /// Change of the stable memory size in pages.
+    pub stable_memory_delta: i64,
+    /// Number of error log records written between the snapshots.
+    pub log_errors: u64,
+    /// Number of warning log records written between the snapshots.
+    pub log_warnings: u64,
+}
+
+impl MetricsDelta {
+    pub fn new(from_ts: u64, from: &MetricsData, to_ts: u64, to: &MetricsData) -> Self {
+        let seconds = (to_ts.saturating_sub(from_ts) / 1_000_000_000).max(1) as u128;
+        Self {
+            from_ts,
+            to_ts,
+            cycles_delta: to.cycles as i128 - from.cycles as i128,
+            cycles_burned_per_second: from.cycles.saturating_sub(to.cycles) / seconds,
+            heap_memory_delta: to.heap_memory_size as i64 - from.heap_memory_size as i64,
+            stable_memory_delta: to.stable_memory_size as i64 - from.stable_memory_size as i64,
+            log_errors: to.log_errors_total.saturating_sub(from.log_errors_total),
+            log_warnings: to
+                .log_warnings_total
+                .saturating_sub(from.log_warnings_total),
+        }
+    }
+}
+
+/// Returns the deltas between the consecutive `snapshots` sorted by timestamp.
+pub fn deltas<'a>(
+    snapshots: impl Iterator<Item = (&'a u64, &'a MetricsData)>,
+) -> Vec<MetricsDelta> {
+    let snapshots: Vec<_> = snapshots.collect();
+    snapshots
+        .windows(2)
+        .map(|pair| {
+            let (from_ts, from) = pair[0];
+            let (to_ts, to) = pair[1];
+            MetricsDelta::new(*from_ts, from, *to_ts, to)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: u64 = 1_000_000_000;
+
+    fn snapshot(cycles: u128, heap_memory_size: u64, log_errors_total: u64) -> MetricsData {
+        MetricsData {
+            cycles,
+            heap_memory_size,
+            log_errors_total,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn should_compute_delta() {
+        let from = snapshot(1_000, 200, 1);
+        let to = snapshot(400, 100, 4);
+
+        let delta = MetricsDelta::new(0, &from, 10 * SECOND, &to);
+        assert_eq!(delta.cycles_delta, -600);
+        assert_eq!(delta.cycles_burned_per_second, 60);
+        assert_eq!(delta.heap_memory_delta, -100);
+        assert_eq!(delta.stable_memory_delta, 0);
+        assert_eq!(delta.log_errors, 3);
+        assert_eq!(delta.log_warnings, 0);
+    }
+
+    #[test]
+    fn should_not_burn_cycles_after_top_up() {
+        let delta = MetricsDelta::new(0, &snapshot(100, 0, 0), SECOND, &snapshot(500, 0, 0));
+        assert_eq!(delta.cycles_delta, 400);
+        assert_eq!(delta.cycles_burned_per_second, 0);
+    }
+
+    #[test]
+    fn should_compute_deltas_of_consecutive_snapshots() {
+        let snapshots = [
+            (0, snapshot(300, 0, 0)),
+            (SECOND, snapshot(200, 0, 0)),
+            (3 * SECOND, snapshot(100, 0, 0)),
+        ];
+
+        assert!(deltas(snapshots[..1].iter().map(|(ts, s)| (ts, s))).is_empty());
+
+        let deltas = deltas(snapshots.iter().map(|(ts, s)| (ts, s)));
+        assert_eq!(deltas.len(), 2);
+        assert_eq!((deltas[0].from_ts, deltas[0].to_ts), (0, SECOND));
+        assert_eq!(deltas[0].cycles_burned_per_second, 100);
+        assert_eq!((deltas[1].from_ts, deltas[1].to_ts), (SECOND, 3 * SECOND));
+        assert_eq!(deltas[1].cycles_burned_per_second, 50);
+    }
+}
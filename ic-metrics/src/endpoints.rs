@@ -0,0 +1,97 @@
+//! Per-endpoint call metrics.
+
+use std::cell::RefCell;
+
+use ic_exports::candid::{CandidType, Deserialize};
+
+thread_local! {
+    /// Method being executed and the instruction counter at its start.
+    static CURRENT_CALL: RefCell<Option<(String, u64)>> = const { RefCell::new(None) };
+}
+
+/// Call metrics of a canister endpoint.
+///
+/// The calls are counted by [`Metrics::record_endpoint_call`](crate::Metrics::record_endpoint_call),
+/// which is opted in from `PreUpdate::pre_update`. The rejects and the instructions are recorded
+/// only if the endpoint calls [`Metrics::record_endpoint_reject`](crate::Metrics::record_endpoint_reject)
+/// and [`Metrics::finish_endpoint_call`](crate::Metrics::finish_endpoint_call). The collected
+/// metrics have the following limitations:
+///
+/// * Query calls are not counted, as their state changes are discarded.
+/// * Calls that trap are not counted, as their state changes are rolled back. Calls which return
+///   an error are counted, and can be marked with
+///   [`Metrics::record_endpoint_reject`](crate::Metrics::record_endpoint_reject).
+/// * The instruction counter is reset after every `await`, so only the instructions used after
+///   the last `await` of an async call are measured.
+/// * If a call is not finished, its instructions are not recorded.
+#[derive(CandidType, Deserialize, Default, Clone, Debug, PartialEq, Eq)]
+pub struct EndpointMetrics {
+    /// Number of calls to the endpoint.
+    pub calls: u64,
+    /// Number of calls rejected by the endpoint.
+    pub rejects: u64,
+    /// Total number of instructions used by the measured calls.
+    pub instructions_total: u64,
+    /// Maximum number of instructions used by a single call.
+    pub instructions_max: u64,
+}
+
+/// Marks the start of the `method_name` call.
+pub(crate) fn start_call(method_name: &str) {
+    CURRENT_CALL
+        .with(|call| *call.borrow_mut() = Some((method_name.to_string(), instruction_counter())));
+}
+
+/// Returns the method being executed and the number of instructions used since its start.
+pub(crate) fn finish_call() -> Option<(String, u64)> {
+    CURRENT_CALL.with(|call| {
+        call.borrow_mut()
+            .take()
+            .map(|(method, start)| (method, instruction_counter().saturating_sub(start)))
+    })
+}
+
+impl EndpointMetrics {
+    pub(crate) fn record_instructions(&mut self, instructions: u64) {
+        self.instructions_total = self.instructions_total.saturating_add(instructions);
+        self.instructions_max = self.instructions_max.max(instructions);
+    }
+}
+
+fn instruction_counter() -> u64 {
+    #[cfg(target_family = "wasm")]
+    {
+        ic_exports::ic_cdk::api::performance_counter(0)
+    }
+    #[cfg(not(target_family = "wasm"))]
+    {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_finish_started_call() {
+        assert_eq!(finish_call(), None);
+
+        start_call("swap");
+        start_call("deposit");
+        assert_eq!(finish_call(), Some(("deposit".to_string(), 0)));
+        assert_eq!(finish_call(), None);
+    }
+
+    #[test]
+    fn should_record_instructions() {
+        let mut metrics = EndpointMetrics::default();
+        metrics.record_instructions(100);
+        metrics.record_instructions(50);
+        assert_eq!(metrics.instructions_total, 150);
+        assert_eq!(metrics.instructions_max, 100);
+
+        metrics.record_instructions(u64::MAX);
+        assert_eq!(metrics.instructions_total, u64::MAX);
+    }
+}
@@ -0,0 +1,87 @@
+//! Application events recorded into the metrics timeline.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+
+use ic_exports::candid::{CandidType, Deserialize};
+
+/// Maximum number of events kept between two snapshots. The oldest events are dropped
+/// when the limit is reached.
+pub const MAX_PENDING_EVENTS: usize = 1000;
+
+thread_local! {
+    static PENDING_EVENTS: RefCell<VecDeque<MetricEvent>> = const { RefCell::new(VecDeque::new()) };
+}
+
+/// Application measurement recorded with [`record_event`].
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MetricEvent {
+    pub name: String,
+    pub value: i64,
+    /// Time of the event in nanoseconds.
+    pub timestamp: u64,
+}
+
+/// Records an application event, e.g. an executed swap with its amount.
+///
+/// The events are included into the next metrics snapshot, see [`crate::MetricsData::events`].
+pub fn record_event(name: &str, value: i64) {
+    let event = MetricEvent {
+        name: name.to_string(),
+        value,
+        timestamp: ic_exports::ic_kit::ic::time(),
+    };
+
+    PENDING_EVENTS.with(|events| {
+        let mut events = events.borrow_mut();
+        if events.len() >= MAX_PENDING_EVENTS {
+            events.pop_front();
+        }
+        events.push_back(event);
+    });
+}
+
+/// Returns the events recorded since the last snapshot.
+pub(crate) fn pending() -> Vec<MetricEvent> {
+    PENDING_EVENTS.with(|events| events.borrow().iter().cloned().collect())
+}
+
+/// Removes the events included into a snapshot.
+pub(crate) fn clear_pending() {
+    PENDING_EVENTS.with(|events| events.borrow_mut().clear());
+}
+
+#[cfg(test)]
+mod tests {
+    use ic_exports::ic_kit::MockContext;
+
+    use super::*;
+
+    #[test]
+    fn should_record_pending_events_until_cleared() {
+        let context = MockContext::new().inject();
+        record_event("swap", 10);
+        context.add_time(5);
+        record_event("swap", -3);
+
+        let events = pending();
+        assert_eq!(events.len(), 2);
+        assert_eq!((events[1].name.as_str(), events[1].value), ("swap", -3));
+        assert_eq!(events[1].timestamp, events[0].timestamp + 5);
+
+        clear_pending();
+        assert!(pending().is_empty());
+    }
+
+    #[test]
+    fn should_drop_oldest_events_over_limit() {
+        MockContext::new().inject();
+        for value in 0..MAX_PENDING_EVENTS as i64 + 2 {
+            record_event("event", value);
+        }
+
+        let events = pending();
+        assert_eq!(events.len(), MAX_PENDING_EVENTS);
+        assert_eq!(events[0].value, 2);
+    }
+}
@@ -0,0 +1,97 @@
+//! Export of the metrics to a collector canister.
+
+use candid::Principal;
+use ic_exports::candid::{CandidType, Deserialize};
+
+use crate::{MetricsData, MetricsStorage};
+
+/// Collector canister the metrics are pushed to.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MetricsExporter {
+    /// Principal of the collector canister.
+    pub collector: Principal,
+    /// Update method of the collector which accepts a [`MetricsReport`].
+    pub method: String,
+}
+
+/// Metrics snapshot pushed to the collector canister.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct MetricsReport {
+    /// Canister the metrics belong to.
+    pub canister: Principal,
+    /// Timestamp of the snapshot in nanoseconds.
+    pub timestamp: u64,
+    pub metrics: MetricsData,
+}
+
+/// Pushes the latest metrics snapshot to the configured collector canister.
+///
+/// The collector is notified without waiting for the response, so the export
+/// doesn't block the canister.
+pub(crate) fn push_latest(storage: &MetricsStorage) {
+    let Some(exporter) = &storage.exporter else {
+        return;
+    };
+
+    let Some(report) = latest_report(storage) else {
+        return;
+    };
+
+    if let Err(code) =
+        ic_exports::ic_cdk::api::call::notify(exporter.collector, &exporter.method, (report,))
+    {
+        log::warn!(
+            "failed to push metrics to the collector {}: {code:?}",
+            exporter.collector
+        );
+    }
+}
+
+/// Returns the report of the latest metrics snapshot, `None` if there are no snapshots.
+fn latest_report(storage: &MetricsStorage) -> Option<MetricsReport> {
+    let (timestamp, metrics) = storage.metrics.map.iter().next_back()?;
+    Some(MetricsReport {
+        canister: ic_exports::ic_kit::ic::id(),
+        timestamp: *timestamp,
+        metrics: metrics.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use ic_exports::ic_kit::mock_principals::alice;
+    use ic_exports::ic_kit::MockContext;
+
+    use super::*;
+
+    #[test]
+    fn should_report_latest_snapshot() {
+        MockContext::new().with_id(alice()).inject();
+        let mut storage = MetricsStorage::default();
+        assert!(latest_report(&storage).is_none());
+
+        for cycles in [10, 20] {
+            storage.metrics.map.insert(
+                cycles as u64,
+                MetricsData {
+                    cycles,
+                    ..Default::default()
+                },
+            );
+        }
+
+        let report = latest_report(&storage).unwrap();
+        assert_eq!(report.canister, alice());
+        assert_eq!(report.timestamp, 20);
+        assert_eq!(report.metrics.cycles, 20);
+    }
+
+    #[test]
+    fn should_not_push_without_exporter() {
+        let mut storage = MetricsStorage::default();
+        storage.metrics.map.insert(1, MetricsData::default());
+
+        // Would call the system API if the exporter was set.
+        push_latest(&storage);
+    }
+}
@@ -51,21 +51,125 @@
 //! overwritten.
 //!
 //! For the further example you can refer to the tests in the `canister-b` crate.
+//!
+//! Besides the system metrics, the application can declare custom counters, gauges and histograms
+//! in the [`MetricsRegistry`]. Their values are included into every snapshot:
+//!
+//! ```ignore
+//! MetricsRegistry::register_counter("swaps", "Number of executed swaps");
+//! MetricsRegistry::inc_counter("swaps", 1);
+//! MetricsRegistry::observe("swap_amount", amount);
+//! ```
+//!
+//! Per-endpoint call counts are collected for all the update endpoints of the canister by
+//! opting in with the `PreUpdate` implementation, which is called by every `#[update]` method:
+//!
+//! ```ignore
+//! impl PreUpdate for MyCanister {
+//!     fn pre_update(&self, method_name: &str, _method_type: MethodType) {
+//!         self.record_endpoint_call(method_name);
+//!     }
+//! }
+//! ```
+//!
+//! The rejects and the instructions of a call are known only when the endpoint returns, so they
+//! still have to be recorded by the endpoint with [`Metrics::record_endpoint_reject`] and
+//! [`Metrics::finish_endpoint_call`]:
+//!
+//! ```ignore
+//! #[update]
+//! fn swap(&mut self, args: SwapArgs) -> Result<(), SwapError> {
+//!     let result = self.do_swap(args);
+//!     if result.is_err() {
+//!         self.record_endpoint_reject("swap");
+//!     }
+//!     self.finish_endpoint_call();
+//!     result
+//! }
+//! ```
+//!
+//! See [`EndpointMetrics`] for the limitations of these metrics.
 
-use std::cell::RefCell;
+mod alerts;
+mod config;
+mod cycles;
+mod delta;
+mod endpoints;
+mod events;
+mod export;
+mod memory;
+mod registry;
+mod retention;
+mod stable;
+mod upgrade;
+
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeSet;
 use std::rc::Rc;
+use std::time::Duration;
 
+pub use alerts::{Alert, AlertCondition, Alerts};
 use candid::Principal;
-use ic_canister::{generate_exports, generate_idl, query, state_getter, Canister, Idl, PreUpdate};
+pub use config::{MetricsConfig, MetricsError};
+pub use delta::{deltas, MetricsDelta};
+pub use endpoints::EndpointMetrics;
+pub use events::{record_event, MetricEvent, MAX_PENDING_EVENTS};
+pub use export::{MetricsExporter, MetricsReport};
+use ic_canister::{
+    generate_exports, generate_idl, query, state_getter, update, Canister, Idl, PreUpdate,
+};
 use ic_exports::candid::{CandidType, Deserialize};
+use ic_exports::ic_cdk_timers::{self, TimerId};
+use ic_stable_structures::stable_structures::DefaultMemoryImpl;
+use ic_stable_structures::VirtualMemory;
 use ic_storage::IcStorage;
+pub use memory::{register_stable_memory, CountingAllocator};
+pub use registry::{
+    CustomMetric, Histogram, MetricValue, MetricsRegistry, DEFAULT_HISTOGRAM_BUCKETS,
+};
+pub use retention::{Aggregation, Downsample, RetentionTier};
+pub use stable::StableMetricsMap;
 
-#[cfg(target_family = "wasm")]
 const WASM_PAGE_SIZE: u64 = 65536;
 
+/// Maximum number of snapshots returned by [`Metrics::get_metrics_range`].
+pub const MAX_METRICS_RANGE_LIMIT: u64 = 1000;
+
 #[derive(CandidType, Deserialize, IcStorage, Default, Clone, Debug)]
 pub struct MetricsStorage {
     pub metrics: MetricsMap<MetricsData>,
+    /// Call metrics of the endpoints by method name.
+    #[serde(default)]
+    pub endpoints: std::collections::BTreeMap<String, EndpointMetrics>,
+    /// Freezing threshold of the canister in cycles, used for the runway projection.
+    #[serde(default)]
+    pub freezing_threshold_cycles: u128,
+    /// Collector canister the metrics are pushed to by [`Metrics::export_metrics_timer`].
+    #[serde(default)]
+    pub exporter: Option<MetricsExporter>,
+    /// Principals allowed to change the metrics configuration in addition to the controllers of
+    /// the canister.
+    #[serde(default)]
+    pub admins: BTreeSet<Principal>,
+}
+
+impl MetricsStorage {
+    /// Checks that the `caller` is allowed to change the metrics configuration: it must be one of
+    /// the [`MetricsStorage::admins`] or a controller of the canister.
+    pub fn check_admin(&self, caller: Principal) -> Result<(), MetricsError> {
+        if self.admins.contains(&caller) || is_controller(&caller) {
+            Ok(())
+        } else {
+            Err(MetricsError::NotAuthorized)
+        }
+    }
+}
+
+thread_local! {
+    /// Timer taking the metric snapshots, started by [`Metrics::update_metrics_timer`].
+    static METRICS_TIMER: Cell<Option<TimerId>> = const { Cell::new(None) };
+    /// Timer pushing the metrics to the collector, started by [`Metrics::export_metrics_timer`].
+    static EXPORT_TIMER: Cell<Option<TimerId>> = const { Cell::new(None) };
 }
 
 #[derive(CandidType, Deserialize, IcStorage, Default, Clone, Debug, PartialEq, Eq)]
@@ -73,6 +177,37 @@ pub struct MetricsData {
     pub cycles: u128,
     pub stable_memory_size: u64,
     pub heap_memory_size: u64,
+    /// Number of error records written by the `ic-log` logger. Always zero if the `log-counters`
+    /// feature is disabled.
+    #[serde(default)]
+    pub log_errors_total: u64,
+    /// Number of warning records written by the `ic-log` logger. Always zero if the
+    /// `log-counters` feature is disabled.
+    #[serde(default)]
+    pub log_warnings_total: u64,
+    /// Custom metrics declared in the [`MetricsRegistry`].
+    #[serde(default)]
+    pub custom: Vec<CustomMetric>,
+    /// Number of cycles burned per day since the previous snapshot.
+    #[serde(default)]
+    pub cycles_burn_rate_per_day: u128,
+    /// Estimated number of seconds until the canister reaches the freezing threshold,
+    /// `None` if no cycles are burned.
+    #[serde(default)]
+    pub runway_seconds: Option<u64>,
+    /// Size of the wasm memory in pages.
+    #[serde(default)]
+    pub wasm_memory_pages: u64,
+    /// Number of heap bytes in use, `None` if the [`CountingAllocator`] is not installed.
+    /// The difference with `heap_memory_size` is the reserved but unused heap.
+    #[serde(default)]
+    pub heap_used_bytes: Option<u64>,
+    /// Sizes in pages of the stable memories registered with [`register_stable_memory`] by memory id.
+    #[serde(default)]
+    pub stable_memory_pages: Vec<(u8, u64)>,
+    /// Application events recorded with [`record_event`] during the snapshot interval.
+    #[serde(default)]
+    pub events: Vec<MetricEvent>,
 }
 
 pub trait Metrics: Canister {
@@ -81,7 +216,7 @@ pub trait Metrics: Canister {
 
     #[query(trait = true)]
     fn get_curr_metrics(&self) -> MetricsData {
-        curr_values()
+        curr_values(&MetricsStorage::get().borrow())
     }
 
     #[query(trait = true)]
@@ -89,35 +224,235 @@ pub trait Metrics: Canister {
         MetricsStorage::get().borrow().clone()
     }
 
+    /// Returns at most `limit` snapshots with timestamps in `from_ts..=to_ts`, skipping the first `offset` of them.
+    ///
+    /// The `limit` is capped by [`MAX_METRICS_RANGE_LIMIT`].
+    #[query(trait = true)]
+    fn get_metrics_range(&self, from_ts: u64, to_ts: u64, limit: u64, offset: u64) -> MetricsRange {
+        MetricsStorage::get().borrow().metrics.range(
+            from_ts,
+            to_ts,
+            offset as usize,
+            limit.min(MAX_METRICS_RANGE_LIMIT) as usize,
+        )
+    }
+
+    /// Returns the deltas between the consecutive snapshots with timestamps in `from_ts..=to_ts`.
+    ///
+    /// At most [`MAX_METRICS_RANGE_LIMIT`] deltas are returned.
+    #[query(trait = true)]
+    fn get_metrics_deltas(&self, from_ts: u64, to_ts: u64) -> Vec<MetricsDelta> {
+        if from_ts > to_ts {
+            return vec![];
+        }
+
+        let storage = MetricsStorage::get();
+        let storage = storage.borrow();
+        deltas(
+            storage
+                .metrics
+                .map
+                .range(from_ts..=to_ts)
+                .take(MAX_METRICS_RANGE_LIMIT as usize + 1),
+        )
+    }
+
+    #[query(trait = true)]
+    fn get_metrics_config(&self) -> MetricsConfig {
+        let storage = MetricsStorage::get();
+        let storage = storage.borrow();
+        let metrics = &storage.metrics;
+        MetricsConfig {
+            interval: metrics.get_interval(),
+            history_length_nanos: metrics.get_history_length_nanos(),
+            retention: metrics.get_retention().to_vec(),
+        }
+    }
+
+    /// Sets the interval between the metric snapshots.
+    ///
+    /// If the snapshots are taken by the [`Metrics::update_metrics_timer`], the timer is
+    /// restarted with the new interval.
+    #[update(trait = true)]
+    fn set_metrics_interval(&mut self, interval: Interval) -> Result<(), MetricsError> {
+        self.check_metrics_admin()?;
+        MetricsConfig::validate_interval(interval)?;
+
+        Self::set_interval(interval);
+        if cfg!(target_family = "wasm") && METRICS_TIMER.with(Cell::get).is_some() {
+            schedule_snapshots(Duration::from_nanos(interval.nanos()));
+        }
+
+        Ok(())
+    }
+
+    /// Sets how long the metric snapshots are kept.
+    #[update(trait = true)]
+    fn set_metrics_history_length(
+        &mut self,
+        history_length_nanos: u64,
+    ) -> Result<(), MetricsError> {
+        self.check_metrics_admin()?;
+        MetricsConfig::validate_history_length(history_length_nanos)?;
+
+        MetricsStorage::get()
+            .borrow_mut()
+            .metrics
+            .set_history_length_nanos(history_length_nanos);
+        Ok(())
+    }
+
+    /// Sets the retention tiers of the metric snapshots.
+    #[update(trait = true)]
+    fn set_metrics_retention(&mut self, tiers: Vec<RetentionTier>) -> Result<(), MetricsError> {
+        self.check_metrics_admin()?;
+        MetricsConfig::validate_retention(&tiers)?;
+
+        MetricsStorage::get()
+            .borrow_mut()
+            .metrics
+            .set_retention(tiers);
+        Ok(())
+    }
+
+    /// Checks that the caller is allowed to change the metrics configuration.
+    ///
+    /// By default the controllers of the canister and the principals added with
+    /// [`Metrics::add_metrics_admin`] are allowed.
+    fn check_metrics_admin(&self) -> Result<(), MetricsError> {
+        MetricsStorage::get()
+            .borrow()
+            .check_admin(ic_exports::ic_kit::ic::caller())
+    }
+
+    /// Allows the `principal` to change the metrics configuration. Can be called, for example,
+    /// from the `init` method of the canister.
+    fn add_metrics_admin(principal: Principal) {
+        MetricsStorage::get().borrow_mut().admins.insert(principal);
+    }
+
     fn update_metrics(&self) {
-        let metrics = MetricsStorage::get();
-        let mut metrics = metrics.borrow_mut();
-        metrics.metrics.insert(curr_values());
+        take_snapshot(&MetricsStorage::get());
     }
 
     /// This function updates the metrics at intervals with the specified timer
     ///
+    /// If the timer is already started, it is replaced by the new one.
+    ///
     /// This function is only available for the wasm target and won't do
     /// anything on other targets
     fn update_metrics_timer(&mut self, timer: std::time::Duration) {
         if cfg!(target_family = "wasm") {
-            use ic_exports::ic_cdk_timers;
-            let metrics = MetricsStorage::get();
-
             // Set the interval
             let interval = Interval::from_secs(timer.as_secs());
-            metrics.borrow_mut().metrics.interval = interval;
+            MetricsStorage::get().borrow_mut().metrics.interval = interval;
 
-            ic_cdk_timers::set_timer_interval(timer, move || {
-                metrics.borrow_mut().metrics.insert(curr_values());
+            schedule_snapshots(timer);
+        }
+    }
+
+    /// This function pushes the latest metrics snapshot to the `collector` canister at intervals
+    /// with the specified timer, calling its `method` with a [`MetricsReport`] argument.
+    ///
+    /// If the export is already started, it is replaced by the new one. The export is stopped
+    /// with [`Metrics::stop_metrics_export`].
+    ///
+    /// This function is only available for the wasm target and won't do
+    /// anything on other targets
+    fn export_metrics_timer(
+        &mut self,
+        collector: Principal,
+        method: &str,
+        timer: std::time::Duration,
+    ) {
+        if cfg!(target_family = "wasm") {
+            MetricsStorage::get().borrow_mut().exporter = Some(MetricsExporter {
+                collector,
+                method: method.to_string(),
             });
+
+            schedule_export(timer);
+        }
+    }
+
+    /// Stops pushing the metrics to the collector canister.
+    fn stop_metrics_export(&mut self) {
+        stop_export();
+    }
+
+    /// Records a call to the `method_name` endpoint. Supposed to be called from `PreUpdate::pre_update`,
+    /// so the calls of all the update endpoints are counted.
+    ///
+    /// If the previous call was not finished with [`Metrics::finish_endpoint_call`], its instructions are not
+    /// recorded. Note that the state changes of a trapped call are rolled back, so the call counter includes
+    /// only the calls that didn't trap. Query calls are never counted, as their state changes are discarded.
+    fn record_endpoint_call(&self, method_name: &str) {
+        MetricsStorage::get()
+            .borrow_mut()
+            .endpoints
+            .entry(method_name.to_string())
+            .or_default()
+            .calls += 1;
+        endpoints::start_call(method_name);
+    }
+
+    /// Records the instructions used by the current endpoint call since [`Metrics::record_endpoint_call`].
+    ///
+    /// Should be called at the end of the endpoint. In async methods the instruction counter is reset
+    /// after every `await`, so only the instructions used after the last one are recorded.
+    fn finish_endpoint_call(&self) {
+        if let Some((method_name, instructions)) = endpoints::finish_call() {
+            MetricsStorage::get()
+                .borrow_mut()
+                .endpoints
+                .entry(method_name)
+                .or_default()
+                .record_instructions(instructions);
         }
     }
 
+    /// Records that the `method_name` endpoint rejected the call, e.g. returned an error.
+    fn record_endpoint_reject(&self, method_name: &str) {
+        MetricsStorage::get()
+            .borrow_mut()
+            .endpoints
+            .entry(method_name.to_string())
+            .or_default()
+            .rejects += 1;
+    }
+
+    /// Saves the metrics history to the `memory`, so it can be restored after the upgrade
+    /// with [`Metrics::restore_metrics`].
+    ///
+    /// This method should be called from `#[pre_upgrade]` method.
+    fn save_metrics(
+        &self,
+        memory: VirtualMemory<DefaultMemoryImpl>,
+    ) -> ic_stable_structures::Result<()> {
+        upgrade::save(memory)
+    }
+
+    /// Restores the metrics history saved by [`Metrics::save_metrics`] from the `memory`.
+    ///
+    /// This method should be called from `#[post_upgrade]` method. The restored metrics
+    /// replace the current ones, including the snapshot interval.
+    fn restore_metrics(
+        &self,
+        memory: VirtualMemory<DefaultMemoryImpl>,
+    ) -> ic_stable_structures::Result<()> {
+        upgrade::restore(memory)
+    }
+
     fn set_interval(interval: Interval) {
         MetricsStorage::get().borrow_mut().metrics.interval = interval;
     }
 
+    /// Sets the freezing threshold of the canister in cycles, used to estimate the runway
+    /// reported in [`MetricsData::runway_seconds`].
+    fn set_freezing_threshold_cycles(cycles: u128) {
+        MetricsStorage::get().borrow_mut().freezing_threshold_cycles = cycles;
+    }
+
     // Important: This function *must* be defined to be the
     // last one in the trait because it depends on the order
     // of expansion of update/query(trait = true) methods.
@@ -127,9 +462,89 @@ pub trait Metrics: Canister {
     }
 }
 
-fn curr_values() -> MetricsData {
+/// Starts the timer taking the metric snapshots every `period`, stopping the previous one.
+fn schedule_snapshots(period: Duration) {
+    let metrics = MetricsStorage::get();
+    let timer = ic_cdk_timers::set_timer_interval(period, move || take_snapshot(&metrics));
+    if let Some(previous) = METRICS_TIMER.with(|v| v.replace(Some(timer))) {
+        ic_cdk_timers::clear_timer(previous);
+    }
+}
+
+/// Starts pushing the metrics to the collector every `period`, replacing the previous export
+/// timer.
+fn schedule_export(period: Duration) {
+    let metrics = MetricsStorage::get();
+    let timer =
+        ic_cdk_timers::set_timer_interval(period, move || export::push_latest(&metrics.borrow()));
+    if let Some(previous) = EXPORT_TIMER.with(|v| v.replace(Some(timer))) {
+        ic_cdk_timers::clear_timer(previous);
+    }
+}
+
+/// Stops the export timer and removes the collector.
+fn stop_export() {
+    MetricsStorage::get().borrow_mut().exporter = None;
+    if let Some(timer) = EXPORT_TIMER.with(Cell::take) {
+        ic_cdk_timers::clear_timer(timer);
+    }
+}
+
+/// Returns `true` if the `principal` is a controller of the canister. Always `false` on the
+/// targets other than wasm.
+fn is_controller(principal: &Principal) -> bool {
+    #[cfg(target_family = "wasm")]
+    {
+        ic_exports::ic_cdk::api::is_controller(principal)
+    }
+    #[cfg(not(target_family = "wasm"))]
+    {
+        let _ = principal;
+        false
+    }
+}
+
+/// Stores the snapshot of the current metrics and evaluates the alert rules on it.
+fn take_snapshot(metrics: &RefCell<MetricsStorage>) {
+    let (previous, values) = {
+        let mut metrics = metrics.borrow_mut();
+        let values = curr_values(&metrics);
+        let previous = metrics.metrics.map.values().next_back().cloned();
+        events::clear_pending();
+
+        // The snapshot of the same interval is overwritten, but its events must be kept.
+        if let Some(overwritten) = metrics.metrics.insert(values.clone()) {
+            if let Some(last) = metrics.metrics.map.values_mut().next_back() {
+                let mut events = overwritten.events;
+                events.append(&mut last.events);
+                last.events = events;
+            }
+        }
+        metrics.metrics.downsample();
+        (previous, values)
+    };
+
+    alerts::evaluate(previous.as_ref(), &values);
+}
+
+fn curr_values(storage: &MetricsStorage) -> MetricsData {
+    let cycles = ic_exports::ic_kit::ic::balance128();
+    let cycles_burn_rate_per_day =
+        cycles::burn_rate_per_day(&storage.metrics, ic_exports::ic_kit::ic::time(), cycles);
+
+    let wasm_memory_pages = {
+        #[cfg(target_family = "wasm")]
+        {
+            core::arch::wasm32::memory_size(0) as u64
+        }
+        #[cfg(not(target_family = "wasm"))]
+        {
+            0
+        }
+    };
+
     MetricsData {
-        cycles: ic_exports::ic_kit::ic::balance128(),
+        cycles,
         stable_memory_size: {
             #[cfg(target_family = "wasm")]
             {
@@ -140,16 +555,33 @@ fn curr_values() -> MetricsData {
                 0
             }
         },
-        heap_memory_size: {
-            #[cfg(target_family = "wasm")]
-            {
-                (core::arch::wasm32::memory_size(0) as u64) * WASM_PAGE_SIZE
-            }
-            #[cfg(not(target_family = "wasm"))]
-            {
-                0
-            }
-        },
+        heap_memory_size: wasm_memory_pages * WASM_PAGE_SIZE,
+        log_errors_total: log_count(log::Level::Error),
+        log_warnings_total: log_count(log::Level::Warn),
+        custom: MetricsRegistry::metrics(),
+        cycles_burn_rate_per_day,
+        runway_seconds: cycles::runway_seconds(
+            cycles,
+            storage.freezing_threshold_cycles,
+            cycles_burn_rate_per_day,
+        ),
+        wasm_memory_pages,
+        heap_used_bytes: memory::heap_used_bytes(),
+        stable_memory_pages: memory::stable_memory_pages(),
+        events: events::pending(),
+    }
+}
+
+/// Returns the number of records with the `level` written by the `ic-log` logger.
+fn log_count(level: log::Level) -> u64 {
+    #[cfg(feature = "log-counters")]
+    {
+        ic_log::writer::CounterWriter::level_count(level)
+    }
+    #[cfg(not(feature = "log-counters"))]
+    {
+        let _ = level;
+        0
     }
 }
 
@@ -178,11 +610,27 @@ impl Interval {
     }
 }
 
+/// Page of metric snapshots.
+#[derive(Clone, CandidType, Deserialize, Debug)]
+pub struct MetricsRange<T = MetricsData> {
+    /// Total number of snapshots in the requested time range.
+    pub total: u64,
+    /// Snapshots sorted by timestamp.
+    pub snapshots: Vec<(u64, T)>,
+}
+
 #[derive(Clone, CandidType, Deserialize, Debug)]
 pub struct MetricsMap<T: IcStorage> {
     interval: Interval,
     history_length_nanos: u64,
     pub map: std::collections::BTreeMap<u64, T>,
+    /// Retention tiers sorted by the snapshot age.
+    #[serde(default)]
+    retention: Vec<RetentionTier>,
+    /// Number of samples aggregated into the downsampled snapshots, by timestamp. Snapshots
+    /// which are not downsampled are not listed.
+    #[serde(default)]
+    sample_counts: std::collections::BTreeMap<u64, u64>,
 }
 
 impl<T: IcStorage> MetricsMap<T> {
@@ -191,6 +639,49 @@ impl<T: IcStorage> MetricsMap<T> {
             interval,
             history_length_nanos,
             map: std::collections::BTreeMap::new(),
+            retention: vec![],
+            sample_counts: std::collections::BTreeMap::new(),
+        }
+    }
+
+    /// Sets the retention tiers used by [`MetricsMap::downsample`], e.g. to keep per-minute
+    /// snapshots for a day, hourly snapshots for a month and daily snapshots beyond that:
+    ///
+    /// ```ignore
+    /// metrics.set_retention(vec![
+    ///     RetentionTier::new(Interval::PerDay.nanos(), Interval::PerHour, Aggregation::Average),
+    ///     RetentionTier::new(30 * Interval::PerDay.nanos(), Interval::PerDay, Aggregation::Average),
+    /// ]);
+    /// ```
+    pub fn set_retention(&mut self, mut tiers: Vec<RetentionTier>) {
+        tiers.sort_by_key(|tier| tier.min_age_nanos);
+        self.retention = tiers;
+    }
+
+    pub fn get_retention(&self) -> &[RetentionTier] {
+        &self.retention
+    }
+
+    /// Returns at most `limit` snapshots with timestamps in `from_ts..=to_ts`, skipping the first `offset` of them.
+    pub fn range(&self, from_ts: u64, to_ts: u64, offset: usize, limit: usize) -> MetricsRange<T>
+    where
+        T: Clone,
+    {
+        if from_ts > to_ts {
+            return MetricsRange {
+                total: 0,
+                snapshots: vec![],
+            };
+        }
+
+        let range = self.map.range(from_ts..=to_ts);
+        MetricsRange {
+            total: range.clone().count() as u64,
+            snapshots: range
+                .skip(offset)
+                .take(limit)
+                .map(|(ts, snapshot)| (*ts, snapshot.clone()))
+                .collect(),
         }
     }
 
@@ -198,20 +689,19 @@ impl<T: IcStorage> MetricsMap<T> {
         self.interval
     }
 
+    pub fn get_history_length_nanos(&self) -> u64 {
+        self.history_length_nanos
+    }
+
+    pub fn set_history_length_nanos(&mut self, history_length_nanos: u64) {
+        self.history_length_nanos = history_length_nanos;
+    }
+
     pub fn insert(&mut self, new_metric: T) -> Option<T> {
         self.trim();
         let current_ts = ic_exports::ic_kit::ic::time();
-        let last_ts = self
-            .map
-            .iter()
-            .next_back()
-            .map(|(k, _)| *k)
-            .unwrap_or(current_ts);
-        let new_ts = if current_ts < last_ts + self.interval.nanos() {
-            last_ts
-        } else {
-            current_ts - (current_ts % self.interval.nanos())
-        };
+        let last_ts = self.map.keys().next_back().copied();
+        let new_ts = snapshot_timestamp(last_ts, current_ts, self.interval);
         self.map.insert(new_ts, new_metric)
     }
 
@@ -219,6 +709,31 @@ impl<T: IcStorage> MetricsMap<T> {
         let current_ts = ic_exports::ic_kit::ic::time();
         let oldest_to_keep = current_ts.saturating_sub(self.history_length_nanos);
         self.map.retain(|&ts, _| ts >= oldest_to_keep);
+        self.sample_counts.retain(|&ts, _| ts >= oldest_to_keep);
+    }
+}
+
+impl<T: IcStorage + Downsample> MetricsMap<T> {
+    /// Aggregates the snapshots older than the retention tiers ages.
+    pub fn downsample(&mut self) {
+        let current_ts = ic_exports::ic_kit::ic::time();
+        retention::downsample(
+            &mut self.map,
+            &mut self.sample_counts,
+            &self.retention,
+            current_ts,
+        );
+    }
+}
+
+/// Returns the timestamp of a new snapshot: the timestamp of the last snapshot if the `interval`
+/// has not passed since it, or the current time rounded down to the `interval` otherwise.
+fn snapshot_timestamp(last_ts: Option<u64>, current_ts: u64, interval: Interval) -> u64 {
+    let last_ts = last_ts.unwrap_or(current_ts);
+    if current_ts < last_ts + interval.nanos() {
+        last_ts
+    } else {
+        current_ts - (current_ts % interval.nanos())
     }
 }
 
@@ -229,3 +744,91 @@ impl<T: IcStorage> std::default::Default for MetricsMap<T> {
 }
 
 generate_exports!(Metrics);
+
+#[cfg(test)]
+mod tests {
+    use ic_exports::ic_kit::mock_principals::{alice, bob};
+    use ic_exports::ic_kit::MockContext;
+
+    use super::*;
+
+    const SECOND: u64 = 1_000_000_000;
+
+    #[test]
+    fn should_allow_only_admins_to_configure_metrics() {
+        let mut storage = MetricsStorage::default();
+        assert_eq!(
+            storage.check_admin(alice()),
+            Err(MetricsError::NotAuthorized)
+        );
+
+        storage.admins.insert(alice());
+        assert_eq!(storage.check_admin(alice()), Ok(()));
+        assert_eq!(storage.check_admin(bob()), Err(MetricsError::NotAuthorized));
+    }
+
+    #[test]
+    fn should_remove_collector_when_export_is_stopped() {
+        MetricsStorage::get().borrow_mut().exporter = Some(MetricsExporter {
+            collector: alice(),
+            method: "push_metrics".to_string(),
+        });
+
+        stop_export();
+        assert_eq!(MetricsStorage::get().borrow().exporter, None);
+    }
+
+    #[test]
+    fn should_compute_snapshot_timestamp() {
+        let interval = Interval::from_secs(10);
+        assert_eq!(snapshot_timestamp(None, 15 * SECOND, interval), 15 * SECOND);
+        assert_eq!(
+            snapshot_timestamp(Some(15 * SECOND), 24 * SECOND, interval),
+            15 * SECOND
+        );
+        assert_eq!(
+            snapshot_timestamp(Some(15 * SECOND), 27 * SECOND, interval),
+            20 * SECOND
+        );
+    }
+
+    #[test]
+    fn should_return_metrics_range() {
+        let mut metrics = MetricsMap::<MetricsData>::default();
+        for ts in 1..=5 {
+            metrics.map.insert(ts, MetricsData::default());
+        }
+
+        let range = metrics.range(2, 4, 1, 10);
+        assert_eq!(range.total, 3);
+        let timestamps: Vec<_> = range.snapshots.iter().map(|(ts, _)| *ts).collect();
+        assert_eq!(timestamps, vec![3, 4]);
+
+        assert_eq!(metrics.range(4, 2, 0, 10).total, 0);
+    }
+
+    #[test]
+    fn should_keep_events_of_overwritten_snapshot() {
+        MockContext::new().inject();
+        let storage = RefCell::new(MetricsStorage::default());
+
+        record_event("swap", 1);
+        take_snapshot(&storage);
+        record_event("swap", 2);
+        take_snapshot(&storage);
+
+        let storage = storage.borrow();
+        assert_eq!(storage.metrics.map.len(), 1);
+        let values: Vec<_> = storage
+            .metrics
+            .map
+            .values()
+            .next_back()
+            .unwrap()
+            .events
+            .iter()
+            .map(|event| event.value)
+            .collect();
+        assert_eq!(values, vec![1, 2]);
+    }
+}
@@ -0,0 +1,102 @@
+//! Detailed memory usage of the canister.
+
+use std::alloc::{GlobalAlloc, Layout};
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use ic_stable_structures::stable_structures::Memory;
+
+static HEAP_USED_BYTES: AtomicU64 = AtomicU64::new(0);
+static COUNTING_ALLOCATOR_USED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static STABLE_MEMORIES: RefCell<Vec<(u8, Box<dyn Fn() -> u64>)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Global allocator wrapper which counts the allocated heap bytes, reported in
+/// [`crate::MetricsData::heap_used_bytes`].
+///
+/// ```ignore
+/// #[global_allocator]
+/// static ALLOCATOR: CountingAllocator<std::alloc::System> = CountingAllocator::new(std::alloc::System);
+/// ```
+pub struct CountingAllocator<A> {
+    inner: A,
+}
+
+impl<A> CountingAllocator<A> {
+    pub const fn new(inner: A) -> Self {
+        Self { inner }
+    }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for CountingAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.inner.alloc(layout);
+        if !ptr.is_null() {
+            COUNTING_ALLOCATOR_USED.store(true, Ordering::Relaxed);
+            HEAP_USED_BYTES.fetch_add(layout.size() as u64, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.inner.dealloc(ptr, layout);
+        HEAP_USED_BYTES.fetch_sub(layout.size() as u64, Ordering::Relaxed);
+    }
+}
+
+/// Returns the number of heap bytes allocated through the [`CountingAllocator`],
+/// `None` if it is not installed.
+pub(crate) fn heap_used_bytes() -> Option<u64> {
+    COUNTING_ALLOCATOR_USED
+        .load(Ordering::Relaxed)
+        .then(|| HEAP_USED_BYTES.load(Ordering::Relaxed))
+}
+
+/// Registers the stable memory with the `id`, so its size is reported in
+/// [`crate::MetricsData::stable_memory_pages`]. Replaces the memory registered with the same id.
+pub fn register_stable_memory(id: u8, memory: impl Memory + 'static) {
+    STABLE_MEMORIES.with(|memories| {
+        let mut memories = memories.borrow_mut();
+        memories.retain(|(memory_id, _)| *memory_id != id);
+        memories.push((id, Box::new(move || memory.size())));
+        memories.sort_by_key(|(memory_id, _)| *memory_id);
+    });
+}
+
+/// Returns the sizes in pages of the registered stable memories.
+pub(crate) fn stable_memory_pages() -> Vec<(u8, u64)> {
+    STABLE_MEMORIES.with(|memories| {
+        memories
+            .borrow()
+            .iter()
+            .map(|(id, size)| (*id, size()))
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use ic_stable_structures::stable_structures::VectorMemory;
+
+    use super::*;
+
+    fn memory(pages: u64) -> VectorMemory {
+        let memory = VectorMemory::default();
+        memory.grow(pages);
+        memory
+    }
+
+    #[test]
+    fn should_report_registered_memories_by_id() {
+        register_stable_memory(3, memory(1));
+        register_stable_memory(1, memory(2));
+        assert_eq!(stable_memory_pages(), vec![(1, 2), (3, 1)]);
+
+        let growing = memory(1);
+        register_stable_memory(3, growing.clone());
+        growing.grow(4);
+        assert_eq!(stable_memory_pages(), vec![(1, 2), (3, 5)]);
+    }
+}
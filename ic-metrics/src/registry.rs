@@ -0,0 +1,280 @@
+//! Registry of the custom metrics declared by the application.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use ic_exports::candid::{CandidType, Deserialize};
+
+/// Default upper bounds of the histogram buckets.
+pub const DEFAULT_HISTOGRAM_BUCKETS: &[u64] = &[
+    1, 2, 5, 10, 20, 50, 100, 200, 500, 1_000, 2_000, 5_000, 10_000,
+];
+
+thread_local! {
+    static REGISTRY: RefCell<BTreeMap<String, CustomMetric>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+/// Custom metric with its current value.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CustomMetric {
+    pub name: String,
+    pub description: String,
+    pub value: MetricValue,
+}
+
+/// Value of a custom metric.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum MetricValue {
+    /// Monotonically increasing value.
+    Counter(u64),
+    /// Value which can go up and down.
+    Gauge(i64),
+    /// Distribution of the observed values.
+    Histogram(Histogram),
+}
+
+/// Distribution of the observed values over the buckets.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+pub struct Histogram {
+    /// Upper bounds of the buckets, sorted in ascending order.
+    pub buckets: Vec<u64>,
+    /// Number of observed values in each bucket. The last element counts the values
+    /// greater than the last bound.
+    pub counts: Vec<u64>,
+    /// Sum of the observed values.
+    pub sum: u128,
+    /// Number of the observed values.
+    pub count: u64,
+}
+
+impl Histogram {
+    pub fn new(mut buckets: Vec<u64>) -> Self {
+        buckets.sort_unstable();
+        buckets.dedup();
+        Self {
+            counts: vec![0; buckets.len() + 1],
+            buckets,
+            sum: 0,
+            count: 0,
+        }
+    }
+
+    pub fn observe(&mut self, value: u64) {
+        let bucket = self.buckets.partition_point(|bound| *bound < value);
+        self.counts[bucket] += 1;
+        self.sum += value as u128;
+        self.count += 1;
+    }
+}
+
+/// Registry of the custom metrics.
+///
+/// The metrics are stored in the heap of the canister and included into every metrics snapshot.
+/// Updating a metric which was not registered registers it with an empty description.
+pub struct MetricsRegistry {}
+
+impl MetricsRegistry {
+    /// Registers a counter with the `name`. Does nothing if a metric with the `name` exists.
+    pub fn register_counter(name: &str, description: &str) {
+        Self::register(name, description, MetricValue::Counter(0));
+    }
+
+    /// Registers a gauge with the `name`. Does nothing if a metric with the `name` exists.
+    pub fn register_gauge(name: &str, description: &str) {
+        Self::register(name, description, MetricValue::Gauge(0));
+    }
+
+    /// Registers a histogram with the `name` and the bucket upper bounds.
+    /// Does nothing if a metric with the `name` exists.
+    pub fn register_histogram(name: &str, description: &str, buckets: Vec<u64>) {
+        Self::register(
+            name,
+            description,
+            MetricValue::Histogram(Histogram::new(buckets)),
+        );
+    }
+
+    /// Removes the metric with the `name`.
+    pub fn unregister(name: &str) {
+        REGISTRY.with(|registry| registry.borrow_mut().remove(name));
+    }
+
+    /// Increases the counter with the `name` by `value`.
+    pub fn inc_counter(name: &str, value: u64) {
+        Self::update(name, MetricValue::Counter(0), |metric| match metric {
+            MetricValue::Counter(counter) => {
+                *counter = counter.saturating_add(value);
+                true
+            }
+            _ => false,
+        });
+    }
+
+    /// Sets the value of the gauge with the `name`.
+    pub fn set_gauge(name: &str, value: i64) {
+        Self::update(name, MetricValue::Gauge(0), |metric| match metric {
+            MetricValue::Gauge(gauge) => {
+                *gauge = value;
+                true
+            }
+            _ => false,
+        });
+    }
+
+    /// Adds `delta` to the gauge with the `name`.
+    pub fn add_gauge(name: &str, delta: i64) {
+        Self::update(name, MetricValue::Gauge(0), |metric| match metric {
+            MetricValue::Gauge(gauge) => {
+                *gauge = gauge.saturating_add(delta);
+                true
+            }
+            _ => false,
+        });
+    }
+
+    /// Adds the `value` to the histogram with the `name`.
+    ///
+    /// If the histogram is not registered, it is registered with [`DEFAULT_HISTOGRAM_BUCKETS`].
+    pub fn observe(name: &str, value: u64) {
+        let histogram = Histogram::new(DEFAULT_HISTOGRAM_BUCKETS.to_vec());
+        Self::update(
+            name,
+            MetricValue::Histogram(histogram),
+            |metric| match metric {
+                MetricValue::Histogram(histogram) => {
+                    histogram.observe(value);
+                    true
+                }
+                _ => false,
+            },
+        );
+    }
+
+    /// Returns the metric with the `name`.
+    pub fn get(name: &str) -> Option<CustomMetric> {
+        REGISTRY.with(|registry| registry.borrow().get(name).cloned())
+    }
+
+    /// Returns all the registered metrics sorted by name.
+    pub fn metrics() -> Vec<CustomMetric> {
+        REGISTRY.with(|registry| registry.borrow().values().cloned().collect())
+    }
+
+    /// Resets the values of all the registered metrics.
+    pub fn reset() {
+        REGISTRY.with(|registry| {
+            for metric in registry.borrow_mut().values_mut() {
+                metric.value = match &metric.value {
+                    MetricValue::Counter(_) => MetricValue::Counter(0),
+                    MetricValue::Gauge(_) => MetricValue::Gauge(0),
+                    MetricValue::Histogram(histogram) => {
+                        MetricValue::Histogram(Histogram::new(histogram.buckets.clone()))
+                    }
+                };
+            }
+        });
+    }
+
+    fn register(name: &str, description: &str, value: MetricValue) {
+        REGISTRY.with(|registry| {
+            registry
+                .borrow_mut()
+                .entry(name.to_string())
+                .or_insert_with(|| CustomMetric {
+                    name: name.to_string(),
+                    description: description.to_string(),
+                    value,
+                });
+        });
+    }
+
+    fn update(name: &str, initial: MetricValue, update: impl FnOnce(&mut MetricValue) -> bool) {
+        REGISTRY.with(|registry| {
+            let mut registry = registry.borrow_mut();
+            let metric = registry
+                .entry(name.to_string())
+                .or_insert_with(|| CustomMetric {
+                    name: name.to_string(),
+                    description: String::new(),
+                    value: initial,
+                });
+
+            if !update(&mut metric.value) {
+                log::warn!("metric {name} has a different type: {:?}", metric.value);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(name: &str) -> MetricValue {
+        MetricsRegistry::get(name).unwrap().value
+    }
+
+    #[test]
+    fn should_count_observed_values_in_buckets() {
+        let mut histogram = Histogram::new(vec![10, 1, 10]);
+        assert_eq!(histogram.buckets, vec![1, 10]);
+
+        for value in [0, 1, 5, 10, 11] {
+            histogram.observe(value);
+        }
+        assert_eq!(histogram.counts, vec![2, 2, 1]);
+        assert_eq!(histogram.sum, 27);
+        assert_eq!(histogram.count, 5);
+    }
+
+    #[test]
+    fn should_update_metrics() {
+        MetricsRegistry::register_counter("calls", "Number of calls");
+        MetricsRegistry::inc_counter("calls", 2);
+        MetricsRegistry::inc_counter("calls", 3);
+        assert_eq!(value("calls"), MetricValue::Counter(5));
+
+        MetricsRegistry::set_gauge("users", 10);
+        MetricsRegistry::add_gauge("users", -3);
+        assert_eq!(value("users"), MetricValue::Gauge(7));
+        assert_eq!(MetricsRegistry::get("users").unwrap().description, "");
+
+        MetricsRegistry::observe("amount", 3);
+        let MetricValue::Histogram(histogram) = value("amount") else {
+            panic!("amount should be a histogram");
+        };
+        assert_eq!(histogram.buckets, DEFAULT_HISTOGRAM_BUCKETS);
+        assert_eq!(histogram.count, 1);
+    }
+
+    #[test]
+    fn should_not_change_metric_of_other_type() {
+        MetricsRegistry::register_gauge("gauge", "");
+        MetricsRegistry::inc_counter("gauge", 1);
+        assert_eq!(value("gauge"), MetricValue::Gauge(0));
+
+        MetricsRegistry::register_counter("gauge", "");
+        assert_eq!(value("gauge"), MetricValue::Gauge(0));
+    }
+
+    #[test]
+    fn should_reset_and_unregister_metrics() {
+        MetricsRegistry::register_histogram("latency", "", vec![1, 2]);
+        MetricsRegistry::observe("latency", 2);
+        MetricsRegistry::inc_counter("errors", 1);
+
+        MetricsRegistry::reset();
+        assert_eq!(value("errors"), MetricValue::Counter(0));
+        assert_eq!(
+            value("latency"),
+            MetricValue::Histogram(Histogram::new(vec![1, 2]))
+        );
+
+        MetricsRegistry::unregister("errors");
+        let names: Vec<_> = MetricsRegistry::metrics()
+            .into_iter()
+            .map(|metric| metric.name)
+            .collect();
+        assert_eq!(names, vec!["latency".to_string()]);
+    }
+}
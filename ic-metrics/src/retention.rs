@@ -0,0 +1,247 @@
+//! Downsampling of the old metric snapshots.
+
+use std::collections::BTreeMap;
+
+use ic_exports::candid::{CandidType, Deserialize};
+
+use crate::{Interval, MetricsData};
+
+/// Function used to aggregate several snapshots into one.
+#[derive(Debug, Copy, Clone, CandidType, Deserialize, PartialEq, Eq)]
+pub enum Aggregation {
+    Average,
+    Min,
+    Max,
+    Last,
+}
+
+/// Retention tier of the metric snapshots.
+///
+/// The snapshots older than `min_age_nanos` are aggregated into one snapshot per `interval`.
+#[derive(Debug, Copy, Clone, CandidType, Deserialize)]
+pub struct RetentionTier {
+    pub min_age_nanos: u64,
+    pub interval: Interval,
+    pub aggregation: Aggregation,
+}
+
+impl RetentionTier {
+    pub fn new(min_age_nanos: u64, interval: Interval, aggregation: Aggregation) -> Self {
+        Self {
+            min_age_nanos,
+            interval,
+            aggregation,
+        }
+    }
+}
+
+/// Snapshot type which can be aggregated by the retention tiers.
+pub trait Downsample: Sized {
+    /// Aggregates the `snapshots` sorted by time into one snapshot.
+    ///
+    /// Every snapshot is given with the number of samples it represents: snapshots already
+    /// aggregated by a previous downsampling represent several samples, and must be weighted
+    /// accordingly (e.g. by [`Aggregation::Average`]).
+    fn downsample(snapshots: Vec<(Self, u64)>, aggregation: Aggregation) -> Self;
+}
+
+/// Aggregates the snapshots of the `map` according to the `tiers`.
+///
+/// `sample_counts` holds the number of samples of the aggregated snapshots by timestamp, the
+/// snapshots missing there are single samples.
+pub(crate) fn downsample<T: Downsample>(
+    map: &mut BTreeMap<u64, T>,
+    sample_counts: &mut BTreeMap<u64, u64>,
+    tiers: &[RetentionTier],
+    current_ts: u64,
+) {
+    for tier in tiers {
+        let interval = tier.interval.nanos().max(1);
+        let max_ts = current_ts.saturating_sub(tier.min_age_nanos);
+        let old_timestamps: Vec<u64> = map.range(..max_ts).map(|(ts, _)| *ts).collect();
+
+        let mut buckets: BTreeMap<u64, Vec<u64>> = BTreeMap::new();
+        for ts in old_timestamps {
+            buckets.entry(ts - ts % interval).or_default().push(ts);
+        }
+
+        for (bucket_ts, timestamps) in buckets {
+            if timestamps.len() == 1 && timestamps[0] == bucket_ts {
+                continue;
+            }
+
+            let snapshots: Vec<_> = timestamps
+                .iter()
+                .filter_map(|ts| {
+                    let count = sample_counts.remove(ts).unwrap_or(1);
+                    map.remove(ts).map(|snapshot| (snapshot, count))
+                })
+                .collect();
+            let count = snapshots.iter().map(|(_, count)| count).sum();
+            map.insert(bucket_ts, T::downsample(snapshots, tier.aggregation));
+            sample_counts.insert(bucket_ts, count);
+        }
+    }
+}
+
+/// Aggregates the `values` given with the number of samples they represent.
+fn aggregate<V>(values: impl Iterator<Item = (V, u64)>, aggregation: Aggregation) -> V
+where
+    V: Copy + Ord + Default + Into<u128> + TryFrom<u128>,
+{
+    let values = values.map(|(value, count)| (value, count as u128));
+    match aggregation {
+        Aggregation::Average => {
+            let (sum, count) = values.fold((0u128, 0u128), |(sum, total), (value, count)| {
+                (
+                    sum.saturating_add(value.into().saturating_mul(count)),
+                    total + count,
+                )
+            });
+            V::try_from(sum / count.max(1)).unwrap_or_default()
+        }
+        Aggregation::Min => values.map(|(value, _)| value).min().unwrap_or_default(),
+        Aggregation::Max => values.map(|(value, _)| value).max().unwrap_or_default(),
+        Aggregation::Last => values.map(|(value, _)| value).last().unwrap_or_default(),
+    }
+}
+
+impl Downsample for MetricsData {
+    fn downsample(snapshots: Vec<(Self, u64)>, aggregation: Aggregation) -> Self {
+        let values = snapshots.iter();
+        let last = snapshots.last().map(|(s, _)| s);
+        Self {
+            cycles: aggregate(values.clone().map(|(s, n)| (s.cycles, *n)), aggregation),
+            stable_memory_size: aggregate(
+                values.clone().map(|(s, n)| (s.stable_memory_size, *n)),
+                aggregation,
+            ),
+            heap_memory_size: aggregate(
+                values.clone().map(|(s, n)| (s.heap_memory_size, *n)),
+                aggregation,
+            ),
+            // Totals only grow, so the latest value is kept.
+            log_errors_total: aggregate(
+                values.clone().map(|(s, n)| (s.log_errors_total, *n)),
+                Aggregation::Max,
+            ),
+            log_warnings_total: aggregate(
+                values.clone().map(|(s, n)| (s.log_warnings_total, *n)),
+                Aggregation::Max,
+            ),
+            custom: last.map(|s| s.custom.clone()).unwrap_or_default(),
+            cycles_burn_rate_per_day: aggregate(
+                values
+                    .clone()
+                    .map(|(s, n)| (s.cycles_burn_rate_per_day, *n)),
+                aggregation,
+            ),
+            runway_seconds: last.and_then(|s| s.runway_seconds),
+            wasm_memory_pages: aggregate(
+                values.clone().map(|(s, n)| (s.wasm_memory_pages, *n)),
+                aggregation,
+            ),
+            heap_used_bytes: last.and_then(|s| s.heap_used_bytes),
+            events: snapshots
+                .iter()
+                .flat_map(|(s, _)| s.events.iter().cloned())
+                .collect(),
+            stable_memory_pages: last
+                .map(|s| s.stable_memory_pages.clone())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(cycles: u128) -> MetricsData {
+        MetricsData {
+            cycles,
+            ..Default::default()
+        }
+    }
+
+    fn tier(aggregation: Aggregation) -> RetentionTier {
+        RetentionTier::new(0, Interval::from_secs(10), aggregation)
+    }
+
+    const SECOND: u64 = 1_000_000_000;
+
+    #[test]
+    fn should_aggregate_old_snapshots_into_buckets() {
+        let mut map = BTreeMap::from([
+            (0, snapshot(10)),
+            (SECOND, snapshot(20)),
+            (10 * SECOND, snapshot(30)),
+            (11 * SECOND, snapshot(40)),
+            (25 * SECOND, snapshot(50)),
+        ]);
+        let mut counts = BTreeMap::new();
+
+        downsample(
+            &mut map,
+            &mut counts,
+            &[tier(Aggregation::Max)],
+            20 * SECOND,
+        );
+
+        assert_eq!(
+            map,
+            BTreeMap::from([
+                (0, snapshot(20)),
+                (10 * SECOND, snapshot(40)),
+                (25 * SECOND, snapshot(50)),
+            ])
+        );
+        assert_eq!(counts, BTreeMap::from([(0, 2), (10 * SECOND, 2)]));
+    }
+
+    #[test]
+    fn should_weight_aggregated_snapshots_by_sample_count() {
+        let mut map = BTreeMap::from([
+            (0, snapshot(10)),
+            (SECOND, snapshot(10)),
+            (2 * SECOND, snapshot(10)),
+        ]);
+        let mut counts = BTreeMap::new();
+        let tiers = [tier(Aggregation::Average)];
+
+        downsample(&mut map, &mut counts, &tiers, 3 * SECOND);
+        assert_eq!(map, BTreeMap::from([(0, snapshot(10))]));
+        assert_eq!(counts, BTreeMap::from([(0, 3)]));
+
+        // The bucket is completed later: the aggregated snapshot weighs as its three samples.
+        map.insert(5 * SECOND, snapshot(50));
+        downsample(&mut map, &mut counts, &tiers, 10 * SECOND);
+        assert_eq!(map, BTreeMap::from([(0, snapshot(20))]));
+        assert_eq!(counts, BTreeMap::from([(0, 4)]));
+    }
+
+    #[test]
+    fn should_keep_recent_snapshots() {
+        let mut map = BTreeMap::from([(0, snapshot(10)), (SECOND, snapshot(20))]);
+        let mut counts = BTreeMap::new();
+        let tiers = [RetentionTier::new(
+            10 * SECOND,
+            Interval::from_secs(10),
+            Aggregation::Average,
+        )];
+
+        downsample(&mut map, &mut counts, &tiers, 5 * SECOND);
+
+        assert_eq!(map.len(), 2);
+        assert!(counts.is_empty());
+    }
+
+    #[test]
+    fn should_aggregate_values() {
+        let values = || [(1u64, 1), (4, 2), (2, 1)].into_iter();
+        assert_eq!(aggregate(values(), Aggregation::Average), 2);
+        assert_eq!(aggregate(values(), Aggregation::Min), 1);
+        assert_eq!(aggregate(values(), Aggregation::Max), 4);
+        assert_eq!(aggregate(values(), Aggregation::Last), 2);
+    }
+}
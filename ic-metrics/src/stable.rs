@@ -0,0 +1,182 @@
+//! Metrics history stored in stable memory.
+
+use std::borrow::Cow;
+
+use candid::{CandidType, Decode, Encode};
+use ic_stable_structures::stable_structures::Memory;
+use ic_stable_structures::{
+    BTreeMapStructure, Bound, IterableSortedMapStructure, StableBTreeMap, Storable,
+};
+use serde::de::DeserializeOwned;
+
+use crate::{snapshot_timestamp, Interval, MetricsRange};
+
+/// Maximum number of outdated snapshots removed by a single insertion.
+const TRIM_LIMIT: usize = 100;
+
+/// Alternative to [`crate::MetricsMap`] which stores the snapshots in a `StableBTreeMap`,
+/// so long histories don't consume the heap and survive upgrades without serialization.
+///
+/// The interval and history length are not stored in the memory and should be set every time
+/// the map is created, e.g. in `#[init]` and `#[post_upgrade]` methods.
+pub struct StableMetricsMap<T, M: Memory>
+where
+    T: CandidType + DeserializeOwned,
+{
+    interval: Interval,
+    history_length_nanos: u64,
+    map: StableBTreeMap<u64, StorableSnapshot<T>, M>,
+}
+
+impl<T, M> StableMetricsMap<T, M>
+where
+    T: CandidType + DeserializeOwned,
+    M: Memory,
+{
+    pub fn new(interval: Interval, history_length_nanos: u64, memory: M) -> Self {
+        Self {
+            interval,
+            history_length_nanos,
+            map: StableBTreeMap::new(memory),
+        }
+    }
+
+    pub fn get_interval(&self) -> Interval {
+        self.interval
+    }
+
+    pub fn set_interval(&mut self, interval: Interval) {
+        self.interval = interval;
+    }
+
+    pub fn get_history_length_nanos(&self) -> u64 {
+        self.history_length_nanos
+    }
+
+    pub fn set_history_length_nanos(&mut self, history_length_nanos: u64) {
+        self.history_length_nanos = history_length_nanos;
+    }
+
+    /// Number of the stored snapshots.
+    pub fn len(&self) -> u64 {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Returns the snapshot with the timestamp `ts`.
+    pub fn get(&self, ts: u64) -> Option<T> {
+        self.map.get(&ts).map(|snapshot| snapshot.0)
+    }
+
+    /// Returns the latest snapshot with its timestamp.
+    pub fn last(&self) -> Option<(u64, T)> {
+        self.map
+            .last_key_value()
+            .map(|(ts, snapshot)| (ts, snapshot.0))
+    }
+
+    pub fn insert(&mut self, new_metric: T) -> Option<T> {
+        self.trim();
+        let current_ts = ic_exports::ic_kit::ic::time();
+        let last_ts = self.map.last_key_value().map(|(ts, _)| ts);
+        let new_ts = snapshot_timestamp(last_ts, current_ts, self.interval);
+        self.map
+            .insert(new_ts, StorableSnapshot(new_metric))
+            .map(|snapshot| snapshot.0)
+    }
+
+    /// Returns at most `limit` snapshots with timestamps in `from_ts..=to_ts`, skipping the first `offset` of them.
+    pub fn range(&self, from_ts: u64, to_ts: u64, offset: usize, limit: usize) -> MetricsRange<T> {
+        if from_ts > to_ts {
+            return MetricsRange {
+                total: 0,
+                snapshots: vec![],
+            };
+        }
+
+        MetricsRange {
+            total: self.map.range(from_ts..=to_ts).count() as u64,
+            snapshots: self
+                .map
+                .range(from_ts..=to_ts)
+                .skip(offset)
+                .take(limit)
+                .map(|(ts, snapshot)| (ts, snapshot.0))
+                .collect(),
+        }
+    }
+
+    /// Removes the outdated snapshots. The removal is bounded by [`TRIM_LIMIT`] to limit
+    /// the instructions used by a single insertion, the rest is removed by the next ones.
+    fn trim(&mut self) {
+        let current_ts = ic_exports::ic_kit::ic::time();
+        let oldest_to_keep = current_ts.saturating_sub(self.history_length_nanos);
+        self.map.remove_range(..oldest_to_keep, TRIM_LIMIT);
+    }
+}
+
+struct StorableSnapshot<T>(T);
+
+impl<T: CandidType + DeserializeOwned> Storable for StorableSnapshot<T> {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::from(Encode!(&self.0).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Self(Decode!(&bytes, T).unwrap())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[cfg(test)]
+mod tests {
+    use ic_exports::ic_kit::MockContext;
+    use ic_stable_structures::stable_structures::VectorMemory;
+
+    use super::*;
+
+    const SECOND: u64 = 1_000_000_000;
+
+    #[test]
+    fn should_insert_snapshot_per_interval() {
+        let context = MockContext::new().inject();
+        let mut map = StableMetricsMap::new(
+            Interval::from_secs(10),
+            100 * SECOND,
+            VectorMemory::default(),
+        );
+
+        map.insert(1u64);
+        assert_eq!(map.insert(2), Some(1));
+        context.add_time(10 * SECOND);
+        assert_eq!(map.insert(3), None);
+
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.last().map(|(_, value)| value), Some(3));
+        let range = map.range(0, u64::MAX, 1, 10);
+        assert_eq!(range.total, 2);
+        assert_eq!(range.snapshots.len(), 1);
+        assert!(map.range(1, 0, 0, 10).snapshots.is_empty());
+    }
+
+    #[test]
+    fn should_remove_outdated_snapshots() {
+        let context = MockContext::new().inject();
+        // Snapshot timestamps are aligned to the interval.
+        context.add_time(SECOND - ic_exports::ic_kit::ic::time() % SECOND);
+        let mut map =
+            StableMetricsMap::new(Interval::from_secs(1), 5 * SECOND, VectorMemory::default());
+
+        for value in 0..10u64 {
+            map.insert(value);
+            context.add_time(SECOND);
+        }
+
+        assert_eq!(map.len(), 6);
+        assert_eq!(map.last().map(|(_, value)| value), Some(9));
+    }
+}
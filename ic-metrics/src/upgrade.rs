@@ -0,0 +1,91 @@
+//! Persistence of the metrics across canister upgrades.
+
+use std::borrow::Cow;
+
+use candid::{Decode, Encode};
+use ic_stable_structures::stable_structures::DefaultMemoryImpl;
+use ic_stable_structures::{Bound, CellStructure, StableCell, Storable, VirtualMemory};
+use ic_storage::IcStorage;
+
+use crate::MetricsStorage;
+
+/// Saves the metrics to the `memory`.
+pub(crate) fn save(memory: VirtualMemory<DefaultMemoryImpl>) -> ic_stable_structures::Result<()> {
+    let metrics = MetricsStorage::get().borrow().clone();
+    let mut cell = StableCell::new(memory, StorableMetrics::default())?;
+    cell.set(StorableMetrics(Some(metrics)))
+}
+
+/// Restores the metrics saved by [`save`] from the `memory`.
+pub(crate) fn restore(
+    memory: VirtualMemory<DefaultMemoryImpl>,
+) -> ic_stable_structures::Result<()> {
+    let mut cell = StableCell::new(memory, StorableMetrics::default())?;
+    let StorableMetrics(metrics) = cell.get().clone();
+
+    // The metrics must not be restored twice if the next upgrade skips `pre_upgrade`.
+    cell.set(StorableMetrics::default())?;
+
+    if let Some(metrics) = metrics {
+        *MetricsStorage::get().borrow_mut() = metrics;
+    }
+
+    Ok(())
+}
+
+#[derive(Default, Clone)]
+struct StorableMetrics(Option<MetricsStorage>);
+
+impl Storable for StorableMetrics {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::from(Encode!(&self.0).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Self(Decode!(&bytes, Option<MetricsStorage>).unwrap())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[cfg(test)]
+mod tests {
+    use ic_exports::ic_kit::mock_principals::alice;
+    use ic_stable_structures::{IcMemoryManager, MemoryId};
+
+    use super::*;
+    use crate::MetricsData;
+
+    #[test]
+    fn should_restore_saved_metrics_once() {
+        let memory_manager = IcMemoryManager::init(DefaultMemoryImpl::default());
+        let memory = || memory_manager.get(MemoryId::new(1));
+        {
+            let storage = MetricsStorage::get();
+            let mut storage = storage.borrow_mut();
+            storage.freezing_threshold_cycles = 42;
+            storage.admins.insert(alice());
+            storage.metrics.map.insert(
+                1,
+                MetricsData {
+                    cycles: 100,
+                    ..Default::default()
+                },
+            );
+        }
+
+        save(memory()).unwrap();
+        *MetricsStorage::get().borrow_mut() = MetricsStorage::default();
+        restore(memory()).unwrap();
+
+        let storage = MetricsStorage::get();
+        assert_eq!(storage.borrow().freezing_threshold_cycles, 42);
+        assert!(storage.borrow().admins.contains(&alice()));
+        assert_eq!(storage.borrow().metrics.map[&1].cycles, 100);
+
+        // The saved metrics are cleared by the restore.
+        *storage.borrow_mut() = MetricsStorage::default();
+        restore(memory()).unwrap();
+        assert!(storage.borrow().metrics.map.is_empty());
+    }
+}
@@ -0,0 +1,62 @@
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+
+use candid::Principal;
+
+use crate::error::PaymentError;
+
+thread_local! {
+    static LOCKED_ACCOUNTS: RefCell<BTreeSet<(Principal, Principal)>> = const { RefCell::new(BTreeSet::new()) };
+}
+
+/// Guard that prevents concurrent terminal operations of the same account owner with the same
+/// token.
+///
+/// Without the guard two concurrent updates could both pass the balance checks before either of
+/// the transfers completes. The lock is released when the guard is dropped, including the case
+/// when the call traps in the callback and the future is cleaned up by the CDK.
+#[derive(Debug)]
+pub(crate) struct AccountLock {
+    key: (Principal, Principal),
+}
+
+impl AccountLock {
+    /// Locks the account of the `owner` in the `token`. Returns
+    /// [`PaymentError::OperationInProgress`] if the account is already locked.
+    pub(crate) fn acquire(token: Principal, owner: Principal) -> Result<Self, PaymentError> {
+        let key = (token, owner);
+        let acquired = LOCKED_ACCOUNTS.with(|locked| locked.borrow_mut().insert(key));
+        match acquired {
+            true => Ok(Self { key }),
+            false => Err(PaymentError::OperationInProgress),
+        }
+    }
+}
+
+impl Drop for AccountLock {
+    fn drop(&mut self) {
+        LOCKED_ACCOUNTS.with(|locked| locked.borrow_mut().remove(&self.key));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ic_exports::ic_kit::mock_principals::{alice, bob};
+
+    use super::*;
+
+    #[test]
+    fn account_cannot_be_locked_twice() {
+        let token = Principal::management_canister();
+        let lock = AccountLock::acquire(token, alice()).unwrap();
+
+        assert_eq!(
+            AccountLock::acquire(token, alice()).unwrap_err(),
+            PaymentError::OperationInProgress
+        );
+        assert!(AccountLock::acquire(token, bob()).is_ok());
+
+        drop(lock);
+        assert!(AccountLock::acquire(token, alice()).is_ok());
+    }
+}
@@ -0,0 +1,108 @@
+use candid::{CandidType, Deserialize, Nat, Principal};
+
+use crate::error::PaymentError;
+
+/// What the [`TokenTerminal`](crate::TokenTerminal) does with amounts smaller than the configured
+/// minimum.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, CandidType, Deserialize)]
+pub enum DustPolicy {
+    /// Reject the operation with [`PaymentError::AmountBelowMinimum`].
+    #[default]
+    Reject,
+
+    /// Leave the deposited amount where it is, so it can be transferred later together with
+    /// further amounts (e.g. the next deposit to the interim account). The deposit returns
+    /// [`PaymentError::DustAccumulated`].
+    ///
+    /// The withdrawn amount stays on the caller's balance anyway, so withdrawals below the
+    /// minimum are rejected with [`PaymentError::AmountBelowMinimum`].
+    Accumulate,
+
+    /// Move the amount to the balance of the given fee account. Withdrawals are moved between the
+    /// balances without a ledger call, deposits are credited to the fee account instead of the
+    /// caller. The operation returns [`PaymentError::DustDonated`] once the amount is moved.
+    ///
+    /// Cycles top-ups below the minimum withdrawal are always rejected.
+    Donate(Principal),
+}
+
+/// Minimum amounts of the operations performed by the [`TokenTerminal`](crate::TokenTerminal).
+///
+/// The amounts are checked before any ledger calls are made. By default there are no limits.
+#[derive(Debug, Default, Clone, PartialEq, Eq, CandidType, Deserialize)]
+pub struct AmountLimits {
+    /// Minimum amount of a deposit, including the transfer fee.
+    pub min_deposit: Nat,
+
+    /// Minimum amount debited from the caller's balance by a withdrawal, including the transfer
+    /// fee.
+    pub min_withdrawal: Nat,
+
+    /// Policy for the amounts below the minimum.
+    pub dust_policy: DustPolicy,
+}
+
+impl AmountLimits {
+    /// Creates limits with the given minimum amounts, rejecting the smaller amounts.
+    pub fn new(min_deposit: Nat, min_withdrawal: Nat) -> Self {
+        Self {
+            min_deposit,
+            min_withdrawal,
+            dust_policy: DustPolicy::Reject,
+        }
+    }
+
+    /// Sets the policy for the amounts below the minimum.
+    pub fn with_dust_policy(self, dust_policy: DustPolicy) -> Self {
+        Self {
+            dust_policy,
+            ..self
+        }
+    }
+
+    /// Checks the deposit `amount`. Returns the fee account if the amount must be donated.
+    pub(crate) fn check_deposit(&self, amount: &Nat) -> Result<Option<Principal>, PaymentError> {
+        if *amount >= self.min_deposit {
+            return Ok(None);
+        }
+
+        match self.dust_policy {
+            DustPolicy::Reject => Err(below_minimum(amount, &self.min_deposit)),
+            DustPolicy::Accumulate => Err(PaymentError::DustAccumulated {
+                minimum: self.min_deposit.clone(),
+                actual: amount.clone(),
+            }),
+            DustPolicy::Donate(fee_account) => Ok(Some(fee_account)),
+        }
+    }
+
+    /// Checks the withdrawal `amount`. Returns the fee account if the amount must be donated.
+    pub(crate) fn check_withdrawal(&self, amount: &Nat) -> Result<Option<Principal>, PaymentError> {
+        if *amount >= self.min_withdrawal {
+            return Ok(None);
+        }
+
+        match self.dust_policy {
+            DustPolicy::Reject | DustPolicy::Accumulate => {
+                Err(below_minimum(amount, &self.min_withdrawal))
+            }
+            DustPolicy::Donate(fee_account) => Ok(Some(fee_account)),
+        }
+    }
+
+    /// Checks the cycles top-up `amount` against the minimum withdrawal.
+    pub(crate) fn check_top_up(&self, amount: &Nat) -> Result<(), PaymentError> {
+        if *amount < self.min_withdrawal {
+            return Err(below_minimum(amount, &self.min_withdrawal));
+        }
+
+        Ok(())
+    }
+}
+
+fn below_minimum(amount: &Nat, minimum: &Nat) -> PaymentError {
+    PaymentError::AmountBelowMinimum {
+        minimum: minimum.clone(),
+        actual: amount.clone(),
+    }
+}
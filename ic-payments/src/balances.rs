@@ -1,3 +1,5 @@
+use std::collections::BTreeSet;
+
 use candid::{Nat, Principal};
 use thiserror::Error;
 
@@ -19,3 +21,23 @@ pub trait Balances {
     /// Decrease the `account_owners`'s balance by the given `amount`.
     fn debit(&mut self, account_owner: Principal, amount: Nat) -> Result<Nat, BalanceError>;
 }
+
+/// Storage of the deposit transactions already credited to the users, used to prevent the same
+/// transaction from being claimed twice.
+pub trait DepositClaims {
+    /// Returns `true` if the transaction with the given block index was already claimed.
+    fn is_claimed(&self, block_index: &Nat) -> bool;
+
+    /// Marks the transaction with the given block index as claimed.
+    fn mark_claimed(&mut self, block_index: Nat);
+}
+
+impl DepositClaims for BTreeSet<Nat> {
+    fn is_claimed(&self, block_index: &Nat) -> bool {
+        self.contains(block_index)
+    }
+
+    fn mark_claimed(&mut self, block_index: Nat) {
+        self.insert(block_index);
+    }
+}
@@ -0,0 +1,65 @@
+//! Helpers to convert ICP into cycles using the cycles minting canister (CMC).
+//!
+//! Topping up a canister is done in two steps: ICP is transferred to the top-up account of the
+//! target canister in the CMC, and then the CMC is notified about the transfer block with the
+//! `notify_top_up` method. See [`TokenTerminal::top_up_cycles`](crate::TokenTerminal::top_up_cycles).
+
+use candid::{CandidType, Deserialize, Nat, Principal};
+use ic_canister::virtual_canister_call;
+use ic_exports::icrc_types::icrc1::account::{Account, Subaccount};
+
+use crate::error::TopUpError;
+
+/// Memo of the ICP transfers that top up canisters with cycles (`TPUP`).
+pub const MEMO_TOP_UP_CANISTER: u64 = 0x50555054;
+
+/// Cycles minting canister of the IC mainnet.
+pub const MAINNET_CYCLES_MINTING_CANISTER_ID: &str = "rkp4c-7iaaa-aaaaa-aaaca-cai";
+
+/// Arguments of the `notify_top_up` method of the CMC.
+#[derive(Debug, CandidType, Deserialize, Clone, PartialEq, Eq)]
+pub struct NotifyTopUpArg {
+    pub block_index: u64,
+    pub canister_id: Principal,
+}
+
+/// Returns the CMC subaccount used to top up the `canister_id`.
+///
+/// The subaccount consists of the principal length followed by the principal bytes.
+pub fn top_up_subaccount(canister_id: &Principal) -> Subaccount {
+    let bytes = canister_id.as_slice();
+    let mut subaccount = [0; 32];
+    subaccount[0] = bytes.len() as u8;
+    subaccount[1..1 + bytes.len()].copy_from_slice(bytes);
+    subaccount
+}
+
+/// Returns the account in the `cmc` ICP ledger transfers to top up the `canister_id` are sent to.
+pub fn top_up_account(cmc: Principal, canister_id: &Principal) -> Account {
+    Account {
+        owner: cmc,
+        subaccount: Some(top_up_subaccount(canister_id)),
+    }
+}
+
+/// Notifies the `cmc` canister about the ICP transfer in the block `block_index` made to top up
+/// the `canister_id`. Returns the amount of cycles deposited to the canister.
+///
+/// The notification can be safely repeated for the same block, e.g. if the CMC returned
+/// [`TopUpError::Processing`].
+pub async fn notify_top_up(
+    cmc: Principal,
+    block_index: Nat,
+    canister_id: Principal,
+) -> Result<Nat, TopUpError> {
+    let block_index = u64::try_from(block_index.0)
+        .map_err(|_| TopUpError::InvalidTransaction("block index overflow".into()))?;
+    let args = NotifyTopUpArg {
+        block_index,
+        canister_id,
+    };
+
+    virtual_canister_call!(cmc, "notify_top_up", (args,), Result<Nat, TopUpError>)
+        .await
+        .map_err(|(code, message)| TopUpError::CallFailed(format!("{code:?}: {message}")))?
+}
@@ -0,0 +1,85 @@
+//! Automatic detection of the deposits made to the canister main account, using an ICRC-1 index
+//! canister.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use candid::{CandidType, Deserialize, Nat, Principal};
+use ic_exports::ic_cdk_timers::{self, TimerId};
+
+use crate::{Balances, DepositClaims, RecoveryList, TokenTerminal, TxId};
+
+/// Default number of transactions requested from the index canister in one call.
+pub const DEFAULT_INDEX_BATCH_SIZE: u64 = 100;
+
+/// State of the deposits polling. This state should be persisted by the canister between the
+/// upgrades, so the already processed transactions are not requested again.
+#[derive(Debug, CandidType, Deserialize, Clone, PartialEq, Eq)]
+pub struct DepositWatcher {
+    /// Index canister of the token.
+    pub index: Principal,
+
+    /// Id of the newest transaction processed by the watcher. If `None`, the watcher didn't poll
+    /// the index canister yet.
+    pub last_tx_id: Option<TxId>,
+
+    /// Number of transactions requested from the index canister in one call.
+    pub batch_size: u64,
+}
+
+impl DepositWatcher {
+    /// Creates a new watcher using the given `index` canister.
+    pub fn new(index: Principal) -> Self {
+        Self {
+            index,
+            last_tx_id: None,
+            batch_size: DEFAULT_INDEX_BATCH_SIZE,
+        }
+    }
+}
+
+/// Deposit detected and credited by [`TokenTerminal::poll_deposits`].
+#[derive(Debug, CandidType, Deserialize, Clone, PartialEq, Eq)]
+pub struct DetectedDeposit {
+    /// Id of the deposit transaction (ledger block index).
+    pub tx_id: TxId,
+
+    /// Principal whose balance was credited.
+    pub owner: Principal,
+
+    /// Credited amount.
+    pub amount: Nat,
+}
+
+/// Starts a timer which calls [`TokenTerminal::poll_deposits`] every `interval`.
+///
+/// Timers don't survive upgrades, so this function should be called in both `#[init]` and
+/// `#[post_upgrade]` methods.
+///
+/// Note that the `terminal` and the `claims` are borrowed mutably for the whole polling run,
+/// including the index canister calls.
+pub fn start_deposit_polling<B, R, C>(
+    terminal: Rc<RefCell<TokenTerminal<B, R>>>,
+    watcher: Rc<RefCell<DepositWatcher>>,
+    claims: Rc<RefCell<C>>,
+    interval: Duration,
+) -> TimerId
+where
+    B: Balances + 'static,
+    R: RecoveryList + 'static,
+    C: DepositClaims + 'static,
+{
+    ic_cdk_timers::set_timer_interval(interval, move || {
+        let terminal = terminal.clone();
+        let watcher = watcher.clone();
+        let claims = claims.clone();
+        ic_exports::ic_cdk::spawn(async move {
+            #[allow(clippy::await_holding_refcell_ref)]
+            let _ = terminal
+                .borrow_mut()
+                .poll_deposits(&mut watcher.borrow_mut(), &mut *claims.borrow_mut())
+                .await;
+        })
+    })
+}
@@ -1,6 +1,9 @@
-use candid::{CandidType, Deserialize, Nat};
+use candid::{CandidType, Deserialize, Nat, Principal};
 use ic_exports::ic_cdk::api::call::RejectionCode;
 use ic_exports::icrc_types::icrc1::transfer::TransferError;
+use ic_exports::icrc_types::icrc2::approve::ApproveError;
+use ic_exports::icrc_types::icrc2::transfer_from::TransferFromError;
+use ic_exports::ledger::TransferError as IcpTransferError;
 use thiserror::Error;
 
 use crate::BalanceError;
@@ -22,6 +25,15 @@ pub enum TransferFailReason {
     #[error("transaction is too old to be executed")]
     TooOld,
 
+    #[error("allowance {allowance} given to the canister is not enough for the transfer")]
+    InsufficientAllowance { allowance: Nat },
+
+    #[error("current allowance {current_allowance} is not equal to the expected one")]
+    AllowanceChanged { current_allowance: Nat },
+
+    #[error("allowance expiration time is in the past, ledger time is {ledger_time}")]
+    AllowanceExpired { ledger_time: u64 },
+
     #[error("unknown")]
     Unknown,
 }
@@ -64,10 +76,114 @@ pub enum PaymentError {
     #[error("caller's balance is not enough to perform the operation")]
     InsufficientFunds,
 
+    /// Requested amount is smaller than the minimum configured in the terminal
+    /// [amount limits](crate::AmountLimits). No ledger calls were made and no balances were
+    /// changed.
+    #[error("amount {actual} is smaller than the configured minimum {minimum}")]
+    AmountBelowMinimum { minimum: Nat, actual: Nat },
+
+    /// Requested amount is smaller than the configured minimum and was left to accumulate with
+    /// further amounts. No ledger calls were made and no balances were changed.
+    #[error("amount {actual} is smaller than the configured minimum {minimum} and is kept to accumulate")]
+    DustAccumulated { minimum: Nat, actual: Nat },
+
+    /// Requested amount is smaller than the configured minimum and was moved to the balance of the
+    /// `fee_account`, see [`DustPolicy::Donate`](crate::DustPolicy::Donate). Unlike the other
+    /// errors, the operation is completed: for deposits `tx_id` is the token transaction and
+    /// `amount` is the amount credited to the fee account, withdrawals are moved between the
+    /// balances without a token transaction.
+    #[error(
+        "amount {amount} is smaller than the configured minimum and is donated to {fee_account}"
+    )]
+    DustDonated {
+        tx_id: Option<Nat>,
+        amount: Nat,
+        fee_account: Principal,
+    },
+
+    /// Claimed deposit transaction doesn't match the claim. No balances were changed.
+    #[error("deposit claim is invalid: {0}")]
+    InvalidClaim(ClaimError),
+
+    /// ICP was transferred to the cycles minting canister in the block `block_index`, but the
+    /// top-up notification failed.
+    ///
+    /// Depending on the reason, the notification can be repeated with
+    /// [`notify_top_up`](crate::cmc::notify_top_up).
+    #[error("cycles top-up with the transfer {block_index} failed: {reason}")]
+    TopUpFailed {
+        block_index: Nat,
+        reason: TopUpError,
+    },
+
+    /// Another deposit or withdrawal of the caller is in progress. No ledger calls were made and
+    /// no balances were changed, the operation can be attempted again after the current one is
+    /// completed.
+    #[error("another operation of the caller is in progress")]
+    OperationInProgress,
+
     #[error("unrecoverable error: {0}")]
     Fatal(String),
 }
 
+/// Error returned by the `notify_top_up` method of the cycles minting canister.
+#[derive(Debug, PartialEq, Eq, Clone, CandidType, Deserialize, Error)]
+pub enum TopUpError {
+    /// The transfer was refunded by the CMC in the block `block_index`.
+    #[error("transfer was refunded: {reason}")]
+    Refunded {
+        reason: String,
+        block_index: Option<u64>,
+    },
+
+    /// The transfer is being processed by the CMC, the notification should be repeated later.
+    #[error("transfer is being processed")]
+    Processing,
+
+    /// The transfer is too old to be processed, the argument is the oldest block the CMC
+    /// accepts.
+    #[error("transfer is too old, the oldest accepted block is {0}")]
+    TransactionTooOld(u64),
+
+    #[error("invalid transaction: {0}")]
+    InvalidTransaction(String),
+
+    #[error("CMC error {error_code}: {error_message}")]
+    Other {
+        error_code: u64,
+        error_message: String,
+    },
+
+    /// The CMC call was rejected by the IC.
+    #[error("CMC call failed: {0}")]
+    CallFailed(String),
+}
+
+/// Reason for a deposit claim to be rejected.
+#[derive(Debug, PartialEq, Eq, Clone, CandidType, Deserialize, Error)]
+pub enum ClaimError {
+    #[error("block with the given index doesn't exist")]
+    BlockNotFound,
+
+    #[error("block is not a transfer")]
+    NotATransfer,
+
+    #[error("transfer was sent from an account of another principal")]
+    WrongSender,
+
+    #[error("transfer was sent to another account")]
+    WrongRecipient,
+
+    #[error("transferred amount {actual} doesn't match the claimed amount {expected}")]
+    WrongAmount { expected: Nat, actual: Nat },
+
+    #[error("transfer was already claimed")]
+    AlreadyClaimed,
+
+    #[error("transfer was made by a spender using an allowance")]
+    SpenderTransfer,
+}
+
 /// Reason for the transfer failure.
 #[derive(Debug, CandidType, Deserialize, PartialEq, Eq, Clone)]
 pub enum RecoveryDetails {
@@ -108,6 +224,12 @@ pub enum ParametersError {
 
     #[error("target account cannot be equal to the source account")]
     TargetAccountInvalid,
+
+    #[error("transfer type is not supported by the ledger")]
+    UnsupportedByLedger,
+
+    #[error("memo of {actual} bytes is longer than {max_length} bytes supported by the ledger")]
+    MemoTooLong { max_length: u64, actual: u64 },
 }
 
 impl From<(RejectionCode, String)> for InternalPaymentError {
@@ -149,6 +271,103 @@ impl From<TransferError> for InternalPaymentError {
     }
 }
 
+impl From<TransferFromError> for InternalPaymentError {
+    fn from(err: TransferFromError) -> Self {
+        // All errors except for the allowance one have the same meaning as ICRC-1 transfer errors,
+        // so they are handled (and recovered) the same way.
+        let err = match err {
+            TransferFromError::InsufficientAllowance { allowance } => {
+                return Self::TransferFailed(TransferFailReason::InsufficientAllowance {
+                    allowance,
+                })
+            }
+            TransferFromError::BadFee { expected_fee } => TransferError::BadFee { expected_fee },
+            TransferFromError::BadBurn { min_burn_amount } => {
+                TransferError::BadBurn { min_burn_amount }
+            }
+            TransferFromError::InsufficientFunds { balance } => {
+                TransferError::InsufficientFunds { balance }
+            }
+            TransferFromError::TooOld => TransferError::TooOld,
+            TransferFromError::CreatedInFuture { ledger_time } => {
+                TransferError::CreatedInFuture { ledger_time }
+            }
+            TransferFromError::Duplicate { duplicate_of } => {
+                TransferError::Duplicate { duplicate_of }
+            }
+            TransferFromError::TemporarilyUnavailable => TransferError::TemporarilyUnavailable,
+            TransferFromError::GenericError {
+                error_code,
+                message,
+            } => TransferError::GenericError {
+                error_code,
+                message,
+            },
+        };
+
+        err.into()
+    }
+}
+
+impl From<ApproveError> for InternalPaymentError {
+    fn from(err: ApproveError) -> Self {
+        // Errors not specific to approvals have the same meaning as ICRC-1 transfer errors.
+        let err = match err {
+            ApproveError::AllowanceChanged { current_allowance } => {
+                return Self::TransferFailed(TransferFailReason::AllowanceChanged {
+                    current_allowance,
+                })
+            }
+            ApproveError::Expired { ledger_time } => {
+                return Self::TransferFailed(TransferFailReason::AllowanceExpired { ledger_time })
+            }
+            ApproveError::BadFee { expected_fee } => TransferError::BadFee { expected_fee },
+            ApproveError::InsufficientFunds { balance } => {
+                TransferError::InsufficientFunds { balance }
+            }
+            ApproveError::TooOld => TransferError::TooOld,
+            ApproveError::CreatedInFuture { ledger_time } => {
+                TransferError::CreatedInFuture { ledger_time }
+            }
+            ApproveError::Duplicate { duplicate_of } => TransferError::Duplicate { duplicate_of },
+            ApproveError::TemporarilyUnavailable => TransferError::TemporarilyUnavailable,
+            ApproveError::GenericError {
+                error_code,
+                message,
+            } => TransferError::GenericError {
+                error_code,
+                message,
+            },
+        };
+
+        err.into()
+    }
+}
+
+impl From<IcpTransferError> for InternalPaymentError {
+    fn from(err: IcpTransferError) -> Self {
+        // ICP ledger errors are converted into ICRC-1 ones, so they are handled (and recovered)
+        // the same way.
+        let err = match err {
+            IcpTransferError::BadFee { expected_fee } => TransferError::BadFee {
+                expected_fee: expected_fee.e8s().into(),
+            },
+            IcpTransferError::InsufficientFunds { balance } => TransferError::InsufficientFunds {
+                balance: balance.e8s().into(),
+            },
+            IcpTransferError::TxTooOld { .. } => TransferError::TooOld,
+            IcpTransferError::TxCreatedInFuture => TransferError::CreatedInFuture {
+                ledger_time: ic_exports::ic_kit::ic::time(),
+            },
+            IcpTransferError::TxDuplicate { duplicate_of } => TransferError::Duplicate {
+                duplicate_of: duplicate_of.into(),
+            },
+        };
+
+        err.into()
+    }
+}
+
 impl From<InternalPaymentError> for PaymentError {
     fn from(internal: InternalPaymentError) -> Self {
         match internal {
@@ -0,0 +1,338 @@
+//! Persistent history of the transfers performed by the [`TokenTerminal`](crate::TokenTerminal).
+//!
+//! To record the history, initialize the storage with [`TransferHistory::init`] in both `init`
+//! and `post_upgrade` methods of the canister, and give the [`TransferHistory`] to the terminal
+//! with [`TokenTerminal::with_transfer_hooks`](crate::TokenTerminal::with_transfer_hooks). The
+//! recorded transfers can be queried with the [`TransferHistoryApi`] canister trait.
+//!
+//! The history is recorded by a transfer hook, so it can be used together with other hooks
+//! added to the terminal.
+
+use std::cell::RefCell;
+
+use candid::{CandidType, Deserialize, Nat, Principal};
+use ic_canister::{generate_exports, generate_idl, query, Canister, Idl, PreUpdate};
+use ic_exports::ic_kit::ic;
+use ic_exports::icrc_types::icrc1::account::Account;
+use ic_stable_structures::stable_structures::DefaultMemoryImpl;
+use ic_stable_structures::{MultimapStructure, RecordLog, StableMultimap, VirtualMemory};
+
+use crate::error::PaymentError;
+use crate::transfer::{Operation, Stage, Transfer, TransferType};
+use crate::{OnTransferEvent, Timestamp, TxId};
+
+/// Maximum number of records returned by one [`TransferHistory::page`] request.
+pub const MAX_HISTORY_PAGE_SIZE: u64 = 100;
+
+/// Index of the records of a principal: `(principal, seq)` to the index of the principal's
+/// `seq`-th record in the history log.
+type PrincipalIndex = StableMultimap<Principal, u64, u64, VirtualMemory<DefaultMemoryImpl>>;
+
+thread_local! {
+    static HISTORY_STORAGE: RecordLog<TransferRecord> = const { RecordLog::new() };
+    static PRINCIPAL_INDEX: RefCell<Option<PrincipalIndex>> = const { RefCell::new(None) };
+}
+
+/// Direction of a recorded transfer, relative to the user balances in the canister.
+#[derive(Debug, CandidType, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum TransferDirection {
+    /// Tokens are credited to the user balance on success.
+    Deposit,
+
+    /// Tokens are debited from the user balance (and credited back on failure).
+    Withdrawal,
+
+    /// Transfer doesn't change user balances.
+    Other,
+}
+
+/// Final status of a recorded transfer.
+#[derive(Debug, CandidType, Deserialize, Clone, PartialEq, Eq)]
+pub enum TransferStatus {
+    /// Transfer is completed with the given token transaction id.
+    Succeeded { tx_id: TxId },
+
+    /// Transfer failed with the given reason.
+    Failed { reason: String },
+}
+
+/// Record of a completed or failed transfer.
+#[derive(Debug, CandidType, Deserialize, Clone, PartialEq, Eq)]
+pub struct TransferRecord {
+    /// Unique id of the transfer.
+    pub id: [u8; 32],
+
+    /// Token principal.
+    pub token: Principal,
+
+    /// Principal whose balance is affected by the transfer.
+    pub caller: Principal,
+
+    /// Direction of the transfer.
+    pub direction: TransferDirection,
+
+    /// Source account of the transfer.
+    pub from: Account,
+
+    /// Target account of the transfer.
+    pub to: Account,
+
+    /// Amount of the transfer, including the fee.
+    pub amount: Nat,
+
+    /// Total fee of the transfer.
+    pub fee: Nat,
+
+    /// Final status of the transfer.
+    pub status: TransferStatus,
+
+    /// Timestamp when the transfer was created.
+    pub created_at: Timestamp,
+
+    /// Timestamp when the transfer was completed or failed.
+    pub finished_at: Timestamp,
+}
+
+impl TransferRecord {
+    fn new(transfer: &Transfer, status: TransferStatus) -> Self {
+        let direction = match transfer.operation() {
+            Operation::CreditOnSuccess => TransferDirection::Deposit,
+            Operation::CreditOnError => TransferDirection::Withdrawal,
+            Operation::None => TransferDirection::Other,
+        };
+
+        // Fee of the first step of double-step transfer includes fees of both steps, and the
+        // second step has the fee of the first step subtracted from the amount.
+        let (from, amount, fee) = match transfer.r#type() {
+            TransferType::DoubleStep(Stage::Second, _) => (
+                transfer.from_acc(),
+                transfer.amount() + transfer.fee.clone(),
+                transfer.fee.clone() * 2u64,
+            ),
+            _ => (
+                transfer.from_acc(),
+                transfer.amount(),
+                transfer.effective_fee(),
+            ),
+        };
+
+        Self {
+            id: transfer.id(),
+            token: transfer.token,
+            caller: transfer.caller(),
+            direction,
+            from,
+            to: transfer.to,
+            amount,
+            fee,
+            status,
+            created_at: transfer.created_at(),
+            finished_at: ic::time(),
+        }
+    }
+}
+
+/// Page of the transfer history.
+#[derive(Debug, CandidType, Deserialize, Clone, PartialEq, Eq)]
+pub struct TransferHistoryPage {
+    /// Total number of records matching the request.
+    pub total: u64,
+
+    /// Records of the page, newest first.
+    pub records: Vec<TransferRecord>,
+}
+
+/// History of the transfers stored in stable memory.
+///
+/// Records are added when a transfer is finally completed or failed. Transfers moved to the
+/// recovery list are recorded when the recovery is finished.
+///
+/// If the storage is not initialized with [`TransferHistory::init`], no records are stored.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TransferHistory;
+
+impl TransferHistory {
+    /// Initializes the history storage in the given memories, loading the records already stored
+    /// there. The `principal_index_memory` keeps the records of every principal, so the transfers
+    /// of a principal are read without scanning the whole history.
+    pub fn init(
+        index_memory: VirtualMemory<DefaultMemoryImpl>,
+        data_memory: VirtualMemory<DefaultMemoryImpl>,
+        principal_index_memory: VirtualMemory<DefaultMemoryImpl>,
+    ) -> ic_stable_structures::Result<Self> {
+        HISTORY_STORAGE.with(|log| log.init(index_memory, data_memory))?;
+        PRINCIPAL_INDEX.with(|index| {
+            *index.borrow_mut() = Some(StableMultimap::new(principal_index_memory));
+        });
+        Ok(Self)
+    }
+
+    /// Number of records in the history.
+    pub fn len(&self) -> u64 {
+        HISTORY_STORAGE.with(|log| log.len())
+    }
+
+    /// Returns `true` if the history has no records.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns at most `limit` records, newest first, skipping the first `offset` of them.
+    ///
+    /// If `principal` is given, only the transfers of this principal are returned. They are read
+    /// through the principal index, so the cost of the request doesn't depend on the size of
+    /// the history.
+    ///
+    /// The `limit` is capped by [`MAX_HISTORY_PAGE_SIZE`].
+    pub fn page(
+        &self,
+        principal: Option<Principal>,
+        offset: u64,
+        limit: u64,
+    ) -> TransferHistoryPage {
+        let limit = limit.min(MAX_HISTORY_PAGE_SIZE);
+        let Some(principal) = principal else {
+            let (total, records) = HISTORY_STORAGE
+                .with(|log| log.page::<fn(&TransferRecord) -> bool>(offset, limit, None));
+            return TransferHistoryPage { total, records };
+        };
+
+        PRINCIPAL_INDEX.with(|index| {
+            let index = index.borrow();
+            let Some(index) = index.as_ref() else {
+                return TransferHistoryPage {
+                    total: 0,
+                    records: vec![],
+                };
+            };
+
+            let total = principal_records_count(index, principal);
+            let newest_first = (0..total.saturating_sub(offset)).rev().take(limit as usize);
+            let records = newest_first
+                .filter_map(|seq| index.get(&principal, &seq))
+                .filter_map(|log_index| HISTORY_STORAGE.with(|log| log.get(log_index)))
+                .collect();
+
+            TransferHistoryPage { total, records }
+        })
+    }
+
+    /// Returns the page of the history the `caller` is allowed to read.
+    ///
+    /// Callers for which `can_read_all` returns `false` can only read their own transfers, so
+    /// the `principal` filter is set to the caller for them.
+    pub fn page_for_caller(
+        &self,
+        caller: Principal,
+        can_read_all: impl FnOnce(&Principal) -> bool,
+        principal: Option<Principal>,
+        offset: u64,
+        limit: u64,
+    ) -> TransferHistoryPage {
+        let principal = if can_read_all(&caller) {
+            principal
+        } else {
+            Some(caller)
+        };
+
+        self.page(principal, offset, limit)
+    }
+
+    fn append(&self, record: TransferRecord) {
+        let principal = record.caller;
+        let Some(log_index) = HISTORY_STORAGE.with(|log| log.append(record)) else {
+            return;
+        };
+
+        PRINCIPAL_INDEX.with(|index| {
+            if let Some(index) = index.borrow_mut().as_mut() {
+                let seq = principal_records_count(index, principal);
+                index.insert(&principal, &seq, log_index);
+            }
+        });
+    }
+}
+
+/// Number of the records of the `principal` in the index.
+fn principal_records_count(index: &PrincipalIndex, principal: Principal) -> u64 {
+    index
+        .iter_upper_bound(&(principal, u64::MAX))
+        .next()
+        .filter(|(record_principal, _, _)| *record_principal == principal)
+        .map(|(_, seq, _)| seq + 1)
+        .unwrap_or_default()
+}
+
+impl OnTransferEvent for TransferHistory {
+    fn on_succeeded(&mut self, transfer: &Transfer, tx_id: &TxId) {
+        // First step of a double-step transfer is not the completion of the transfer.
+        if let TransferType::DoubleStep(Stage::First, _) = transfer.r#type() {
+            return;
+        }
+
+        self.append(TransferRecord::new(
+            transfer,
+            TransferStatus::Succeeded {
+                tx_id: tx_id.clone(),
+            },
+        ));
+    }
+
+    fn on_failed(&mut self, transfer: &Transfer, error: &PaymentError) {
+        self.append(TransferRecord::new(
+            transfer,
+            TransferStatus::Failed {
+                reason: error.to_string(),
+            },
+        ));
+    }
+}
+
+/// Canister API to query the [`TransferHistory`].
+pub trait TransferHistoryApi: Canister {
+    /// Returns at most `limit` transfers records, newest first, skipping the first `offset` of
+    /// them. If `principal` is given, only the transfers of this principal are returned.
+    ///
+    /// Only the callers allowed by [`TransferHistoryApi::can_read_all_transfers`] can read the
+    /// transfers of other principals, the other callers always get their own transfers.
+    #[query(trait = true)]
+    fn get_transfer_history(
+        &self,
+        principal: Option<Principal>,
+        offset: u64,
+        limit: u64,
+    ) -> TransferHistoryPage {
+        TransferHistory.page_for_caller(
+            ic::caller(),
+            |caller| self.can_read_all_transfers(caller),
+            principal,
+            offset,
+            limit,
+        )
+    }
+
+    /// Checks if the `caller` is allowed to read the transfers of all principals.
+    ///
+    /// By default only the controllers of the canister are allowed.
+    fn can_read_all_transfers(&self, caller: &Principal) -> bool {
+        #[cfg(target_family = "wasm")]
+        {
+            ic_exports::ic_cdk::api::is_controller(caller)
+        }
+
+        #[cfg(not(target_family = "wasm"))]
+        {
+            let _ = caller;
+            false
+        }
+    }
+
+    // Important: This function *must* be defined to be the
+    // last one in the trait because it depends on the order
+    // of expansion of update/query(trait = true) methods.
+    fn get_idl() -> Idl {
+        generate_idl!()
+    }
+}
+
+generate_exports!(TransferHistoryApi);
@@ -0,0 +1,24 @@
+use crate::error::PaymentError;
+use crate::{Transfer, TxId};
+
+/// Callbacks invoked by the [`TokenTerminal`](crate::TokenTerminal) on the transfer lifecycle
+/// events.
+///
+/// Can be used to emit logs, metrics or notifications for each transfer. All methods have empty
+/// default implementations, so only the events of interest have to be implemented.
+///
+/// Double-step transfers produce events for each of the steps separately.
+pub trait OnTransferEvent {
+    /// Transfer (or a step of the transfer) is about to be sent to the token canister.
+    fn on_started(&mut self, _transfer: &Transfer) {}
+
+    /// Transfer (or a step of the transfer) is completed successfully with the given token
+    /// transaction id.
+    fn on_succeeded(&mut self, _transfer: &Transfer, _tx_id: &TxId) {}
+
+    /// Transfer failed and will not be retried.
+    fn on_failed(&mut self, _transfer: &Transfer, _error: &PaymentError) {}
+
+    /// Result of the transfer is unknown, so it is moved to the recovery list.
+    fn on_moved_to_recovery(&mut self, _transfer: &Transfer) {}
+}
@@ -0,0 +1,98 @@
+//! Helpers to call the legacy ICP ledger with ICRC-1 style arguments.
+
+use candid::{Nat, Principal};
+use ic_canister::virtual_canister_call;
+use ic_exports::icrc_types::icrc1::account::{Account, Subaccount};
+use ic_exports::icrc_types::icrc1::transfer::Memo;
+use ic_exports::ledger::{
+    AccountBalanceArgs, AccountIdentifier, BlockIndex, Memo as IcpMemo,
+    Subaccount as IcpSubaccount, Timestamp as IcpTimestamp, Tokens, TransferArgs, TransferError,
+};
+
+use crate::error::{InternalPaymentError, ParametersError, Result};
+use crate::icrc1::TokenTransferInfo;
+use crate::Timestamp;
+
+/// Returns the ICP ledger account identifier of the ICRC-1 `account`.
+pub fn account_identifier(account: &Account) -> AccountIdentifier {
+    AccountIdentifier::new(
+        &account.owner,
+        &IcpSubaccount(account.effective_subaccount().to_owned()),
+    )
+}
+
+/// Maximum length of the memo supported by the ICP ledger.
+pub const ICP_MEMO_MAX_LENGTH: usize = 8;
+
+/// Converts the ICRC-1 memo into the ICP ledger memo.
+///
+/// The memo bytes are interpreted as a big-endian number. Memos longer than
+/// [`ICP_MEMO_MAX_LENGTH`] bytes cannot be converted without collisions, so
+/// [`ParametersError::MemoTooLong`] is returned for them.
+pub fn icp_memo(memo: Option<&Memo>) -> std::result::Result<IcpMemo, ParametersError> {
+    let Some(memo) = memo else {
+        return Ok(IcpMemo(0));
+    };
+
+    let bytes = memo.0.as_slice();
+    if bytes.len() > ICP_MEMO_MAX_LENGTH {
+        return Err(ParametersError::MemoTooLong {
+            max_length: ICP_MEMO_MAX_LENGTH as u64,
+            actual: bytes.len() as u64,
+        });
+    }
+
+    let mut number = [0u8; ICP_MEMO_MAX_LENGTH];
+    number[ICP_MEMO_MAX_LENGTH - bytes.len()..].copy_from_slice(bytes);
+    Ok(IcpMemo(u64::from_be_bytes(number)))
+}
+
+/// Returns current balance of the `account` in the ICP `ledger` canister.
+pub async fn get_icp_balance(ledger: Principal, account: &Account) -> Result<Nat> {
+    let args = AccountBalanceArgs {
+        account: account_identifier(account),
+    };
+    let balance = virtual_canister_call!(ledger, "account_balance", (args,), Tokens).await?;
+    Ok(balance.e8s().into())
+}
+
+/// Requests a transfer in the ICP `ledger` canister.
+///
+/// Duplicate transactions are detected by the ledger using the block height based deduplication,
+/// so the same arguments including `created_at_time` and `memo` must be used for retries.
+pub async fn transfer_icp(
+    ledger: Principal,
+    to: Account,
+    amount: Nat,
+    fee: Nat,
+    from_subaccount: Option<Subaccount>,
+    created_at_time: Option<Timestamp>,
+    memo: Option<Memo>,
+) -> Result<TokenTransferInfo> {
+    let args = TransferArgs {
+        memo: icp_memo(memo.as_ref())?,
+        amount: Tokens::from_e8s(to_e8s(&amount)?),
+        fee: Tokens::from_e8s(to_e8s(&fee)?),
+        from_subaccount: from_subaccount.map(IcpSubaccount),
+        to: account_identifier(&to),
+        created_at_time: created_at_time.map(|timestamp_nanos| IcpTimestamp { timestamp_nanos }),
+    };
+
+    let block_index = virtual_canister_call!(
+        ledger,
+        "transfer",
+        (args,),
+        std::result::Result<BlockIndex, TransferError>
+    )
+    .await??;
+
+    Ok(TokenTransferInfo {
+        token_tx_id: block_index.into(),
+        amount_transferred: amount,
+        token_principal: ledger,
+    })
+}
+
+fn to_e8s(amount: &Nat) -> Result<u64> {
+    u64::try_from(amount.0.clone()).map_err(|_| InternalPaymentError::Overflow)
+}
@@ -2,6 +2,9 @@ use ic_canister::virtual_canister_call;
 use ic_exports::candid::{CandidType, Nat, Principal};
 use ic_exports::icrc_types::icrc1::account::{Account, Subaccount};
 use ic_exports::icrc_types::icrc1::transfer::{Memo, TransferArg, TransferError};
+use ic_exports::icrc_types::icrc2::allowance::{Allowance, AllowanceArgs};
+use ic_exports::icrc_types::icrc2::approve::{ApproveArgs, ApproveError};
+use ic_exports::icrc_types::icrc2::transfer_from::{TransferFromArgs, TransferFromError};
 use serde::Deserialize;
 
 use crate::error::Result;
@@ -52,6 +55,112 @@ pub async fn transfer_icrc1(
     })
 }
 
+/// Requests a transfer from the `from` account using the allowance given to the current canister
+/// in an ICRC-2 `token` canister.
+#[allow(clippy::too_many_arguments)]
+pub async fn transfer_from_icrc2(
+    token: Principal,
+    from: Account,
+    to: Account,
+    amount: Nat,
+    fee: Nat,
+    spender_subaccount: Option<Subaccount>,
+    created_at_time: Option<Timestamp>,
+    memo: Option<Memo>,
+) -> Result<TokenTransferInfo> {
+    let args = TransferFromArgs {
+        spender_subaccount,
+        from,
+        to,
+        amount: amount.clone(),
+        fee: Some(fee),
+        memo,
+        created_at_time,
+    };
+
+    let tx_id = virtual_canister_call!(
+        token,
+        "icrc2_transfer_from",
+        (args,),
+        std::result::Result<TxId, TransferFromError>
+    )
+    .await??;
+
+    Ok(TokenTransferInfo {
+        token_tx_id: tx_id,
+        amount_transferred: amount,
+        token_principal: token,
+    })
+}
+
+/// Approves the `spender` to transfer at most `amount` tokens from the `from_subaccount` of the
+/// current canister in an ICRC-2 `token` canister. Returns the id of the approve transaction.
+///
+/// If `expected_allowance` is given, the approval fails with
+/// [`TransferFailReason::AllowanceChanged`](crate::error::TransferFailReason::AllowanceChanged)
+/// error if the current allowance is different. The allowance can be used until `expires_at`
+/// timestamp, if given.
+#[allow(clippy::too_many_arguments)]
+pub async fn approve_icrc2(
+    token: Principal,
+    spender: Account,
+    amount: Nat,
+    fee: Nat,
+    from_subaccount: Option<Subaccount>,
+    expected_allowance: Option<Nat>,
+    expires_at: Option<Timestamp>,
+    created_at_time: Option<Timestamp>,
+) -> Result<TxId> {
+    let args = ApproveArgs {
+        from_subaccount,
+        spender,
+        amount,
+        expected_allowance,
+        expires_at,
+        fee: Some(fee),
+        memo: None,
+        created_at_time,
+    };
+
+    Ok(
+        virtual_canister_call!(token, "icrc2_approve", (args,), std::result::Result<TxId, ApproveError>)
+            .await??,
+    )
+}
+
+/// Requests the allowance given by the `account` to the `spender` from an ICRC-2 `token` canister.
+pub async fn get_icrc2_allowance(
+    token: Principal,
+    account: Account,
+    spender: Account,
+) -> Result<Allowance> {
+    let args = AllowanceArgs { account, spender };
+    Ok(virtual_canister_call!(token, "icrc2_allowance", (args,), Allowance).await?)
+}
+
+/// Requests the allowance given by the `account` to the `spender` from an ICRC-2 `token` canister,
+/// taking its expiration into account. Returns `0` if the allowance is expired.
+pub async fn get_icrc2_active_allowance(
+    token: Principal,
+    account: Account,
+    spender: Account,
+) -> Result<Nat> {
+    let allowance = get_icrc2_allowance(token, account, spender).await?;
+    Ok(active_allowance_amount(
+        &allowance,
+        ic_exports::ic_kit::ic::time(),
+    ))
+}
+
+/// Returns the amount of the `allowance` which can be used at `now`. Returns `0` if the allowance
+/// is expired.
+pub fn active_allowance_amount(allowance: &Allowance, now: Timestamp) -> Nat {
+    match allowance.expires_at {
+        Some(expires_at) if expires_at <= now => 0u64.into(),
+        _ => allowance.allowance.clone(),
+    }
+}
+
 /// Requests fee and minting account configuration from an ICRC-1 canister.
 pub async fn get_icrc1_configuration(token: Principal) -> Result<TokenConfiguration> {
     // ICRC-1 standard metadata doesn't include a minting account, so we have to do two requests
@@ -62,11 +171,7 @@ pub async fn get_icrc1_configuration(token: Principal) -> Result<TokenConfigurat
         subaccount: None,
     });
 
-    Ok(TokenConfiguration {
-        principal: token,
-        fee,
-        minting_account,
-    })
+    Ok(TokenConfiguration::new(token, fee, minting_account))
 }
 
 /// Requests fee configuration from an ICRC-1 canister.
@@ -0,0 +1,224 @@
+//! Helpers to read and verify transactions of ICRC-3 compatible token canisters.
+//!
+//! The types in this module follow the ICRC-3 standard definitions of the `icrc3_get_blocks`
+//! method.
+
+use std::collections::BTreeMap;
+
+use candid::{define_function, CandidType, Deserialize, Int, Nat, Principal};
+use ic_canister::virtual_canister_call;
+use ic_exports::icrc_types::icrc1::account::Account;
+
+use crate::error::{ClaimError, Result};
+
+/// Generic ICRC-3 value.
+#[derive(Debug, CandidType, Deserialize, Clone, PartialEq, Eq)]
+pub enum Icrc3Value {
+    Blob(Vec<u8>),
+    Text(String),
+    Nat(Nat),
+    Int(Int),
+    Array(Vec<Icrc3Value>),
+    Map(BTreeMap<String, Icrc3Value>),
+}
+
+impl Icrc3Value {
+    fn field(&self, name: &str) -> Option<&Icrc3Value> {
+        match self {
+            Self::Map(map) => map.get(name),
+            _ => None,
+        }
+    }
+
+    fn as_nat(&self) -> Option<&Nat> {
+        match self {
+            Self::Nat(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    fn as_text(&self) -> Option<&str> {
+        match self {
+            Self::Text(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    fn as_blob(&self) -> Option<&[u8]> {
+        match self {
+            Self::Blob(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    fn as_account(&self) -> Option<Account> {
+        let Self::Array(parts) = self else {
+            return None;
+        };
+
+        let owner = Principal::try_from_slice(parts.first()?.as_blob()?).ok()?;
+        let subaccount = match parts.get(1) {
+            Some(subaccount) => Some(subaccount.as_blob()?.try_into().ok()?),
+            None => None,
+        };
+
+        Some(Account { owner, subaccount })
+    }
+}
+
+/// Range of blocks requested from the token canister.
+#[derive(Debug, CandidType, Deserialize, Clone, PartialEq, Eq)]
+pub struct GetBlocksArgs {
+    pub start: Nat,
+    pub length: Nat,
+}
+
+/// Block with its index.
+#[derive(Debug, CandidType, Deserialize, Clone, PartialEq, Eq)]
+pub struct BlockWithId {
+    pub id: Nat,
+    pub block: Icrc3Value,
+}
+
+define_function!(pub QueryArchiveFn : (Vec<GetBlocksArgs>) -> (GetBlocksResult) query);
+
+/// Blocks stored in an archive canister.
+#[derive(Debug, CandidType, Deserialize, Clone)]
+pub struct ArchivedBlocks {
+    pub args: Vec<GetBlocksArgs>,
+    pub callback: QueryArchiveFn,
+}
+
+/// Response of the `icrc3_get_blocks` method.
+#[derive(Debug, CandidType, Deserialize, Clone)]
+pub struct GetBlocksResult {
+    pub log_length: Nat,
+    pub blocks: Vec<BlockWithId>,
+    pub archived_blocks: Vec<ArchivedBlocks>,
+}
+
+/// Token transfer read from an ICRC-3 block.
+#[derive(Debug, CandidType, Deserialize, Clone, PartialEq, Eq)]
+pub struct Icrc3Transfer {
+    /// Index of the block.
+    pub block_index: Nat,
+
+    /// Source account of the transfer.
+    pub from: Account,
+
+    /// Target account of the transfer.
+    pub to: Account,
+
+    /// Transferred amount, not including the fee.
+    pub amount: Nat,
+
+    /// Account which made the transfer using an ICRC-2 allowance of the `from` account.
+    pub spender: Option<Account>,
+
+    /// Memo of the transaction.
+    pub memo: Option<Vec<u8>>,
+}
+
+impl Icrc3Transfer {
+    /// Reads the transfer from the ICRC-3 `block`. Returns `None` if the block is not a transfer
+    /// block.
+    pub fn from_block(block_index: Nat, block: &Icrc3Value) -> Option<Self> {
+        let tx = block.field("tx")?;
+        let op = match block.field("btype").and_then(Icrc3Value::as_text) {
+            Some(btype) => btype,
+            None => tx.field("op")?.as_text()?,
+        };
+        if !matches!(op, "xfer" | "1xfer" | "2xfer") {
+            return None;
+        }
+
+        Some(Self {
+            block_index,
+            from: tx.field("from")?.as_account()?,
+            to: tx.field("to")?.as_account()?,
+            amount: tx.field("amt")?.as_nat()?.clone(),
+            spender: match tx.field("spender") {
+                Some(spender) => Some(spender.as_account()?),
+                None => None,
+            },
+            memo: tx
+                .field("memo")
+                .and_then(Icrc3Value::as_blob)
+                .map(<[u8]>::to_vec),
+        })
+    }
+
+    /// Checks that the transfer moves exactly `amount` tokens from an account of `from_owner` to
+    /// the `to` account, and that it was made by the owner itself rather than by a spender.
+    ///
+    /// The transfers made by a spender, e.g. the canister pulling a deposit with
+    /// `icrc2_transfer_from`, are rejected, since they are already accounted for by the spender.
+    pub fn verify(
+        &self,
+        from_owner: Principal,
+        to: &Account,
+        amount: &Nat,
+    ) -> std::result::Result<(), ClaimError> {
+        if self.from.owner != from_owner {
+            return Err(ClaimError::WrongSender);
+        }
+
+        if self.to != *to {
+            return Err(ClaimError::WrongRecipient);
+        }
+
+        if self.spender.is_some() {
+            return Err(ClaimError::SpenderTransfer);
+        }
+
+        if self.amount != *amount {
+            return Err(ClaimError::WrongAmount {
+                expected: amount.clone(),
+                actual: self.amount.clone(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Requests the blocks in the given range from an ICRC-3 `token` canister.
+pub async fn get_icrc3_blocks(
+    token: Principal,
+    args: Vec<GetBlocksArgs>,
+) -> Result<GetBlocksResult> {
+    Ok(virtual_canister_call!(token, "icrc3_get_blocks", (args,), GetBlocksResult).await?)
+}
+
+/// Requests the block with the given index from an ICRC-3 `token` canister, following the archive
+/// callback if the block is archived.
+///
+/// Returns `None` if the block doesn't exist.
+pub async fn get_icrc3_block(token: Principal, block_index: Nat) -> Result<Option<Icrc3Value>> {
+    let args = vec![GetBlocksArgs {
+        start: block_index.clone(),
+        length: 1u64.into(),
+    }];
+    let result = get_icrc3_blocks(token, args).await?;
+
+    if let Some(block) = result.blocks.into_iter().find(|b| b.id == block_index) {
+        return Ok(Some(block.block));
+    }
+
+    for archived in result.archived_blocks {
+        let callback = archived.callback.0;
+        let result = virtual_canister_call!(
+            callback.principal,
+            &callback.method,
+            (archived.args,),
+            GetBlocksResult
+        )
+        .await?;
+
+        if let Some(block) = result.blocks.into_iter().find(|b| b.id == block_index) {
+            return Ok(Some(block.block));
+        }
+    }
+
+    Ok(None)
+}
@@ -0,0 +1,88 @@
+//! Helpers to call ICRC-1 index canisters.
+//!
+//! The types in this module contain only the fields of the index canister interface used by this
+//! crate.
+
+use candid::{CandidType, Deserialize, Nat, Principal};
+use ic_canister::virtual_canister_call;
+use ic_exports::icrc_types::icrc1::account::Account;
+
+use crate::error::{InternalPaymentError, Result, TransferFailReason};
+use crate::{Timestamp, TxId};
+
+/// Arguments of the `get_account_transactions` method.
+#[derive(Debug, CandidType, Deserialize, Clone, PartialEq, Eq)]
+pub struct GetAccountTransactionsArgs {
+    pub account: Account,
+
+    /// Id of the last transaction seen by the client. If set, only older transactions are returned.
+    pub start: Option<TxId>,
+
+    pub max_results: Nat,
+}
+
+/// Transfer operation of a transaction.
+#[derive(Debug, CandidType, Deserialize, Clone, PartialEq, Eq)]
+pub struct IndexTransfer {
+    pub from: Account,
+    pub to: Account,
+    pub amount: Nat,
+    pub spender: Option<Account>,
+    pub memo: Option<Vec<u8>>,
+}
+
+/// Transaction returned by the index canister.
+#[derive(Debug, CandidType, Deserialize, Clone, PartialEq, Eq)]
+pub struct IndexTransaction {
+    pub kind: String,
+    pub transfer: Option<IndexTransfer>,
+    pub timestamp: Timestamp,
+}
+
+/// Transaction with its id (ledger block index).
+#[derive(Debug, CandidType, Deserialize, Clone, PartialEq, Eq)]
+pub struct TransactionWithId {
+    pub id: TxId,
+    pub transaction: IndexTransaction,
+}
+
+/// Transactions of an account, newest first.
+#[derive(Debug, CandidType, Deserialize, Clone, PartialEq, Eq)]
+pub struct GetTransactions {
+    pub balance: Nat,
+    pub transactions: Vec<TransactionWithId>,
+    pub oldest_tx_id: Option<TxId>,
+}
+
+/// Error returned by the index canister.
+#[derive(Debug, CandidType, Deserialize, Clone, PartialEq, Eq)]
+pub struct GetTransactionsErr {
+    pub message: String,
+}
+
+/// Requests at most `max_results` transactions of the `account` from the `index` canister, newest
+/// first. If `start` is given, only transactions older than `start` are returned.
+pub async fn get_account_transactions(
+    index: Principal,
+    account: Account,
+    start: Option<TxId>,
+    max_results: u64,
+) -> Result<GetTransactions> {
+    let args = GetAccountTransactionsArgs {
+        account,
+        start,
+        max_results: max_results.into(),
+    };
+
+    virtual_canister_call!(
+        index,
+        "get_account_transactions",
+        (args,),
+        std::result::Result<GetTransactions, GetTransactionsErr>
+    )
+    .await?
+    // Index errors mean the same as the token canister failures for the caller.
+    .map_err(|err| {
+        InternalPaymentError::TransferFailed(TransferFailReason::TokenPanic(err.message))
+    })
+}
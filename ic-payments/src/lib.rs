@@ -17,11 +17,24 @@
 //! for a [`Balances`] trait which stores the user balances in the canister.
 //!
 //! There are also convenience methods in [`icrc1`] module to call common operations of ICRC-1
-//! compatible tokens.
+//! compatible tokens, and in [`icrc3`] module to read the transactions of ICRC-3 compatible
+//! tokens.
+//!
+//! Instead of requiring the users to notify the canister about their deposits, the terminal can
+//! detect the deposits to the canister main account using the token index canister, see
+//! [`TokenTerminal::poll_deposits`] and [`start_deposit_polling`].
+//!
+//! To observe transfers performed by the terminal (e.g. for logging or metrics), implement the
+//! [`OnTransferEvent`] trait and give it to [`TokenTerminal::with_transfer_hooks`]. The
+//! [`history`] module provides such an implementation storing the history of the transfers in
+//! stable memory.
+//!
+//! The terminal can also work with the legacy interface of the ICP ledger, see
+//! [`TokenTerminal::with_ledger`] and the [`icp`] module.
 //!
 //! # Transfer types
 //!
-//! There are two [transfer types](transfer::TransferType) available for token terminal:
+//! There are three [transfer types](transfer::TransferType) available for token terminal:
 //! * Single-step transfer - performed with one inter-canister call an allows recovery during the
 //!   deduplication period of the token (typically 24 hours). After deduplication period is over,
 //!   the transfer cannot be recovered and would be considered failed.
@@ -31,6 +44,9 @@
 //!   considered failed only if the first step failed. If the first step is successful but the
 //!   second step failed, the transfer is always kept in recovery list until it can be successfully
 //!   completed (since the tokens are already locked in the interim account).
+//! * Allowance transfer - ICRC-2 `icrc2_transfer_from` call pulling the tokens from an account
+//!   which approved the canister to spend them. It is recovered the same way as single-step
+//!   transfers. See [`TokenTerminal::deposit_from_allowance`].
 //!
 //! # Performing a transfer
 //!
@@ -57,12 +73,14 @@
 //! # Recovery
 //!
 //! Transfers stored in the recovery list can be recovered by calling
-//! [`TokenTerminal::recover_all()`] method. There are two ways to recover a transfer, result of
+//! [`TokenTerminal::recover_all()`] method, or automatically by a timer started with
+//! [`start_recovery_timer`]. There are two ways to recover a transfer, result of
 //! which is not know to the terminal:
 //!
 //! 1. Using deduplication mechanism of ICRC-1 tokens. This mechanism is applied to all
 //!    transfers that are recent enough, e.g. are initiated less than deduplication period of the
-//!    token (typically 24 hours).
+//!    token (typically 24 hours). The deduplication period and the permitted time drift can be
+//!    configured with [`TokenConfiguration::with_deduplication_window`].
 //! 2. Using interim accounts of double-step transfers. This mechanism can only be applied to the
 //!    double-step transfers, and applied for transfers older than deduplication period.
 //!
@@ -109,23 +127,48 @@
 
 use candid::{CandidType, Deserialize, Nat, Principal};
 
+mod account_lock;
+mod amount_limits;
 mod balances;
+pub mod cmc;
+mod deposit_watcher;
 pub mod error;
+pub mod history;
+mod hooks;
+pub mod icp;
 pub mod icrc1;
+pub mod icrc3;
+pub mod index;
+#[cfg(feature = "payouts")]
+pub mod payouts;
 pub mod recovery_list;
+mod recovery_timer;
 mod token_terminal;
 mod transfer;
 
+pub use amount_limits::*;
 pub use balances::*;
+pub use deposit_watcher::*;
 pub use error::PaymentError;
+pub use hooks::*;
 use ic_exports::icrc_types::icrc1::account::Account;
 pub use recovery_list::*;
+pub use recovery_timer::*;
 pub use token_terminal::*;
 pub use transfer::*;
 
 type Timestamp = u64;
 type TxId = Nat;
 
+/// Default period when deduplication of a transaction is possible. This is set by the token
+/// implementation. 24 hours used here is the most common value, used by ICP and SNS-1 ledgers.
+pub const DEFAULT_DEDUPLICATION_PERIOD: u64 = 10u64.pow(9) * 60 * 60 * 24;
+
+/// Default permitted time drift between the canister and the token. Different IC nodes can have
+/// times not synchronized perfectly, so we use 5 minute margin to make sure we don't try to
+/// deduplicate transactions when it's not possible already.
+pub const DEFAULT_PERMITTED_DRIFT: u64 = 10u64.pow(9) * 60 * 5;
+
 /// Configuration of the token canister.
 ///
 /// This configuration can be obtained by the [`icrc1::get_icrc1_configuration`] function.
@@ -139,9 +182,71 @@ pub struct TokenConfiguration {
 
     /// Token minting account.
     pub minting_account: Account,
+
+    /// Period in nanoseconds during which the token deduplicates transactions.
+    #[serde(default = "default_deduplication_period")]
+    pub deduplication_period: u64,
+
+    /// Maximum time drift in nanoseconds between the canister and the token. Transactions older
+    /// than `deduplication_period - permitted_drift` are not recovered through deduplication.
+    #[serde(default = "default_permitted_drift")]
+    pub permitted_drift: u64,
+}
+
+fn default_deduplication_period() -> u64 {
+    DEFAULT_DEDUPLICATION_PERIOD
+}
+
+fn default_permitted_drift() -> u64 {
+    DEFAULT_PERMITTED_DRIFT
+}
+
+/// Kind of the ledger canister of the token.
+#[derive(CandidType, Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LedgerKind {
+    /// ICRC-1 compatible ledger.
+    #[default]
+    Icrc1,
+
+    /// Legacy ICP ledger interface, using account identifiers and `u64` memos.
+    Icp,
 }
 
 impl TokenConfiguration {
+    /// Creates a new configuration with the default deduplication window.
+    pub fn new(principal: Principal, fee: Nat, minting_account: Account) -> Self {
+        Self {
+            principal,
+            fee,
+            minting_account,
+            deduplication_period: DEFAULT_DEDUPLICATION_PERIOD,
+            permitted_drift: DEFAULT_PERMITTED_DRIFT,
+        }
+    }
+
+    /// Sets the deduplication period of the token and the permitted time drift, both in
+    /// nanoseconds.
+    pub fn with_deduplication_window(
+        self,
+        deduplication_period: u64,
+        permitted_drift: u64,
+    ) -> Self {
+        Self {
+            deduplication_period,
+            permitted_drift,
+            ..self
+        }
+    }
+
+    /// Returns `true` if a transaction created at `created_at` can still be deduplicated by the
+    /// token at `now`.
+    pub(crate) fn can_deduplicate(&self, created_at: Timestamp, now: Timestamp) -> bool {
+        now.saturating_sub(created_at)
+            < self
+                .deduplication_period
+                .saturating_sub(self.permitted_drift)
+    }
+
     pub(crate) fn get_fee(&self, from_acc: &Account, to_acc: &Account) -> Nat {
         if *from_acc == self.minting_account || *to_acc == self.minting_account {
             0u64.into()
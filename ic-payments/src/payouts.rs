@@ -0,0 +1,230 @@
+//! Scheduled and recurring payouts executed by the [`ic_task_scheduler`] scheduler.
+//!
+//! A payout is a transfer from the canister account to a recipient, executed through the
+//! [`TokenTerminal`] at the configured time. The scheduler stores the pending payouts, so they are
+//! persisted if the scheduler uses stable memory storage.
+//!
+//! ```ignore
+//! let context = PayoutContext::new(terminal.clone());
+//! let payout = Payout::new(recipient, 1_000_000).recurring(60 * 60 * 24);
+//! scheduler.append_task(PayoutTask::new(payout).scheduled_at(start_secs));
+//!
+//! // In the timer handler:
+//! scheduler.run(context.clone())?;
+//! ```
+
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+
+use candid::CandidType;
+use ic_exports::ic_kit::ic;
+use ic_exports::icrc_types::icrc1::account::Account;
+use ic_exports::icrc_types::icrc1::transfer::Memo;
+use ic_task_scheduler::scheduler::TaskScheduler;
+use ic_task_scheduler::task::{ScheduledTask, Task, TaskOptions};
+use ic_task_scheduler::SchedulerError;
+use serde::{Deserialize, Serialize};
+
+use crate::error::PaymentError;
+use crate::token_terminal::N_RETRIES;
+use crate::{Balances, Operation, RecoveryList, TokenTerminal, Transfer, TxId};
+
+/// Default number of times a failed payout is retried by the scheduler.
+pub const DEFAULT_PAYOUT_RETRIES: u32 = 5;
+
+/// Default delay in seconds between the retries of a failed payout.
+pub const DEFAULT_PAYOUT_RETRY_DELAY_SECS: u32 = 60;
+
+/// Parameters of a payout.
+#[derive(Debug, CandidType, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Payout {
+    /// Recipient of the payout.
+    pub to: Account,
+
+    /// Amount the recipient receives. The transfer fee is paid by the canister on top of this
+    /// amount.
+    pub amount: u128,
+
+    /// Memo attached to the payout transactions.
+    pub memo: Option<Vec<u8>>,
+
+    /// If set, the payout is repeated with this interval in seconds after every successful
+    /// execution.
+    pub interval_secs: Option<u64>,
+
+    /// Number of times a failed payout is retried.
+    pub max_retries: u32,
+
+    /// Delay in seconds between the retries of a failed payout.
+    pub retry_delay_secs: u32,
+}
+
+impl Payout {
+    /// Creates a new one-time payout of `amount` tokens to `to` account.
+    pub fn new(to: Account, amount: u128) -> Self {
+        Self {
+            to,
+            amount,
+            memo: None,
+            interval_secs: None,
+            max_retries: DEFAULT_PAYOUT_RETRIES,
+            retry_delay_secs: DEFAULT_PAYOUT_RETRY_DELAY_SECS,
+        }
+    }
+
+    /// Makes the payout repeat every `interval_secs` seconds.
+    pub fn recurring(mut self, interval_secs: u64) -> Self {
+        self.interval_secs = Some(interval_secs);
+        self
+    }
+
+    /// Sets the memo of the payout transactions.
+    pub fn with_memo(mut self, memo: Vec<u8>) -> Self {
+        self.memo = Some(memo);
+        self
+    }
+
+    /// Sets the retry policy of a failed payout.
+    pub fn with_retries(mut self, max_retries: u32, retry_delay_secs: u32) -> Self {
+        self.max_retries = max_retries;
+        self.retry_delay_secs = retry_delay_secs;
+        self
+    }
+}
+
+type PayoutFn = dyn Fn(Payout) -> Pin<Box<dyn Future<Output = Result<TxId, PaymentError>>>>;
+
+/// Context of the [`PayoutTask`] execution, giving the task access to the token terminal.
+#[derive(Clone)]
+pub struct PayoutContext {
+    execute: Rc<PayoutFn>,
+}
+
+impl PayoutContext {
+    /// Creates a context executing the payouts through the given `terminal`.
+    ///
+    /// The payouts are executed with [`TokenTerminal::transfer_shared`], so the `terminal` is not
+    /// borrowed while the ledger calls are awaited.
+    pub fn new<B, R>(terminal: Rc<RefCell<TokenTerminal<B, R>>>) -> Self
+    where
+        B: Balances + 'static,
+        R: RecoveryList + 'static,
+    {
+        Self {
+            execute: Rc::new(move |payout| {
+                let terminal = terminal.clone();
+                Box::pin(async move {
+                    let transfer = {
+                        let terminal = terminal.borrow();
+                        let transfer = Transfer::new(
+                            terminal.token_config(),
+                            ic::id(),
+                            payout.to,
+                            None,
+                            payout.amount.into(),
+                        )
+                        .with_operation(Operation::None);
+                        let amount = transfer.amount.clone() + transfer.effective_fee();
+                        let transfer = Transfer { amount, ..transfer };
+                        match payout.memo {
+                            Some(memo) => transfer.with_memo(Memo::from(memo)),
+                            None => transfer,
+                        }
+                    };
+
+                    TokenTerminal::<B, R>::transfer_shared(&terminal, transfer, N_RETRIES).await
+                })
+            }),
+        }
+    }
+}
+
+/// Scheduler task executing a [`Payout`].
+#[derive(Debug, CandidType, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PayoutTask {
+    pub payout: Payout,
+}
+
+impl PayoutTask {
+    /// Creates a new task for the `payout`.
+    pub fn new(payout: Payout) -> Self {
+        Self { payout }
+    }
+
+    /// Returns the scheduled task to be executed after `timestamp_secs`, with the retry policy of
+    /// the payout.
+    pub fn scheduled_at(self, timestamp_secs: u64) -> ScheduledTask<Self> {
+        let options = TaskOptions::new()
+            .with_execute_after_timestamp_in_secs(timestamp_secs)
+            .with_max_retries_policy(self.payout.max_retries)
+            .with_fixed_backoff_policy(self.payout.retry_delay_secs);
+
+        ScheduledTask::with_options(self, options)
+    }
+}
+
+impl Task for PayoutTask {
+    type Ctx = PayoutContext;
+
+    fn execute(
+        &self,
+        context: Self::Ctx,
+        task_scheduler: Box<dyn 'static + TaskScheduler<Self>>,
+    ) -> Pin<Box<dyn Future<Output = Result<(), SchedulerError>>>> {
+        let task = self.clone();
+        Box::pin(async move {
+            let result = (context.execute)(task.payout.clone()).await;
+            payout_result(result)?;
+
+            if let Some(interval_secs) = task.payout.interval_secs {
+                let next_time = ic::time() / 10u64.pow(9) + interval_secs;
+                task_scheduler.append_task(task.scheduled_at(next_time));
+            }
+
+            Ok(())
+        })
+    }
+}
+
+/// Converts the result of the payout transfer into the task result.
+///
+/// Transfers with unknown result are stored in the recovery list of the terminal, so they must not
+/// be retried by the scheduler to prevent paying out twice.
+fn payout_result(result: Result<TxId, PaymentError>) -> Result<(), SchedulerError> {
+    match result {
+        Ok(_) | Err(PaymentError::Recoverable(_)) => Ok(()),
+        Err(err @ (PaymentError::InvalidParameters(_) | PaymentError::Fatal(_))) => {
+            Err(SchedulerError::Unrecoverable(err.to_string()))
+        }
+        Err(err) => Err(SchedulerError::TaskExecutionFailed(err.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{ParametersError, RecoveryDetails, TransferFailReason};
+
+    #[test]
+    fn payout_is_not_retried_if_result_is_unknown() {
+        assert_eq!(payout_result(Ok(1u64.into())), Ok(()));
+        assert_eq!(
+            payout_result(Err(PaymentError::Recoverable(RecoveryDetails::IcError))),
+            Ok(())
+        );
+        assert!(matches!(
+            payout_result(Err(PaymentError::TransferFailed(
+                TransferFailReason::TooOld
+            ))),
+            Err(SchedulerError::TaskExecutionFailed(_))
+        ));
+        assert!(matches!(
+            payout_result(Err(PaymentError::InvalidParameters(
+                ParametersError::TargetAccountInvalid
+            ))),
+            Err(SchedulerError::Unrecoverable(_))
+        ));
+    }
+}
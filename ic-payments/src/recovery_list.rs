@@ -65,10 +65,27 @@ impl Storable for TransferValue {
     const BOUND: Bound = Bound::Unbounded;
 }
 
+/// Recovery list stored in stable memory.
+///
+/// As the list is kept in stable memory, pending transfers survive canister upgrades without
+/// any `pre_upgrade`/`post_upgrade` handling.
+///
+/// By default the list uses memory `MEM_ID` of a memory manager owned by this crate. If the
+/// canister uses its own memory manager, two managers over the same stable memory would
+/// overwrite each other's data, so in this case a memory from the canister's manager must be
+/// provided with [`StableRecoveryList::init_with_memory`] in both `init` and `post_upgrade`
+/// methods, before the list is used.
 #[derive(Debug)]
 pub struct StableRecoveryList<const MEM_ID: u8>;
 
 impl<const MEM_ID: u8> StableRecoveryList<MEM_ID> {
+    /// Initializes the recovery list storage in the given `memory`, loading the transfers
+    /// already stored there.
+    pub fn init_with_memory(memory: VirtualMemory<DefaultMemoryImpl>) -> Self {
+        RECOVERY_LIST_STORAGE.with(|v| *v.borrow_mut() = Some(StableBTreeMap::new(memory)));
+        Self
+    }
+
     fn with_storage<R>(
         &self,
         f: impl Fn(
@@ -0,0 +1,108 @@
+//! Timer-driven recovery of the transfers stored in the recovery list.
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::Duration;
+
+use ic_exports::ic_cdk_timers::{self, TimerId};
+
+use crate::{Balances, RecoveryList, TokenTerminal};
+
+/// Options of the automatic recovery timer.
+#[derive(Debug, Clone, Copy)]
+pub struct RecoveryTimerOptions {
+    /// Delay between the recovery runs when the previous run recovered all the transfers.
+    pub interval: Duration,
+
+    /// Maximum number of transfers recovered in a single run.
+    pub batch_size: usize,
+
+    /// Maximum delay between the runs. The delay is doubled after every run which left some
+    /// transfers in the recovery list, up to this value.
+    pub max_backoff: Duration,
+}
+
+impl Default for RecoveryTimerOptions {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(60 * 10),
+            batch_size: 10,
+            max_backoff: Duration::from_secs(60 * 60 * 6),
+        }
+    }
+}
+
+/// Handle of the recovery timer started with [`start_recovery_timer`].
+#[derive(Debug, Clone)]
+pub struct RecoveryTimerHandle {
+    timer: Rc<Cell<Option<TimerId>>>,
+    stopped: Rc<Cell<bool>>,
+}
+
+impl RecoveryTimerHandle {
+    /// Stops the recovery timer. A recovery run which is already in progress is finished.
+    pub fn stop(&self) {
+        self.stopped.set(true);
+        if let Some(timer) = self.timer.take() {
+            ic_cdk_timers::clear_timer(timer);
+        }
+    }
+}
+
+/// Starts a timer which periodically calls [`TokenTerminal::recover_batch_shared`] on the
+/// `terminal`.
+///
+/// The delay between the runs grows exponentially while the transfers can't be recovered and is
+/// reset to the `interval` once the recovery list is empty. Timers don't survive upgrades, so this
+/// function should be called in both `#[init]` and `#[post_upgrade]` methods.
+///
+/// The `terminal` is not borrowed while the ledger calls are awaited, so it can be used by other
+/// messages during a recovery run.
+pub fn start_recovery_timer<B, R>(
+    terminal: Rc<RefCell<TokenTerminal<B, R>>>,
+    options: RecoveryTimerOptions,
+) -> RecoveryTimerHandle
+where
+    B: Balances + 'static,
+    R: RecoveryList + 'static,
+{
+    let handle = RecoveryTimerHandle {
+        timer: Rc::default(),
+        stopped: Rc::default(),
+    };
+
+    schedule(terminal, options, options.interval, handle.clone());
+    handle
+}
+
+fn schedule<B, R>(
+    terminal: Rc<RefCell<TokenTerminal<B, R>>>,
+    options: RecoveryTimerOptions,
+    delay: Duration,
+    handle: RecoveryTimerHandle,
+) where
+    B: Balances + 'static,
+    R: RecoveryList + 'static,
+{
+    if handle.stopped.get() {
+        return;
+    }
+
+    let timer_handle = handle.clone();
+    let timer = ic_cdk_timers::set_timer(delay, move || {
+        ic_exports::ic_cdk::spawn(async move {
+            TokenTerminal::<B, R>::recover_batch_shared(&terminal, options.batch_size).await;
+            let pending = terminal.borrow().list_for_recovery().len();
+
+            let next_delay = if pending == 0 {
+                options.interval
+            } else {
+                (delay * 2).min(options.max_backoff).max(options.interval)
+            };
+
+            schedule(terminal, options, next_delay, timer_handle);
+        })
+    });
+
+    handle.timer.set(Some(timer));
+}
@@ -1,16 +1,27 @@
+use std::cell::RefCell;
 use std::sync::atomic::AtomicU64;
+use std::time::Duration;
 
-use async_recursion::async_recursion;
 use candid::{Nat, Principal};
 use ic_exports::ic_kit::ic;
 use ic_exports::icrc_types::icrc1::account::{Account, Subaccount};
-use ic_exports::icrc_types::icrc1::transfer::TransferError;
+use ic_exports::icrc_types::icrc1::transfer::{Memo, TransferError};
 
-use crate::error::{InternalPaymentError, PaymentError, RecoveryDetails, TransferFailReason};
-use crate::icrc1::{self, get_icrc1_balance, get_icrc1_minting_account, TokenTransferInfo};
+use crate::account_lock::AccountLock;
+use crate::error::{
+    ClaimError, InternalPaymentError, PaymentError, RecoveryDetails, TransferFailReason,
+};
+use crate::icrc1::{
+    get_icrc1_balance, get_icrc1_configuration, get_icrc1_minting_account, TokenTransferInfo,
+};
+use crate::icrc3::{get_icrc3_block, Icrc3Transfer};
+use crate::index::get_account_transactions;
 use crate::recovery_list::{RecoveryList, StableRecoveryList};
 use crate::transfer::{Operation, Stage, Transfer, TransferType};
-use crate::{Balances, TokenConfiguration, TxId};
+use crate::{
+    cmc, icp, AmountLimits, Balances, DepositClaims, DepositWatcher, DetectedDeposit, LedgerKind,
+    OnTransferEvent, Timestamp, TokenConfiguration, TxId,
+};
 
 /// Id that is used by the terminal to specify that the transaction ID is unknown, but it knows for
 /// sure that the transaction exists.
@@ -18,15 +29,7 @@ pub const UNKNOWN_TX_ID: u128 = u64::MAX as u128;
 
 /// Default number of retries in case of IC error, before a transfer stored into the list for
 /// recovery.
-const N_RETRIES: usize = 3;
-
-/// Default period when deduplication of a transaction is possible. This is set by the token
-/// implementation. 24 hours used here is the most common value, used by ICP and SNS-1 ledgers.
-const DEFAULT_DEDUP_PERIOD: u64 = 10u64.pow(9) * 60 * 60 * 24;
-
-/// Different IC nodes can have times not synchronized perfectly. We use 5 minute margin to make
-/// sure we don't try to deduplicate transactions when it's not possible already.
-const TX_WINDOW: u64 = 10u64.pow(9) * 60 * 5;
+pub(crate) const N_RETRIES: usize = 3;
 
 // We use this counter to make every transfer created by the terminal unique, even if current
 // timestamp is the same. Since it's impossible to have timestamp repeat in operations before and
@@ -81,6 +84,10 @@ type ConfigChangePredicate = dyn Fn(&TokenConfiguration) + Send + Sync + 'static
 /// # };
 /// ```
 ///
+/// Deposits and withdrawals of the same caller are not executed concurrently: while an operation
+/// of the caller is awaiting the token canister, other operations of the caller with the same
+/// token fail with [`PaymentError::OperationInProgress`].
+///
 /// # Generic parameters
 /// * `B` - [`Balances`] storage.
 /// * `R` - [`RecoveryList`] storage.
@@ -93,8 +100,12 @@ pub struct TokenTerminal<B: Balances, R: RecoveryList> {
     token_config: TokenConfiguration,
     balances: B,
     recovery_list: R,
-    deduplication_period: u64,
     update_token_config: Option<Box<ConfigChangePredicate>>,
+    ledger: LedgerKind,
+    config_ttl: Option<u64>,
+    config_updated_at: Timestamp,
+    hooks: Vec<Box<dyn OnTransferEvent>>,
+    amount_limits: AmountLimits,
 }
 
 impl<T: Balances, const MEM_ID: u8> TokenTerminal<T, StableRecoveryList<MEM_ID>> {
@@ -106,8 +117,12 @@ impl<T: Balances, const MEM_ID: u8> TokenTerminal<T, StableRecoveryList<MEM_ID>>
             token_config: config,
             balances,
             recovery_list,
-            deduplication_period: DEFAULT_DEDUP_PERIOD,
             update_token_config: None,
+            ledger: LedgerKind::Icrc1,
+            config_ttl: None,
+            config_updated_at: ic::time(),
+            hooks: vec![],
+            amount_limits: AmountLimits::default(),
         }
     }
 }
@@ -123,8 +138,12 @@ impl<T: Balances, R: RecoveryList> TokenTerminal<T, R> {
             token_config: config,
             balances,
             recovery_list,
-            deduplication_period: DEFAULT_DEDUP_PERIOD,
             update_token_config: None,
+            ledger: LedgerKind::Icrc1,
+            config_ttl: None,
+            config_updated_at: ic::time(),
+            hooks: vec![],
+            amount_limits: AmountLimits::default(),
         }
     }
 }
@@ -148,13 +167,106 @@ impl<T: Balances, R: RecoveryList> TokenTerminal<T, R> {
         }
     }
 
+    /// Sets the kind of the token ledger. By default the terminal works with ICRC-1 ledgers.
+    ///
+    /// With [`LedgerKind::Icp`] the terminal uses the legacy ICP ledger interface: accounts are
+    /// converted into account identifiers and memos into `u64` values (see [`icp::icp_memo`]).
+    /// Allowance transfers are not supported by this interface.
+    pub fn with_ledger(self, ledger: LedgerKind) -> Self {
+        Self { ledger, ..self }
+    }
+
+    /// Kind of the token ledger used by the terminal.
+    pub fn ledger(&self) -> LedgerKind {
+        self.ledger
+    }
+
+    /// Adds the callbacks to be invoked on the lifecycle events of the transfers performed by the
+    /// terminal. See [`OnTransferEvent`] for the list of events.
+    ///
+    /// Several hooks can be added (e.g. the [`TransferHistory`](crate::history::TransferHistory)
+    /// and a custom one), they are invoked in the order they were added.
+    pub fn with_transfer_hooks<H>(mut self, hooks: H) -> Self
+    where
+        H: OnTransferEvent + 'static,
+    {
+        self.hooks.push(Box::new(hooks));
+        self
+    }
+
+    /// Sets the minimum amounts of deposits and withdrawals, and the policy for the smaller
+    /// amounts. See [`AmountLimits`] for details.
+    pub fn with_amount_limits(self, amount_limits: AmountLimits) -> Self {
+        Self {
+            amount_limits,
+            ..self
+        }
+    }
+
+    /// Makes the terminal re-request the token fee and minting account from the token canister
+    /// when the configuration is older than `ttl`.
+    ///
+    /// The check is done before every deposit, withdrawal and recovery, so the fee change is
+    /// detected before the transfers with the outdated fee are rejected by the token. If the
+    /// configuration is changed, the [`TokenTerminal::on_config_update`] callback is called.
+    ///
+    /// If the configuration cannot be requested, the terminal continues to use the current one and
+    /// tries to refresh it on the next operation.
+    pub fn with_config_refresh(self, ttl: Duration) -> Self {
+        Self {
+            config_ttl: Some(ttl.as_nanos() as u64),
+            ..self
+        }
+    }
+
+    /// Requests the token fee and minting account from the token canister and updates the
+    /// terminal configuration. Returns `true` if the configuration was changed.
+    pub async fn refresh_config(&mut self) -> Result<bool, PaymentError> {
+        let config = get_icrc1_configuration(self.token_config.principal).await?;
+        Ok(self.apply_config(config))
+    }
+
+    fn apply_config(&mut self, config: TokenConfiguration) -> bool {
+        self.config_updated_at = ic::time();
+
+        if config.fee == self.token_config.fee
+            && config.minting_account == self.token_config.minting_account
+        {
+            return false;
+        }
+
+        self.token_config.fee = config.fee;
+        self.token_config.minting_account = config.minting_account;
+        self.update_recovery_fees();
+
+        if let Some(f) = &self.update_token_config {
+            f(self.token_config());
+        }
+
+        true
+    }
+
+    fn config_expired(&self) -> bool {
+        self.config_ttl
+            .is_some_and(|ttl| ic::time().saturating_sub(self.config_updated_at) >= ttl)
+    }
+
+    async fn refresh_config_if_expired(&mut self) {
+        if self.config_expired() {
+            // Failure to refresh is not critical: the terminal still handles `BadFee` errors, and
+            // the refresh is retried on the next operation.
+            let _ = self.refresh_config().await;
+        }
+    }
+
     /// [`TokenTerminal::deposit`] for details.
     ///
     /// The amount the caller will receive on their balance is `interim_account_balance -
     /// transfer_fee`, where `transfer_fee` is the fee set by the token canister.
     pub async fn deposit_all(&mut self, caller: Principal) -> Result<(TxId, Nat), PaymentError> {
+        self.refresh_config_if_expired().await;
         let account = get_deposit_interim_account(caller);
-        let balance = get_icrc1_balance(self.token_config.principal, &account).await?;
+        let balance = self.balance_of(&account).await?;
         self.deposit(caller, balance).await
     }
 
@@ -182,13 +294,16 @@ impl<T: Balances, R: RecoveryList> TokenTerminal<T, R> {
         caller: Principal,
         amount: Nat,
     ) -> Result<(TxId, Nat), PaymentError> {
+        let _lock = AccountLock::acquire(self.token_config.principal, caller)?;
+        let donate_to = self.amount_limits.check_deposit(&amount)?;
+        self.refresh_config_if_expired().await;
         let to = ic::id().into();
         let memo = TX_COUNTER
             .fetch_add(1, std::sync::atomic::Ordering::Relaxed)
             .into();
         let transfer = Transfer::new(
             &self.token_config,
-            caller,
+            donate_to.unwrap_or(caller),
             to,
             get_principal_subaccount(&caller),
             amount.clone(),
@@ -199,7 +314,177 @@ impl<T: Balances, R: RecoveryList> TokenTerminal<T, R> {
 
         let tx_id = self.transfer(transfer, N_RETRIES).await?;
 
-        Ok((tx_id, amount))
+        deposit_result(tx_id, amount, donate_to)
+    }
+
+    /// Transfer the specified amount from the caller's main account to the canister using the
+    /// ICRC-2 allowance given by the caller to the canister, and credit it to the caller's balance.
+    ///
+    /// The flow is:
+    /// 1. Caller approves the canister to spend at least `amount` tokens with `icrc2_approve`.
+    /// 2. Caller calls a method in the canister to initiate the deposit.
+    /// 3. The canister pulls the tokens with `icrc2_transfer_from` and credits the transferred
+    ///    amount to the caller's balance.
+    ///
+    /// The `amount` includes the transfer fee, so the amount the caller will receive on their
+    /// balance is `amount - transfer_fee`, and the allowance must be at least `amount`.
+    ///
+    /// If the allowance is not enough, [`TransferFailReason::InsufficientAllowance`] error is
+    /// returned. Transfers with unknown result are recovered through deduplication, the same way
+    /// as single-step transfers.
+    pub async fn deposit_from_allowance(
+        &mut self,
+        caller: Principal,
+        amount: Nat,
+    ) -> Result<(TxId, Nat), PaymentError> {
+        let _lock = AccountLock::acquire(self.token_config.principal, caller)?;
+        let donate_to = self.amount_limits.check_deposit(&amount)?;
+        self.refresh_config_if_expired().await;
+        let from = caller.into();
+        let to = ic::id().into();
+        let memo = TX_COUNTER
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+            .into();
+        let transfer = Transfer::new(
+            &self.token_config,
+            donate_to.unwrap_or(caller),
+            to,
+            None,
+            amount,
+        )
+        .from_allowance(from)
+        .with_fee(self.token_config.get_fee(&from, &to))
+        .with_operation(Operation::CreditOnSuccess)
+        .with_memo(memo);
+        let amount = transfer.final_amount()?;
+
+        let tx_id = self.transfer(transfer, N_RETRIES).await?;
+
+        deposit_result(tx_id, amount, donate_to)
+    }
+
+    /// Credit the caller's balance with the amount of a transfer the caller made directly to the
+    /// main account of the canister.
+    ///
+    /// The flow is:
+    /// 1. Caller transfers `amount` tokens from any of their accounts to the canister main
+    ///    account.
+    /// 2. Caller calls a method in the canister to claim the deposit, giving the block index of
+    ///    the transaction.
+    /// 3. The canister fetches the block with `icrc3_get_blocks`, verifies that it transfers
+    ///    exactly `amount` tokens from the caller to the canister, and credits the caller's balance.
+    ///
+    /// The claimed block indices are stored in `claims`, so the same transaction cannot be
+    /// credited twice. The transfers made by a spender using an allowance, including the deposits
+    /// made with [`TokenTerminal::deposit_from_allowance`], cannot be claimed. If the block
+    /// doesn't match the claim, [`PaymentError::InvalidClaim`] is returned and no balances are
+    /// changed.
+    pub async fn claim_deposit<C: DepositClaims>(
+        &mut self,
+        caller: Principal,
+        block_index: Nat,
+        amount: Nat,
+        claims: &mut C,
+    ) -> Result<Nat, PaymentError> {
+        if claims.is_claimed(&block_index) {
+            return Err(PaymentError::InvalidClaim(ClaimError::AlreadyClaimed));
+        }
+
+        let block = get_icrc3_block(self.token_config.principal, block_index.clone())
+            .await?
+            .ok_or(PaymentError::InvalidClaim(ClaimError::BlockNotFound))?;
+        let transfer = Icrc3Transfer::from_block(block_index.clone(), &block)
+            .ok_or(PaymentError::InvalidClaim(ClaimError::NotATransfer))?;
+        transfer
+            .verify(caller, &ic::id().into(), &amount)
+            .map_err(PaymentError::InvalidClaim)?;
+
+        // Another claim of the same block could be processed while waiting for the block.
+        if claims.is_claimed(&block_index) {
+            return Err(PaymentError::InvalidClaim(ClaimError::AlreadyClaimed));
+        }
+
+        let credited = self.credit(caller, transfer.amount)?;
+        claims.mark_claimed(block_index);
+
+        Ok(credited)
+    }
+
+    /// Credit the balances with the new deposits to the canister main account, detected using
+    /// the index canister of the `watcher`.
+    ///
+    /// Every incoming transfer to the main account newer than the last transaction processed by
+    /// the `watcher` is credited to the owner of the source account. The transfers made by the
+    /// canister itself (e.g. from the deposit interim accounts or using an allowance) are skipped.
+    /// Credited transactions are stored in `claims`, so the same transaction cannot also be
+    /// claimed with [`TokenTerminal::claim_deposit`].
+    ///
+    /// On the first call the watcher only remembers the newest transaction, so the transfers
+    /// made before the polling was started are not credited automatically.
+    pub async fn poll_deposits<C: DepositClaims>(
+        &mut self,
+        watcher: &mut DepositWatcher,
+        claims: &mut C,
+    ) -> Result<Vec<DetectedDeposit>, PaymentError> {
+        let account: Account = ic::id().into();
+        let mut transactions = vec![];
+        let mut start = None;
+        loop {
+            let page =
+                get_account_transactions(watcher.index, account, start, watcher.batch_size).await?;
+            let page_len = page.transactions.len() as u64;
+            let mut reached_processed = false;
+            for tx in page.transactions {
+                if watcher
+                    .last_tx_id
+                    .as_ref()
+                    .is_some_and(|last| tx.id <= *last)
+                {
+                    reached_processed = true;
+                    break;
+                }
+                transactions.push(tx);
+            }
+
+            start = transactions.last().map(|tx| tx.id.clone());
+            if reached_processed || page_len < watcher.batch_size || watcher.last_tx_id.is_none() {
+                break;
+            }
+        }
+
+        let Some(newest) = transactions.first().map(|tx| tx.id.clone()) else {
+            return Ok(vec![]);
+        };
+
+        if watcher.last_tx_id.replace(newest).is_none() {
+            return Ok(vec![]);
+        }
+
+        let mut deposits = vec![];
+        for tx in transactions.into_iter().rev() {
+            let Some(transfer) = tx.transaction.transfer else {
+                continue;
+            };
+
+            if transfer.to != account
+                || transfer.from.owner == ic::id()
+                || transfer.spender.is_some()
+                || claims.is_claimed(&tx.id)
+            {
+                continue;
+            }
+
+            let owner = transfer.from.owner;
+            self.credit(owner, transfer.amount.clone())?;
+            claims.mark_claimed(tx.id.clone());
+            deposits.push(DetectedDeposit {
+                tx_id: tx.id,
+                owner,
+                amount: transfer.amount,
+            });
+        }
+
+        Ok(deposits)
     }
 
     /// Move the specified amount from the caller's balance to the caller's main account.
@@ -212,10 +497,44 @@ impl<T: Balances, R: RecoveryList> TokenTerminal<T, R> {
         caller: Principal,
         amount: Nat,
     ) -> Result<(TxId, Nat), PaymentError> {
-        let to = caller.into();
-        let memo = TX_COUNTER
-            .fetch_add(1, std::sync::atomic::Ordering::Relaxed)
-            .into();
+        self.withdraw_with_memo(caller, None, amount, None).await
+    }
+
+    /// Move the specified amount from the caller's balance to the given subaccount of the caller,
+    /// attaching the given `memo` to the token transactions.
+    ///
+    /// The memo can be used by the downstream indexers to correlate the token transaction with
+    /// the application operation (e.g. an order id). As the memo is a part of the transaction
+    /// deduplication key, the memo must be unique for every withdrawal of the same amount done
+    /// within the same nanosecond. If `memo` is `None`, a unique memo is generated by the
+    /// terminal.
+    ///
+    /// Otherwise works the same way as [`TokenTerminal::withdraw`].
+    pub async fn withdraw_with_memo(
+        &mut self,
+        caller: Principal,
+        to_subaccount: Option<Subaccount>,
+        amount: Nat,
+        memo: Option<Memo>,
+    ) -> Result<(TxId, Nat), PaymentError> {
+        let _lock = AccountLock::acquire(self.token_config.principal, caller)?;
+        if let Some(fee_account) = self.amount_limits.check_withdrawal(&amount)? {
+            return self.donate_dust(caller, fee_account, amount);
+        }
+
+        self.refresh_config_if_expired().await;
+        let to = Account {
+            owner: caller,
+            subaccount: to_subaccount,
+        };
+        let memo = memo.unwrap_or_else(|| {
+            TX_COUNTER
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+                .into()
+        });
+        if self.ledger == LedgerKind::Icp {
+            icp::icp_memo(Some(&memo)).map_err(PaymentError::InvalidParameters)?;
+        }
 
         let transfer = Transfer::new(&self.token_config, caller, to, None, amount)
             .double_step()
@@ -232,37 +551,99 @@ impl<T: Balances, R: RecoveryList> TokenTerminal<T, R> {
         Ok((tx_id, amount))
     }
 
+    /// Converts the specified amount from the caller's balance into cycles deposited to the
+    /// `canister_id`, using the `cmc` cycles minting canister. The terminal token must be the ICP
+    /// ledger known to the `cmc`.
+    ///
+    /// The amount minus the transfer fee is sent to the top-up account of the canister in the CMC,
+    /// after which the CMC is notified about the transfer. Returns the number of deposited cycles.
+    ///
+    /// If the transfer fails, the amount is credited back to the caller. If the result of the
+    /// transfer is unknown, it's added to the recovery list and the CMC must be notified after the
+    /// transfer is recovered (e.g. from the [`OnTransferEvent::on_succeeded`] hook). If the
+    /// notification fails, [`PaymentError::TopUpFailed`] with the transfer block index is
+    /// returned.
+    pub async fn top_up_cycles(
+        &mut self,
+        caller: Principal,
+        canister_id: Principal,
+        amount: Nat,
+        cmc: Principal,
+    ) -> Result<Nat, PaymentError> {
+        let _lock = AccountLock::acquire(self.token_config.principal, caller)?;
+        self.amount_limits.check_top_up(&amount)?;
+
+        self.refresh_config_if_expired().await;
+        let to = cmc::top_up_account(cmc, &canister_id);
+        let transfer = Transfer::new(&self.token_config, caller, to, None, amount)
+            .with_operation(Operation::CreditOnError)
+            .with_memo(cmc::MEMO_TOP_UP_CANISTER.into());
+
+        transfer.validate()?;
+        self.balances.debit(caller, transfer.amount())?;
+
+        let block_index = self.transfer(transfer, N_RETRIES).await?;
+
+        cmc::notify_top_up(cmc, block_index.clone(), canister_id)
+            .await
+            .map_err(|reason| PaymentError::TopUpFailed {
+                block_index,
+                reason,
+            })
+    }
+
     /// Executes the given [`transfer`](Transfer). If IC returns an error that does not guarantee
     /// either success or failure of the operation, the transaction will be retried `n_retries`
     /// times before saving it to the [recover_list`](RecoveryList).
     ///
     /// If the transaction succeeds or fails (e.g. it's not saved to the recovery list), the
     /// [transfer operation](Transfer.operation) is executed before the method returns.
-    #[async_recursion(?Send)]
     pub async fn transfer(
         &mut self,
         transfer: Transfer,
         n_retries: usize,
     ) -> Result<TxId, PaymentError> {
-        transfer.validate()?;
-        self.execute_transfer(transfer, n_retries).await
+        let step = self.start(transfer, n_retries);
+        run_steps(self, step).await
     }
 
-    #[async_recursion(?Send)]
-    async fn complete(
-        &mut self,
+    /// Same as [`TokenTerminal::transfer`], but the shared `terminal` is only borrowed between the
+    /// ledger calls, so it can be used by other messages while the transfer is awaiting the token
+    /// canister.
+    pub async fn transfer_shared(
+        terminal: &RefCell<Self>,
         transfer: Transfer,
-        tx_id: TxId,
         n_retries: usize,
     ) -> Result<TxId, PaymentError> {
+        let step = terminal.borrow_mut().start(transfer, n_retries);
+        run_steps(terminal, step).await
+    }
+
+    fn start(&mut self, transfer: Transfer, n_retries: usize) -> Step {
+        if let Err(e) = transfer.validate() {
+            return Step::Done(Err(e.into()));
+        }
+
+        self.notify(|hooks| hooks.on_started(&transfer));
+        Step::Execute {
+            transfer,
+            n_retries,
+            recovery: false,
+        }
+    }
+
+    fn complete(&mut self, transfer: Transfer, tx_id: TxId, n_retries: usize) -> Step {
+        self.notify(|hooks| hooks.on_succeeded(&transfer, &tx_id));
         match transfer.next_step() {
-            Some(t) => self.transfer(t, n_retries).await,
+            Some(t) => self.start(t, n_retries),
             None => {
                 if transfer.operation() == Operation::CreditOnSuccess {
-                    self.credit(transfer.caller(), transfer.amount_minus_fee())?;
+                    if let Err(e) = self.credit(transfer.caller(), transfer.amount_minus_fee()) {
+                        return Step::Done(Err(e));
+                    }
                 }
 
-                Ok(tx_id)
+                Step::Done(Ok(tx_id))
             }
         }
     }
@@ -283,23 +664,29 @@ impl<T: Balances, R: RecoveryList> TokenTerminal<T, R> {
                 }
             }
             _ => {
+                let error: PaymentError = error.into();
+                self.notify(|hooks| hooks.on_failed(&transfer, &error));
+
                 if transfer.operation() == Operation::CreditOnError {
                     self.credit(transfer.caller(), transfer.amount())?;
                 }
 
-                Err(error.into())
+                Err(error)
             }
         }
     }
 
-    #[async_recursion(?Send)]
-    async fn retry(&mut self, transfer: Transfer, n_retries: usize) -> Result<TxId, PaymentError> {
+    fn retry(&mut self, transfer: Transfer, n_retries: usize) -> Step {
         if n_retries == 0 {
             self.add_for_recovery(transfer);
-            return Err(PaymentError::Recoverable(RecoveryDetails::IcError));
+            return Step::Done(Err(PaymentError::Recoverable(RecoveryDetails::IcError)));
         }
 
-        self.execute_recovery_transfer(transfer, n_retries).await
+        Step::Execute {
+            transfer,
+            n_retries,
+            recovery: true,
+        }
     }
 
     /// Returns reference to balances structure used by the terminal.
@@ -347,10 +734,38 @@ impl<T: Balances, R: RecoveryList> TokenTerminal<T, R> {
         Ok(self.balances.credit(recipient, amount)?)
     }
 
+    /// Moves the dust `amount` from the `caller` balance to the `fee_account` balance. If the
+    /// fee account cannot be credited, the amount is returned to the caller.
+    fn donate_dust<V>(
+        &mut self,
+        caller: Principal,
+        fee_account: Principal,
+        amount: Nat,
+    ) -> Result<V, PaymentError> {
+        self.balances.debit(caller, amount.clone())?;
+        if let Err(e) = self.balances.credit(fee_account, amount.clone()) {
+            self.balances.credit(caller, amount)?;
+            return Err(e.into());
+        }
+
+        Err(PaymentError::DustDonated {
+            tx_id: None,
+            amount,
+            fee_account,
+        })
+    }
+
     fn add_for_recovery(&mut self, transfer: Transfer) {
+        self.notify(|hooks| hooks.on_moved_to_recovery(&transfer));
         self.recovery_list.push(transfer);
     }
 
+    fn notify(&mut self, mut event: impl FnMut(&mut dyn OnTransferEvent)) {
+        for hooks in &mut self.hooks {
+            event(hooks.as_mut());
+        }
+    }
+
     /// Recover all transfers stored in the recovery list. Exact strategy of recovery depends for
     /// each transfer is decided by the transfer properties. Returns result of the recovery for
     /// each transfer in the recovery list. If the recovery list was empty, returns an empty list.
@@ -360,134 +775,205 @@ impl<T: Balances, R: RecoveryList> TokenTerminal<T, R> {
     /// the list. If the recovery was not successful, e.g. if the terminal has still no proof
     /// whether the transfer is successful or not, the transfer is returned to the recovery list.
     pub async fn recover_all(&mut self) -> Vec<Result<(TxId, Transfer), PaymentError>> {
+        self.recover_batch(usize::MAX).await
+    }
+
+    /// Recover at most `max_count` transfers stored in the recovery list. The rest of the
+    /// transfers are kept in the list. See [`TokenTerminal::recover_all()`] for details.
+    pub async fn recover_batch(
+        &mut self,
+        max_count: usize,
+    ) -> Vec<Result<(TxId, Transfer), PaymentError>> {
+        self.refresh_config_if_expired().await;
         let mut results = vec![];
-        for tx in self.recovery_list.take_all() {
-            if tx.token == self.token_config.principal {
-                results.push(self.recover_tx(tx).await);
-            } else {
-                // Return foreigh transfers to the recovery list
-                self.recovery_list.push(tx);
-            }
+        for tx in self.take_for_recovery(max_count) {
+            let step = self.start_recovery(&tx);
+            results.push(run_steps(&mut *self, step).await.map(|tx_id| (tx_id, tx)));
         }
 
         results
     }
 
-    async fn recover_tx(&mut self, transfer: Transfer) -> Result<(TxId, Transfer), PaymentError> {
-        let tx_id = if self.can_deduplicate(&transfer) {
-            self.execute_recovery_transfer(transfer.clone(), N_RETRIES)
-                .await?
-        } else {
-            self.recover_old_tx(transfer.clone()).await?
-        };
+    /// Same as [`TokenTerminal::recover_batch`], but the shared `terminal` is only borrowed
+    /// between the ledger calls, so it can be used by other messages while the transfers are
+    /// recovered.
+    ///
+    /// The transfers being recovered are taken out of the recovery list, so they are not
+    /// recovered twice by concurrent calls.
+    pub async fn recover_batch_shared(
+        terminal: &RefCell<Self>,
+        max_count: usize,
+    ) -> Vec<Result<(TxId, Transfer), PaymentError>> {
+        if terminal.borrow().config_expired() {
+            let token = terminal.borrow().token_config.principal;
+            if let Ok(config) = get_icrc1_configuration(token).await {
+                terminal.borrow_mut().apply_config(config);
+            }
+        }
 
-        Ok((tx_id, transfer))
+        let transfers = terminal.borrow_mut().take_for_recovery(max_count);
+        let mut results = vec![];
+        for tx in transfers {
+            let step = terminal.borrow_mut().start_recovery(&tx);
+            results.push(run_steps(terminal, step).await.map(|tx_id| (tx_id, tx)));
+        }
+
+        results
     }
 
-    async fn execute_transfer(
-        &mut self,
-        transfer: Transfer,
-        n_retries: usize,
-    ) -> Result<TxId, PaymentError> {
-        match transfer.execute().await {
-            Ok(TokenTransferInfo { token_tx_id, .. }) => {
-                Ok(self.complete(transfer, token_tx_id, n_retries).await?)
-            }
-            Err(InternalPaymentError::MaybeFailed) => {
-                self.retry(transfer, n_retries.saturating_sub(1)).await
-            }
-            Err(InternalPaymentError::WrongFee(expected)) => {
-                self.update_config_and_retry(expected, transfer, n_retries.saturating_sub(1))
-                    .await
+    /// Takes at most `max_count` transfers of the terminal token from the recovery list.
+    fn take_for_recovery(&mut self, max_count: usize) -> Vec<Transfer> {
+        let mut taken = vec![];
+        for tx in self.recovery_list.take_all() {
+            if tx.token == self.token_config.principal && taken.len() < max_count {
+                taken.push(tx);
+            } else {
+                // Return foreign and exceeding transfers to the recovery list
+                self.recovery_list.push(tx);
             }
-            Err(e) => Ok(self.reject(transfer, e)?),
         }
+
+        taken
     }
 
-    async fn execute_recovery_transfer(
+    fn start_recovery(&mut self, transfer: &Transfer) -> Step {
+        if self.can_deduplicate(transfer) {
+            return Step::Execute {
+                transfer: transfer.clone(),
+                n_retries: N_RETRIES,
+                recovery: true,
+            };
+        }
+
+        match transfer.r#type() {
+            TransferType::DoubleStep(..) => Step::CheckInterimBalance {
+                transfer: transfer.clone(),
+            },
+            _ => Step::Done(Err(PaymentError::TransferFailed(
+                TransferFailReason::TooOld,
+            ))),
+        }
+    }
+
+    /// Handles the result of the transfer execution. In the `recovery` mode the duplicate
+    /// transfers are considered successful and the temporary token errors are retried.
+    fn on_executed(
         &mut self,
         transfer: Transfer,
         n_retries: usize,
-    ) -> Result<TxId, PaymentError> {
-        match transfer.execute().await {
+        recovery: bool,
+        result: Result<TokenTransferInfo, InternalPaymentError>,
+    ) -> Step {
+        match result {
             Ok(TokenTransferInfo { token_tx_id, .. }) => {
-                Ok(self.complete(transfer, token_tx_id, n_retries).await?)
+                self.complete(transfer, token_tx_id, n_retries)
             }
             Err(InternalPaymentError::WrongFee(expected)) => {
                 self.update_config_and_retry(expected, transfer, n_retries.saturating_sub(1))
-                    .await
+            }
+            Err(InternalPaymentError::MaybeFailed) => {
+                self.retry(transfer, n_retries.saturating_sub(1))
             }
             Err(InternalPaymentError::TransferFailed(TransferFailReason::Rejected(
                 TransferError::Duplicate { duplicate_of },
-            ))) => Ok(self.complete(transfer, duplicate_of, n_retries).await?),
-            Err(InternalPaymentError::MaybeFailed)
-            | Err(InternalPaymentError::TransferFailed(TransferFailReason::Rejected(
+            ))) if recovery => self.complete(transfer, duplicate_of, n_retries),
+            Err(InternalPaymentError::TransferFailed(TransferFailReason::Rejected(
                 TransferError::TemporarilyUnavailable,
             )))
-            | Err(InternalPaymentError::TransferFailed(TransferFailReason::TokenPanic(_))) => {
-                self.retry(transfer, n_retries.saturating_sub(1)).await
+            | Err(InternalPaymentError::TransferFailed(TransferFailReason::TokenPanic(_)))
+                if recovery =>
+            {
+                self.retry(transfer, n_retries.saturating_sub(1))
             }
-            Err(e) => Ok(self.reject(transfer, e)?),
+            Err(e) => Step::Done(self.reject(transfer, e)),
         }
     }
 
-    async fn update_config_and_retry(
+    fn update_config_and_retry(
         &mut self,
         expected_fee: Nat,
         transfer: Transfer,
         n_retries: usize,
-    ) -> Result<TxId, PaymentError> {
-        match expected_fee {
-            v if v == 0u64 => self.set_minting_account(self.get_minting_account(v).await?),
-            v if v == self.token_config.fee => {
-                self.set_minting_account(self.get_minting_account(v).await?)
-            }
-            v => self.set_fee(v),
-        };
+    ) -> Step {
+        if expected_fee == 0u64 || expected_fee == self.token_config.fee {
+            return Step::UpdateMintingAccount {
+                expected_fee,
+                transfer,
+                n_retries,
+            };
+        }
+
+        self.set_fee(expected_fee);
+        self.retry_with_new_config(transfer, n_retries)
+    }
 
+    fn on_minting_account(
+        &mut self,
+        minting_account: Result<Account, PaymentError>,
+        transfer: Transfer,
+        n_retries: usize,
+    ) -> Step {
+        match minting_account {
+            Ok(minting_account) => self.set_minting_account(minting_account),
+            Err(e) => return Step::Done(Err(e)),
+        }
+
+        self.retry_with_new_config(transfer, n_retries)
+    }
+
+    fn retry_with_new_config(&mut self, transfer: Transfer, n_retries: usize) -> Step {
         let to = transfer.to();
         let from = transfer.from();
         let transfer = transfer.with_fee(self.token_config.get_fee(&to, &from));
+        self.config_updated_at = ic::time();
 
         if let Some(f) = &self.update_token_config {
             f(self.token_config());
         }
 
-        self.retry(transfer, n_retries).await
+        self.retry(transfer, n_retries)
     }
 
-    async fn get_minting_account(&self, expected_fee: Nat) -> Result<Account, PaymentError> {
-        match get_icrc1_minting_account(self.token_config.principal).await {
-            Ok(v) => Ok(v.unwrap_or(Account {
-                owner: Principal::management_canister(),
-                subaccount: None,
-            })),
-            Err(_e) => Err(PaymentError::BadFee(expected_fee)),
-        }
+    async fn balance_of(&self, account: &Account) -> Result<Nat, PaymentError> {
+        ledger_balance(self.ledger, self.token_config.principal, account).await
     }
 
     fn can_deduplicate(&self, tx: &Transfer) -> bool {
-        ic::time().saturating_sub(tx.created_at()) < self.deduplication_period - TX_WINDOW
+        self.token_config
+            .can_deduplicate(tx.created_at(), ic::time())
     }
 
-    async fn recover_old_tx(&mut self, tx: Transfer) -> Result<TxId, PaymentError> {
-        let TransferType::DoubleStep(stage, acc) = tx.r#type() else {
-            return Err(PaymentError::TransferFailed(TransferFailReason::TooOld));
+    /// Handles the interim account balance of a double-step transfer too old to be deduplicated
+    /// by the token canister.
+    fn on_interim_balance(
+        &mut self,
+        tx: Transfer,
+        interim_balance: Result<Nat, PaymentError>,
+    ) -> Step {
+        let interim_balance = match interim_balance {
+            Ok(balance) => balance,
+            Err(e) => return Step::Done(Err(e)),
+        };
+        let TransferType::DoubleStep(stage, _) = tx.r#type() else {
+            return Step::Done(Err(PaymentError::TransferFailed(
+                TransferFailReason::TooOld,
+            )));
         };
-        let interim_balance = icrc1::get_icrc1_balance(self.token_config.principal, acc).await?;
 
         match stage {
-            Stage::First if interim_balance == 0u64 => self.reject(
+            Stage::First if interim_balance == 0u64 => Step::Done(self.reject(
                 tx,
                 InternalPaymentError::TransferFailed(TransferFailReason::Unknown),
-            ),
-            Stage::First => self.complete(tx, UNKNOWN_TX_ID.into(), N_RETRIES).await,
+            )),
+            Stage::First => self.complete(tx, UNKNOWN_TX_ID.into(), N_RETRIES),
             Stage::Second if interim_balance == 0u64 => {
-                self.complete(tx, UNKNOWN_TX_ID.into(), N_RETRIES).await
+                self.complete(tx, UNKNOWN_TX_ID.into(), N_RETRIES)
             }
-            Stage::Second => Ok(self
-                .execute_recovery_transfer(tx.renew(), N_RETRIES)
-                .await?),
+            Stage::Second => Step::Execute {
+                transfer: tx.renew(),
+                n_retries: N_RETRIES,
+                recovery: true,
+            },
         }
     }
 
@@ -498,6 +984,123 @@ impl<T: Balances, R: RecoveryList> TokenTerminal<T, R> {
     }
 }
 
+fn deposit_result(
+    tx_id: TxId,
+    amount: Nat,
+    donated_to: Option<Principal>,
+) -> Result<(TxId, Nat), PaymentError> {
+    match donated_to {
+        Some(fee_account) => Err(PaymentError::DustDonated {
+            tx_id: Some(tx_id),
+            amount,
+            fee_account,
+        }),
+        None => Ok((tx_id, amount)),
+    }
+}
+
+/// Next step of a transfer executed by the terminal.
+///
+/// The steps calling the token canister don't need the terminal, so a shared terminal is only
+/// borrowed to handle the results of the calls and no borrow is held across an `.await`.
+enum Step {
+    /// Execute the transfer in the token canister.
+    Execute {
+        transfer: Transfer,
+        n_retries: usize,
+        recovery: bool,
+    },
+    /// Request the minting account after the token canister rejected the transfer with the fee
+    /// which is already configured.
+    UpdateMintingAccount {
+        expected_fee: Nat,
+        transfer: Transfer,
+        n_retries: usize,
+    },
+    /// Request the interim account balance of a double-step transfer.
+    CheckInterimBalance { transfer: Transfer },
+    /// The transfer is completed, failed or moved to the recovery list.
+    Done(Result<TxId, PaymentError>),
+}
+
+/// Mutable access to the terminal executing the transfer steps.
+trait TerminalAccess<T: Balances, R: RecoveryList> {
+    fn with<V>(&mut self, f: impl FnOnce(&mut TokenTerminal<T, R>) -> V) -> V;
+}
+
+impl<T: Balances, R: RecoveryList> TerminalAccess<T, R> for &mut TokenTerminal<T, R> {
+    fn with<V>(&mut self, f: impl FnOnce(&mut TokenTerminal<T, R>) -> V) -> V {
+        f(self)
+    }
+}
+
+impl<T: Balances, R: RecoveryList> TerminalAccess<T, R> for &RefCell<TokenTerminal<T, R>> {
+    fn with<V>(&mut self, f: impl FnOnce(&mut TokenTerminal<T, R>) -> V) -> V {
+        f(&mut self.borrow_mut())
+    }
+}
+
+/// Executes the steps of a transfer until it's done.
+async fn run_steps<T, R>(
+    mut terminal: impl TerminalAccess<T, R>,
+    mut step: Step,
+) -> Result<TxId, PaymentError>
+where
+    T: Balances,
+    R: RecoveryList,
+{
+    loop {
+        let (ledger, token) = terminal.with(|t| (t.ledger, t.token_config.principal));
+        step = match step {
+            Step::Done(result) => return result,
+            Step::Execute {
+                transfer,
+                n_retries,
+                recovery,
+            } => {
+                let result = transfer.execute_on(ledger).await;
+                terminal.with(|t| t.on_executed(transfer, n_retries, recovery, result))
+            }
+            Step::UpdateMintingAccount {
+                expected_fee,
+                transfer,
+                n_retries,
+            } => {
+                let minting_account = match get_icrc1_minting_account(token).await {
+                    Ok(account) => Ok(account.unwrap_or(Account {
+                        owner: Principal::management_canister(),
+                        subaccount: None,
+                    })),
+                    Err(_e) => Err(PaymentError::BadFee(expected_fee)),
+                };
+                terminal.with(|t| t.on_minting_account(minting_account, transfer, n_retries))
+            }
+            Step::CheckInterimBalance { transfer } => {
+                let balance = match transfer.r#type() {
+                    TransferType::DoubleStep(_, interim) => {
+                        ledger_balance(ledger, token, interim).await
+                    }
+                    _ => Err(PaymentError::TransferFailed(TransferFailReason::TooOld)),
+                };
+                terminal.with(|t| t.on_interim_balance(transfer, balance))
+            }
+        };
+    }
+}
+
+async fn ledger_balance(
+    ledger: LedgerKind,
+    token: Principal,
+    account: &Account,
+) -> Result<Nat, PaymentError> {
+    let balance = match ledger {
+        LedgerKind::Icrc1 => get_icrc1_balance(token, account).await?,
+        LedgerKind::Icp => icp::get_icp_balance(token, account).await?,
+    };
+
+    Ok(balance)
+}
+
 /// Returns the interim account for deposit transfers. This account belongs to the `this` canister
 /// and has subaccount derived from the `principal` (for details see [`get_principal_subaccount`]).
 pub fn get_deposit_interim_account(principal: Principal) -> Account {
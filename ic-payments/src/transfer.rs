@@ -5,7 +5,7 @@ use ic_exports::icrc_types::icrc1::transfer::Memo;
 
 use crate::error::{InternalPaymentError, ParametersError};
 use crate::icrc1::{self, TokenTransferInfo};
-use crate::{Timestamp, TokenConfiguration};
+use crate::{icp, LedgerKind, Timestamp, TokenConfiguration};
 
 /// Transfer to be executed.
 #[derive(Debug, CandidType, Deserialize, Clone)]
@@ -41,7 +41,11 @@ pub struct Transfer {
     pub created_at: Timestamp,
 
     /// Arbitrary byte-string that can be added to the transaction. Use this field in case several
-    /// transfers with the same timestamp must be done.
+    /// transfers with the same timestamp must be done, or to attach an application-defined
+    /// identifier (e.g. an order id) to the transaction.
+    ///
+    /// The memo is a part of the transaction deduplication key, so two transfers with the same
+    /// parameters, timestamp and memo are considered to be the same transfer.
     pub memo: Option<Memo>,
 }
 
@@ -66,6 +70,10 @@ pub enum TransferType {
 
     /// Transfer through an interim account, given as the second parameter.
     DoubleStep(Stage, Account),
+
+    /// ICRC-2 transfer from the given account, using the allowance given to the canister.
+    /// The `from` subaccount of the transfer is used as the spender subaccount.
+    FromAllowance(Account),
 }
 
 /// Current step of a double-step transfer.
@@ -86,8 +94,11 @@ impl Transfer {
     /// # use ic_payments::*;
     /// # use ic_exports::icrc_types::icrc1::account::{Account, Subaccount};
     /// # use candid::Principal;
-    /// # let token_config = ic_payments::TokenConfiguration { principal:
-    /// # Principal::management_canister(), fee: 0u64.into(), minting_account: Account { owner: Principal::management_canister().into(), subaccount: None }};
+    /// # let token_config = ic_payments::TokenConfiguration::new(
+    /// #     Principal::management_canister(),
+    /// #     0u64.into(),
+    /// #     Principal::management_canister().into(),
+    /// # );
     /// # let caller = Principal::management_canister();
     /// # let to = caller.into();
     /// let transfer = Transfer::new(&token_config, caller, to, None, 10_000u64.into())
@@ -130,7 +141,9 @@ impl Transfer {
     /// Makes the transfer double-step.
     pub fn double_step(self) -> Self {
         let interim_acc = match self.r#type {
-            TransferType::SingleStep => self.generate_interim_acc(),
+            TransferType::SingleStep | TransferType::FromAllowance(_) => {
+                self.generate_interim_acc()
+            }
             TransferType::DoubleStep(_, interim_acc) => interim_acc,
         };
 
@@ -140,6 +153,15 @@ impl Transfer {
         }
     }
 
+    /// Makes the transfer pull the tokens from the `from` account using the ICRC-2 allowance given
+    /// to the canister.
+    pub fn from_allowance(self, from: Account) -> Self {
+        Self {
+            r#type: TransferType::FromAllowance(from),
+            ..self
+        }
+    }
+
     /// Sets memo for transactions of this transfer.
     pub fn with_memo(self, memo: Memo) -> Self {
         Self {
@@ -148,11 +170,33 @@ impl Transfer {
         }
     }
 
+    /// Sets the creation timestamp of the transfer.
+    ///
+    /// The timestamp is used for the transaction deduplication, so it must not be older than the
+    /// deduplication period of the token.
+    pub fn with_created_at(self, created_at: Timestamp) -> Self {
+        Self { created_at, ..self }
+    }
+
     /// Executes the transfer.
     ///
     /// This method does not consume the transfer since the caller might need to retry executing it
     /// in case of a transient error.
     pub async fn execute(&self) -> Result<TokenTransferInfo, InternalPaymentError> {
+        if let TransferType::FromAllowance(from) = self.r#type {
+            return icrc1::transfer_from_icrc2(
+                self.token,
+                from,
+                self.to(),
+                self.amount_minus_fee(),
+                self.fee.clone(),
+                self.from,
+                Some(self.created_at()),
+                self.memo.clone(),
+            )
+            .await;
+        }
+
         icrc1::transfer_icrc1(
             self.token,
             self.to(),
@@ -165,6 +209,31 @@ impl Transfer {
         .await
     }
 
+    /// Executes the transfer in the ledger of the given kind.
+    pub async fn execute_on(
+        &self,
+        ledger: LedgerKind,
+    ) -> Result<TokenTransferInfo, InternalPaymentError> {
+        match (ledger, &self.r#type) {
+            (LedgerKind::Icrc1, _) => self.execute().await,
+            (LedgerKind::Icp, TransferType::FromAllowance(_)) => Err(
+                InternalPaymentError::InvalidParameters(ParametersError::UnsupportedByLedger),
+            ),
+            (LedgerKind::Icp, _) => {
+                icp::transfer_icp(
+                    self.token,
+                    self.to(),
+                    self.amount_minus_fee(),
+                    self.fee.clone(),
+                    self.from().subaccount,
+                    Some(self.created_at()),
+                    self.memo.clone(),
+                )
+                .await
+            }
+        }
+    }
+
     pub(crate) fn id(&self) -> [u8; 32] {
         use sha2::{Digest, Sha224};
 
@@ -176,6 +245,13 @@ impl Transfer {
         hash.update(self.amount.0.to_bytes_le());
         hash.update(self.token.as_slice());
         hash.update(self.created_at.to_le_bytes());
+        if let Some(memo) = &self.memo {
+            hash.update(memo.0.as_slice());
+        }
+        if let TransferType::FromAllowance(from) = &self.r#type {
+            hash.update(from.owner.as_slice());
+            hash.update(from.effective_subaccount());
+        }
 
         let hash_result: [u8; 28] = hash.finalize().into();
         let mut subaccount = [0; 32];
@@ -190,14 +266,18 @@ impl Transfer {
             TransferType::SingleStep => self.from_acc(),
             TransferType::DoubleStep(Stage::First, _) => self.from_acc(),
             TransferType::DoubleStep(Stage::Second, acc) => *acc,
+            TransferType::FromAllowance(acc) => *acc,
         }
     }
 
     /// Source account of the transfer.
     pub fn from_acc(&self) -> Account {
-        Account {
-            owner: ic::id(),
-            subaccount: self.from,
+        match &self.r#type {
+            TransferType::FromAllowance(acc) => *acc,
+            _ => Account {
+                owner: ic::id(),
+                subaccount: self.from,
+            },
         }
     }
 
@@ -207,6 +287,7 @@ impl Transfer {
             TransferType::SingleStep => self.to,
             TransferType::DoubleStep(Stage::First, acc) => *acc,
             TransferType::DoubleStep(Stage::Second, _) => self.to,
+            TransferType::FromAllowance(_) => self.to,
         }
     }
 
@@ -665,14 +746,7 @@ mod tests {
     fn token_constructor_considers_minter_for_fee() {
         MockContext::new().with_id(alice()).inject();
         let t = Transfer::new(
-            &TokenConfiguration {
-                principal: bob(),
-                fee: 10u64.into(),
-                minting_account: Account {
-                    owner: alice(),
-                    subaccount: None,
-                },
-            },
+            &TokenConfiguration::new(bob(), 10u64.into(), alice().into()),
             john(),
             Account {
                 owner: john(),
@@ -685,14 +759,7 @@ mod tests {
         assert_eq!(t.effective_fee(), 0u64);
 
         let t = Transfer::new(
-            &TokenConfiguration {
-                principal: bob(),
-                fee: 10u64.into(),
-                minting_account: Account {
-                    owner: john(),
-                    subaccount: None,
-                },
-            },
+            &TokenConfiguration::new(bob(), 10u64.into(), john().into()),
             john(),
             Account {
                 owner: john(),
@@ -704,4 +771,36 @@ mod tests {
 
         assert_eq!(t.effective_fee(), 0u64);
     }
+
+    #[test]
+    fn deduplication_window_is_configurable() {
+        let config = TokenConfiguration::new(bob(), 10u64.into(), alice().into());
+        let day = 10u64.pow(9) * 60 * 60 * 24;
+        assert!(config.can_deduplicate(day, day * 2 - 10u64.pow(9) * 60 * 6));
+        assert!(!config.can_deduplicate(day, day * 2 - 10u64.pow(9) * 60 * 5));
+
+        let config = config.with_deduplication_window(1000, 100);
+        assert!(config.can_deduplicate(0, 899));
+        assert!(!config.can_deduplicate(0, 900));
+        assert!(config.can_deduplicate(1000, 0));
+
+        let config = config.with_deduplication_window(100, 1000);
+        assert!(!config.can_deduplicate(0, 0));
+    }
+
+    #[test]
+    fn transfer_id_depends_on_memo() {
+        MockContext::new().with_id(john()).inject();
+        let config = TokenConfiguration::new(bob(), 10u64.into(), xtc().into());
+        let t = Transfer::new(&config, john(), alice().into(), None, 1000u64.into())
+            .with_created_at(42);
+
+        let with_memo = t.clone().with_memo(Memo::from(1u64));
+        let with_other_memo = t.clone().with_memo(Memo::from(2u64));
+
+        assert_eq!(t.id(), t.clone().with_created_at(42).id());
+        assert_ne!(t.id(), with_memo.id());
+        assert_ne!(with_memo.id(), with_other_memo.id());
+        assert_eq!(with_memo.id(), t.with_memo(Memo::from(1u64)).id());
+    }
 }
@@ -22,11 +22,11 @@ impl Default for PaymentState {
     fn default() -> Self {
         Self {
             terminal: TokenTerminal::new(
-                TokenConfiguration {
-                    principal: Principal::management_canister(),
-                    fee: 0u64.into(),
-                    minting_account: Principal::management_canister().into(),
-                },
+                TokenConfiguration::new(
+                    Principal::management_canister(),
+                    0u64.into(),
+                    Principal::management_canister().into(),
+                ),
                 TestBalances::default(),
             ),
         }
@@ -64,14 +64,14 @@ impl PreUpdate for PaymentCanister {}
 impl PaymentCanister {
     #[init]
     pub async fn init(&self, token_canister: Principal) {
-        let config = TokenConfiguration {
-            principal: token_canister,
-            fee: 0u64.into(),
-            minting_account: Account {
+        let config = TokenConfiguration::new(
+            token_canister,
+            0u64.into(),
+            Account {
                 owner: Principal::management_canister(),
                 subaccount: None,
             },
-        };
+        );
         let terminal = TokenTerminal::new(config, TestBalances::default());
 
         PaymentState::get().replace(PaymentState { terminal });
@@ -0,0 +1,190 @@
+use candid::Nat;
+use ic_canister::{register_raw_virtual_responder, register_virtual_responder};
+use ic_exports::ic_cdk::api::call::RejectionCode;
+use ic_exports::ic_kit::mock_principals::{alice, bob};
+use ic_exports::icrc_types::icrc2::allowance::{Allowance, AllowanceArgs};
+use ic_exports::icrc_types::icrc2::approve::{ApproveArgs, ApproveError};
+use ic_exports::icrc_types::icrc2::transfer_from::{TransferFromArgs, TransferFromError};
+use ic_payments::error::{InternalPaymentError, PaymentError, RecoveryDetails, TransferFailReason};
+use ic_payments::icrc1::{active_allowance_amount, approve_icrc2, get_icrc2_active_allowance};
+use ic_payments::recovery_list::{RecoveryList, StableRecoveryList};
+
+use crate::common::{init_test, this_principal, token_principal, TestBalances};
+
+pub mod common;
+
+#[tokio::test]
+async fn deposit_from_allowance_with_success() {
+    let mut terminal = init_test();
+    register_virtual_responder(
+        token_principal(),
+        "icrc2_transfer_from",
+        |(args,): (TransferFromArgs,)| {
+            assert_eq!(args.from, alice().into());
+            assert_eq!(args.to, this_principal().into());
+            assert_eq!(args.amount, 990u64);
+            Ok::<Nat, TransferFromError>(1u64.into())
+        },
+    );
+
+    let (tx_id, amount) = terminal
+        .deposit_from_allowance(alice(), 1000u64.into())
+        .await
+        .unwrap();
+    assert_eq!(tx_id, 1u64);
+    assert_eq!(amount, 990u64);
+    assert_eq!(TestBalances::balance_of(alice()), 990u64);
+}
+
+#[tokio::test]
+async fn deposit_from_allowance_with_insufficient_allowance() {
+    let mut terminal = init_test();
+    register_virtual_responder(
+        token_principal(),
+        "icrc2_transfer_from",
+        |_: (TransferFromArgs,)| {
+            Err::<Nat, TransferFromError>(TransferFromError::InsufficientAllowance {
+                allowance: 500u64.into(),
+            })
+        },
+    );
+
+    let result = terminal
+        .deposit_from_allowance(alice(), 1000u64.into())
+        .await;
+    assert_eq!(
+        result,
+        Err(PaymentError::TransferFailed(
+            TransferFailReason::InsufficientAllowance {
+                allowance: 500u64.into()
+            }
+        ))
+    );
+    assert_eq!(TestBalances::balance_of(alice()), 0u64);
+}
+
+#[tokio::test]
+async fn deposit_from_allowance_recovered_by_deduplication() {
+    let mut terminal = init_test();
+    register_raw_virtual_responder(token_principal(), "icrc2_transfer_from", |_| {
+        Err((RejectionCode::SysTransient, "recoverable".into()))
+    });
+
+    let result = terminal
+        .deposit_from_allowance(alice(), 1000u64.into())
+        .await;
+    assert_eq!(
+        result,
+        Err(PaymentError::Recoverable(RecoveryDetails::IcError))
+    );
+    assert_eq!(StableRecoveryList::<0>.list().len(), 1);
+
+    register_virtual_responder(
+        token_principal(),
+        "icrc2_transfer_from",
+        |_: (TransferFromArgs,)| {
+            Err::<Nat, TransferFromError>(TransferFromError::Duplicate {
+                duplicate_of: 3u64.into(),
+            })
+        },
+    );
+
+    let results = terminal.recover_all().await;
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].as_ref().unwrap().0, 3u64);
+    assert_eq!(TestBalances::balance_of(alice()), 990u64);
+    assert!(StableRecoveryList::<0>.list().is_empty());
+}
+
+#[tokio::test]
+async fn approve_and_query_allowance() {
+    init_test();
+    register_virtual_responder(
+        token_principal(),
+        "icrc2_approve",
+        |(args,): (ApproveArgs,)| match args.expected_allowance {
+            Some(current_allowance) if current_allowance != 0u64 => {
+                Err(ApproveError::AllowanceChanged {
+                    current_allowance: 0u64.into(),
+                })
+            }
+            _ => Ok::<Nat, ApproveError>(7u64.into()),
+        },
+    );
+
+    let tx_id = approve_icrc2(
+        token_principal(),
+        alice().into(),
+        1000u64.into(),
+        10u64.into(),
+        None,
+        Some(0u64.into()),
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(tx_id, 7u64);
+
+    let result = approve_icrc2(
+        token_principal(),
+        alice().into(),
+        1000u64.into(),
+        10u64.into(),
+        None,
+        Some(500u64.into()),
+        None,
+        None,
+    )
+    .await;
+    assert_eq!(
+        result,
+        Err(InternalPaymentError::TransferFailed(
+            TransferFailReason::AllowanceChanged {
+                current_allowance: 0u64.into()
+            }
+        ))
+    );
+}
+
+#[tokio::test]
+async fn expired_allowance_is_not_active() {
+    init_test();
+    register_virtual_responder(
+        token_principal(),
+        "icrc2_allowance",
+        |(args,): (AllowanceArgs,)| Allowance {
+            allowance: 1000u64.into(),
+            // Allowance given to alice is expired.
+            expires_at: (args.spender.owner == alice()).then_some(0),
+        },
+    );
+
+    let allowance =
+        get_icrc2_active_allowance(token_principal(), this_principal().into(), bob().into())
+            .await
+            .unwrap();
+    assert_eq!(allowance, 1000u64);
+
+    let allowance =
+        get_icrc2_active_allowance(token_principal(), this_principal().into(), alice().into())
+            .await
+            .unwrap();
+    assert_eq!(allowance, 0u64);
+}
+
+#[test]
+fn active_allowance_respects_expiration() {
+    let allowance = Allowance {
+        allowance: 1000u64.into(),
+        expires_at: Some(100),
+    };
+    assert_eq!(active_allowance_amount(&allowance, 99), 1000u64);
+    assert_eq!(active_allowance_amount(&allowance, 100), 0u64);
+
+    let allowance = Allowance {
+        allowance: 1000u64.into(),
+        expires_at: None,
+    };
+    assert_eq!(active_allowance_amount(&allowance, u64::MAX), 1000u64);
+}
@@ -0,0 +1,65 @@
+use candid::{Nat, Principal};
+use common::*;
+use ic_canister::register_virtual_responder;
+use ic_exports::ic_kit::mock_principals::{alice, bob};
+use ic_exports::icrc_types::icrc1::transfer::{TransferArg, TransferError};
+use ic_payments::cmc::{top_up_account, NotifyTopUpArg, MEMO_TOP_UP_CANISTER};
+use ic_payments::error::{PaymentError, TopUpError};
+
+pub mod common;
+
+fn cmc_principal() -> Principal {
+    Principal::from_slice(&[4; 29])
+}
+
+fn setup_cmc(result: Result<Nat, TopUpError>) {
+    register_virtual_responder(
+        cmc_principal(),
+        "notify_top_up",
+        move |(args,): (NotifyTopUpArg,)| {
+            assert_eq!(args.block_index, 7);
+            assert_eq!(args.canister_id, bob());
+            result.clone()
+        },
+    );
+}
+
+#[tokio::test]
+async fn top_up_cycles_transfers_to_cmc_and_notifies() {
+    let mut terminal = init_test();
+    register_virtual_responder(
+        token_principal(),
+        "icrc1_transfer",
+        move |(args,): (TransferArg,)| {
+            assert_eq!(args.to, top_up_account(cmc_principal(), &bob()));
+            assert_eq!(args.amount, 990u64);
+            assert_eq!(args.memo, Some(MEMO_TOP_UP_CANISTER.into()));
+            Ok::<Nat, TransferError>(7u64.into())
+        },
+    );
+    setup_cmc(Ok(1_000_000u64.into()));
+
+    let cycles = terminal
+        .top_up_cycles(alice(), bob(), 1000u64.into(), cmc_principal())
+        .await
+        .unwrap();
+    assert_eq!(cycles, 1_000_000u64);
+}
+
+#[tokio::test]
+async fn top_up_cycles_returns_block_index_if_notification_fails() {
+    let mut terminal = init_test();
+    setup_success(7);
+    setup_cmc(Err(TopUpError::Processing));
+
+    let result = terminal
+        .top_up_cycles(alice(), bob(), 1000u64.into(), cmc_principal())
+        .await;
+    assert_eq!(
+        result,
+        Err(PaymentError::TopUpFailed {
+            block_index: 7u64.into(),
+            reason: TopUpError::Processing,
+        })
+    );
+}
@@ -79,11 +79,7 @@ pub fn minting_account() -> Account {
 }
 
 pub fn token_config() -> TokenConfiguration {
-    TokenConfiguration {
-        principal: token_principal(),
-        fee: 100u64.into(),
-        minting_account: minting_account(),
-    }
+    TokenConfiguration::new(token_principal(), 100u64.into(), minting_account())
 }
 
 pub fn this_principal() -> Principal {
@@ -107,11 +103,7 @@ pub fn init_test() -> TokenTerminal<TestBalances, StableRecoveryList<0>> {
     init_context();
 
     TokenTerminal::new(
-        TokenConfiguration {
-            principal: token_principal(),
-            fee: 10u64.into(),
-            minting_account: minting_account(),
-        },
+        TokenConfiguration::new(token_principal(), 10u64.into(), minting_account()),
         TestBalances {},
     )
 }
@@ -0,0 +1,120 @@
+use std::collections::BTreeSet;
+
+use candid::{Nat, Principal};
+use common::*;
+use ic_canister::register_virtual_responder;
+use ic_exports::ic_kit::mock_principals::{alice, bob};
+use ic_exports::icrc_types::icrc1::account::Account;
+use ic_payments::index::{
+    GetAccountTransactionsArgs, GetTransactions, GetTransactionsErr, IndexTransaction,
+    IndexTransfer, TransactionWithId,
+};
+use ic_payments::{DepositClaims, DepositWatcher, DetectedDeposit};
+
+pub mod common;
+
+fn index_principal() -> Principal {
+    Principal::from_slice(&[4; 29])
+}
+
+fn incoming(id: u64, from: Account, amount: u64) -> TransactionWithId {
+    TransactionWithId {
+        id: id.into(),
+        transaction: IndexTransaction {
+            kind: "transfer".into(),
+            transfer: Some(IndexTransfer {
+                from,
+                to: this_principal().into(),
+                amount: amount.into(),
+                spender: None,
+                memo: None,
+            }),
+            timestamp: 0,
+        },
+    }
+}
+
+/// Registers the index responder with the given transactions, newest first.
+fn setup_index(transactions: Vec<TransactionWithId>) {
+    register_virtual_responder(
+        index_principal(),
+        "get_account_transactions",
+        move |(args,): (GetAccountTransactionsArgs,)| {
+            let transactions = transactions
+                .iter()
+                .filter(|tx| match &args.start {
+                    Some(start) => tx.id < *start,
+                    None => true,
+                })
+                .take(usize::try_from(args.max_results.0).unwrap())
+                .cloned()
+                .collect();
+
+            Ok::<_, GetTransactionsErr>(GetTransactions {
+                balance: 0u64.into(),
+                transactions,
+                oldest_tx_id: Some(1u64.into()),
+            })
+        },
+    );
+}
+
+#[tokio::test]
+async fn poll_deposits_credits_new_incoming_transfers() {
+    let mut terminal = init_test();
+    let mut watcher = DepositWatcher::new(index_principal());
+    watcher.batch_size = 2;
+    let mut claims = BTreeSet::<Nat>::new();
+
+    // Transactions existing before the first poll are not credited.
+    setup_index(vec![
+        incoming(2, alice().into(), 100),
+        incoming(1, alice().into(), 100),
+    ]);
+    let deposits = terminal
+        .poll_deposits(&mut watcher, &mut claims)
+        .await
+        .unwrap();
+    assert!(deposits.is_empty());
+    assert_eq!(watcher.last_tx_id, Some(2u64.into()));
+
+    claims.mark_claimed(6u64.into());
+    setup_index(vec![
+        incoming(6, bob().into(), 300),
+        incoming(5, bob().into(), 200),
+        incoming(4, this_principal().into(), 500),
+        incoming(3, alice().into(), 1000),
+        incoming(2, alice().into(), 100),
+        incoming(1, alice().into(), 100),
+    ]);
+    let deposits = terminal
+        .poll_deposits(&mut watcher, &mut claims)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        deposits,
+        vec![
+            DetectedDeposit {
+                tx_id: 3u64.into(),
+                owner: alice(),
+                amount: 1000u64.into(),
+            },
+            DetectedDeposit {
+                tx_id: 5u64.into(),
+                owner: bob(),
+                amount: 200u64.into(),
+            },
+        ]
+    );
+    assert_eq!(watcher.last_tx_id, Some(6u64.into()));
+    assert_eq!(TestBalances::balance_of(alice()), 1000u64);
+    assert_eq!(TestBalances::balance_of(bob()), 200u64);
+    assert!(claims.is_claimed(&3u64.into()));
+
+    let deposits = terminal
+        .poll_deposits(&mut watcher, &mut claims)
+        .await
+        .unwrap();
+    assert!(deposits.is_empty());
+}
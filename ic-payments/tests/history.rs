@@ -0,0 +1,131 @@
+use std::cell::Cell;
+
+use candid::Nat;
+use common::*;
+use ic_exports::ic_kit::mock_principals::{alice, bob, john};
+use ic_payments::history::{TransferDirection, TransferHistory, TransferStatus};
+use ic_payments::{OnTransferEvent, Transfer};
+use ic_stable_structures::stable_structures::DefaultMemoryImpl;
+use ic_stable_structures::{IcMemoryManager, MemoryId};
+
+pub mod common;
+
+thread_local! {
+    static SUCCEEDED: Cell<u32> = const { Cell::new(0) };
+}
+
+struct SucceededCounter;
+
+impl OnTransferEvent for SucceededCounter {
+    fn on_succeeded(&mut self, _transfer: &Transfer, _tx_id: &Nat) {
+        SUCCEEDED.with(|v| v.set(v.get() + 1));
+    }
+}
+
+fn init_history() -> TransferHistory {
+    let memory_manager = IcMemoryManager::init(DefaultMemoryImpl::default());
+    TransferHistory::init(
+        memory_manager.get(MemoryId::new(10)),
+        memory_manager.get(MemoryId::new(11)),
+        memory_manager.get(MemoryId::new(12)),
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn completed_and_failed_transfers_are_recorded() {
+    let history = init_history();
+    let mut terminal = init_test().with_transfer_hooks(history);
+
+    setup_success(1);
+    terminal.withdraw(alice(), 1000u64.into()).await.unwrap();
+
+    setup_error();
+    terminal.deposit(alice(), 500u64.into()).await.unwrap_err();
+
+    let page = history.page(None, 0, 10);
+    assert_eq!(page.total, 2);
+    assert_eq!(page.records[0].direction, TransferDirection::Deposit);
+    assert_eq!(page.records[0].amount, 500u64);
+    assert!(matches!(
+        page.records[0].status,
+        TransferStatus::Failed { .. }
+    ));
+
+    assert_eq!(page.records[1].direction, TransferDirection::Withdrawal);
+    assert_eq!(page.records[1].amount, 1000u64);
+    assert_eq!(page.records[1].fee, 20u64);
+    assert_eq!(
+        page.records[1].status,
+        TransferStatus::Succeeded { tx_id: 1u64.into() }
+    );
+}
+
+#[tokio::test]
+async fn history_is_paginated_and_filtered() {
+    let history = init_history();
+    let mut terminal = init_test().with_transfer_hooks(history);
+    setup_success(1);
+
+    for (principal, amount) in [
+        (alice(), 1000u64),
+        (bob(), 1500),
+        (alice(), 2000),
+        (alice(), 3000),
+    ] {
+        terminal.deposit(principal, amount.into()).await.unwrap();
+    }
+
+    let page = history.page(None, 1, 1);
+    assert_eq!(page.total, 4);
+    assert_eq!(page.records.len(), 1);
+    assert_eq!(page.records[0].amount, 2000u64);
+
+    let page = history.page(Some(alice()), 1, 10);
+    assert_eq!(page.total, 3);
+    let amounts: Vec<_> = page.records.iter().map(|r| r.amount.clone()).collect();
+    assert_eq!(amounts, vec![Nat::from(2000u64), Nat::from(1000u64)]);
+
+    let page = history.page(Some(bob()), 0, 10);
+    assert_eq!(page.total, 1);
+    assert_eq!(page.records[0].amount, 1500u64);
+
+    let page = history.page(Some(alice()), 3, 10);
+    assert_eq!(page.total, 3);
+    assert!(page.records.is_empty());
+
+    let page = history.page(Some(john()), 0, 10);
+    assert_eq!(page.total, 0);
+    assert!(page.records.is_empty());
+}
+
+#[tokio::test]
+async fn only_allowed_callers_read_transfers_of_others() {
+    let history = init_history();
+    let mut terminal = init_test().with_transfer_hooks(history);
+    setup_success(1);
+    terminal.deposit(alice(), 1000u64.into()).await.unwrap();
+
+    let page = history.page_for_caller(bob(), |_| false, Some(alice()), 0, 10);
+    assert_eq!(page.total, 0);
+    let page = history.page_for_caller(bob(), |_| false, None, 0, 10);
+    assert_eq!(page.total, 0);
+
+    let page = history.page_for_caller(alice(), |_| false, None, 0, 10);
+    assert_eq!(page.total, 1);
+    let page = history.page_for_caller(bob(), |caller| *caller == bob(), Some(alice()), 0, 10);
+    assert_eq!(page.total, 1);
+}
+
+#[tokio::test]
+async fn history_composes_with_other_hooks() {
+    let history = init_history();
+    let mut terminal = init_test()
+        .with_transfer_hooks(history)
+        .with_transfer_hooks(SucceededCounter);
+    setup_success(1);
+    terminal.deposit(alice(), 1000u64.into()).await.unwrap();
+
+    assert_eq!(history.len(), 1);
+    assert_eq!(SUCCEEDED.with(Cell::get), 1);
+}
@@ -0,0 +1,89 @@
+use candid::Nat;
+use ic_canister::register_virtual_responder;
+use ic_exports::ic_kit::mock_principals::alice;
+use ic_exports::icrc_types::icrc1::transfer::Memo;
+use ic_exports::ledger::{BlockIndex, Memo as IcpMemo, Tokens, TransferArgs, TransferError};
+use ic_payments::error::{ParametersError, PaymentError};
+use ic_payments::icp::{account_identifier, icp_memo};
+use ic_payments::recovery_list::{RecoveryList, StableRecoveryList};
+use ic_payments::LedgerKind;
+
+use crate::common::{init_test, this_principal, token_principal, TestBalances};
+
+pub mod common;
+
+#[tokio::test]
+async fn icp_deposit_with_success() {
+    let mut terminal = init_test().with_ledger(LedgerKind::Icp);
+    register_virtual_responder(token_principal(), "transfer", |(args,): (TransferArgs,)| {
+        assert_eq!(args.to, account_identifier(&this_principal().into()));
+        assert_eq!(args.amount, Tokens::from_e8s(990));
+        assert_eq!(args.fee, Tokens::from_e8s(10));
+        Ok::<BlockIndex, TransferError>(5)
+    });
+
+    let (tx_id, amount) = terminal.deposit(alice(), 1000u64.into()).await.unwrap();
+    assert_eq!(tx_id, 5u64);
+    assert_eq!(amount, 990u64);
+    assert_eq!(TestBalances::balance_of(alice()), 990u64);
+}
+
+#[tokio::test]
+async fn icp_duplicate_is_recovered() {
+    let mut terminal = init_test().with_ledger(LedgerKind::Icp);
+    register_virtual_responder(token_principal(), "transfer", |_: (TransferArgs,)| {
+        Err::<BlockIndex, TransferError>(TransferError::TxDuplicate { duplicate_of: 7 })
+    });
+
+    let transfer = ic_payments::Transfer::new(
+        terminal.token_config(),
+        alice(),
+        alice().into(),
+        None,
+        1000u64.into(),
+    );
+    StableRecoveryList::<0>.push(transfer);
+
+    let results = terminal.recover_all().await;
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].as_ref().unwrap().0, Nat::from(7u64));
+}
+
+#[test]
+fn icp_memo_rejects_long_memos() {
+    assert_eq!(icp_memo(None), Ok(IcpMemo(0)));
+    assert_eq!(icp_memo(Some(&Memo::from(vec![1, 2]))), Ok(IcpMemo(0x0102)));
+    assert_eq!(
+        icp_memo(Some(&Memo::from(vec![0xff; 8]))),
+        Ok(IcpMemo(u64::MAX))
+    );
+
+    // Would collide with the 8 byte memo above if truncated
+    assert_eq!(
+        icp_memo(Some(&Memo::from([vec![1], vec![0xff; 8]].concat()))),
+        Err(ParametersError::MemoTooLong {
+            max_length: 8,
+            actual: 9
+        })
+    );
+}
+
+#[tokio::test]
+async fn icp_withdraw_with_long_memo_is_rejected() {
+    let mut terminal = init_test().with_ledger(LedgerKind::Icp);
+
+    // The memo is checked before the balance is debited and the ledger is called
+    let result = terminal
+        .withdraw_with_memo(alice(), None, 1000u64.into(), Some(Memo::from(vec![1; 9])))
+        .await;
+    assert_eq!(
+        result,
+        Err(PaymentError::InvalidParameters(
+            ParametersError::MemoTooLong {
+                max_length: 8,
+                actual: 9
+            }
+        ))
+    );
+    assert_eq!(TestBalances::balance_of(alice()), 0u64);
+}
@@ -0,0 +1,149 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use candid::{Nat, Principal};
+use common::*;
+use ic_canister::register_virtual_responder;
+use ic_exports::ic_kit::mock_principals::{alice, bob};
+use ic_payments::error::{ClaimError, PaymentError};
+use ic_payments::icrc3::{BlockWithId, GetBlocksArgs, GetBlocksResult, Icrc3Value};
+
+pub mod common;
+
+fn account_value(owner: Principal) -> Icrc3Value {
+    Icrc3Value::Array(vec![Icrc3Value::Blob(owner.as_slice().to_vec())])
+}
+
+fn transfer_block(from: Principal, to: Principal, amount: u64) -> Icrc3Value {
+    let tx = BTreeMap::from([
+        ("op".to_string(), Icrc3Value::Text("xfer".into())),
+        ("from".to_string(), account_value(from)),
+        ("to".to_string(), account_value(to)),
+        ("amt".to_string(), Icrc3Value::Nat(amount.into())),
+    ]);
+
+    Icrc3Value::Map(BTreeMap::from([
+        ("ts".to_string(), Icrc3Value::Nat(0u64.into())),
+        ("tx".to_string(), Icrc3Value::Map(tx)),
+    ]))
+}
+
+fn transfer_from_block(
+    from: Principal,
+    to: Principal,
+    spender: Principal,
+    amount: u64,
+) -> Icrc3Value {
+    let tx = BTreeMap::from([
+        ("from".to_string(), account_value(from)),
+        ("to".to_string(), account_value(to)),
+        ("spender".to_string(), account_value(spender)),
+        ("amt".to_string(), Icrc3Value::Nat(amount.into())),
+    ]);
+
+    Icrc3Value::Map(BTreeMap::from([
+        ("btype".to_string(), Icrc3Value::Text("2xfer".into())),
+        ("ts".to_string(), Icrc3Value::Nat(0u64.into())),
+        ("tx".to_string(), Icrc3Value::Map(tx)),
+    ]))
+}
+
+fn setup_block(block: Icrc3Value) {
+    register_virtual_responder(
+        token_principal(),
+        "icrc3_get_blocks",
+        move |(args,): (Vec<GetBlocksArgs>,)| GetBlocksResult {
+            log_length: 100u64.into(),
+            blocks: vec![BlockWithId {
+                id: args[0].start.clone(),
+                block: block.clone(),
+            }],
+            archived_blocks: vec![],
+        },
+    );
+}
+
+#[tokio::test]
+async fn claim_deposit_credits_verified_transfer_once() {
+    let mut terminal = init_test();
+    let mut claims = BTreeSet::<Nat>::new();
+    setup_block(transfer_block(alice(), this_principal(), 1000));
+
+    let credited = terminal
+        .claim_deposit(alice(), 5u64.into(), 1000u64.into(), &mut claims)
+        .await
+        .unwrap();
+    assert_eq!(credited, 1000u64);
+    assert_eq!(TestBalances::balance_of(alice()), 1000u64);
+
+    let result = terminal
+        .claim_deposit(alice(), 5u64.into(), 1000u64.into(), &mut claims)
+        .await;
+    assert_eq!(
+        result,
+        Err(PaymentError::InvalidClaim(ClaimError::AlreadyClaimed))
+    );
+    assert_eq!(TestBalances::balance_of(alice()), 1000u64);
+}
+
+#[tokio::test]
+async fn claim_deposit_rejects_mismatching_transfer() {
+    let mut terminal = init_test();
+    let mut claims = BTreeSet::<Nat>::new();
+
+    setup_block(transfer_block(bob(), this_principal(), 1000));
+    let result = terminal
+        .claim_deposit(alice(), 5u64.into(), 1000u64.into(), &mut claims)
+        .await;
+    assert_eq!(
+        result,
+        Err(PaymentError::InvalidClaim(ClaimError::WrongSender))
+    );
+
+    setup_block(transfer_block(alice(), bob(), 1000));
+    let result = terminal
+        .claim_deposit(alice(), 5u64.into(), 1000u64.into(), &mut claims)
+        .await;
+    assert_eq!(
+        result,
+        Err(PaymentError::InvalidClaim(ClaimError::WrongRecipient))
+    );
+
+    setup_block(transfer_block(alice(), this_principal(), 999));
+    let result = terminal
+        .claim_deposit(alice(), 5u64.into(), 1000u64.into(), &mut claims)
+        .await;
+    assert_eq!(
+        result,
+        Err(PaymentError::InvalidClaim(ClaimError::WrongAmount {
+            expected: 1000u64.into(),
+            actual: 999u64.into(),
+        }))
+    );
+
+    assert!(claims.is_empty());
+    assert_eq!(TestBalances::balance_of(alice()), 0u64);
+}
+
+#[tokio::test]
+async fn claim_deposit_rejects_allowance_deposit() {
+    let mut terminal = init_test();
+    let mut claims = BTreeSet::<Nat>::new();
+
+    // Block created by `deposit_from_allowance`, already credited by the terminal
+    setup_block(transfer_from_block(
+        alice(),
+        this_principal(),
+        this_principal(),
+        1000,
+    ));
+    let result = terminal
+        .claim_deposit(alice(), 5u64.into(), 1000u64.into(), &mut claims)
+        .await;
+    assert_eq!(
+        result,
+        Err(PaymentError::InvalidClaim(ClaimError::SpenderTransfer))
+    );
+
+    assert!(claims.is_empty());
+    assert_eq!(TestBalances::balance_of(alice()), 0u64);
+}
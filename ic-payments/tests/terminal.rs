@@ -1,8 +1,20 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use candid::Nat;
 use common::*;
-use ic_exports::ic_kit::mock_principals::alice;
+use ic_canister::register_virtual_responder;
+use ic_exports::ic_kit::mock_principals::{alice, bob};
 use ic_exports::icrc_types::icrc1::account::Account;
+use ic_exports::icrc_types::icrc1::transfer::{Memo, TransferArg, TransferError};
 use ic_payments::recovery_list::{RecoveryList, StableRecoveryList};
-use ic_payments::{Balances, TokenConfiguration, Transfer};
+use ic_payments::{
+    AmountLimits, Balances, DustPolicy, OnTransferEvent, PaymentError, TokenConfiguration,
+    TokenTerminal, Transfer,
+};
+use ic_stable_structures::stable_structures::DefaultMemoryImpl;
+use ic_stable_structures::{IcMemoryManager, MemoryId};
 
 pub mod common;
 
@@ -66,11 +78,7 @@ fn update_fees() {
 #[test]
 fn update_minting_account() {
     let mut terminal = init_test();
-    let token_config = TokenConfiguration {
-        principal: token_principal(),
-        fee: 10u64.into(),
-        minting_account: minting_account(),
-    };
+    let token_config = TokenConfiguration::new(token_principal(), 10u64.into(), minting_account());
 
     let transfer = Transfer::new(
         &token_config,
@@ -93,3 +101,282 @@ fn update_minting_account() {
     assert_eq!(StableRecoveryList::<0>.list()[0].fee, 10u64);
     assert_eq!(StableRecoveryList::<0>.list()[0].effective_fee(), 10u64);
 }
+
+#[tokio::test]
+async fn recover_batch_keeps_exceeding_transfers() {
+    let mut terminal = init_test();
+    setup_success(1);
+
+    for amount in [1000u64, 2000, 3000] {
+        let transfer = Transfer::new(
+            terminal.token_config(),
+            alice(),
+            alice().into(),
+            None,
+            amount.into(),
+        );
+        StableRecoveryList::<0>.push(transfer);
+    }
+
+    let results = terminal.recover_batch(2).await;
+    assert_eq!(results.len(), 2);
+    assert!(results.iter().all(Result::is_ok));
+    assert_eq!(terminal.list_for_recovery().len(), 1);
+
+    let results = terminal.recover_all().await;
+    assert_eq!(results.len(), 1);
+    assert!(terminal.list_for_recovery().is_empty());
+}
+
+#[tokio::test]
+async fn shared_recovery_does_not_borrow_terminal_during_ledger_calls() {
+    let terminal = Rc::new(RefCell::new(init_test()));
+    for amount in [1000u64, 2000] {
+        let transfer = Transfer::new(
+            terminal.borrow().token_config(),
+            alice(),
+            alice().into(),
+            None,
+            amount.into(),
+        );
+        StableRecoveryList::<0>.push(transfer);
+    }
+
+    let shared = terminal.clone();
+    register_virtual_responder(
+        token_principal(),
+        "icrc1_transfer",
+        move |_: (TransferArg,)| {
+            // Other messages can use the terminal while the transfer is awaited
+            assert!(shared.try_borrow_mut().is_ok());
+            Ok::<Nat, TransferError>(1u64.into())
+        },
+    );
+
+    let results = TokenTerminal::recover_batch_shared(&*terminal, 10).await;
+    assert_eq!(results.len(), 2);
+    assert!(results.iter().all(Result::is_ok));
+    assert!(terminal.borrow().list_for_recovery().is_empty());
+}
+
+#[tokio::test]
+async fn recovery_list_with_custom_memory_is_restored() {
+    let terminal = init_test();
+    let memory_manager = IcMemoryManager::init(DefaultMemoryImpl::default());
+
+    let mut list = StableRecoveryList::<0>::init_with_memory(memory_manager.get(MemoryId::new(3)));
+    let transfer = Transfer::new(
+        terminal.token_config(),
+        alice(),
+        alice().into(),
+        None,
+        1000.into(),
+    );
+    let id = transfer.id();
+    list.push(transfer);
+
+    // Emulate canister upgrade by initializing the list again over the same memory.
+    let list = StableRecoveryList::<0>::init_with_memory(memory_manager.get(MemoryId::new(3)));
+    let restored = list.list();
+    assert_eq!(restored.len(), 1);
+    assert_eq!(restored[0].id(), id);
+}
+
+#[tokio::test]
+async fn withdraw_with_memo_passes_memo_and_subaccount() {
+    let mut terminal = init_test();
+    let memo = Memo::from(b"order-42".to_vec());
+    let expected_memo = memo.clone();
+    register_virtual_responder(
+        token_principal(),
+        "icrc1_transfer",
+        move |(args,): (TransferArg,)| {
+            assert_eq!(args.memo, Some(expected_memo.clone()));
+            if args.to.owner == alice() {
+                assert_eq!(args.to.subaccount, Some([7; 32]));
+            }
+            Ok::<Nat, TransferError>(1u64.into())
+        },
+    );
+
+    let (tx_id, amount) = terminal
+        .withdraw_with_memo(alice(), Some([7; 32]), 1000u64.into(), Some(memo))
+        .await
+        .unwrap();
+    assert_eq!(tx_id, 1u64);
+    assert_eq!(amount, 980u64);
+}
+
+thread_local! {
+    static UPDATED_FEE: RefCell<Option<Nat>> = const { RefCell::new(None) };
+}
+
+#[tokio::test]
+async fn config_is_refreshed_after_ttl() {
+    let mut terminal = init_test()
+        .with_config_refresh(Duration::ZERO)
+        .on_config_update(|config| {
+            UPDATED_FEE.with(|v| *v.borrow_mut() = Some(config.fee.clone()))
+        });
+    setup_success(1);
+    register_virtual_responder(token_principal(), "icrc1_fee", |_: ()| Nat::from(20u64));
+    register_virtual_responder(token_principal(), "icrc1_minting_account", |_: ()| {
+        Some(minting_account())
+    });
+
+    let (_, amount) = terminal.deposit(alice(), 1000u64.into()).await.unwrap();
+    assert_eq!(amount, 980u64);
+    assert_eq!(terminal.fee(), 20u64);
+    assert_eq!(UPDATED_FEE.with(|v| v.borrow().clone()), Some(20u64.into()));
+
+    // No callback if the configuration is the same.
+    UPDATED_FEE.with(|v| *v.borrow_mut() = None);
+    assert!(!terminal.refresh_config().await.unwrap());
+    assert_eq!(UPDATED_FEE.with(|v| v.borrow().clone()), None);
+}
+
+thread_local! {
+    static TRANSFER_EVENTS: RefCell<Vec<&'static str>> = const { RefCell::new(vec![]) };
+}
+
+struct RecordingHooks;
+
+impl RecordingHooks {
+    fn record(event: &'static str) {
+        TRANSFER_EVENTS.with(|v| v.borrow_mut().push(event));
+    }
+
+    fn events() -> Vec<&'static str> {
+        TRANSFER_EVENTS.with(|v| v.borrow().clone())
+    }
+}
+
+impl OnTransferEvent for RecordingHooks {
+    fn on_started(&mut self, _transfer: &Transfer) {
+        Self::record("started");
+    }
+
+    fn on_succeeded(&mut self, _transfer: &Transfer, _tx_id: &Nat) {
+        Self::record("succeeded");
+    }
+
+    fn on_failed(&mut self, _transfer: &Transfer, _error: &PaymentError) {
+        Self::record("failed");
+    }
+
+    fn on_moved_to_recovery(&mut self, _transfer: &Transfer) {
+        Self::record("moved_to_recovery");
+    }
+}
+
+#[tokio::test]
+async fn transfer_hooks_are_invoked() {
+    let mut terminal = init_test().with_transfer_hooks(RecordingHooks);
+
+    setup_success(1);
+    terminal.withdraw(alice(), 1000u64.into()).await.unwrap();
+    assert_eq!(
+        RecordingHooks::events(),
+        vec!["started", "succeeded", "started", "succeeded"]
+    );
+
+    TRANSFER_EVENTS.with(|v| v.borrow_mut().clear());
+    setup_error();
+    terminal.deposit(alice(), 1000u64.into()).await.unwrap_err();
+    assert_eq!(RecordingHooks::events(), vec!["started", "failed"]);
+}
+
+#[tokio::test]
+async fn amounts_below_minimum_are_rejected_before_ledger_call() {
+    let mut terminal =
+        init_test().with_amount_limits(AmountLimits::new(500u64.into(), 1000u64.into()));
+    setup_error();
+
+    let result = terminal.deposit(alice(), 400u64.into()).await;
+    assert_eq!(
+        result,
+        Err(PaymentError::AmountBelowMinimum {
+            minimum: 500u64.into(),
+            actual: 400u64.into(),
+        })
+    );
+
+    let result = terminal.withdraw(alice(), 900u64.into()).await;
+    assert_eq!(
+        result,
+        Err(PaymentError::AmountBelowMinimum {
+            minimum: 1000u64.into(),
+            actual: 900u64.into(),
+        })
+    );
+    assert_eq!(TestBalances::balance_of(alice()), 0u64);
+}
+
+#[tokio::test]
+async fn withdrawal_dust_is_donated_to_fee_account() {
+    let mut terminal = init_test().with_amount_limits(
+        AmountLimits::new(0u64.into(), 1000u64.into()).with_dust_policy(DustPolicy::Donate(bob())),
+    );
+    setup_success(1);
+    terminal.deposit(alice(), 1000u64.into()).await.unwrap();
+
+    let result = terminal.withdraw(alice(), 90u64.into()).await;
+    assert_eq!(
+        result,
+        Err(PaymentError::DustDonated {
+            tx_id: None,
+            amount: 90u64.into(),
+            fee_account: bob(),
+        })
+    );
+    assert_eq!(TestBalances::balance_of(alice()), 900u64);
+    assert_eq!(TestBalances::balance_of(bob()), 90u64);
+}
+
+#[tokio::test]
+async fn deposit_dust_is_credited_to_fee_account() {
+    let mut terminal = init_test().with_amount_limits(
+        AmountLimits::new(500u64.into(), 0u64.into()).with_dust_policy(DustPolicy::Donate(bob())),
+    );
+    setup_success(1);
+
+    let result = terminal.deposit(alice(), 400u64.into()).await;
+    assert_eq!(
+        result,
+        Err(PaymentError::DustDonated {
+            tx_id: Some(1u64.into()),
+            amount: 390u64.into(),
+            fee_account: bob(),
+        })
+    );
+    assert_eq!(TestBalances::balance_of(alice()), 0u64);
+    assert_eq!(TestBalances::balance_of(bob()), 390u64);
+}
+
+#[tokio::test]
+async fn withdrawal_dust_is_not_accumulated() {
+    let mut terminal = init_test().with_amount_limits(
+        AmountLimits::new(500u64.into(), 1000u64.into()).with_dust_policy(DustPolicy::Accumulate),
+    );
+    setup_error();
+    TestBalances.credit(alice(), 3000u64.into()).unwrap();
+
+    let result = terminal.deposit(alice(), 400u64.into()).await;
+    assert_eq!(
+        result,
+        Err(PaymentError::DustAccumulated {
+            minimum: 500u64.into(),
+            actual: 400u64.into(),
+        })
+    );
+
+    let result = terminal.withdraw(alice(), 900u64.into()).await;
+    assert_eq!(
+        result,
+        Err(PaymentError::AmountBelowMinimum {
+            minimum: 1000u64.into(),
+            actual: 900u64.into(),
+        })
+    );
+    assert_eq!(TestBalances::balance_of(alice()), 3000u64);
+}
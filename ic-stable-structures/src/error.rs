@@ -15,6 +15,12 @@ pub enum Error {
     IncompatibleElementType,
     #[error("bad magic number: actual: {actual:?}, expected: {expected:?}")]
     BadMagic { actual: [u8; 3], expected: [u8; 3] },
+    #[error("candid error: {0}")]
+    Candid(#[from] candid::Error),
+    #[error("versioned value error: {0}")]
+    Versioned(#[from] ic_storage::Error),
+    #[error("memory quota exceeded: {used_pages} of {max_pages} pages used")]
+    QuotaExceeded { used_pages: u64, max_pages: u64 },
 }
 
 impl From<cell::InitError> for Error {
@@ -4,6 +4,9 @@ mod error;
 mod memory;
 #[cfg(feature = "memory-mapped-files-memory")]
 mod memory_mapped_files;
+mod migration;
+#[cfg(feature = "profiling")]
+mod profiling;
 
 #[cfg(test)]
 mod test_utils;
@@ -13,6 +16,9 @@ pub use error::{Error, Result};
 pub use memory::*;
 #[cfg(feature = "memory-mapped-files-memory")]
 pub use memory_mapped_files::*;
+pub use migration::{HeapToStableMigration, MigrationProgress};
+#[cfg(feature = "profiling")]
+pub use profiling::*;
 pub use stable_structures::memory_manager::{
     MemoryId, MemoryManager as IcMemoryManager, VirtualMemory,
 };
@@ -12,6 +12,8 @@ pub enum MemMapError {
     SizeShouldBePageSizeMultiple,
     #[error("invalid source file name")]
     InvalidSourceFileName,
+    #[error("failed to sync changes to file: {0}")]
+    SyncFailed(std::io::Error),
 }
 
 pub type MemMapResult<T> = Result<T, MemMapError>;
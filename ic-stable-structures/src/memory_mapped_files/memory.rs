@@ -7,7 +7,7 @@ use dfinity_stable_structures::Memory;
 use parking_lot::{RwLock, RwLockReadGuard};
 
 use super::error::{MemMapError, MemMapResult};
-use super::memory_mapped_file::MemoryMappedFile;
+use super::memory_mapped_file::{MemoryMappedFile, SyncPolicy};
 use crate::memory::MemoryManager;
 
 const WASM_PAGE_SIZE_IN_BYTES: u64 = 65536;
@@ -26,6 +26,7 @@ pub struct MemoryMappedFileMemoryManager {
     is_persistent: bool,
     created_memory_resources: RwLock<BTreeMap<PathBuf, MemoryMappedFileMemory>>,
     max_memory_length: usize,
+    sync_policy: SyncPolicy,
 }
 
 impl MemoryMappedFileMemoryManager {
@@ -37,6 +38,7 @@ impl MemoryMappedFileMemoryManager {
             is_persistent,
             created_memory_resources: Default::default(),
             max_memory_length: DEFAULT_MEM_MAP_RESERVED_LENGTH,
+            sync_policy: SyncPolicy::default(),
         }
     }
 
@@ -48,6 +50,22 @@ impl MemoryMappedFileMemoryManager {
         self
     }
 
+    /// Set the policy used to sync the changes of each memory-mapped file to disk.
+    pub fn with_sync_policy(mut self, sync_policy: SyncPolicy) -> Self {
+        self.sync_policy = sync_policy;
+
+        self
+    }
+
+    /// Flush the changes of all the memory-mapped files to disk.
+    pub fn flush_all(&self) -> MemMapResult<()> {
+        for memory in self.created_memory_resources.read().values() {
+            memory.flush()?;
+        }
+
+        Ok(())
+    }
+
     /// Flush and save the memory-mapped files to the given path.
     /// Note that this function should be executed at the point when the stable storage state in consistent in order
     /// to save a consistent backup.
@@ -94,6 +112,7 @@ impl MemoryMappedFileMemoryManager {
                     )
                 });
 
+                result.set_sync_policy(self.sync_policy);
                 entry.insert(result.clone());
 
                 result
@@ -137,6 +156,30 @@ impl MemoryMappedFileMemory {
         self.0.write().set_is_persistent(is_persistent)
     }
 
+    /// Set the policy used to sync the changes to disk.
+    pub fn set_sync_policy(&self, sync_policy: SyncPolicy) {
+        self.0.write().set_sync_policy(sync_policy)
+    }
+
+    /// Flush all the changes to disk.
+    pub fn flush(&self) -> MemMapResult<()> {
+        self.0.read().flush()
+    }
+
+    /// Flush the changes in the given byte range to disk.
+    pub fn flush_range(&self, offset: u64, len: u64) -> MemMapResult<()> {
+        self.0.read().flush_range(offset as usize, len as usize)
+    }
+
+    /// Returns the error of the last failed sync triggered by the sync policy, if any.
+    /// `Memory::write` can't return errors, so they are stored until taken by this method.
+    pub fn take_sync_error(&self) -> Option<MemMapError> {
+        self.0
+            .write()
+            .take_sync_error()
+            .map(MemMapError::SyncFailed)
+    }
+
     pub(super) fn read_lock(&self) -> RwLockReadGuard<'_, MemoryMappedFile> {
         self.0.read()
     }
@@ -169,9 +212,10 @@ impl Memory for MemoryMappedFileMemory {
     }
 
     fn write(&self, offset: u64, src: &[u8]) {
-        self.0
-            .write()
-            .write(offset as usize, src)
-            .expect("invalid memory-mapped file write")
+        let mut memory = self.0.write();
+        match memory.write(offset as usize, src) {
+            Err(MemMapError::SyncFailed(err)) => memory.set_sync_error(err),
+            result => result.expect("invalid memory-mapped file write"),
+        }
     }
 }
@@ -1,5 +1,7 @@
 use std::fs::{copy, remove_file, File, OpenOptions};
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
 use memmap2::{MmapMut, MmapOptions};
 
@@ -10,6 +12,18 @@ use super::error::{MemMapError, MemMapResult};
 /// that we will benefit from using huge page size (2 MB or 1 GB)
 const PAGE_SIZE: usize = 4096;
 
+/// Defines when the changes written to a memory-mapped file are synced to disk.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SyncPolicy {
+    /// Changes are synced only by explicit flush calls and when the memory is dropped.
+    #[default]
+    Manual,
+    /// The written range is synced after every write.
+    OnWrite,
+    /// All the changes are synced on a write if the given time passed since the last sync.
+    Periodic(Duration),
+}
+
 /// Memory mapped file implementation.
 /// If `is_persistent` flag is true then after the
 /// structure is dropped all the changes are saved to file.
@@ -20,6 +34,11 @@ pub(super) struct MemoryMappedFile {
     max_length: usize,
     is_persistent: bool,
     mapping: MmapMut,
+    sync_policy: SyncPolicy,
+    last_sync: Instant,
+    /// True if some changes were written to the memory after the last full sync.
+    unsynced_changes: AtomicBool,
+    sync_error: Option<std::io::Error>,
 }
 
 impl MemoryMappedFile {
@@ -51,6 +70,10 @@ impl MemoryMappedFile {
             max_length,
             length,
             mapping,
+            sync_policy: SyncPolicy::default(),
+            last_sync: Instant::now(),
+            unsynced_changes: AtomicBool::new(false),
+            sync_error: None,
         })
     }
 
@@ -98,14 +121,32 @@ impl MemoryMappedFile {
     }
 
     /// Write data from `src` to the memory starting at `offset`.
+    /// The changes are synced to the file according to the sync policy.
+    /// If the sync fails, the data is written to the memory but `MemMapError::SyncFailed` is returned.
     pub fn write(&mut self, offset: usize, src: &[u8]) -> MemMapResult<()> {
         if offset + src.len() > self.len() {
             return Err(MemMapError::AccessOutOfBounds);
         }
 
         self.mapping[offset..offset + src.len()].copy_from_slice(src);
-
-        Ok(())
+        let had_unsynced_changes = self.unsynced_changes.swap(true, Ordering::Relaxed);
+
+        match self.sync_policy {
+            SyncPolicy::Manual => Ok(()),
+            SyncPolicy::OnWrite => {
+                self.flush_range(offset, src.len())?;
+                // Only the written range is synced, the previous changes are still pending
+                self.unsynced_changes
+                    .store(had_unsynced_changes, Ordering::Relaxed);
+                Ok(())
+            }
+            SyncPolicy::Periodic(interval) if self.last_sync.elapsed() >= interval => {
+                self.flush()?;
+                self.last_sync = Instant::now();
+                Ok(())
+            }
+            SyncPolicy::Periodic(_) => Ok(()),
+        }
     }
 
     /// Fill range with zeros.
@@ -115,17 +156,50 @@ impl MemoryMappedFile {
         }
 
         self.mapping[offset..(offset + count)].fill(0);
+        self.unsynced_changes.store(true, Ordering::Relaxed);
 
         Ok(())
     }
 
     /// Flush all the changes to the underlying file.
     pub fn flush(&self) -> MemMapResult<()> {
-        self.mapping.flush()?;
-
+        self.mapping.flush().map_err(MemMapError::SyncFailed)?;
+        self.unsynced_changes.store(false, Ordering::Relaxed);
         Ok(())
     }
 
+    /// Returns true if some changes were not synced to the file since the last full flush.
+    pub fn has_unsynced_changes(&self) -> bool {
+        self.unsynced_changes.load(Ordering::Relaxed)
+    }
+
+    /// Flush the changes in the given range to the underlying file.
+    pub fn flush_range(&self, offset: usize, len: usize) -> MemMapResult<()> {
+        if offset + len > self.length {
+            return Err(MemMapError::AccessOutOfBounds);
+        }
+
+        self.mapping
+            .flush_range(offset, len)
+            .map_err(MemMapError::SyncFailed)
+    }
+
+    /// Set the policy used to sync the changes to the file.
+    pub fn set_sync_policy(&mut self, sync_policy: SyncPolicy) {
+        self.sync_policy = sync_policy;
+        self.last_sync = Instant::now();
+    }
+
+    /// Store the error of a sync that couldn't be reported to the caller.
+    pub fn set_sync_error(&mut self, error: std::io::Error) {
+        self.sync_error = Some(error);
+    }
+
+    /// Take the last error of a sync that couldn't be reported to the caller.
+    pub fn take_sync_error(&mut self) -> Option<std::io::Error> {
+        self.sync_error.take()
+    }
+
     /// Save the copy to a file at the specified path.
     pub fn save_copy(&self, path: impl AsRef<Path>) -> MemMapResult<()> {
         self.flush()?;
@@ -336,6 +410,93 @@ mod tests {
         });
     }
 
+    #[test]
+    fn should_flush_range() {
+        with_temp_file(|path| {
+            let mut file_memory =
+                MemoryMappedFile::new(path.clone(), DEFAULT_MAX_LENGTH, true).unwrap();
+            file_memory.resize(PAGE_SIZE).unwrap();
+
+            let mut data = create_data();
+            file_memory.write(0, &data).unwrap();
+
+            file_memory.flush_range(0, PAGE_SIZE).unwrap();
+            assert!(matches!(
+                file_memory.flush_range(1, PAGE_SIZE),
+                Err(MemMapError::AccessOutOfBounds)
+            ));
+
+            let mut file = File::open(&path).unwrap();
+            data.fill(0);
+            file.read_exact(&mut data).unwrap();
+            check_data(&data);
+        });
+    }
+
+    #[test]
+    fn should_sync_according_to_policy() {
+        // (policy, unsynced changes expected after every write)
+        for (policy, expect_unsynced) in [
+            (SyncPolicy::Manual, true),
+            (SyncPolicy::OnWrite, false),
+            (SyncPolicy::Periodic(Duration::ZERO), false),
+            (SyncPolicy::Periodic(Duration::from_secs(3600)), true),
+        ] {
+            with_temp_file(|path| {
+                let mut file_memory =
+                    MemoryMappedFile::new(path.clone(), DEFAULT_MAX_LENGTH, true).unwrap();
+                file_memory.set_sync_policy(policy);
+                file_memory.resize(PAGE_SIZE).unwrap();
+                assert!(!file_memory.has_unsynced_changes());
+
+                for offset in 0..3 {
+                    file_memory.write(offset * 8, &[1; 8]).unwrap();
+                    assert_eq!(
+                        file_memory.has_unsynced_changes(),
+                        expect_unsynced,
+                        "policy: {policy:?}, write: {offset}"
+                    );
+                }
+
+                file_memory.flush().unwrap();
+                assert!(!file_memory.has_unsynced_changes());
+            });
+        }
+    }
+
+    #[test]
+    fn on_write_policy_should_not_sync_other_changes() {
+        with_temp_file(|path| {
+            let mut file_memory =
+                MemoryMappedFile::new(path.clone(), DEFAULT_MAX_LENGTH, true).unwrap();
+            file_memory.resize(PAGE_SIZE).unwrap();
+            file_memory.zero_range(0, 16).unwrap();
+
+            file_memory.set_sync_policy(SyncPolicy::OnWrite);
+            file_memory.write(32, &[1; 8]).unwrap();
+            assert!(file_memory.has_unsynced_changes());
+        });
+    }
+
+    #[test]
+    fn should_sync_on_drop_if_persistent() {
+        with_temp_file(|path| {
+            {
+                let mut file_memory =
+                    MemoryMappedFile::new(path.clone(), DEFAULT_MAX_LENGTH, true).unwrap();
+                file_memory.resize(PAGE_SIZE).unwrap();
+                file_memory.write(0, &create_data()).unwrap();
+                assert!(file_memory.has_unsynced_changes());
+            }
+
+            let file_memory = MemoryMappedFile::new(path, DEFAULT_MAX_LENGTH, true).unwrap();
+            assert!(!file_memory.has_unsynced_changes());
+            let mut data = vec![0; PAGE_SIZE];
+            file_memory.read(0, &mut data).unwrap();
+            check_data(&data);
+        });
+    }
+
     #[test]
     fn should_copy_file() {
         with_temp_file(|path| {
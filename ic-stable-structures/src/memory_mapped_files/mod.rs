@@ -2,4 +2,6 @@ mod error;
 mod memory;
 mod memory_mapped_file;
 
+pub use error::{MemMapError, MemMapResult};
 pub use memory::{MemoryMappedFileMemory, MemoryMappedFileMemoryManager};
+pub use memory_mapped_file::SyncPolicy;
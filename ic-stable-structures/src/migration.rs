@@ -0,0 +1,178 @@
+use candid::CandidType;
+use ic_storage::stable::{self, Versioned};
+
+use crate::Result;
+
+/// Progress of a heap-to-stable migration.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, CandidType)]
+pub struct MigrationProgress {
+    /// Number of migrated items.
+    pub migrated: u64,
+    /// Total number of items to migrate.
+    pub total: u64,
+}
+
+impl MigrationProgress {
+    /// Returns true if all the items are migrated.
+    pub fn is_complete(&self) -> bool {
+        self.migrated >= self.total
+    }
+}
+
+/// Moves items of a heap state into stable structures in batches,
+/// so the migration of a big state can be split across several calls
+/// (e.g. `post_upgrade` and a series of timers) to stay within the instruction limits.
+///
+/// The items are kept in the heap until they are migrated, so the migration must be
+/// completed before the next upgrade.
+pub struct HeapToStableMigration<T> {
+    items: std::vec::IntoIter<T>,
+    progress: MigrationProgress,
+}
+
+impl<T> HeapToStableMigration<T> {
+    /// Creates a migration of the given items.
+    pub fn new(items: Vec<T>) -> Self {
+        let total = items.len() as u64;
+        Self {
+            items: items.into_iter(),
+            progress: MigrationProgress { migrated: 0, total },
+        }
+    }
+
+    /// Reads the state stored with `ic_storage::stable::write`, upgrading it if needed,
+    /// and splits it into items with the `into_items` function.
+    ///
+    /// This must be called before any stable structure is initialized, since the memory manager
+    /// overwrites the stable memory with its own layout.
+    pub fn from_stable_state<S: Versioned>(into_items: impl FnOnce(S) -> Vec<T>) -> Result<Self> {
+        let state = stable::read::<S>()?;
+        Ok(Self::new(into_items(state)))
+    }
+
+    /// Migrates at most `batch_size` items using the `migrate` function.
+    ///
+    /// If `migrate` fails, the error is returned and the failed item is considered migrated.
+    pub fn migrate_batch(
+        &mut self,
+        batch_size: usize,
+        mut migrate: impl FnMut(T) -> Result<()>,
+    ) -> Result<MigrationProgress> {
+        for item in self.items.by_ref().take(batch_size) {
+            self.progress.migrated += 1;
+            migrate(item)?;
+        }
+
+        Ok(self.progress)
+    }
+
+    /// Returns the progress of the migration.
+    pub fn progress(&self) -> MigrationProgress {
+        self.progress
+    }
+
+    /// Returns true if all the items are migrated.
+    pub fn is_complete(&self) -> bool {
+        self.progress.is_complete()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use dfinity_stable_structures::VectorMemory;
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::{BTreeMapStructure, Error, StableBTreeMap};
+
+    #[derive(Debug, Default, CandidType, Deserialize)]
+    struct HeapState {
+        balances: HashMap<u64, u64>,
+    }
+
+    impl Versioned for HeapState {
+        type Previous = ();
+
+        fn upgrade((): ()) -> Self {
+            Self::default()
+        }
+    }
+
+    #[test]
+    fn should_migrate_in_batches() {
+        let mut map = StableBTreeMap::new(VectorMemory::default());
+        let mut migration = HeapToStableMigration::new((0..10u64).map(|i| (i, i * 2)).collect());
+
+        assert_eq!(
+            migration.progress(),
+            MigrationProgress {
+                migrated: 0,
+                total: 10
+            }
+        );
+
+        let progress = migration
+            .migrate_batch(4, |(key, value)| {
+                map.insert(key, value);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(progress.migrated, 4);
+        assert!(!progress.is_complete());
+        assert_eq!(map.len(), 4);
+
+        while !migration.is_complete() {
+            migration
+                .migrate_batch(4, |(key, value)| {
+                    map.insert(key, value);
+                    Ok(())
+                })
+                .unwrap();
+        }
+
+        assert_eq!(migration.progress().migrated, 10);
+        assert_eq!(map.len(), 10);
+        assert_eq!(map.get(&9), Some(18));
+    }
+
+    #[test]
+    fn should_return_migration_error() {
+        let mut migration = HeapToStableMigration::new(vec![1u64, 2, 3]);
+
+        let result = migration.migrate_batch(3, |item| match item {
+            2 => Err(Error::OutOfStableMemory),
+            _ => Ok(()),
+        });
+
+        assert!(matches!(result, Err(Error::OutOfStableMemory)));
+        assert_eq!(migration.progress().migrated, 2);
+    }
+
+    #[test]
+    fn should_read_stable_state() {
+        let state = HeapState {
+            balances: HashMap::from([(1, 10), (2, 20)]),
+        };
+        ic_storage::stable::write(&state).unwrap();
+
+        let mut migration = HeapToStableMigration::from_stable_state(|state: HeapState| {
+            state.balances.into_iter().collect()
+        })
+        .unwrap();
+        assert_eq!(migration.progress().total, 2);
+
+        let mut map = StableBTreeMap::new(VectorMemory::default());
+        migration
+            .migrate_batch(10, |(key, value)| {
+                map.insert(key, value);
+                Ok(())
+            })
+            .unwrap();
+
+        assert!(migration.is_complete());
+        assert_eq!(map.get(&1), Some(10));
+        assert_eq!(map.get(&2), Some(20));
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use candid::CandidType;
+use dfinity_stable_structures::Memory;
+use parking_lot::RwLock;
+
+use crate::memory::MemoryManager;
+
+/// Access statistics of a single memory.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, CandidType)]
+pub struct MemoryStats {
+    /// Number of read calls.
+    pub reads: u64,
+    /// Number of write calls.
+    pub writes: u64,
+    /// Total number of bytes read.
+    pub bytes_read: u64,
+    /// Total number of bytes written.
+    pub bytes_written: u64,
+    /// Number of grow calls.
+    pub grows: u64,
+    /// Total number of wasm pages requested by the grow calls.
+    pub pages_grown: u64,
+}
+
+#[derive(Default)]
+struct MemoryCounters {
+    reads: AtomicU64,
+    writes: AtomicU64,
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+    grows: AtomicU64,
+    pages_grown: AtomicU64,
+}
+
+impl MemoryCounters {
+    fn stats(&self) -> MemoryStats {
+        MemoryStats {
+            reads: self.reads.load(Ordering::Relaxed),
+            writes: self.writes.load(Ordering::Relaxed),
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+            grows: self.grows.load(Ordering::Relaxed),
+            pages_grown: self.pages_grown.load(Ordering::Relaxed),
+        }
+    }
+
+    fn reset(&self) {
+        for counter in [
+            &self.reads,
+            &self.writes,
+            &self.bytes_read,
+            &self.bytes_written,
+            &self.grows,
+            &self.pages_grown,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Memory wrapper that counts accesses to the inner memory.
+#[derive(Clone)]
+pub struct ProfiledMemory<M: Memory> {
+    inner: M,
+    counters: Arc<MemoryCounters>,
+}
+
+impl<M: Memory> ProfiledMemory<M> {
+    /// Wraps the memory with new zeroed counters.
+    pub fn new(inner: M) -> Self {
+        Self {
+            inner,
+            counters: Default::default(),
+        }
+    }
+
+    /// Returns the access statistics of the memory.
+    pub fn stats(&self) -> MemoryStats {
+        self.counters.stats()
+    }
+
+    /// Resets the access statistics of the memory.
+    pub fn reset_stats(&self) {
+        self.counters.reset()
+    }
+}
+
+impl<M: Memory> Memory for ProfiledMemory<M> {
+    fn size(&self) -> u64 {
+        self.inner.size()
+    }
+
+    fn grow(&self, pages: u64) -> i64 {
+        self.counters.grows.fetch_add(1, Ordering::Relaxed);
+        self.counters
+            .pages_grown
+            .fetch_add(pages, Ordering::Relaxed);
+        self.inner.grow(pages)
+    }
+
+    fn read(&self, offset: u64, dst: &mut [u8]) {
+        self.counters.reads.fetch_add(1, Ordering::Relaxed);
+        self.counters
+            .bytes_read
+            .fetch_add(dst.len() as u64, Ordering::Relaxed);
+        self.inner.read(offset, dst)
+    }
+
+    fn write(&self, offset: u64, src: &[u8]) {
+        self.counters.writes.fetch_add(1, Ordering::Relaxed);
+        self.counters
+            .bytes_written
+            .fetch_add(src.len() as u64, Ordering::Relaxed);
+        self.inner.write(offset, src)
+    }
+}
+
+/// Memory manager that wraps every returned memory with a [`ProfiledMemory`]
+/// and collects access statistics per memory id.
+pub struct ProfilingMemoryManager<Manager> {
+    inner: Manager,
+    counters: RwLock<BTreeMap<u8, Arc<MemoryCounters>>>,
+}
+
+impl<Manager> ProfilingMemoryManager<Manager> {
+    /// Wraps the memory manager.
+    pub fn new(inner: Manager) -> Self {
+        Self {
+            inner,
+            counters: Default::default(),
+        }
+    }
+
+    /// Returns the access statistics of the memory with the given id.
+    pub fn stats(&self, id: u8) -> Option<MemoryStats> {
+        self.counters
+            .read()
+            .get(&id)
+            .map(|counters| counters.stats())
+    }
+
+    /// Returns the access statistics of all the memories created by the manager.
+    pub fn all_stats(&self) -> BTreeMap<u8, MemoryStats> {
+        self.counters
+            .read()
+            .iter()
+            .map(|(id, counters)| (*id, counters.stats()))
+            .collect()
+    }
+
+    /// Returns the access statistics summed over all the memories.
+    pub fn total_stats(&self) -> MemoryStats {
+        self.all_stats()
+            .into_values()
+            .fold(MemoryStats::default(), |total, stats| MemoryStats {
+                reads: total.reads + stats.reads,
+                writes: total.writes + stats.writes,
+                bytes_read: total.bytes_read + stats.bytes_read,
+                bytes_written: total.bytes_written + stats.bytes_written,
+                grows: total.grows + stats.grows,
+                pages_grown: total.pages_grown + stats.pages_grown,
+            })
+    }
+
+    /// Resets the access statistics of all the memories.
+    pub fn reset_stats(&self) {
+        self.counters
+            .read()
+            .values()
+            .for_each(|counters| counters.reset());
+    }
+}
+
+impl<M, Manager> MemoryManager<ProfiledMemory<M>, u8> for ProfilingMemoryManager<Manager>
+where
+    M: Memory,
+    Manager: MemoryManager<M, u8>,
+{
+    fn get(&self, id: u8) -> ProfiledMemory<M> {
+        let counters = self.counters.write().entry(id).or_default().clone();
+        ProfiledMemory {
+            inner: self.inner.get(id),
+            counters,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dfinity_stable_structures::VectorMemory;
+
+    use super::*;
+    use crate::{BTreeMapStructure, IcMemoryManager, StableBTreeMap};
+
+    #[test]
+    fn should_count_memory_accesses() {
+        let memory = ProfiledMemory::new(VectorMemory::default());
+
+        memory.grow(2);
+        memory.write(0, &[1, 2, 3]);
+        let mut buf = [0; 2];
+        memory.read(1, &mut buf);
+
+        assert_eq!(
+            memory.stats(),
+            MemoryStats {
+                reads: 1,
+                writes: 1,
+                bytes_read: 2,
+                bytes_written: 3,
+                grows: 1,
+                pages_grown: 2,
+            }
+        );
+
+        memory.reset_stats();
+        assert_eq!(memory.stats(), MemoryStats::default());
+    }
+
+    #[test]
+    fn should_collect_stats_per_memory_id() {
+        let memory_manager =
+            ProfilingMemoryManager::new(IcMemoryManager::init(VectorMemory::default()));
+
+        let mut first = StableBTreeMap::<u32, u32, _>::new(memory_manager.get(0));
+        let mut second = StableBTreeMap::<u32, u32, _>::new(memory_manager.get(1));
+        first.insert(1, 1);
+        second.insert(1, 1);
+
+        memory_manager.reset_stats();
+        first.insert(2, 2);
+        assert_eq!(second.get(&1), Some(1));
+
+        let first_stats = memory_manager.stats(0).unwrap();
+        assert!(first_stats.writes > 0);
+        assert!(first_stats.bytes_written > 0);
+
+        let second_stats = memory_manager.stats(1).unwrap();
+        assert_eq!(second_stats.writes, 0);
+        assert!(second_stats.reads > 0);
+
+        assert!(memory_manager.stats(2).is_none());
+        assert_eq!(memory_manager.all_stats().len(), 2);
+        assert_eq!(
+            memory_manager.total_stats().writes,
+            first_stats.writes + second_stats.writes
+        );
+    }
+}
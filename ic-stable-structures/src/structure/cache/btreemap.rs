@@ -34,10 +34,60 @@ where
         }
     }
 
+    /// Create new instance of the CachedUnboundedMap with a fixed number of max cached elements
+    /// and a limit on the total size in bytes of the cached entries.
+    /// The size of an entry is the length of the serialized key and value.
+    pub fn with_map_and_max_bytes(
+        inner: StableBTreeMap<K, V, M>,
+        max_cache_items: u32,
+        max_cache_bytes: usize,
+    ) -> Self {
+        Self {
+            inner,
+            cache: SyncLruCache::with_max_bytes(
+                max_cache_items,
+                max_cache_bytes,
+                storable_entry_size::<K, V>,
+            ),
+        }
+    }
+
     /// Returns the inner collection so that the caller can have a readonly access to it that bypasses the cache.
     pub fn inner(&self) -> &StableBTreeMap<K, V, M> {
         &self.inner
     }
+
+    /// Iterate over the key-value pairs whose key bytes start with the `prefix`. See
+    /// [`StableBTreeMap::iter_prefix`] for the supported keys.
+    ///
+    /// WARN: this bypasses the cache
+    pub fn iter_prefix(&self, prefix: &[u8]) -> impl Iterator<Item = (K, V)> + '_ {
+        self.inner.iter_prefix(prefix)
+    }
+
+    /// Pins the key in the cache, so its value is never evicted.
+    pub fn pin(&self, key: K) {
+        self.cache.pin(key)
+    }
+
+    /// Unpins the key, making its value evictable again.
+    pub fn unpin(&self, key: &K) {
+        self.cache.unpin(key)
+    }
+
+    /// Returns the cache hit/miss counters and size.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    /// Resets the cache hit/miss counters.
+    pub fn reset_cache_stats(&self) {
+        self.cache.reset_stats()
+    }
+}
+
+fn storable_entry_size<K: Storable, V: Storable>(key: &K, value: &V) -> usize {
+    key.to_bytes().len() + value.to_bytes().len()
 }
 
 impl<K, V, M> BTreeMapStructure<K, V> for CachedStableBTreeMap<K, V, M>
@@ -89,6 +139,31 @@ where
     fn last_key_value(&self) -> Option<(K, V)> {
         self.inner.last_key_value()
     }
+
+    fn pop_first(&mut self) -> Option<(K, V)> {
+        let (key, value) = self.inner.pop_first()?;
+        self.cache.remove(&key);
+        Some((key, value))
+    }
+
+    fn pop_last(&mut self) -> Option<(K, V)> {
+        let (key, value) = self.inner.pop_last()?;
+        self.cache.remove(&key);
+        Some((key, value))
+    }
+
+    fn remove_range(&mut self, key_range: impl RangeBounds<K>, limit: usize) -> RangeRemoval<K> {
+        let keys = self
+            .inner
+            .range(key_range)
+            .map(|(key, _)| key)
+            .take(limit.saturating_add(1))
+            .collect();
+
+        RangeRemoval::remove_keys(keys, limit, |key| {
+            self.remove(key);
+        })
+    }
 }
 
 /// NOTE: we can't implement this trait for a heap inner map because
@@ -293,6 +368,49 @@ mod tests {
         assert_eq!(iter.next(), None);
     }
 
+    #[test]
+    fn should_remove_range() {
+        let cache_items = 10;
+        let mut map =
+            CachedStableBTreeMap::<u32, u32, _>::new(VectorMemory::default(), cache_items);
+
+        for i in 0..10 {
+            map.insert(i, i);
+        }
+
+        let removal = map.remove_range(..5, 3);
+        assert_eq!(removal.removed, 3);
+        assert_eq!(removal.cursor, Some(3));
+        assert_eq!(None, map.get(&0));
+        assert_eq!(None, map.get(&2));
+        assert_eq!(Some(3), map.get(&3));
+
+        let removal = map.remove_range(3..5, 3);
+        assert_eq!(removal.removed, 2);
+        assert_eq!(removal.cursor, None);
+        assert_eq!(None, map.get(&3));
+        assert_eq!(5, map.len());
+    }
+
+    #[test]
+    fn test_pop_first_and_last() {
+        let cache_items = 10;
+        let mut map =
+            CachedStableBTreeMap::<u32, u32, _>::new(VectorMemory::default(), cache_items);
+        assert!(map.pop_first().is_none());
+
+        for i in 0..5 {
+            map.insert(i, i * 10);
+        }
+
+        assert_eq!(map.pop_first(), Some((0, 0)));
+        assert_eq!(map.pop_last(), Some((4, 40)));
+        assert_eq!(map.len(), 3);
+
+        assert_eq!(None, map.get(&0));
+        assert!(!map.contains_key(&4));
+    }
+
     #[test]
     fn test_last_key_value() {
         let cache_items = 2;
@@ -315,6 +433,52 @@ mod tests {
         assert_eq!(map.last_key_value(), Some((5, 100)));
     }
 
+    #[test]
+    fn should_limit_cache_by_bytes() {
+        let mut map = CachedStableBTreeMap::<u32, Array<2>, _>::with_map_and_max_bytes(
+            StableBTreeMap::new(VectorMemory::default()),
+            100,
+            12,
+        );
+
+        map.insert(1, Array([1u8, 1]));
+        map.insert(2, Array([2u8, 1]));
+        map.insert(3, Array([3u8, 1]));
+
+        let stats = map.cache_stats();
+        assert_eq!(stats.len, 2);
+        assert_eq!(stats.bytes, 12);
+
+        assert_eq!(Some(Array([1u8, 1])), map.get(&1));
+        let stats = map.cache_stats();
+        assert_eq!(stats.misses, 1);
+        assert_eq!(stats.hits, 0);
+
+        assert_eq!(Some(Array([1u8, 1])), map.get(&1));
+        assert_eq!(map.cache_stats().hits, 1);
+
+        map.reset_cache_stats();
+        assert_eq!(map.cache_stats().hits, 0);
+    }
+
+    #[test]
+    fn should_keep_pinned_keys_in_cache() {
+        let mut map = CachedStableBTreeMap::<u32, Array<2>, _>::new(VectorMemory::default(), 1);
+
+        map.pin(1);
+        map.insert(1, Array([1u8, 1]));
+        map.insert(2, Array([2u8, 1]));
+        map.insert(3, Array([3u8, 1]));
+
+        assert!(map.cache.contains_key(&1));
+        assert_eq!(Some(Array([1u8, 1])), map.get(&1));
+
+        map.unpin(&1);
+        map.insert(4, Array([4u8, 1]));
+        assert!(!map.cache.contains_key(&1));
+        assert_eq!(Some(Array([1u8, 1])), map.get(&1));
+    }
+
     #[test]
     fn should_get_and_insert_from_existing_map() {
         let cache_items = 2;
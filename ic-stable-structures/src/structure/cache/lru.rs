@@ -1,13 +1,106 @@
+use std::collections::HashMap;
 use std::convert::Infallible;
 use std::hash::Hash;
 
+use candid::CandidType;
 use parking_lot::Mutex;
-use schnellru::{ByLength, LruMap};
+use schnellru::{Limiter, LruMap};
+
+/// Function that returns the size in bytes of a cached entry.
+pub type EntryWeigher<K, V> = fn(&K, &V) -> usize;
+
+/// Cache statistics that can be queried by the canister to tune the cache limits.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, CandidType)]
+pub struct CacheStats {
+    /// Number of `get` calls that found the value in the cache.
+    pub hits: u64,
+    /// Number of `get` calls that didn't find the value in the cache.
+    pub misses: u64,
+    /// Number of entries currently held by the cache, including pinned ones.
+    pub len: u64,
+    /// Number of pinned keys.
+    pub pinned: u64,
+    /// Total size in bytes of the evictable entries, as reported by the weigher.
+    pub bytes: u64,
+}
+
+/// Limits the LRU map both by number of entries and by total size of the entries.
+struct CacheLimiter<K, V> {
+    max_items: u32,
+    max_bytes: Option<usize>,
+    bytes: usize,
+    weigher: EntryWeigher<K, V>,
+}
+
+impl<K, V> CacheLimiter<K, V> {
+    fn exceeds_max_bytes(&self, bytes: usize) -> bool {
+        self.max_bytes.is_some_and(|max_bytes| bytes > max_bytes)
+    }
+}
+
+impl<K, V> Limiter<K, V> for CacheLimiter<K, V> {
+    type KeyToInsert<'a> = K;
+    type LinkType = u32;
+
+    fn is_over_the_limit(&self, length: usize) -> bool {
+        length > self.max_items as usize || self.exceeds_max_bytes(self.bytes)
+    }
+
+    fn on_insert(&mut self, _length: usize, key: K, value: V) -> Option<(K, V)> {
+        let size = (self.weigher)(&key, &value);
+        if self.max_items == 0 || self.exceeds_max_bytes(size) {
+            return None;
+        }
+        self.bytes += size;
+        Some((key, value))
+    }
+
+    fn on_replace(
+        &mut self,
+        _length: usize,
+        old_key: &mut K,
+        _new_key: K,
+        old_value: &mut V,
+        new_value: &mut V,
+    ) -> bool {
+        let new_size = (self.weigher)(old_key, new_value);
+        if self.exceeds_max_bytes(new_size) {
+            // The old entry is removed by the map and accounted in `on_removed`.
+            return false;
+        }
+        let old_size = (self.weigher)(old_key, old_value);
+        self.bytes = self.bytes.saturating_sub(old_size) + new_size;
+        true
+    }
+
+    fn on_removed(&mut self, key: &mut K, value: &mut V) {
+        self.bytes = self.bytes.saturating_sub((self.weigher)(key, value));
+    }
+
+    fn on_cleared(&mut self) {
+        self.bytes = 0;
+    }
+
+    fn on_grow(&mut self, _new_memory_usage: usize) -> bool {
+        true
+    }
+}
+
+struct CacheInner<K, V> {
+    lru: LruMap<K, V, CacheLimiter<K, V>>,
+    /// Pinned keys are never evicted. The value is `None` until it is loaded.
+    pinned: HashMap<K, Option<V>>,
+    hits: u64,
+    misses: u64,
+}
 
 /// A wrapper around `LruCache`. This struct is thread safe, doesn't return any references to any
 /// elements inside.
+///
+/// The cache is bounded by the number of entries and, optionally, by the total size in bytes of
+/// the entries. Pinned keys are kept outside of the LRU list and don't count against the limits.
 pub struct SyncLruCache<K, V> {
-    inner: Mutex<LruMap<K, V>>,
+    inner: Mutex<CacheInner<K, V>>,
 }
 
 impl<K, V> SyncLruCache<K, V>
@@ -17,20 +110,51 @@ where
 {
     /// Creats a new `LRU` cache that holds at most `cap` items.
     pub fn new(cap: u32) -> Self {
+        Self::with_limiter(CacheLimiter {
+            max_items: cap,
+            max_bytes: None,
+            bytes: 0,
+            weigher: |_, _| 0,
+        })
+    }
+
+    /// Creates a new `LRU` cache that holds at most `cap` items with total size of at most
+    /// `max_bytes`. The size of every entry is computed by the `weigher` function.
+    pub fn with_max_bytes(cap: u32, max_bytes: usize, weigher: EntryWeigher<K, V>) -> Self {
+        Self::with_limiter(CacheLimiter {
+            max_items: cap,
+            max_bytes: Some(max_bytes),
+            bytes: 0,
+            weigher,
+        })
+    }
+
+    fn with_limiter(limiter: CacheLimiter<K, V>) -> Self {
         Self {
-            // Creating an inner LruMap with a fixed hasher
-            inner: Mutex::new(LruMap::<K, V>::with_seed(ByLength::new(cap), [0, 1, 3, 4])),
+            inner: Mutex::new(CacheInner {
+                // Creating an inner LruMap with a fixed hasher
+                lru: LruMap::with_seed(limiter, [0, 1, 3, 4]),
+                pinned: HashMap::new(),
+                hits: 0,
+                misses: 0,
+            }),
         }
     }
 
     /// Returns the number of key-value pairs that are currently in the the cache.
     pub fn len(&self) -> usize {
-        self.inner.lock().len()
+        let inner = self.inner.lock();
+        inner.lru.len() + inner.pinned.values().filter(|v| v.is_some()).count()
     }
 
     /// Returns true if the cache is empty and false otherwise.
     pub fn is_empty(&self) -> bool {
-        self.inner.lock().is_empty()
+        self.len() == 0
+    }
+
+    /// Returns the total size in bytes of the evictable entries.
+    pub fn size_in_bytes(&self) -> usize {
+        self.inner.lock().lru.limiter().bytes
     }
 
     /// Return the value of they key in the cache otherwise computes the value and inserts it into
@@ -63,7 +187,7 @@ where
         let val = f(key)?;
         if let Some(val) = val.as_ref() {
             let val_clone = val.clone();
-            self.inner.lock().insert(key.clone(), val_clone);
+            self.insert(key.clone(), val_clone);
         }
         Ok(val)
     }
@@ -71,29 +195,103 @@ where
     /// Puts a key-value pair into cache. If the key already exists in the cache,
     /// then it updates the key's value.
     pub fn insert(&self, key: K, value: V) {
-        self.inner.lock().insert(key, value);
+        let mut inner = self.inner.lock();
+        if let Some(pinned) = inner.pinned.get_mut(&key) {
+            *pinned = Some(value);
+            return;
+        }
+        inner.lru.insert(key, value);
     }
 
     /// Returns whether the key is in the cache
     pub fn contains_key(&self, key: &K) -> bool {
-        self.inner.lock().get(key).is_some()
+        let mut guard = self.inner.lock();
+        let inner = &mut *guard;
+        if let Some(pinned) = inner.pinned.get(key) {
+            return pinned.is_some();
+        }
+        inner.lru.get(key).is_some()
     }
 
     /// Returns the value of the key in the cache or None if it is not present in the cache.
     /// Moves the key to the head of the LRU list if it exists.
     pub fn get(&self, key: &K) -> Option<V> {
-        self.inner.lock().get(key).cloned()
+        let mut guard = self.inner.lock();
+        let inner = &mut *guard;
+        let value = match inner.pinned.get(key) {
+            Some(pinned) => pinned.clone(),
+            None => inner.lru.get(key).cloned(),
+        };
+
+        if value.is_some() {
+            inner.hits += 1;
+        } else {
+            inner.misses += 1;
+        }
+
+        value
     }
 
     /// Removes an element from the cache.
+    /// If the key is pinned, it stays pinned.
     pub fn remove(&self, key: &K) -> Option<V> {
-        self.inner.lock().remove(key)
+        let mut inner = self.inner.lock();
+        if let Some(pinned) = inner.pinned.get_mut(key) {
+            return pinned.take();
+        }
+        inner.lru.remove(key)
     }
 
-    /// Puts a key-value pair into cache. If the key already exists in the cache,
-    /// then it updates the key's value.
+    /// Removes all the elements from the cache.
+    /// Pinned keys stay pinned.
     pub fn clear(&self) {
-        self.inner.lock().clear()
+        let mut inner = self.inner.lock();
+        inner.lru.clear();
+        inner.pinned.values_mut().for_each(|value| *value = None);
+    }
+
+    /// Pins the key, so its value is never evicted from the cache.
+    /// If the value is already cached, it is moved out of the LRU list.
+    pub fn pin(&self, key: K) {
+        let mut inner = self.inner.lock();
+        if inner.pinned.contains_key(&key) {
+            return;
+        }
+        let value = inner.lru.remove(&key);
+        inner.pinned.insert(key, value);
+    }
+
+    /// Unpins the key. Its value, if cached, is moved back to the LRU list.
+    pub fn unpin(&self, key: &K) {
+        let mut inner = self.inner.lock();
+        if let Some(Some(value)) = inner.pinned.remove(key) {
+            inner.lru.insert(key.clone(), value);
+        }
+    }
+
+    /// Returns whether the key is pinned.
+    pub fn is_pinned(&self, key: &K) -> bool {
+        self.inner.lock().pinned.contains_key(key)
+    }
+
+    /// Returns the cache statistics.
+    pub fn stats(&self) -> CacheStats {
+        let inner = self.inner.lock();
+        let pinned_values = inner.pinned.values().filter(|v| v.is_some()).count();
+        CacheStats {
+            hits: inner.hits,
+            misses: inner.misses,
+            len: (inner.lru.len() + pinned_values) as u64,
+            pinned: inner.pinned.len() as u64,
+            bytes: inner.lru.limiter().bytes as u64,
+        }
+    }
+
+    /// Resets the hit and miss counters.
+    pub fn reset_stats(&self) {
+        let mut inner = self.inner.lock();
+        inner.hits = 0;
+        inner.misses = 0;
     }
 }
 
@@ -117,4 +315,82 @@ mod tests {
         assert_eq!(cache.get(&0u64), None);
         assert!(!cache.contains_key(&0u64));
     }
+
+    #[test]
+    fn test_cache_bounded_by_bytes() {
+        let cache = SyncLruCache::<u64, Vec<u8>>::with_max_bytes(100, 10, |_, value| value.len());
+
+        cache.insert(1, vec![0; 4]);
+        cache.insert(2, vec![0; 4]);
+        assert_eq!(cache.size_in_bytes(), 8);
+        assert_eq!(cache.len(), 2);
+
+        // Evicts the least recently used entry to fit the new one.
+        cache.insert(3, vec![0; 4]);
+        assert_eq!(cache.size_in_bytes(), 8);
+        assert!(!cache.contains_key(&1));
+        assert!(cache.contains_key(&2));
+        assert!(cache.contains_key(&3));
+
+        // Entries larger than the limit are never cached.
+        cache.insert(4, vec![0; 11]);
+        assert!(!cache.contains_key(&4));
+
+        cache.remove(&2);
+        assert_eq!(cache.size_in_bytes(), 4);
+
+        cache.clear();
+        assert_eq!(cache.size_in_bytes(), 0);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_pinned_keys_are_not_evicted() {
+        let cache = SyncLruCache::<u64, u64>::new(2);
+
+        cache.insert(1, 10);
+        cache.pin(1);
+        cache.pin(2);
+        assert!(cache.is_pinned(&1));
+        assert!(!cache.contains_key(&2));
+
+        for i in 3..10 {
+            cache.insert(i, i * 10);
+        }
+        cache.insert(2, 20);
+
+        assert_eq!(cache.get(&1), Some(10));
+        assert_eq!(cache.get(&2), Some(20));
+        assert_eq!(cache.len(), 4);
+
+        cache.unpin(&1);
+        assert!(!cache.is_pinned(&1));
+        assert_eq!(cache.get(&1), Some(10));
+
+        cache.clear();
+        assert!(cache.is_pinned(&2));
+        assert_eq!(cache.get(&2), None);
+    }
+
+    #[test]
+    fn test_cache_stats() {
+        let cache = SyncLruCache::<u64, u64>::new(10);
+
+        assert_eq!(cache.get(&1), None);
+        cache.insert(1, 10);
+        assert_eq!(cache.get(&1), Some(10));
+        assert_eq!(cache.get(&1), Some(10));
+        cache.pin(2);
+
+        let stats = cache.stats();
+        assert_eq!(stats.hits, 2);
+        assert_eq!(stats.misses, 1);
+        assert_eq!(stats.len, 1);
+        assert_eq!(stats.pinned, 1);
+
+        cache.reset_stats();
+        let stats = cache.stats();
+        assert_eq!(stats.hits, 0);
+        assert_eq!(stats.misses, 0);
+    }
 }
@@ -3,5 +3,5 @@ pub mod lru;
 pub mod multimap;
 
 pub use btreemap::CachedStableBTreeMap;
-pub use lru::SyncLruCache;
+pub use lru::{CacheStats, EntryWeigher, SyncLruCache};
 pub use multimap::CachedStableMultimap;
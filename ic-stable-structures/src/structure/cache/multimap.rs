@@ -36,10 +36,55 @@ where
         }
     }
 
+    /// Create new instance of the CachedStableMultimap with a fixed number of max cached elements
+    /// and a limit on the total size in bytes of the cached entries.
+    /// The size of an entry is the length of the serialized keys and value.
+    pub fn with_map_and_max_bytes(
+        inner: StableMultimap<K1, K2, V, M>,
+        max_cache_items: u32,
+        max_cache_bytes: usize,
+    ) -> Self {
+        Self {
+            inner,
+            cache: SyncLruCache::with_max_bytes(
+                max_cache_items,
+                max_cache_bytes,
+                storable_entry_size::<K1, K2, V>,
+            ),
+        }
+    }
+
     /// Returns the inner collection so that the caller can have a readonly access to it that bypasses the cache.
     pub fn inner(&self) -> &StableMultimap<K1, K2, V, M> {
         &self.inner
     }
+
+    /// Pins the pair of keys in the cache, so its value is never evicted.
+    pub fn pin(&self, first_key: K1, second_key: K2) {
+        self.cache.pin((first_key, second_key))
+    }
+
+    /// Unpins the pair of keys, making its value evictable again.
+    pub fn unpin(&self, first_key: &K1, second_key: &K2) {
+        self.cache.unpin(&(first_key.clone(), second_key.clone()))
+    }
+
+    /// Returns the cache hit/miss counters and size.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    /// Resets the cache hit/miss counters.
+    pub fn reset_cache_stats(&self) {
+        self.cache.reset_stats()
+    }
+}
+
+fn storable_entry_size<K1: Storable, K2: Storable, V: Storable>(
+    (first_key, second_key): &(K1, K2),
+    value: &V,
+) -> usize {
+    first_key.to_bytes().len() + second_key.to_bytes().len() + value.to_bytes().len()
 }
 
 impl<K1, K2, V, M> MultimapStructure<K1, K2, V> for CachedStableMultimap<K1, K2, V, M>
@@ -118,6 +163,23 @@ where
     fn iter(&self) -> Self::Iterator<'_> {
         self.inner.iter()
     }
+
+    fn remove_range(
+        &mut self,
+        key_range: impl RangeBounds<(K1, K2)>,
+        limit: usize,
+    ) -> RangeRemoval<(K1, K2)> {
+        let keys = self
+            .inner
+            .iter_range(key_range)
+            .map(|(k1, k2, _)| (k1, k2))
+            .take(limit.saturating_add(1))
+            .collect();
+
+        RangeRemoval::remove_keys(keys, limit, |(first_key, second_key)| {
+            self.remove(first_key, second_key);
+        })
+    }
 }
 
 #[cfg(test)]
@@ -307,6 +369,31 @@ mod test {
         assert_eq!(None, map.get(&29, &29));
     }
 
+    #[test]
+    fn should_limit_cache_by_bytes() {
+        let mut map = CachedStableMultimap::<u32, u32, Array<2>, _>::with_map_and_max_bytes(
+            StableMultimap::new(VectorMemory::default()),
+            100,
+            20,
+        );
+
+        map.insert(&1, &1, Array([1u8, 1]));
+        map.insert(&1, &2, Array([1u8, 2]));
+        map.insert(&2, &1, Array([2u8, 1]));
+
+        let stats = map.cache_stats();
+        assert_eq!(stats.len, 2);
+        assert_eq!(stats.bytes, 20);
+
+        map.pin(1, 1);
+        assert_eq!(Some(Array([1u8, 1])), map.get(&1, &1));
+        assert_eq!(map.cache_stats().pinned, 1);
+        assert_eq!(map.cache_stats().misses, 1);
+
+        map.unpin(&1, &1);
+        assert_eq!(map.cache_stats().pinned, 0);
+    }
+
     #[test]
     fn should_get_and_insert_from_existing_map() {
         let cache_items = 10;
@@ -4,10 +4,12 @@ use crate::Result;
 
 mod cache;
 mod common;
+mod quota;
 mod stable_storage;
 
 pub use cache::*;
 pub use common::*;
+pub use quota::QuotaLimited;
 pub use stable_storage::*;
 
 pub trait BTreeMapStructure<K, V> {
@@ -36,6 +38,14 @@ pub trait BTreeMapStructure<K, V> {
     /// Returns the last key-value pair in the map.
     fn last_key_value(&self) -> Option<(K, V)>;
 
+    /// Removes and returns the first key-value pair in the map.
+    /// The key of this element is the minimum key that was in the map.
+    fn pop_first(&mut self) -> Option<(K, V)>;
+
+    /// Removes and returns the last key-value pair in the map.
+    /// The key of this element is the maximum key that was in the map.
+    fn pop_last(&mut self) -> Option<(K, V)>;
+
     /// Count of items in the map.
     fn len(&self) -> u64;
 
@@ -44,6 +54,42 @@ pub trait BTreeMapStructure<K, V> {
 
     /// Remove all entries from the map.
     fn clear(&mut self);
+
+    /// Remove at most `limit` entries with keys in the `key_range`.
+    ///
+    /// If the range contains more entries than the `limit`, the returned cursor
+    /// points to the first entry left in the range, so the removal can be resumed
+    /// in a later call.
+    fn remove_range(&mut self, key_range: impl RangeBounds<K>, limit: usize) -> RangeRemoval<K>;
+}
+
+/// Result of a bounded range removal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RangeRemoval<K> {
+    /// Number of removed entries.
+    pub removed: u64,
+    /// The first key in the range that was not removed because of the limit.
+    /// `None` if the whole range was removed.
+    pub cursor: Option<K>,
+}
+
+impl<K> RangeRemoval<K> {
+    /// Removes the `keys` that fit into the `limit`. The next key, if any, becomes the cursor.
+    pub(crate) fn remove_keys(mut keys: Vec<K>, limit: usize, mut remove: impl FnMut(&K)) -> Self {
+        let cursor = if keys.len() > limit {
+            keys.truncate(limit + 1);
+            keys.pop()
+        } else {
+            None
+        };
+
+        keys.iter().for_each(&mut remove);
+
+        Self {
+            removed: keys.len() as u64,
+            cursor,
+        }
+    }
 }
 
 /// Map that supports ordered iterator
@@ -147,6 +193,17 @@ pub trait MultimapStructure<K1, K2, V> {
 
     /// Remove all entries from the map.
     fn clear(&mut self);
+
+    /// Remove at most `limit` entries with key pairs in the `key_range`.
+    ///
+    /// If the range contains more entries than the `limit`, the returned cursor
+    /// points to the first entry left in the range, so the removal can be resumed
+    /// in a later call.
+    fn remove_range(
+        &mut self,
+        key_range: impl RangeBounds<(K1, K2)>,
+        limit: usize,
+    ) -> RangeRemoval<(K1, K2)>;
 }
 
 pub trait VecStructure<T> {
@@ -0,0 +1,179 @@
+use dfinity_stable_structures::Memory;
+
+use crate::structure::{BTreeMapStructure, MultimapStructure};
+use crate::{Error, Result};
+
+const WASM_PAGE_SIZE: u64 = 65536;
+
+/// Wraps a stable structure with a soft quota on the size of its memory.
+///
+/// Once the memory grows past the quota, inserts return [`Error::QuotaExceeded`] instead of
+/// growing the memory further, so the canister can degrade gracefully rather than trap when
+/// the stable memory is exhausted. The quota is soft: it is checked before an insert, so a
+/// single insert can grow the memory past the quota.
+///
+/// The pages allocated by the structure at initialization count towards the quota.
+///
+/// Removals and reads are not limited and are available through [`QuotaLimited::inner_mut`]
+/// and [`QuotaLimited::inner`].
+pub struct QuotaLimited<S, M: Memory> {
+    inner: S,
+    memory: M,
+    max_pages: u64,
+}
+
+impl<S, M: Memory> QuotaLimited<S, M> {
+    /// Wraps the `inner` structure that uses the `memory`.
+    /// The quota is set in wasm pages.
+    pub fn new(inner: S, memory: M, max_pages: u64) -> Self {
+        Self {
+            inner,
+            memory,
+            max_pages,
+        }
+    }
+
+    /// Wraps the `inner` structure that uses the `memory`.
+    /// The quota is set in bytes and rounded up to wasm pages.
+    pub fn with_max_bytes(inner: S, memory: M, max_bytes: u64) -> Self {
+        Self::new(inner, memory, max_bytes.div_ceil(WASM_PAGE_SIZE))
+    }
+
+    /// Returns a reference to the wrapped structure.
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the wrapped structure, bypassing the quota.
+    pub fn inner_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    /// Number of wasm pages used by the memory.
+    pub fn used_pages(&self) -> u64 {
+        self.memory.size()
+    }
+
+    /// The quota in wasm pages.
+    pub fn max_pages(&self) -> u64 {
+        self.max_pages
+    }
+
+    /// Updates the quota.
+    pub fn set_max_pages(&mut self, max_pages: u64) {
+        self.max_pages = max_pages;
+    }
+
+    /// Returns an error if the memory has grown past the quota.
+    pub fn check_quota(&self) -> Result<()> {
+        let used_pages = self.used_pages();
+        if used_pages > self.max_pages {
+            return Err(Error::QuotaExceeded {
+                used_pages,
+                max_pages: self.max_pages,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Runs the `mutation` on the wrapped structure if the quota is not reached.
+    pub fn try_mutate<R>(&mut self, mutation: impl FnOnce(&mut S) -> R) -> Result<R> {
+        self.check_quota()?;
+        Ok(mutation(&mut self.inner))
+    }
+
+    /// Inserts the value into the wrapped map if the quota is not reached.
+    pub fn try_insert<K, V>(&mut self, key: K, value: V) -> Result<Option<V>>
+    where
+        S: BTreeMapStructure<K, V>,
+    {
+        self.try_mutate(|map| map.insert(key, value))
+    }
+
+    /// Inserts the value into the wrapped multimap if the quota is not reached.
+    pub fn try_insert_multi<K1, K2, V>(
+        &mut self,
+        first_key: &K1,
+        second_key: &K2,
+        value: V,
+    ) -> Result<Option<V>>
+    where
+        S: MultimapStructure<K1, K2, V>,
+    {
+        self.try_mutate(|map| map.insert(first_key, second_key, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dfinity_stable_structures::VectorMemory;
+
+    use super::*;
+    use crate::test_utils::str_val;
+    use crate::{StableBTreeMap, StableMultimap, StableVec, VecStructure};
+
+    #[test]
+    fn should_refuse_inserts_over_quota() {
+        let memory = VectorMemory::default();
+        let mut map = QuotaLimited::new(StableBTreeMap::new(memory.clone()), memory, 2);
+
+        let mut key = 0u32;
+        let error = loop {
+            match map.try_insert(key, str_val(10_000)) {
+                Ok(_) => key += 1,
+                Err(error) => break error,
+            }
+        };
+
+        assert!(matches!(
+            error,
+            Error::QuotaExceeded {
+                used_pages,
+                max_pages: 2
+            } if used_pages > 2
+        ));
+        assert_eq!(map.inner().len(), key as u64);
+
+        // Removals are not limited, but the memory doesn't shrink.
+        map.inner_mut().remove(&0);
+        assert!(map.check_quota().is_err());
+
+        map.set_max_pages(100);
+        assert!(map.try_insert(0, str_val(10)).is_ok());
+    }
+
+    #[test]
+    fn should_limit_other_structures() {
+        let memory = VectorMemory::default();
+        let mut multimap =
+            QuotaLimited::with_max_bytes(StableMultimap::new(memory.clone()), memory, 1);
+        assert_eq!(multimap.max_pages(), 1);
+        // The page allocated at initialization doesn't exhaust the quota.
+        assert_eq!(multimap.used_pages(), 1);
+
+        let mut inserted = 0u32;
+        while multimap
+            .try_insert_multi(&1u32, &inserted, str_val(1000))
+            .is_ok()
+        {
+            inserted += 1;
+        }
+        assert!(inserted > 1);
+        assert_eq!(multimap.inner().len(), inserted as u64);
+        assert!(multimap.used_pages() > 1);
+
+        let memory = VectorMemory::default();
+        let vec = StableVec::<u64, _>::new(memory.clone()).unwrap();
+        let mut vec = QuotaLimited::new(vec, memory, 1);
+
+        let mut pushed = 0u64;
+        while let Ok(result) = vec.try_mutate(|vec| vec.push(&pushed)) {
+            result.unwrap();
+            pushed += 1;
+        }
+        assert!(pushed > 1);
+        assert_eq!(vec.inner().len(), pushed);
+        assert!(vec.used_pages() > 1);
+    }
+}
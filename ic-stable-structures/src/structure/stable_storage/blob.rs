@@ -0,0 +1,454 @@
+use std::borrow::Cow;
+use std::ops::Bound as RangeBound;
+
+use candid::CandidType;
+use dfinity_stable_structures::storable::Bound;
+use dfinity_stable_structures::{btreemap, Memory, Storable};
+
+/// Size of the chunks the blobs are split into.
+pub const BLOB_CHUNK_SIZE: u64 = 4096;
+
+/// Default number of chunks the garbage collector visits on every mutation.
+pub const DEFAULT_GC_CHUNKS_PER_OPERATION: usize = 16;
+
+/// Result of a garbage collection pass.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, CandidType)]
+pub struct GcReport {
+    /// Number of chunks visited by the pass.
+    pub visited_chunks: u64,
+    /// Number of orphaned chunks removed.
+    pub reclaimed_chunks: u64,
+    /// Number of data bytes reclaimed, including the trimmed tails of partially used chunks.
+    pub reclaimed_bytes: u64,
+    /// True if the pass reached the end of the storage.
+    pub complete: bool,
+}
+
+/// Stores arbitrarily large byte blobs in stable memory.
+///
+/// Every blob is split into chunks of `BLOB_CHUNK_SIZE` bytes, so the blobs are not limited
+/// by the max value size of the stable structures, and parts of a blob can be read or written
+/// without loading the whole blob.
+///
+/// Removing or shrinking a blob only updates its size, and the chunks past the new end are
+/// reclaimed later by an incremental garbage collector. Every mutation visits a bounded number
+/// of chunks (see [`StableBlobStorage::with_gc_chunks_per_operation`]), and the collector can
+/// also be run explicitly with [`StableBlobStorage::gc`].
+pub struct StableBlobStorage<K, M>
+where
+    K: Storable + Ord + Clone,
+    M: Memory,
+{
+    sizes: btreemap::BTreeMap<K, u64, M>,
+    chunks: btreemap::BTreeMap<(K, u32), BlobChunk, M>,
+    gc_cursor: Option<(K, u32)>,
+    gc_chunks_per_operation: usize,
+}
+
+impl<K, M> StableBlobStorage<K, M>
+where
+    K: Storable + Ord + Clone,
+    M: Memory,
+{
+    /// Create new blob storage.
+    /// Blob sizes are stored in `sizes_memory` and blob data in `chunks_memory`.
+    pub fn new(sizes_memory: M, chunks_memory: M) -> Self {
+        Self {
+            sizes: btreemap::BTreeMap::init(sizes_memory),
+            chunks: btreemap::BTreeMap::init(chunks_memory),
+            gc_cursor: None,
+            gc_chunks_per_operation: DEFAULT_GC_CHUNKS_PER_OPERATION,
+        }
+    }
+
+    /// Sets the number of chunks the garbage collector visits on every mutation.
+    /// Zero disables the amortized collection, so only explicit [`StableBlobStorage::gc`]
+    /// calls reclaim the orphaned chunks.
+    pub fn with_gc_chunks_per_operation(mut self, chunks: usize) -> Self {
+        self.gc_chunks_per_operation = chunks;
+        self
+    }
+
+    /// Stores the `data` under the `key`, replacing the previous blob.
+    pub fn insert(&mut self, key: K, data: &[u8]) {
+        self.write_with_size(key, 0, 0, data);
+        self.gc_step();
+    }
+
+    /// Writes the `data` into the blob starting at the `offset`.
+    /// The blob is created if it doesn't exist, and extended if the data is written
+    /// past its end. The gap between the end of the blob and the `offset`, if any, is filled with zeros.
+    pub fn write(&mut self, key: K, offset: u64, data: &[u8]) {
+        let size = self.size(&key).unwrap_or(0);
+        self.write_with_size(key, size, offset, data);
+        self.gc_step();
+    }
+
+    /// Shrinks the blob to `len` bytes. Does nothing if the blob is not longer than `len`.
+    /// Returns false if the blob doesn't exist.
+    pub fn truncate(&mut self, key: K, len: u64) -> bool {
+        let Some(size) = self.size(&key) else {
+            return false;
+        };
+
+        if len < size {
+            self.sizes.insert(key, len);
+            self.gc_step();
+        }
+
+        true
+    }
+
+    fn write_with_size(&mut self, key: K, size: u64, offset: u64, data: &[u8]) {
+        let end = offset + data.len() as u64;
+        let new_size = size.max(end);
+
+        let start = offset.min(size);
+        if start < end {
+            for index in chunk_index(start)..=chunk_index(end - 1) {
+                let chunk_start = index as u64 * BLOB_CHUNK_SIZE;
+                let chunk_len = BLOB_CHUNK_SIZE.min(new_size - chunk_start);
+
+                let chunk_key = (key.clone(), index);
+                let mut chunk = self.chunks.get(&chunk_key).unwrap_or_default();
+                // Orphaned chunks and tails past the end of the blob may still hold stale data.
+                chunk.0.truncate(size.saturating_sub(chunk_start) as usize);
+                chunk.0.resize(chunk_len as usize, 0);
+
+                let write_start = offset.max(chunk_start);
+                let write_end = end.min(chunk_start + chunk_len);
+                if write_start < write_end {
+                    chunk.0
+                        [(write_start - chunk_start) as usize..(write_end - chunk_start) as usize]
+                        .copy_from_slice(
+                            &data[(write_start - offset) as usize..(write_end - offset) as usize],
+                        );
+                }
+
+                self.chunks.insert(chunk_key, chunk);
+            }
+        }
+
+        self.sizes.insert(key, new_size);
+    }
+
+    /// Returns the whole blob stored under the `key`.
+    pub fn get(&self, key: &K) -> Option<Vec<u8>> {
+        self.read(key, 0, u64::MAX)
+    }
+
+    /// Reads at most `len` bytes of the blob starting at the `offset`.
+    /// Returns an empty vector if the `offset` is past the end of the blob.
+    pub fn read(&self, key: &K, offset: u64, len: u64) -> Option<Vec<u8>> {
+        let size = self.size(key)?;
+        let end = offset.saturating_add(len).min(size);
+        if offset >= end {
+            return Some(vec![]);
+        }
+
+        let mut result = Vec::with_capacity((end - offset) as usize);
+        let chunks_range = (key.clone(), chunk_index(offset))..=(key.clone(), chunk_index(end - 1));
+        for ((_, index), chunk) in self.chunks.range(chunks_range) {
+            let chunk_start = index as u64 * BLOB_CHUNK_SIZE;
+            let read_start = (offset.max(chunk_start) - chunk_start) as usize;
+            let read_end = (end.min(chunk_start + chunk.0.len() as u64) - chunk_start) as usize;
+            result.extend_from_slice(&chunk.0[read_start..read_end]);
+        }
+
+        Some(result)
+    }
+
+    /// Returns the size of the blob in bytes.
+    pub fn size(&self, key: &K) -> Option<u64> {
+        self.sizes.get(key)
+    }
+
+    /// Removes the blob. Returns true if the blob existed.
+    /// The chunks of the blob are reclaimed by the garbage collector.
+    pub fn remove(&mut self, key: &K) -> bool {
+        let removed = self.sizes.remove(key).is_some();
+        if removed {
+            self.gc_step();
+        }
+
+        removed
+    }
+
+    /// True if the storage contains a blob under the `key`.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.sizes.contains_key(key)
+    }
+
+    /// Number of stored blobs.
+    pub fn len(&self) -> u64 {
+        self.sizes.len()
+    }
+
+    /// True if there are no blobs in the storage.
+    pub fn is_empty(&self) -> bool {
+        self.sizes.is_empty()
+    }
+
+    /// Iterates over the keys and sizes of the stored blobs.
+    pub fn iter(&self) -> btreemap::Iter<'_, K, u64, M> {
+        self.sizes.iter()
+    }
+
+    /// Removes all the blobs.
+    pub fn clear(&mut self) {
+        self.sizes.clear_new();
+        self.chunks.clear_new();
+        self.gc_cursor = None;
+    }
+
+    /// Visits at most `max_chunks` chunks, continuing from where the previous pass stopped,
+    /// and reclaims the chunks past the end of their blobs.
+    pub fn gc(&mut self, max_chunks: usize) -> GcReport {
+        let start = match self.gc_cursor.take() {
+            Some(cursor) => RangeBound::Excluded(cursor),
+            None => RangeBound::Unbounded,
+        };
+
+        let batch: Vec<_> = self
+            .chunks
+            .range((start, RangeBound::Unbounded))
+            .take(max_chunks)
+            .collect();
+
+        let mut report = GcReport {
+            visited_chunks: batch.len() as u64,
+            complete: batch.len() < max_chunks,
+            ..Default::default()
+        };
+
+        let mut current_blob: Option<(K, u64)> = None;
+        for ((key, index), mut chunk) in batch {
+            let size = match &current_blob {
+                Some((blob_key, size)) if *blob_key == key => *size,
+                _ => {
+                    let size = self.size(&key).unwrap_or(0);
+                    current_blob = Some((key.clone(), size));
+                    size
+                }
+            };
+
+            let chunk_start = index as u64 * BLOB_CHUNK_SIZE;
+            let chunk_key = (key, index);
+            let used_len = size.saturating_sub(chunk_start);
+            if used_len == 0 {
+                report.reclaimed_chunks += 1;
+                report.reclaimed_bytes += chunk.0.len() as u64;
+                self.chunks.remove(&chunk_key);
+            } else if used_len < chunk.0.len() as u64 {
+                report.reclaimed_bytes += chunk.0.len() as u64 - used_len;
+                chunk.0.truncate(used_len as usize);
+                self.chunks.insert(chunk_key.clone(), chunk);
+            }
+
+            self.gc_cursor = Some(chunk_key);
+        }
+
+        if report.complete {
+            self.gc_cursor = None;
+        }
+
+        report
+    }
+
+    fn gc_step(&mut self) {
+        if self.gc_chunks_per_operation > 0 {
+            self.gc(self.gc_chunks_per_operation);
+        }
+    }
+}
+
+fn chunk_index(offset: u64) -> u32 {
+    (offset / BLOB_CHUNK_SIZE)
+        .try_into()
+        .expect("blob size exceeds the max number of chunks")
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct BlobChunk(Vec<u8>);
+
+impl Storable for BlobChunk {
+    const BOUND: Bound = Bound::Bounded {
+        max_size: BLOB_CHUNK_SIZE as u32,
+        is_fixed_size: false,
+    };
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(&self.0)
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        Self(bytes.into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dfinity_stable_structures::VectorMemory;
+
+    use super::*;
+
+    fn new_storage() -> StableBlobStorage<u32, VectorMemory> {
+        StableBlobStorage::new(VectorMemory::default(), VectorMemory::default())
+    }
+
+    fn data(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn should_insert_and_get_blobs() {
+        let mut storage = new_storage();
+        assert!(storage.is_empty());
+
+        let large = data(BLOB_CHUNK_SIZE as usize * 3 + 17);
+        let small = data(10);
+        storage.insert(1, &large);
+        storage.insert(2, &small);
+        storage.insert(3, &[]);
+
+        assert_eq!(storage.len(), 3);
+        assert_eq!(storage.get(&1), Some(large.clone()));
+        assert_eq!(storage.get(&2), Some(small));
+        assert_eq!(storage.get(&3), Some(vec![]));
+        assert_eq!(storage.get(&4), None);
+        assert_eq!(storage.size(&1), Some(large.len() as u64));
+
+        storage.insert(1, &[1, 2, 3]);
+        assert_eq!(storage.get(&1), Some(vec![1, 2, 3]));
+        assert_eq!(storage.chunks.len(), 2);
+    }
+
+    #[test]
+    fn should_read_range() {
+        let mut storage = new_storage();
+        let blob = data(BLOB_CHUNK_SIZE as usize * 2 + 100);
+        storage.insert(1, &blob);
+
+        let offset = BLOB_CHUNK_SIZE - 10;
+        assert_eq!(
+            storage.read(&1, offset, 20),
+            Some(blob[offset as usize..offset as usize + 20].to_vec())
+        );
+        assert_eq!(
+            storage.read(&1, BLOB_CHUNK_SIZE * 2, 1000),
+            Some(blob[BLOB_CHUNK_SIZE as usize * 2..].to_vec())
+        );
+        assert_eq!(storage.read(&1, blob.len() as u64, 10), Some(vec![]));
+        assert_eq!(storage.read(&2, 0, 10), None);
+    }
+
+    #[test]
+    fn should_write_range() {
+        let mut storage = new_storage();
+        let mut blob = data(BLOB_CHUNK_SIZE as usize + 10);
+        storage.insert(1, &blob);
+
+        let patch = vec![255; 20];
+        let offset = BLOB_CHUNK_SIZE as usize - 5;
+        storage.write(1, offset as u64, &patch);
+        blob.resize(offset + patch.len(), 0);
+        blob[offset..].copy_from_slice(&patch);
+        assert_eq!(storage.get(&1), Some(blob.clone()));
+
+        // Writing past the end fills the gap with zeros.
+        let gap_offset = blob.len() + BLOB_CHUNK_SIZE as usize;
+        storage.write(1, gap_offset as u64, &[7, 7]);
+        blob.resize(gap_offset, 0);
+        blob.extend_from_slice(&[7, 7]);
+        assert_eq!(storage.size(&1), Some(blob.len() as u64));
+        assert_eq!(storage.get(&1), Some(blob));
+    }
+
+    #[test]
+    fn should_remove_blobs() {
+        let mut storage = new_storage();
+        storage.insert(1, &data(BLOB_CHUNK_SIZE as usize * 2 + 1));
+        storage.insert(2, &data(10));
+
+        assert!(storage.remove(&1));
+        assert!(!storage.remove(&1));
+        assert!(!storage.contains_key(&1));
+        assert_eq!(storage.chunks.len(), 1);
+        assert_eq!(storage.get(&2), Some(data(10)));
+
+        storage.clear();
+        assert!(storage.is_empty());
+        assert_eq!(storage.chunks.len(), 0);
+    }
+
+    #[test]
+    fn should_collect_orphaned_chunks_explicitly() {
+        let mut storage = new_storage().with_gc_chunks_per_operation(0);
+        storage.insert(1, &data(BLOB_CHUNK_SIZE as usize * 3));
+        storage.insert(2, &data(10));
+        storage.insert(1, &data(BLOB_CHUNK_SIZE as usize + 1));
+        assert_eq!(storage.chunks.len(), 4);
+
+        let report = storage.gc(2);
+        assert_eq!(
+            report,
+            GcReport {
+                visited_chunks: 2,
+                reclaimed_chunks: 0,
+                reclaimed_bytes: 0,
+                complete: false,
+            }
+        );
+
+        let report = storage.gc(10);
+        assert_eq!(
+            report,
+            GcReport {
+                visited_chunks: 2,
+                reclaimed_chunks: 1,
+                reclaimed_bytes: BLOB_CHUNK_SIZE,
+                complete: true,
+            }
+        );
+        assert_eq!(storage.chunks.len(), 3);
+        assert_eq!(storage.get(&1), Some(data(BLOB_CHUNK_SIZE as usize + 1)));
+
+        storage.remove(&2);
+        assert_eq!(storage.gc(10).reclaimed_chunks, 1);
+        assert_eq!(storage.get(&2), None);
+    }
+
+    #[test]
+    fn should_trim_truncated_chunks() {
+        let mut storage = new_storage().with_gc_chunks_per_operation(0);
+        storage.insert(1, &data(100));
+        assert!(storage.truncate(1, 40));
+        assert!(!storage.truncate(2, 40));
+        assert_eq!(storage.get(&1), Some(data(40)));
+
+        // Extending the blob again must not expose the stale bytes.
+        storage.write(1, 60, &[1]);
+        let mut expected = data(40);
+        expected.resize(60, 0);
+        expected.push(1);
+        assert_eq!(storage.get(&1), Some(expected));
+
+        storage.truncate(1, 10);
+        let report = storage.gc(10);
+        assert_eq!(report.reclaimed_chunks, 0);
+        assert_eq!(report.reclaimed_bytes, 51);
+        assert_eq!(storage.get(&1), Some(data(10)));
+    }
+
+    #[test]
+    fn should_collect_garbage_on_mutations() {
+        let mut storage = new_storage().with_gc_chunks_per_operation(1);
+        storage.insert(1, &data(BLOB_CHUNK_SIZE as usize * 4));
+        storage.remove(&1);
+        for _ in 0..4 {
+            storage.insert(2, &[]);
+        }
+
+        assert_eq!(storage.chunks.len(), 0);
+    }
+}
@@ -1,9 +1,11 @@
+use std::borrow::Cow;
 use std::ops::RangeBounds;
 
+use dfinity_stable_structures::storable::Bound;
 use dfinity_stable_structures::{btreemap, Memory, Storable};
 
 use crate::structure::BTreeMapStructure;
-use crate::IterableSortedMapStructure;
+use crate::{IterableSortedMapStructure, RangeRemoval};
 
 /// Stores key-value data in stable memory.
 pub struct StableBTreeMap<K, V, M: Memory>(btreemap::BTreeMap<K, V, M>)
@@ -26,6 +28,38 @@ where
     pub fn iter(&self) -> btreemap::Iter<'_, K, V, M> {
         self.0.iter()
     }
+
+    /// Iterate over the key-value pairs whose key bytes start with the `prefix`.
+    ///
+    /// The range bounds are the `prefix` padded to the key size with the `0x00` and `0xff`
+    /// bytes, so the keys must be of a fixed size, ordered as their bytes and decodable from any
+    /// bytes of that size: byte arrays, big-endian integers or tuples of such keys. E.g. all the
+    /// `(u64, u64)` entries of the `owner` are returned by `map.iter_prefix(&owner.to_be_bytes())`.
+    ///
+    /// Keys of a variable size, like `Principal` or `String`, are not supported, as they are not
+    /// ordered as their bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the keys are not of a fixed size.
+    pub fn iter_prefix(&self, prefix: &[u8]) -> impl Iterator<Item = (K, V)> + '_ {
+        let key_size = match K::BOUND {
+            Bound::Bounded {
+                max_size,
+                is_fixed_size: true,
+            } => max_size as usize,
+            _ => panic!("prefix iteration requires keys of a fixed size"),
+        };
+
+        let bound = |padding: u8| {
+            let mut bytes = prefix.to_vec();
+            bytes.resize(key_size, padding);
+            K::from_bytes(Cow::Owned(bytes))
+        };
+        let range = (prefix.len() <= key_size).then(|| bound(0)..=bound(u8::MAX));
+
+        range.into_iter().flat_map(move |range| self.0.range(range))
+    }
 }
 
 impl<K, V, M> BTreeMapStructure<K, V> for StableBTreeMap<K, V, M>
@@ -69,6 +103,27 @@ where
     fn last_key_value(&self) -> Option<(K, V)> {
         self.0.last_key_value()
     }
+
+    fn pop_first(&mut self) -> Option<(K, V)> {
+        self.0.pop_first()
+    }
+
+    fn pop_last(&mut self) -> Option<(K, V)> {
+        self.0.pop_last()
+    }
+
+    fn remove_range(&mut self, key_range: impl RangeBounds<K>, limit: usize) -> RangeRemoval<K> {
+        let keys = self
+            .0
+            .range(key_range)
+            .map(|(key, _)| key)
+            .take(limit.saturating_add(1))
+            .collect();
+
+        RangeRemoval::remove_keys(keys, limit, |key| {
+            self.0.remove(key);
+        })
+    }
 }
 
 impl<K, V, M> IterableSortedMapStructure<K, V> for StableBTreeMap<K, V, M>
@@ -103,7 +158,7 @@ mod tests {
     use dfinity_stable_structures::VectorMemory;
 
     use super::*;
-    use crate::test_utils::str_val;
+    use crate::test_utils::{str_val, Array};
 
     #[test]
     fn btreemap_works() {
@@ -313,6 +368,103 @@ mod tests {
         assert_eq!(map.last_key_value(), Some((4u32, str_4)));
     }
 
+    #[test]
+    fn iter_prefix_test() {
+        let mut map = StableBTreeMap::new(VectorMemory::default());
+        map.insert(Array([1u8, 1, 1]), 1u32);
+        map.insert(Array([1u8, 2, 0]), 2);
+        map.insert(Array([1u8, 2, 255]), 3);
+        map.insert(Array([1u8, 3, 0]), 4);
+        map.insert(Array([2u8, 2, 0]), 5);
+
+        let values: Vec<_> = map.iter_prefix(&[1, 2]).map(|(_, v)| v).collect();
+        assert_eq!(values, vec![2, 3]);
+
+        let values: Vec<_> = map.iter_prefix(&[1]).map(|(_, v)| v).collect();
+        assert_eq!(values, vec![1, 2, 3, 4]);
+
+        let values: Vec<_> = map.iter_prefix(&[1, 2, 255]).map(|(_, v)| v).collect();
+        assert_eq!(values, vec![3]);
+
+        assert_eq!(map.iter_prefix(&[]).count(), 5);
+        assert_eq!(map.iter_prefix(&[3]).count(), 0);
+        assert_eq!(map.iter_prefix(&[1, 2, 0, 0]).count(), 0);
+    }
+
+    #[test]
+    fn iter_prefix_with_tuple_keys_test() {
+        let mut map = StableBTreeMap::new(VectorMemory::default());
+        for (owner, id) in [(1u64, 3u64), (256, 1), (1, 1), (0, 2), (1, 2), (2, 0)] {
+            map.insert((owner, id), id);
+        }
+
+        let keys: Vec<_> = map
+            .iter_prefix(&1u64.to_be_bytes())
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(keys, vec![(1, 1), (1, 2), (1, 3)]);
+
+        let keys: Vec<_> = map
+            .iter_prefix(&256u64.to_be_bytes())
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(keys, vec![(256, 1)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "fixed size")]
+    fn iter_prefix_with_unbounded_keys_test() {
+        let mut map = StableBTreeMap::new(VectorMemory::default());
+        map.insert("user_1/a".to_string(), 0u32);
+
+        let _ = map.iter_prefix(b"user_1/").count();
+    }
+
+    #[test]
+    fn remove_range_test() {
+        let mut map = StableBTreeMap::new(VectorMemory::default());
+        for i in 0..10u32 {
+            map.insert(i, i * 10);
+        }
+
+        let removal = map.remove_range(2..8, 4);
+        assert_eq!(removal.removed, 4);
+        assert_eq!(removal.cursor, Some(6));
+        assert_eq!(map.len(), 6);
+        assert!(!map.contains_key(&5));
+        assert!(map.contains_key(&6));
+
+        let removal = map.remove_range(removal.cursor.unwrap()..8, 4);
+        assert_eq!(removal.removed, 2);
+        assert_eq!(removal.cursor, None);
+        assert_eq!(map.len(), 4);
+
+        let keys: Vec<_> = map.iter().map(|(k, _)| k).collect();
+        assert_eq!(keys, vec![0, 1, 8, 9]);
+
+        let removal = map.remove_range(.., 0);
+        assert_eq!(removal.removed, 0);
+        assert_eq!(removal.cursor, Some(0));
+        assert_eq!(map.len(), 4);
+    }
+
+    #[test]
+    fn test_pop_first_and_last() {
+        let mut map = StableBTreeMap::new(VectorMemory::default());
+        assert!(map.pop_first().is_none());
+        assert!(map.pop_last().is_none());
+
+        for i in 0..5u32 {
+            map.insert(i, str_val(i as usize * 100));
+        }
+
+        assert_eq!(map.pop_first(), Some((0, str_val(0))));
+        assert_eq!(map.pop_last(), Some((4, str_val(400))));
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.first_key_value(), Some((1, str_val(100))));
+        assert_eq!(map.last_key_value(), Some((3, str_val(300))));
+    }
+
     #[test]
     fn btreemap_works_with_composite_keys() {
         let mut map = StableBTreeMap::new(VectorMemory::default());
@@ -1,11 +1,17 @@
+mod blob;
 mod btreemap;
 mod cell;
 mod log;
 mod multimap;
+mod record_log;
 mod vec;
+mod versioned_cell;
 
+pub use blob::{GcReport, StableBlobStorage, BLOB_CHUNK_SIZE, DEFAULT_GC_CHUNKS_PER_OPERATION};
 pub use btreemap::StableBTreeMap;
 pub use cell::StableCell;
 pub use log::StableLog;
 pub use multimap::{StableMultimap, StableMultimapIter, StableMultimapRangeIter};
+pub use record_log::RecordLog;
 pub use vec::StableVec;
+pub use versioned_cell::VersionedStableCell;
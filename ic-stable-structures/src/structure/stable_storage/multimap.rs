@@ -1,7 +1,9 @@
+use std::ops::RangeBounds;
+
 use dfinity_stable_structures::{btreemap, Memory, StableBTreeMap, Storable};
 
 use crate::structure::MultimapStructure;
-use crate::Bounded;
+use crate::{Bounded, RangeRemoval};
 
 /// `StableMultimap` stores two keys against a single value, making it possible
 /// to fetch all values by the root key, or a single value by specifying both keys.
@@ -28,6 +30,14 @@ where
     pub fn iter_upper_bound(&self, key: &(K1, K2)) -> StableMultimapIter<'_, K1, K2, V, M> {
         StableMultimapIter::new(self.0.iter_upper_bound(key))
     }
+
+    /// Returns iterator over the entries with key pairs in the given range.
+    pub fn iter_range(
+        &self,
+        key_range: impl RangeBounds<(K1, K2)>,
+    ) -> StableMultimapIter<'_, K1, K2, V, M> {
+        StableMultimapIter::new(self.0.range(key_range))
+    }
 }
 
 impl<K1, K2, V, M> MultimapStructure<K1, K2, V> for StableMultimap<K1, K2, V, M>
@@ -104,6 +114,23 @@ where
     fn iter(&self) -> Self::Iterator<'_> {
         StableMultimapIter::new(self.0.iter())
     }
+
+    fn remove_range(
+        &mut self,
+        key_range: impl RangeBounds<(K1, K2)>,
+        limit: usize,
+    ) -> RangeRemoval<(K1, K2)> {
+        let keys = self
+            .0
+            .range(key_range)
+            .map(|(keys, _)| keys)
+            .take(limit.saturating_add(1))
+            .collect();
+
+        RangeRemoval::remove_keys(keys, limit, |keys| {
+            self.0.remove(keys);
+        })
+    }
 }
 
 /// Range iterator
@@ -262,6 +289,31 @@ mod test {
         assert_eq!(val, expected);
     }
 
+    #[test]
+    fn remove_range() {
+        let mut mm = StableMultimap::<u32, u32, u32, _>::new(VectorMemory::default());
+        for i in 0..3 {
+            for j in 0..4 {
+                mm.insert(&i, &j, i * 10 + j);
+            }
+        }
+
+        let removal = mm.remove_range((1, 0)..(2, 0), 3);
+        assert_eq!(removal.removed, 3);
+        assert_eq!(removal.cursor, Some((1, 3)));
+        assert_eq!(mm.len(), 9);
+
+        let removal = mm.remove_range(removal.cursor.unwrap()..(2, 0), 3);
+        assert_eq!(removal.removed, 1);
+        assert_eq!(removal.cursor, None);
+        assert_eq!(mm.len(), 8);
+        assert_eq!(mm.range(&1).count(), 0);
+
+        let removal = mm.remove_range(.., 100);
+        assert_eq!(removal.removed, 8);
+        assert!(mm.is_empty());
+    }
+
     #[test]
     fn remove() {
         let mut mm = make_map();
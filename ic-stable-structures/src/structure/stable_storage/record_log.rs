@@ -0,0 +1,246 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+
+use candid::{CandidType, Deserialize};
+use dfinity_stable_structures::memory_manager::VirtualMemory;
+use dfinity_stable_structures::storable::Bound;
+use dfinity_stable_structures::{DefaultMemoryImpl, Memory, Storable};
+
+use super::StableLog;
+use crate::structure::LogStructure;
+use crate::Result;
+
+/// Append-only log of candid serialized records in stable memory, to be stored in a
+/// `thread_local` and initialized in both `init` and `post_upgrade` methods of the canister.
+///
+/// Until the log is initialized with [`RecordLog::init`], it has no records and appended
+/// records are discarded.
+///
+/// ```ignore
+/// thread_local! {
+///     static HISTORY: RecordLog<Record> = const { RecordLog::new() };
+/// }
+///
+/// HISTORY.with(|log| log.init(memory_manager.get(10), memory_manager.get(11)))?;
+/// HISTORY.with(|log| log.append(record));
+/// let (total, records) = HISTORY.with(|log| log.page(0, 10, Some(|r: &Record| r.owner == caller)));
+/// ```
+pub struct RecordLog<T, M = VirtualMemory<DefaultMemoryImpl>>
+where
+    T: CandidType + for<'de> Deserialize<'de>,
+    M: Memory,
+{
+    log: RefCell<Option<StableLog<CandidRecord<T>, M>>>,
+}
+
+impl<T, M> RecordLog<T, M>
+where
+    T: CandidType + for<'de> Deserialize<'de>,
+    M: Memory,
+{
+    /// Creates a log that is not initialized yet.
+    pub const fn new() -> Self {
+        Self {
+            log: RefCell::new(None),
+        }
+    }
+
+    /// Initializes the log in the given memories, loading the records already stored there.
+    pub fn init(&self, index_memory: M, data_memory: M) -> Result<()> {
+        let log = StableLog::new(index_memory, data_memory)?;
+        *self.log.borrow_mut() = Some(log);
+        Ok(())
+    }
+
+    /// Number of records in the log.
+    pub fn len(&self) -> u64 {
+        self.log
+            .borrow()
+            .as_ref()
+            .map(|log| log.len())
+            .unwrap_or_default()
+    }
+
+    /// Returns `true` if the log has no records.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the record with the given index.
+    pub fn get(&self, index: u64) -> Option<T> {
+        self.log
+            .borrow()
+            .as_ref()
+            .and_then(|log| log.get(index))
+            .map(|record| record.0)
+    }
+
+    /// Appends the record and returns its index.
+    ///
+    /// Records are usually appended after state changes which should not be rolled back by a
+    /// trap, so the record is rather lost if the stable memory is exhausted. In this case, or if
+    /// the log is not initialized, `None` is returned.
+    pub fn append(&self, record: T) -> Option<u64> {
+        self.log
+            .borrow_mut()
+            .as_mut()
+            .and_then(|log| log.append(CandidRecord(record)).ok())
+    }
+
+    /// Returns at most `limit` records starting from the record with the `start` index, oldest
+    /// first.
+    pub fn range(&self, start: u64, limit: u64) -> Vec<T> {
+        let end = start.saturating_add(limit).min(self.len());
+        (start..end).filter_map(|index| self.get(index)).collect()
+    }
+
+    /// Returns at most `limit` records, newest first, skipping the first `offset` of them,
+    /// together with the total number of records.
+    ///
+    /// If the `filter` is given, only the matching records are returned and counted. Note that
+    /// filtering requires scanning the whole log.
+    pub fn page<F>(&self, offset: u64, limit: u64, filter: Option<F>) -> (u64, Vec<T>)
+    where
+        F: Fn(&T) -> bool,
+    {
+        let newest_first = (0..self.len()).rev();
+        let Some(filter) = filter else {
+            let records = newest_first
+                .skip(offset as usize)
+                .take(limit as usize)
+                .filter_map(|index| self.get(index))
+                .collect();
+            return (self.len(), records);
+        };
+
+        let matching = newest_first
+            .filter_map(|index| self.get(index))
+            .filter(|record| filter(record));
+        let mut total = 0;
+        let mut records = vec![];
+        for record in matching {
+            if total >= offset && (records.len() as u64) < limit {
+                records.push(record);
+            }
+            total += 1;
+        }
+
+        (total, records)
+    }
+}
+
+impl<T, M> Default for RecordLog<T, M>
+where
+    T: CandidType + for<'de> Deserialize<'de>,
+    M: Memory,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Record stored in the log as candid.
+struct CandidRecord<T>(T);
+
+impl<T> Storable for CandidRecord<T>
+where
+    T: CandidType + for<'de> Deserialize<'de>,
+{
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let bytes = candid::encode_one(&self.0).expect("serialization of log record failed");
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        Self(candid::decode_one(&bytes).expect("deserialization of log record failed"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dfinity_stable_structures::VectorMemory;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, CandidType, Deserialize)]
+    struct Record {
+        owner: u8,
+        value: u64,
+    }
+
+    fn record(owner: u8, value: u64) -> Record {
+        Record { owner, value }
+    }
+
+    fn init_log() -> RecordLog<Record, VectorMemory> {
+        let log = RecordLog::new();
+        log.init(VectorMemory::default(), VectorMemory::default())
+            .unwrap();
+        log
+    }
+
+    #[test]
+    fn should_discard_records_if_not_initialized() {
+        let log = RecordLog::<Record, VectorMemory>::new();
+        assert_eq!(log.append(record(1, 1)), None);
+        assert!(log.is_empty());
+        assert_eq!(log.page::<fn(&Record) -> bool>(0, 10, None), (0, vec![]));
+    }
+
+    #[test]
+    fn should_append_and_reload_records() {
+        let index_memory = VectorMemory::default();
+        let data_memory = VectorMemory::default();
+        let log = RecordLog::<Record, VectorMemory>::new();
+        log.init(index_memory.clone(), data_memory.clone()).unwrap();
+        assert_eq!(log.append(record(1, 10)), Some(0));
+        assert_eq!(log.append(record(2, 20)), Some(1));
+
+        let log = RecordLog::<Record, VectorMemory>::new();
+        log.init(index_memory, data_memory).unwrap();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log.get(1), Some(record(2, 20)));
+    }
+
+    #[test]
+    fn should_return_range_oldest_first() {
+        let log = init_log();
+        for value in 0..5 {
+            log.append(record(1, value));
+        }
+
+        assert_eq!(log.range(1, 2), vec![record(1, 1), record(1, 2)]);
+        assert_eq!(log.range(4, 10), vec![record(1, 4)]);
+        assert!(log.range(5, 10).is_empty());
+    }
+
+    #[test]
+    fn should_return_page_newest_first() {
+        let log = init_log();
+        for value in 0..5 {
+            log.append(record(1, value));
+        }
+
+        let (total, records) = log.page::<fn(&Record) -> bool>(1, 2, None);
+        assert_eq!(total, 5);
+        assert_eq!(records, vec![record(1, 3), record(1, 2)]);
+    }
+
+    #[test]
+    fn should_filter_page() {
+        let log = init_log();
+        for value in 0..6 {
+            log.append(record((value % 2) as u8, value));
+        }
+
+        let (total, records) = log.page(1, 10, Some(|r: &Record| r.owner == 1));
+        assert_eq!(total, 3);
+        assert_eq!(records, vec![record(1, 3), record(1, 1)]);
+
+        let (total, records) = log.page(0, 10, Some(|r: &Record| r.owner == 2));
+        assert_eq!(total, 0);
+        assert!(records.is_empty());
+    }
+}
@@ -0,0 +1,179 @@
+use std::borrow::Cow;
+
+use dfinity_stable_structures::storable::Bound;
+use dfinity_stable_structures::{cell, Memory, Storable};
+use ic_storage::stable::{upgrade_from_bytes, Versioned};
+
+use crate::structure::CellStructure;
+use crate::Result;
+
+/// Stores a [`Versioned`] value in stable memory, providing `get()/set()` API.
+///
+/// The value is stored as candid together with its version. If the stored version is older
+/// than the version of `T`, the value is upgraded using the `Versioned::upgrade` chain
+/// when the cell is created, and the upgraded value is written back.
+pub struct VersionedStableCell<T: Versioned, M: Memory> {
+    cell: cell::Cell<VersionedBytes, M>,
+    value: T,
+}
+
+impl<T: Versioned, M: Memory> VersionedStableCell<T, M> {
+    /// Create new storage for values with `T` type.
+    /// If the memory already contains a value of an older version, it is upgraded to `T`.
+    pub fn new(memory: M, default_value: T) -> Result<Self> {
+        let mut cell = cell::Cell::init(memory, VersionedBytes::encode(&default_value)?)?;
+
+        let stored = cell.get();
+        let value = upgrade_from_bytes::<T>(stored.version, &stored.payload)?;
+        if stored.version != T::version() {
+            cell.set(VersionedBytes::encode(&value)?)?;
+        }
+
+        Ok(Self { cell, value })
+    }
+
+    /// Version of the value stored in the cell.
+    pub fn stored_version(&self) -> u32 {
+        self.cell.get().version
+    }
+}
+
+impl<T: Versioned, M: Memory> CellStructure<T> for VersionedStableCell<T, M> {
+    fn get(&self) -> &T {
+        &self.value
+    }
+
+    fn set(&mut self, value: T) -> Result<()> {
+        self.cell.set(VersionedBytes::encode(&value)?)?;
+        self.value = value;
+        Ok(())
+    }
+}
+
+/// Candid serialized value prefixed with its version.
+struct VersionedBytes {
+    version: u32,
+    payload: Vec<u8>,
+}
+
+impl VersionedBytes {
+    fn encode<T: Versioned>(value: &T) -> Result<Self> {
+        Ok(Self {
+            version: T::version(),
+            payload: candid::encode_one(value)?,
+        })
+    }
+}
+
+impl Storable for VersionedBytes {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let mut buf = Vec::with_capacity(4 + self.payload.len());
+        buf.extend_from_slice(&self.version.to_le_bytes());
+        buf.extend_from_slice(&self.payload);
+        buf.into()
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        Self {
+            version: u32::from_le_bytes(bytes[..4].try_into().expect("version: expected 4 bytes")),
+            payload: bytes[4..].to_vec(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use candid::CandidType;
+    use dfinity_stable_structures::VectorMemory;
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::Error;
+
+    #[derive(Debug, PartialEq, CandidType, Deserialize)]
+    struct ConfigV1 {
+        limit: u32,
+    }
+
+    impl Versioned for ConfigV1 {
+        type Previous = ();
+
+        fn upgrade((): ()) -> Self {
+            Self { limit: 0 }
+        }
+    }
+
+    #[derive(Debug, PartialEq, CandidType, Deserialize)]
+    struct ConfigV2 {
+        limit: u32,
+        enabled: bool,
+    }
+
+    impl Versioned for ConfigV2 {
+        type Previous = ConfigV1;
+
+        fn upgrade(previous: ConfigV1) -> Self {
+            Self {
+                limit: previous.limit,
+                enabled: true,
+            }
+        }
+    }
+
+    #[test]
+    fn should_init_with_default_value() {
+        let cell =
+            VersionedStableCell::new(VectorMemory::default(), ConfigV1 { limit: 10 }).unwrap();
+        assert_eq!(cell.get(), &ConfigV1 { limit: 10 });
+        assert_eq!(cell.stored_version(), 1);
+    }
+
+    #[test]
+    fn should_set_and_reload_value() {
+        let memory = VectorMemory::default();
+        let mut cell = VersionedStableCell::new(memory.clone(), ConfigV1 { limit: 10 }).unwrap();
+        cell.set(ConfigV1 { limit: 42 }).unwrap();
+
+        let cell = VersionedStableCell::new(memory, ConfigV1 { limit: 10 }).unwrap();
+        assert_eq!(cell.get(), &ConfigV1 { limit: 42 });
+    }
+
+    #[test]
+    fn should_upgrade_stored_value() {
+        let memory = VectorMemory::default();
+        let mut cell = VersionedStableCell::new(memory.clone(), ConfigV1 { limit: 10 }).unwrap();
+        cell.set(ConfigV1 { limit: 42 }).unwrap();
+
+        let default_v2 = ConfigV2 {
+            limit: 0,
+            enabled: false,
+        };
+        let cell = VersionedStableCell::new(memory, default_v2).unwrap();
+        assert_eq!(
+            cell.get(),
+            &ConfigV2 {
+                limit: 42,
+                enabled: true
+            }
+        );
+        assert_eq!(cell.stored_version(), 2);
+    }
+
+    #[test]
+    fn should_not_downgrade_stored_value() {
+        let memory = VectorMemory::default();
+        let default_v2 = ConfigV2 {
+            limit: 0,
+            enabled: false,
+        };
+        VersionedStableCell::new(memory.clone(), default_v2).unwrap();
+
+        let result = VersionedStableCell::new(memory, ConfigV1 { limit: 10 });
+        assert!(matches!(
+            result,
+            Err(Error::Versioned(ic_storage::Error::AttemptedDowngrade))
+        ));
+    }
+}
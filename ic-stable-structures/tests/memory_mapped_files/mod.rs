@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use ic_stable_structures::{
     BTreeMapStructure, IcMemoryManager, MemoryId, MemoryManager, MemoryMappedFileMemory,
-    MemoryMappedFileMemoryManager, StableBTreeMap, StableVec, VecStructure,
+    MemoryMappedFileMemoryManager, StableBTreeMap, StableVec, SyncPolicy, VecStructure,
 };
 use parking_lot::Mutex;
 use tempfile::{NamedTempFile, TempDir};
@@ -170,3 +170,21 @@ fn test_memory_mapped_file_memory_manager_saves_copy() {
     assert_eq!(map.get(&2), Some(3));
     assert_eq!(map.get(&4), Some(5));
 }
+
+#[test]
+fn test_memory_mapped_file_memory_manager_flushes_with_sync_policy() {
+    let base_dir = TempDir::new().unwrap();
+    let base_path = base_dir.into_path();
+
+    let memory_manager =
+        MemoryMappedFileMemoryManager::new(base_path, true).with_sync_policy(SyncPolicy::OnWrite);
+
+    let memory = memory_manager.get(0);
+    let mut vec = StableVec::<u32, _>::new(memory.clone()).unwrap();
+    vec.push(&1).unwrap();
+    vec.push(&2).unwrap();
+
+    assert!(memory.take_sync_error().is_none());
+    memory.flush_range(0, 8).unwrap();
+    memory_manager.flush_all().unwrap();
+}
@@ -186,13 +186,18 @@ fn read_version() -> Result<u32> {
 /// Load a [`Versioned`] from stable storage.
 pub fn read<T: Versioned>() -> Result<T> {
     let version = read_version()?;
+    let bytes = stable_bytes();
+    upgrade_from_bytes::<T>(version, &bytes[VERSION_SIZE..])
+}
+
+/// Decode a [`Versioned`] serialized with the given `version`.
+/// If the `version` is older than the version of `T`, the value is upgraded.
+pub fn upgrade_from_bytes<T: Versioned>(version: u32, bytes: &[u8]) -> Result<T> {
     if T::version() < version {
         return Err(Error::AttemptedDowngrade);
     }
 
-    let bytes = stable_bytes();
-    let res = recursive_upgrade::<T>(version, &bytes[VERSION_SIZE..])?;
-    Ok(res)
+    recursive_upgrade::<T>(version, bytes)
 }
 
 /// Write a [`Versioned`] to stable storage.
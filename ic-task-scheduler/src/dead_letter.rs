@@ -0,0 +1,248 @@
+use std::sync::Arc;
+
+use ic_stable_structures::{BTreeMapStructure, IterableSortedMapStructure};
+use log::debug;
+use parking_lot::Mutex;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::scheduler::TaskScheduler;
+use crate::task::{InnerScheduledTask, ScheduledTask, Task, TaskStatus};
+
+/// A dead-letter queue stores the tasks that failed permanently, i.e. tasks that exhausted their
+/// retry policy or returned an unrecoverable error.
+///
+/// Each entry is keyed by the id the task had in the scheduler and holds the task payload, its
+/// options (including the number of failed executions) and the `TaskStatus::Failed` status with
+/// the last error.
+///
+/// The queue is attached to a scheduler with `Scheduler::set_dead_letter_queue`. The queue is a
+/// handle to the underlying storage, so it can be cloned and kept by the canister to inspect,
+/// re-enqueue or purge the entries.
+pub struct DeadLetterQueue<T, D>
+where
+    T: 'static + Task,
+    D: 'static
+        + IterableSortedMapStructure<u64, InnerScheduledTask<T>>
+        + BTreeMapStructure<u64, InnerScheduledTask<T>>,
+{
+    entries: Arc<Mutex<D>>,
+    phantom: std::marker::PhantomData<T>,
+}
+
+impl<T, D> DeadLetterQueue<T, D>
+where
+    T: 'static + Task + Serialize + DeserializeOwned + Clone,
+    D: 'static
+        + IterableSortedMapStructure<u64, InnerScheduledTask<T>>
+        + BTreeMapStructure<u64, InnerScheduledTask<T>>,
+{
+    /// Create a new dead-letter queue stored in `entries`.
+    pub fn new(entries: D) -> Self {
+        Self {
+            entries: Arc::new(Mutex::new(entries)),
+            phantom: std::marker::PhantomData,
+        }
+    }
+
+    /// Returns the number of tasks in the queue.
+    pub fn len(&self) -> u64 {
+        self.entries.lock().len()
+    }
+
+    /// Returns true if the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.lock().is_empty()
+    }
+
+    /// Returns the failed task with the given id.
+    pub fn get(&self, task_id: u64) -> Option<InnerScheduledTask<T>> {
+        self.entries.lock().get(&task_id)
+    }
+
+    /// Returns up to `limit` failed tasks, ordered by id, starting from the `start_id` task id.
+    pub fn list(&self, start_id: u64, limit: usize) -> Vec<InnerScheduledTask<T>> {
+        self.entries
+            .lock()
+            .range(start_id..)
+            .take(limit)
+            .map(|(_, task)| task)
+            .collect()
+    }
+
+    /// Removes the failed task with the given id from the queue and appends it to the `scheduler`
+    /// with its original options. The failures count and the execution timestamp are reset.
+    ///
+    /// Returns the new id of the task in the scheduler, or `None` if the task is not in the queue.
+    pub fn requeue(&self, task_id: u64, scheduler: &dyn TaskScheduler<T>) -> Option<u64> {
+        let task = self.entries.lock().remove(&task_id)?;
+
+        let mut options = task.options;
+        options.failures = 0;
+        options.execute_after_timestamp_in_secs = 0;
+
+        let new_id = scheduler.append_task(ScheduledTask::with_options(task.task, options));
+        debug!(
+            "Dead-letter queue - Task {} re-enqueued with id {}",
+            task_id, new_id
+        );
+        Some(new_id)
+    }
+
+    /// Removes the failed task with the given id from the queue.
+    pub fn purge(&self, task_id: u64) -> Option<InnerScheduledTask<T>> {
+        self.entries.lock().remove(&task_id)
+    }
+
+    /// Removes all the tasks from the queue. Returns the number of removed tasks.
+    pub fn purge_all(&self) -> u64 {
+        let mut entries = self.entries.lock();
+        let mut count = 0;
+        while entries.pop_first().is_some() {
+            count += 1;
+        }
+        count
+    }
+
+    /// Stores the task in the queue if it failed permanently.
+    pub(crate) fn push(&self, task: &InnerScheduledTask<T>) {
+        if let TaskStatus::Failed { .. } = task.status {
+            debug!("Dead-letter queue - Task {} stored", task.id);
+            self.entries.lock().insert(task.id, task.clone());
+        }
+    }
+}
+
+impl<T, D> Clone for DeadLetterQueue<T, D>
+where
+    T: 'static + Task,
+    D: 'static
+        + IterableSortedMapStructure<u64, InnerScheduledTask<T>>
+        + BTreeMapStructure<u64, InnerScheduledTask<T>>,
+{
+    fn clone(&self) -> Self {
+        Self {
+            entries: self.entries.clone(),
+            phantom: self.phantom,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::time::Duration;
+
+    use ic_stable_structures::{StableBTreeMap, StableCell, VectorMemory};
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::scheduler::Scheduler;
+    use crate::task::TaskOptions;
+    use crate::SchedulerError;
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+    struct FailingTask {
+        id: u64,
+    }
+
+    impl Task for FailingTask {
+        type Ctx = ();
+
+        fn execute(
+            &self,
+            _context: Self::Ctx,
+            _task_scheduler: Box<dyn 'static + TaskScheduler<Self>>,
+        ) -> Pin<Box<dyn Future<Output = Result<(), SchedulerError>>>> {
+            Box::pin(async move { Err(SchedulerError::TaskExecutionFailed("failed".to_string())) })
+        }
+    }
+
+    #[tokio::test]
+    async fn should_store_failed_tasks() {
+        let local = tokio::task::LocalSet::new();
+        local
+            .run_until(async move {
+                let map = StableBTreeMap::new(VectorMemory::default());
+                let sequence = StableCell::new(VectorMemory::default(), 0).unwrap();
+                let mut scheduler = Scheduler::new(map, sequence);
+                let dead_letters =
+                    DeadLetterQueue::new(StableBTreeMap::new(VectorMemory::default()));
+                scheduler.set_dead_letter_queue(dead_letters.clone());
+
+                let id = scheduler.append_task(
+                    (
+                        FailingTask { id: 1 },
+                        TaskOptions::new()
+                            .with_max_retries_policy(1)
+                            .with_fixed_backoff_policy(0),
+                    )
+                        .into(),
+                );
+
+                for _ in 0..2 {
+                    scheduler.run(()).unwrap();
+                    tokio::time::sleep(Duration::from_millis(25)).await;
+                }
+
+                assert!(scheduler.get_task(id).is_none());
+                assert_eq!(dead_letters.len(), 1);
+
+                let failed = dead_letters.get(id).unwrap();
+                assert_eq!(failed.task(), &FailingTask { id: 1 });
+                assert_eq!(failed.options().failures, 2);
+                assert!(matches!(
+                    failed.status(),
+                    TaskStatus::Failed {
+                        error: SchedulerError::TaskExecutionFailed(_),
+                        ..
+                    }
+                ));
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn should_requeue_and_purge_failed_tasks() {
+        let local = tokio::task::LocalSet::new();
+        local
+            .run_until(async move {
+                let map = StableBTreeMap::new(VectorMemory::default());
+                let sequence = StableCell::new(VectorMemory::default(), 0).unwrap();
+                let mut scheduler = Scheduler::new(map, sequence);
+                let dead_letters =
+                    DeadLetterQueue::new(StableBTreeMap::new(VectorMemory::default()));
+                scheduler.set_dead_letter_queue(dead_letters.clone());
+
+                for id in 0..3 {
+                    scheduler.append_task(FailingTask { id }.into());
+                }
+                scheduler.run(()).unwrap();
+                tokio::time::sleep(Duration::from_millis(25)).await;
+
+                assert_eq!(dead_letters.len(), 3);
+                assert_eq!(
+                    dead_letters
+                        .list(1, 10)
+                        .iter()
+                        .map(|task| task.id())
+                        .collect::<Vec<_>>(),
+                    vec![1, 2]
+                );
+
+                let new_id = dead_letters.requeue(0, &scheduler).unwrap();
+                assert_eq!(new_id, 3);
+                assert_eq!(dead_letters.len(), 2);
+                let requeued = scheduler.get_task(new_id).unwrap();
+                assert_eq!(requeued.task(), &FailingTask { id: 0 });
+                assert_eq!(requeued.options().failures, 0);
+                assert!(dead_letters.requeue(0, &scheduler).is_none());
+
+                assert!(dead_letters.purge(1).is_some());
+                assert_eq!(dead_letters.purge_all(), 1);
+                assert!(dead_letters.is_empty());
+            })
+            .await;
+    }
+}
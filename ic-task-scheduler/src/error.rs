@@ -17,6 +17,12 @@ pub enum SchedulerError {
     /// retry policy and will be considered failed right away.
     #[error("Unrecoverable task error: {0}")]
     Unrecoverable(String),
+
+    /// The task execution took more than the execution timeout set for the task.
+    ///
+    /// The task is retried according to its retry policy.
+    #[error("Task execution exceeded the timeout of {0} seconds")]
+    DeadlineExceeded(u64),
 }
 
 /// Result type for the scheduler
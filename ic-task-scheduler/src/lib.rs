@@ -1,4 +1,6 @@
+pub mod dead_letter;
 mod error;
+pub mod rate_limit;
 pub mod retry;
 pub mod scheduler;
 pub mod task;
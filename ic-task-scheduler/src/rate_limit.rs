@@ -0,0 +1,85 @@
+use std::collections::VecDeque;
+
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+/// Max number of task executions allowed in a time window.
+#[derive(CandidType, Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    /// The max number of executions started in the window.
+    pub max_executions: u32,
+    /// The length of the window in seconds.
+    pub window_secs: u64,
+}
+
+impl RateLimit {
+    pub fn new(max_executions: u32, window_secs: u64) -> Self {
+        Self {
+            max_executions,
+            window_secs,
+        }
+    }
+}
+
+/// Tracks the executions started in the sliding window of a [`RateLimit`].
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    limit: RateLimit,
+    /// Start timestamps of the executions in the current window, from the oldest
+    executions: VecDeque<u64>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            executions: VecDeque::new(),
+        }
+    }
+
+    /// Registers an execution started at `now_timestamp_secs` if the limit allows it.
+    /// Returns false if the limit is reached.
+    pub fn try_acquire(&mut self, now_timestamp_secs: u64) -> bool {
+        while let Some(timestamp_secs) = self.executions.front() {
+            if timestamp_secs.saturating_add(self.limit.window_secs) > now_timestamp_secs {
+                break;
+            }
+            self.executions.pop_front();
+        }
+
+        if self.executions.len() >= self.limit.max_executions as usize {
+            return false;
+        }
+
+        self.executions.push_back(now_timestamp_secs);
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_limit_executions_in_window() {
+        let mut limiter = RateLimiter::new(RateLimit::new(2, 60));
+
+        assert!(limiter.try_acquire(100));
+        assert!(limiter.try_acquire(110));
+        assert!(!limiter.try_acquire(120));
+        assert!(!limiter.try_acquire(159));
+
+        // The first execution is out of the window
+        assert!(limiter.try_acquire(160));
+        assert!(!limiter.try_acquire(169));
+        assert!(limiter.try_acquire(170));
+    }
+
+    #[test]
+    fn should_reject_all_executions_if_max_is_zero() {
+        let mut limiter = RateLimiter::new(RateLimit::new(0, 60));
+
+        assert!(!limiter.try_acquire(100));
+        assert!(!limiter.try_acquire(1000));
+    }
+}
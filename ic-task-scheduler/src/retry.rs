@@ -1,4 +1,5 @@
 use core::fmt::Debug;
+use std::cell::Cell;
 
 use candid::CandidType;
 use ic_kit::ic;
@@ -81,6 +82,18 @@ pub enum BackoffPolicy {
         // The multiplier to use to generate the next backoff interval from the last.
         multiplier: u32,
     },
+    /// Exponential backoff with a maximum wait time and a random jitter. A random part of the wait
+    /// time is removed, so that the retries of tasks failing at the same time are spread over time.
+    ExponentialWithJitter {
+        /// The period to sleep on the first backoff.
+        secs: u32,
+        /// The multiplier to use to generate the next backoff interval from the last.
+        multiplier: u32,
+        /// The maximum period to sleep between two retry attempts.
+        max_secs: u32,
+        /// The maximum percentage of the wait time removed by the jitter, from 0 to 100.
+        jitter_percent: u8,
+    },
 }
 
 impl BackoffPolicy {
@@ -99,18 +112,54 @@ impl BackoffPolicy {
                     option_wait_secs.cloned().unwrap_or_default()
                 }
                 BackoffPolicy::Exponential { secs, multiplier } => {
-                    if *secs > 0 {
-                        let multiplier = multiplier.saturating_pow(failed_attempts - 1);
-                        secs.saturating_mul(multiplier)
-                    } else {
-                        0
-                    }
+                    exponential_wait(*secs, *multiplier, failed_attempts)
+                }
+                BackoffPolicy::ExponentialWithJitter {
+                    secs,
+                    multiplier,
+                    max_secs,
+                    jitter_percent,
+                } => {
+                    let wait = exponential_wait(*secs, *multiplier, failed_attempts).min(*max_secs);
+                    let max_jitter = wait as u64 * (*jitter_percent).min(100) as u64 / 100;
+                    let jitter = jitter_random() % (max_jitter + 1);
+                    wait - jitter as u32
                 }
             }
         }
     }
 }
 
+fn exponential_wait(secs: u32, multiplier: u32, failed_attempts: u32) -> u32 {
+    if secs > 0 {
+        let multiplier = multiplier.saturating_pow(failed_attempts - 1);
+        secs.saturating_mul(multiplier)
+    } else {
+        0
+    }
+}
+
+thread_local! {
+    static JITTER_STATE: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Returns a pseudo-random number for the backoff jitter.
+/// The generator is seeded with the IC time, so it can be controlled in tests with a MockContext.
+fn jitter_random() -> u64 {
+    JITTER_STATE.with(|state| {
+        let mut x = state.get();
+        if x == 0 {
+            x = ic::time() | 1;
+        }
+        // xorshift64
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        state.set(x);
+        x
+    })
+}
+
 #[cfg(test)]
 pub mod test {
     use ic_kit::{Context, MockContext};
@@ -326,6 +375,45 @@ pub mod test {
         );
     }
 
+    #[test]
+    fn backoff_policy_exponential_with_jitter_should_return_the_wait_time() {
+        MockContext::new().inject();
+
+        let policy = BackoffPolicy::ExponentialWithJitter {
+            secs: 100,
+            multiplier: 2,
+            max_secs: 1000,
+            jitter_percent: 0,
+        };
+        assert_eq!(0, policy.should_wait(0));
+        assert_eq!(100, policy.should_wait(1));
+        assert_eq!(200, policy.should_wait(2));
+        assert_eq!(800, policy.should_wait(4));
+        assert_eq!(1000, policy.should_wait(5));
+        assert_eq!(1000, policy.should_wait(u32::MAX));
+
+        let policy = BackoffPolicy::ExponentialWithJitter {
+            secs: 100,
+            multiplier: 2,
+            max_secs: 1000,
+            jitter_percent: 50,
+        };
+        assert_eq!(0, policy.should_wait(0));
+        for failed_attempts in 1..10 {
+            let max_wait = 100u32
+                .saturating_mul(2u32.pow(failed_attempts - 1))
+                .min(1000);
+            let wait = policy.should_wait(failed_attempts);
+            assert!(wait <= max_wait);
+            assert!(wait >= max_wait / 2);
+        }
+
+        let waits = (0..20)
+            .map(|_| policy.should_wait(5))
+            .collect::<std::collections::HashSet<_>>();
+        assert!(waits.len() > 1);
+    }
+
     #[test]
     fn retry_policy_should_return_whether_to_retry() {
         let retry_strategy = RetryStrategy {
@@ -1,5 +1,10 @@
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
 
 use ic_stable_structures::{BTreeMapStructure, CellStructure, IterableSortedMapStructure};
 use log::{debug, warn};
@@ -7,13 +12,21 @@ use parking_lot::Mutex;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
-use crate::task::{InnerScheduledTask, ScheduledTask, Task, TaskOptions, TaskStatus};
-use crate::time::time_secs;
+use crate::dead_letter::DeadLetterQueue;
+use crate::rate_limit::{RateLimit, RateLimiter};
+use crate::task::{
+    InnerScheduledTask, ScheduledTask, Task, TaskExecutionInfo, TaskOptions, TaskStatus,
+};
+use crate::time::{time_nanos, time_secs};
 use crate::SchedulerError;
 
 type TaskCompletionCallback<T> = Box<dyn 'static + Fn(InnerScheduledTask<T>) + Send>;
+type DeadLetterSink<T> = Box<dyn 'static + Fn(&InnerScheduledTask<T>)>;
+type BoxedTaskHooks<T> = Box<dyn 'static + TaskHooks<T>>;
+type PauseStorage = Box<dyn 'static + CellStructure<u8>>;
 
 const DEFAULT_RUNNING_TASK_TIMEOUT_SECS: u64 = 120;
+const DEFAULT_FINISHED_TASKS_HISTORY_SIZE: usize = 100;
 
 /// A scheduler is responsible for executing tasks.
 pub struct Scheduler<T, P, S>
@@ -27,7 +40,25 @@ where
     pending_tasks: Arc<Mutex<P>>,
     phantom: std::marker::PhantomData<T>,
     on_completion_callback: Arc<Option<TaskCompletionCallback<T>>>,
+    dead_letter_queue: Arc<Option<DeadLetterSink<T>>>,
+    task_hooks: Arc<Option<BoxedTaskHooks<T>>>,
     running_task_timeout_secs: AtomicU64,
+    /// The max number of tasks started by a single run
+    max_tasks_per_run: AtomicUsize,
+    /// The max number of tasks that can be scheduled or running at the same time
+    max_concurrent_tasks: AtomicUsize,
+    /// The execution state of the last finished tasks
+    finished_tasks: Arc<Mutex<VecDeque<TaskExecutionInfo>>>,
+    /// The max number of finished tasks kept in the history
+    finished_tasks_history_size: AtomicUsize,
+    /// The running tasks to be dropped once their execution completes
+    cancelled_tasks: Arc<Mutex<BTreeSet<u64>>>,
+    /// Whether the scheduler is paused
+    paused: Arc<AtomicBool>,
+    /// The storage where the paused state is persisted
+    pause_storage: Arc<Mutex<Option<PauseStorage>>>,
+    /// The execution rate limits of the tagged tasks
+    tag_rate_limits: Arc<Mutex<BTreeMap<String, RateLimiter>>>,
     /// The next scheduled task id
     task_id_sequence: Arc<Mutex<S>>,
 }
@@ -49,7 +80,17 @@ where
             pending_tasks: Arc::new(Mutex::new(pending_tasks)),
             phantom: std::marker::PhantomData,
             on_completion_callback: Arc::new(None),
+            dead_letter_queue: Arc::new(None),
+            task_hooks: Arc::new(None),
             running_task_timeout_secs: AtomicU64::new(DEFAULT_RUNNING_TASK_TIMEOUT_SECS),
+            max_tasks_per_run: AtomicUsize::new(usize::MAX),
+            max_concurrent_tasks: AtomicUsize::new(usize::MAX),
+            finished_tasks: Arc::new(Mutex::new(VecDeque::new())),
+            finished_tasks_history_size: AtomicUsize::new(DEFAULT_FINISHED_TASKS_HISTORY_SIZE),
+            cancelled_tasks: Arc::new(Mutex::new(BTreeSet::new())),
+            paused: Arc::new(AtomicBool::new(false)),
+            pause_storage: Arc::new(Mutex::new(None)),
+            tag_rate_limits: Arc::new(Mutex::new(BTreeMap::new())),
             task_id_sequence: Arc::new(Mutex::new(task_id_sequence)),
         }
     }
@@ -63,6 +104,194 @@ where
             .store(timeout_secs, Ordering::Relaxed);
     }
 
+    /// Set the max number of tasks started by a single run. The tasks ready to be executed over
+    /// the limit stay in the waiting status and are started by the next runs.
+    /// By default the number of tasks is not limited.
+    pub fn set_max_tasks_per_run(&mut self, max_tasks: usize) {
+        debug!("Setting max tasks per run to {}", max_tasks);
+        self.max_tasks_per_run.store(max_tasks, Ordering::Relaxed);
+    }
+
+    /// Set the max number of tasks that can be scheduled or running at the same time. A run does
+    /// not start new tasks while this number of tasks is in flight.
+    /// By default the number of tasks is not limited.
+    pub fn set_max_concurrent_tasks(&mut self, max_tasks: usize) {
+        debug!("Setting max concurrent tasks to {}", max_tasks);
+        self.max_concurrent_tasks
+            .store(max_tasks, Ordering::Relaxed);
+    }
+
+    /// Set the max number of finished tasks whose execution state is kept in memory and returned
+    /// by [`Scheduler::task_info`]. The oldest finished tasks are dropped first.
+    /// The default value is 100.
+    pub fn set_finished_tasks_history_size(&mut self, size: usize) {
+        debug!("Setting finished tasks history size to {}", size);
+        self.finished_tasks_history_size
+            .store(size, Ordering::Relaxed);
+
+        let mut finished_tasks = self.finished_tasks.lock();
+        while finished_tasks.len() > size {
+            finished_tasks.pop_front();
+        }
+    }
+
+    /// Returns the execution state of the task with the given id.
+    ///
+    /// Tasks that are not in the scheduler anymore are found only if they are still in the
+    /// finished tasks history.
+    pub fn task_info(&self, task_id: u64) -> Option<TaskExecutionInfo> {
+        if let Some(task) = self.pending_tasks.lock().get(&task_id) {
+            return Some(task.execution_info());
+        }
+
+        self.finished_tasks
+            .lock()
+            .iter()
+            .rev()
+            .find(|info| info.id == task_id)
+            .cloned()
+    }
+
+    /// Returns the execution state of all the pending tasks, ordered by task id.
+    ///
+    /// NOTE: Iterating over all tasks requires loading them one by once from IC stable memory
+    /// (if stable memory is used for scheduler), which can be slow in case there are many pending
+    /// tasks in the scheduler.
+    pub fn pending_tasks_info(&self) -> Vec<TaskExecutionInfo> {
+        self.pending_tasks
+            .lock()
+            .iter()
+            .map(|(_, task)| task.execution_info())
+            .collect()
+    }
+
+    /// Returns the execution state of the last finished tasks, from the oldest to the newest.
+    pub fn finished_tasks_info(&self) -> Vec<TaskExecutionInfo> {
+        self.finished_tasks.lock().iter().cloned().collect()
+    }
+
+    /// Cancel the task with the given id.
+    ///
+    /// A task waiting for its execution is removed from the scheduler right away. A running task
+    /// completes its current execution, then it is removed from the scheduler without being
+    /// retried. In both cases the completion callback is called with the
+    /// [`TaskStatus::Cancelled`] status.
+    ///
+    /// Returns false if the task doesn't exist.
+    pub fn cancel(&self, task_id: u64) -> bool {
+        let mut lock = self.pending_tasks.lock();
+        let Some(mut task) = lock.get(&task_id) else {
+            return false;
+        };
+
+        if let TaskStatus::Running { .. } = task.status {
+            debug!(
+                "Scheduler - Task {} will be cancelled after the current execution",
+                task_id
+            );
+            self.cancelled_tasks.lock().insert(task_id);
+            return true;
+        }
+
+        debug!("Scheduler - Task {} cancelled", task_id);
+        lock.remove(&task_id);
+        drop(lock);
+
+        task.status = TaskStatus::cancelled(time_secs());
+        self.task_finished(task);
+        true
+    }
+
+    /// Cancel all the tasks with the given tag. See [`Scheduler::cancel`] for details.
+    ///
+    /// Returns the ids of the cancelled tasks.
+    ///
+    /// NOTE: Iterating over all tasks requires loading them one by once from IC stable memory
+    /// (if stable memory is used for scheduler), which can be slow in case there are many pending
+    /// tasks in the scheduler.
+    pub fn cancel_by_tag(&self, tag: &str) -> Vec<u64> {
+        let task_ids: Vec<u64> = self
+            .pending_tasks
+            .lock()
+            .iter()
+            .filter(|(_, task)| task.options.tag() == Some(tag))
+            .map(|(id, _)| id)
+            .collect();
+
+        task_ids
+            .into_iter()
+            .filter(|task_id| self.cancel(*task_id))
+            .collect()
+    }
+
+    /// Set the queue where the tasks that failed permanently are stored.
+    /// By default the failed tasks are only passed to the completion callback.
+    pub fn set_dead_letter_queue<D>(&mut self, queue: DeadLetterQueue<T, D>)
+    where
+        D: 'static
+            + IterableSortedMapStructure<u64, InnerScheduledTask<T>>
+            + BTreeMapStructure<u64, InnerScheduledTask<T>>,
+    {
+        self.dead_letter_queue = Arc::new(Some(Box::new(move |task| queue.push(task))));
+    }
+
+    /// Set the hooks called after every task execution.
+    pub fn set_task_hooks<H: 'static + TaskHooks<T>>(&mut self, hooks: H) {
+        self.task_hooks = Arc::new(Some(Box::new(hooks)));
+    }
+
+    /// Set the storage where the paused state of the scheduler is persisted, so that it survives
+    /// canister upgrades. The stored value is 1 if the scheduler is paused, 0 otherwise.
+    /// The scheduler state is loaded from the storage.
+    pub fn set_pause_storage<C: 'static + CellStructure<u8>>(&mut self, storage: C) {
+        self.paused.store(*storage.get() != 0, Ordering::Relaxed);
+        *self.pause_storage.lock() = Some(Box::new(storage));
+    }
+
+    /// Pause the scheduler. While paused, the runs don't start any task and the pending tasks stay
+    /// in the scheduler. The tasks already running complete their current execution.
+    pub fn pause(&self) {
+        debug!("Scheduler - Paused");
+        self.set_paused(true);
+    }
+
+    /// Resume the scheduler after a [`Scheduler::pause`].
+    pub fn resume(&self) {
+        debug!("Scheduler - Resumed");
+        self.set_paused(false);
+    }
+
+    /// Returns true if the scheduler is paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+        if let Some(storage) = self.pause_storage.lock().as_mut() {
+            storage
+                .set(paused as u8)
+                .expect("Unable to access the stable storage to set the paused state");
+        }
+    }
+
+    /// Limit the executions of the tasks with the given tag. The tasks ready to be executed over
+    /// the limit stay in the waiting status and are started by the next runs, once the limit
+    /// allows it. Retries of failed tasks count as executions.
+    pub fn set_tag_rate_limit(&self, tag: impl Into<String>, limit: RateLimit) {
+        let tag = tag.into();
+        debug!("Setting rate limit of tag {} to {:?}", tag, limit);
+        self.tag_rate_limits
+            .lock()
+            .insert(tag, RateLimiter::new(limit));
+    }
+
+    /// Remove the execution limit of the tasks with the given tag.
+    pub fn remove_tag_rate_limit(&self, tag: &str) {
+        debug!("Removing rate limit of tag {}", tag);
+        self.tag_rate_limits.lock().remove(tag);
+    }
+
     /// Set a callback to be called when a task execution completes.
     pub fn on_completion_callback<F: 'static + Send + Fn(InnerScheduledTask<T>)>(&mut self, cb: F) {
         self.on_completion_callback = Arc::new(Some(Box::new(cb)));
@@ -71,6 +300,9 @@ where
     /// Execute all pending tasks.
     /// Each task is executed asynchronously in a dedicated ic_cdk::spawn call.
     /// This function does not wait for the tasks to complete.
+    /// If the scheduler is paused, no task is launched.
+    /// The number of launched tasks is bounded by the max tasks per run and max concurrent tasks
+    /// limits, the remaining tasks are launched by the next runs.
     /// Returns the number of tasks that have been launched.
     pub fn run(&self, ctx: T::Ctx) -> Result<usize, SchedulerError> {
         self.run_with_timestamp(ctx, time_secs())
@@ -81,10 +313,16 @@ where
         context: T::Ctx,
         now_timestamp_secs: u64,
     ) -> Result<usize, SchedulerError> {
+        if self.is_paused() {
+            debug!("Scheduler - Paused, no task is launched");
+            return Ok(0);
+        }
+
         debug!("Scheduler - Running tasks");
-        let mut to_be_scheduled_tasks = Vec::new();
+        let mut ready_tasks = Vec::new();
         let mut out_of_time_tasks = Vec::new();
         let running_task_timeout_secs = self.running_task_timeout_secs.load(Ordering::Relaxed);
+        let mut in_flight_tasks = 0usize;
 
         {
             let lock = self.pending_tasks.lock();
@@ -92,8 +330,7 @@ where
                 match task.status {
                     TaskStatus::Waiting { .. } => {
                         if task.options.execute_after_timestamp_in_secs <= now_timestamp_secs {
-                            debug!("Scheduler - Task {} scheduled to be processed", task_key);
-                            to_be_scheduled_tasks.push(task_key);
+                            ready_tasks.push((task_key, task.options.tag));
                         }
                     }
                     TaskStatus::Running { timestamp_secs }
@@ -104,12 +341,52 @@ where
                                 task_key, running_task_timeout_secs
                             );
                             out_of_time_tasks.push(task_key);
+                        } else {
+                            in_flight_tasks += 1;
                         }
                     }
                     TaskStatus::Completed { .. }
                     | TaskStatus::TimeoutOrPanic { .. }
-                    | TaskStatus::Failed { .. } => (),
+                    | TaskStatus::Failed { .. }
+                    | TaskStatus::Cancelled { .. } => (),
+                }
+            }
+        }
+
+        // Limit the number of tasks started by this run, the others stay in the waiting status
+        let max_tasks = self.max_tasks_per_run.load(Ordering::Relaxed).min(
+            self.max_concurrent_tasks
+                .load(Ordering::Relaxed)
+                .saturating_sub(in_flight_tasks),
+        );
+        let mut to_be_scheduled_tasks = Vec::with_capacity(ready_tasks.len().min(max_tasks));
+        {
+            let mut tag_rate_limits = self.tag_rate_limits.lock();
+            for (task_key, tag) in ready_tasks {
+                if to_be_scheduled_tasks.len() >= max_tasks {
+                    debug!(
+                        "Scheduler - {} tasks started by this run, the others are postponed to the next run",
+                        max_tasks
+                    );
+                    break;
+                }
+
+                let limiter = match tag {
+                    Some(tag) => tag_rate_limits.get_mut(&tag),
+                    None => None,
+                };
+                if let Some(limiter) = limiter {
+                    if !limiter.try_acquire(now_timestamp_secs) {
+                        debug!(
+                            "Scheduler - Task {} postponed by the rate limit of its tag",
+                            task_key
+                        );
+                        continue;
+                    }
                 }
+
+                debug!("Scheduler - Task {} scheduled to be processed", task_key);
+                to_be_scheduled_tasks.push(task_key);
             }
         }
 
@@ -123,10 +400,9 @@ where
             let mut lock = self.pending_tasks.lock();
             for task_key in out_of_time_tasks.into_iter() {
                 if let Some(mut task) = lock.remove(&task_key) {
+                    self.cancelled_tasks.lock().remove(&task_key);
                     task.status = TaskStatus::timeout_or_panic(now_timestamp_secs);
-                    if let Some(cb) = &*self.on_completion_callback {
-                        cb(task);
-                    }
+                    self.task_finished(task);
                 }
             }
         }
@@ -169,17 +445,55 @@ where
                         .lock()
                         .insert(task_key, task.clone());
 
-                    let completed_task = match task
-                        .task
-                        .execute(context, Box::new(task_scheduler.clone()))
-                        .await
-                    {
+                    let started_at_nanos = time_nanos();
+                    let execution = task.task.execute(context, Box::new(task_scheduler.clone()));
+                    let result = match task.options.execution_timeout_secs {
+                        Some(timeout_secs) => {
+                            ExecutionDeadline {
+                                execution,
+                                deadline_secs: now_timestamp_secs.saturating_add(timeout_secs),
+                                timeout_secs,
+                            }
+                            .await
+                        }
+                        None => execution.await,
+                    };
+
+                    let duration =
+                        Duration::from_nanos(time_nanos().saturating_sub(started_at_nanos));
+                    let execution_error = result.as_ref().err().cloned();
+                    let executed_task = task.task.clone();
+                    let mut will_retry = false;
+
+                    let cancelled = task_scheduler.cancelled_tasks.lock().remove(&task_key);
+                    let completed_task = match result {
+                        _ if cancelled => {
+                            debug!("Scheduler - Task {} execution completed. Status changed: Running -> Cancelled", task_key);
+                            task_scheduler.pending_tasks.lock().remove(&task_key);
+                            task.status = TaskStatus::cancelled(now_timestamp_secs);
+                            Some(task)
+                        }
                         Ok(()) => {
-                            debug!("Scheduler - Task {} execution succeeded. Status changed: Running -> Completed", task_key);
                             let mut lock = task_scheduler.pending_tasks.lock();
                             let mut task = lock.remove(&task_key).unwrap();
-                            task.status = TaskStatus::completed(now_timestamp_secs);
-                            Some(task)
+                            if let Some(recurrence) = task
+                                .options
+                                .recurrence
+                                .and_then(|recurrence| recurrence.next())
+                            {
+                                debug!("Scheduler - Task {} execution succeeded. Next execution in {} seconds. Status changed: Running -> Waiting", task_key, recurrence.interval_secs);
+                                task.options.recurrence = Some(recurrence);
+                                task.options.failures = 0;
+                                task.options.execute_after_timestamp_in_secs =
+                                    now_timestamp_secs + recurrence.interval_secs;
+                                task.status = TaskStatus::waiting(now_timestamp_secs);
+                                lock.insert(task_key, task);
+                                None
+                            } else {
+                                debug!("Scheduler - Task {} execution succeeded. Status changed: Running -> Completed", task_key);
+                                task.status = TaskStatus::completed(now_timestamp_secs);
+                                Some(task)
+                            }
                         }
                         Err(err) => {
                             let mut lock = task_scheduler.pending_tasks.lock();
@@ -195,6 +509,7 @@ where
                                     .should_retry(task.options.failures),
                             };
 
+                            will_retry = should_retry;
                             if should_retry {
                                 debug!("Scheduler - Task {} execution failed. Execution will be retried. Status changed: Running -> Waiting", task_key);
                                 task.options.execute_after_timestamp_in_secs =
@@ -204,23 +519,57 @@ where
                                 None
                             } else {
                                 debug!("Scheduler - Task {} execution failed. Status changed: Running -> Failed", task_key);
-                                let mut task = lock.remove(&task_key).unwrap();
+                                // Keep the failures count of the last execution
+                                lock.remove(&task_key);
                                 task.status = TaskStatus::failed(now_timestamp_secs, err);
                                 Some(task)
                             }
                         }
                     };
 
-                    if let Some(task) = completed_task {
-                        if let Some(cb) = &*task_scheduler.on_completion_callback {
-                            cb(task);
+                    // The hooks are called without holding the locks, so they can use the scheduler
+                    if let Some(hooks) = &*task_scheduler.task_hooks {
+                        match execution_error {
+                            None => hooks.on_success(task_key, &executed_task, duration),
+                            Some(err) => hooks.on_failure(
+                                task_key,
+                                &executed_task,
+                                duration,
+                                &err,
+                                will_retry,
+                            ),
                         }
                     }
+
+                    if let Some(task) = completed_task {
+                        task_scheduler.task_finished(task);
+                    }
                 }
             }
         });
     }
 
+    /// Stores the execution state of a task removed from the scheduler and calls the completion
+    /// callback.
+    fn task_finished(&self, task: InnerScheduledTask<T>) {
+        let history_size = self.finished_tasks_history_size.load(Ordering::Relaxed);
+        if history_size > 0 {
+            let mut finished_tasks = self.finished_tasks.lock();
+            if finished_tasks.len() >= history_size {
+                finished_tasks.pop_front();
+            }
+            finished_tasks.push_back(task.execution_info());
+        }
+
+        if let Some(dead_letter_queue) = &*self.dead_letter_queue {
+            dead_letter_queue(&task);
+        }
+
+        if let Some(cb) = &*self.on_completion_callback {
+            cb(task);
+        }
+    }
+
     /// Returns the next task id.
     fn next_task_id(&self) -> u64 {
         let mut lock = self.task_id_sequence.lock();
@@ -247,6 +596,50 @@ where
     }
 }
 
+/// Task execution aborted at the first await point after the deadline.
+struct ExecutionDeadline {
+    execution: Pin<Box<dyn Future<Output = Result<(), SchedulerError>>>>,
+    deadline_secs: u64,
+    timeout_secs: u64,
+}
+
+impl Future for ExecutionDeadline {
+    type Output = Result<(), SchedulerError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        match this.execution.as_mut().poll(cx) {
+            Poll::Pending if time_secs() > this.deadline_secs => {
+                warn!(
+                    "Scheduler - Task execution exceeded the timeout of {} seconds, aborting it",
+                    this.timeout_secs
+                );
+                Poll::Ready(Err(SchedulerError::DeadlineExceeded(this.timeout_secs)))
+            }
+            poll => poll,
+        }
+    }
+}
+
+/// Hooks called by the scheduler after every task execution, e.g. to collect metrics or logs, or
+/// to schedule follow-up work, without changing the task definitions.
+pub trait TaskHooks<T: Task> {
+    /// Called after a successful execution of the task with the given id.
+    fn on_success(&self, _task_id: u64, _task: &T, _duration: Duration) {}
+
+    /// Called after a failed execution of the task with the given id. `will_retry` tells whether
+    /// the execution will be retried according to the task retry policy.
+    fn on_failure(
+        &self,
+        _task_id: u64,
+        _task: &T,
+        _duration: Duration,
+        _error: &SchedulerError,
+        _will_retry: bool,
+    ) {
+    }
+}
+
 pub trait TaskScheduler<T: 'static + Task> {
     /// Append a task to the scheduler and return the key of the task.
     fn append_task(&self, task: ScheduledTask<T>) -> u64;
@@ -287,9 +680,23 @@ where
             pending_tasks: self.pending_tasks.clone(),
             phantom: self.phantom,
             on_completion_callback: self.on_completion_callback.clone(),
+            dead_letter_queue: self.dead_letter_queue.clone(),
+            task_hooks: self.task_hooks.clone(),
             running_task_timeout_secs: AtomicU64::new(
                 self.running_task_timeout_secs.load(Ordering::Relaxed),
             ),
+            max_tasks_per_run: AtomicUsize::new(self.max_tasks_per_run.load(Ordering::Relaxed)),
+            max_concurrent_tasks: AtomicUsize::new(
+                self.max_concurrent_tasks.load(Ordering::Relaxed),
+            ),
+            finished_tasks: self.finished_tasks.clone(),
+            cancelled_tasks: self.cancelled_tasks.clone(),
+            paused: self.paused.clone(),
+            pause_storage: self.pause_storage.clone(),
+            tag_rate_limits: self.tag_rate_limits.clone(),
+            finished_tasks_history_size: AtomicUsize::new(
+                self.finished_tasks_history_size.load(Ordering::Relaxed),
+            ),
             task_id_sequence: self.task_id_sequence.clone(),
         }
     }
@@ -634,6 +1041,286 @@ mod test {
         }
     }
 
+    mod test_limits {
+        use std::future::Future;
+        use std::pin::Pin;
+        use std::time::Duration;
+
+        use ic_stable_structures::{StableBTreeMap, StableCell, VectorMemory};
+        use serde::{Deserialize, Serialize};
+
+        use super::*;
+        use crate::task::Recurrence;
+
+        #[derive(Serialize, Deserialize, Debug, Clone)]
+        struct SleepingTask {
+            millis: u64,
+        }
+
+        impl Task for SleepingTask {
+            type Ctx = ();
+
+            fn execute(
+                &self,
+                _: Self::Ctx,
+                _task_scheduler: Box<dyn 'static + TaskScheduler<Self>>,
+            ) -> Pin<Box<dyn Future<Output = Result<(), SchedulerError>>>> {
+                let millis = self.millis;
+                Box::pin(async move {
+                    tokio::time::sleep(Duration::from_millis(millis)).await;
+                    Ok(())
+                })
+            }
+        }
+
+        #[tokio::test]
+        async fn test_max_tasks_per_run() {
+            let local = tokio::task::LocalSet::new();
+            local
+                .run_until(async move {
+                    let map = StableBTreeMap::new(VectorMemory::default());
+                    let sequence = StableCell::new(VectorMemory::default(), 0).unwrap();
+                    let mut scheduler = Scheduler::new(map, sequence);
+                    scheduler.set_max_tasks_per_run(2);
+
+                    for _ in 0..5 {
+                        scheduler.append_task(SleepingTask { millis: 0 }.into());
+                    }
+
+                    assert_eq!(scheduler.run(()).unwrap(), 2);
+                    tokio::time::sleep(Duration::from_millis(25)).await;
+                    assert_eq!(scheduler.pending_tasks.lock().len(), 3);
+
+                    assert_eq!(scheduler.run(()).unwrap(), 2);
+                    tokio::time::sleep(Duration::from_millis(25)).await;
+                    assert_eq!(scheduler.pending_tasks.lock().len(), 1);
+
+                    assert_eq!(scheduler.run(()).unwrap(), 1);
+                    tokio::time::sleep(Duration::from_millis(25)).await;
+                    assert!(scheduler.pending_tasks.lock().is_empty());
+                })
+                .await;
+        }
+
+        #[tokio::test]
+        async fn test_recurring_task() {
+            let local = tokio::task::LocalSet::new();
+            local
+                .run_until(async move {
+                    let map = StableBTreeMap::new(VectorMemory::default());
+                    let sequence = StableCell::new(VectorMemory::default(), 0).unwrap();
+                    let scheduler = Scheduler::new(map, sequence);
+
+                    let id = scheduler.append_task(
+                        (
+                            SleepingTask { millis: 0 },
+                            TaskOptions::new().with_recurrence(Recurrence::times(10, 3)),
+                        )
+                            .into(),
+                    );
+
+                    let timestamp = time_secs();
+                    assert_eq!(scheduler.run_with_timestamp((), timestamp).unwrap(), 1);
+                    tokio::time::sleep(Duration::from_millis(25)).await;
+
+                    let task = scheduler.get_task(id).unwrap();
+                    assert!(matches!(task.status, TaskStatus::Waiting { .. }));
+                    assert_eq!(task.options.recurrence, Some(Recurrence::times(10, 2)));
+                    assert!(task.options.execute_after_timestamp_in_secs >= timestamp + 10);
+
+                    assert_eq!(scheduler.run_with_timestamp((), timestamp + 5).unwrap(), 0);
+                    assert_eq!(scheduler.run_with_timestamp((), timestamp + 11).unwrap(), 1);
+                    tokio::time::sleep(Duration::from_millis(25)).await;
+                    assert_eq!(
+                        scheduler.get_task(id).unwrap().options.recurrence,
+                        Some(Recurrence::times(10, 1))
+                    );
+
+                    assert_eq!(scheduler.run_with_timestamp((), timestamp + 22).unwrap(), 1);
+                    tokio::time::sleep(Duration::from_millis(25)).await;
+                    assert!(scheduler.get_task(id).is_none());
+                    assert!(matches!(
+                        scheduler.task_info(id).unwrap().status,
+                        TaskStatus::Completed { .. }
+                    ));
+                })
+                .await;
+        }
+
+        #[tokio::test]
+        async fn test_tag_rate_limit() {
+            let local = tokio::task::LocalSet::new();
+            local
+                .run_until(async move {
+                    let map = StableBTreeMap::new(VectorMemory::default());
+                    let sequence = StableCell::new(VectorMemory::default(), 0).unwrap();
+                    let scheduler = Scheduler::new(map, sequence);
+                    scheduler.set_tag_rate_limit("ledger", RateLimit::new(2, 60));
+
+                    for _ in 0..3 {
+                        scheduler.append_task(
+                            (
+                                SleepingTask { millis: 0 },
+                                TaskOptions::new().with_tag("ledger"),
+                            )
+                                .into(),
+                        );
+                    }
+                    scheduler.append_task(SleepingTask { millis: 0 }.into());
+
+                    let timestamp = time_secs();
+                    assert_eq!(scheduler.run_with_timestamp((), timestamp).unwrap(), 3);
+                    tokio::time::sleep(Duration::from_millis(25)).await;
+                    assert_eq!(scheduler.pending_tasks.lock().len(), 1);
+
+                    assert_eq!(scheduler.run_with_timestamp((), timestamp + 59).unwrap(), 0);
+                    assert_eq!(scheduler.run_with_timestamp((), timestamp + 60).unwrap(), 1);
+                    tokio::time::sleep(Duration::from_millis(25)).await;
+                    assert!(scheduler.pending_tasks.lock().is_empty());
+                })
+                .await;
+        }
+
+        #[tokio::test]
+        async fn test_max_concurrent_tasks() {
+            let local = tokio::task::LocalSet::new();
+            local
+                .run_until(async move {
+                    let map = StableBTreeMap::new(VectorMemory::default());
+                    let sequence = StableCell::new(VectorMemory::default(), 0).unwrap();
+                    let mut scheduler = Scheduler::new(map, sequence);
+                    scheduler.set_max_concurrent_tasks(2);
+
+                    for _ in 0..3 {
+                        scheduler.append_task(SleepingTask { millis: 100 }.into());
+                    }
+
+                    assert_eq!(scheduler.run(()).unwrap(), 2);
+                    tokio::time::sleep(Duration::from_millis(25)).await;
+
+                    // The first tasks are still running
+                    assert_eq!(scheduler.run(()).unwrap(), 0);
+                    tokio::time::sleep(Duration::from_millis(150)).await;
+                    assert_eq!(scheduler.pending_tasks.lock().len(), 1);
+
+                    assert_eq!(scheduler.run(()).unwrap(), 1);
+                    tokio::time::sleep(Duration::from_millis(150)).await;
+                    assert!(scheduler.pending_tasks.lock().is_empty());
+                })
+                .await;
+        }
+
+        #[derive(Serialize, Deserialize, Debug, Clone)]
+        struct SlowTask {
+            steps: u32,
+        }
+
+        impl Task for SlowTask {
+            type Ctx = ();
+
+            fn execute(
+                &self,
+                _: Self::Ctx,
+                _task_scheduler: Box<dyn 'static + TaskScheduler<Self>>,
+            ) -> Pin<Box<dyn Future<Output = Result<(), SchedulerError>>>> {
+                let steps = self.steps;
+                Box::pin(async move {
+                    for _ in 0..steps {
+                        tokio::time::sleep(Duration::from_millis(600)).await;
+                    }
+                    Ok(())
+                })
+            }
+        }
+
+        #[tokio::test]
+        async fn test_execution_timeout() {
+            let local = tokio::task::LocalSet::new();
+            local
+                .run_until(async move {
+                    let map = StableBTreeMap::new(VectorMemory::default());
+                    let sequence = StableCell::new(VectorMemory::default(), 0).unwrap();
+                    let scheduler = Scheduler::new(map, sequence);
+
+                    let id = scheduler.append_task(
+                        (
+                            SlowTask { steps: 10 },
+                            TaskOptions::new().with_execution_timeout_secs(0),
+                        )
+                            .into(),
+                    );
+
+                    scheduler.run(()).unwrap();
+                    // The execution crosses the deadline within two steps
+                    tokio::time::sleep(Duration::from_millis(1500)).await;
+
+                    let info = scheduler.task_info(id).unwrap();
+                    assert_eq!(
+                        info.status,
+                        TaskStatus::failed(
+                            info.status.timestamp_secs(),
+                            SchedulerError::DeadlineExceeded(0)
+                        )
+                    );
+                })
+                .await;
+        }
+    }
+
+    mod test_pause {
+        use std::time::Duration;
+
+        use ic_stable_structures::{StableBTreeMap, StableCell, VectorMemory};
+
+        use super::*;
+        use crate::scheduler::test::test_delay::SimpleTask;
+
+        #[tokio::test]
+        async fn should_not_run_tasks_while_paused() {
+            let local = tokio::task::LocalSet::new();
+            local
+                .run_until(async move {
+                    let map = StableBTreeMap::new(VectorMemory::default());
+                    let sequence = StableCell::new(VectorMemory::default(), 0).unwrap();
+                    let scheduler = Scheduler::new(map, sequence);
+                    let task_id = scheduler.append_task(SimpleTask::StepOne { id: 0 }.into());
+
+                    scheduler.pause();
+                    assert!(scheduler.is_paused());
+                    assert_eq!(scheduler.run(()).unwrap(), 0);
+                    tokio::time::sleep(Duration::from_millis(25)).await;
+                    assert!(scheduler.get_task(task_id).is_some());
+
+                    scheduler.resume();
+                    assert!(!scheduler.is_paused());
+                    assert_eq!(scheduler.run(()).unwrap(), 1);
+                    tokio::time::sleep(Duration::from_millis(25)).await;
+                    assert!(scheduler.get_task(task_id).is_none());
+                })
+                .await;
+        }
+
+        #[test]
+        fn should_persist_paused_state() {
+            let memory = VectorMemory::default();
+
+            let map: StableBTreeMap<u64, InnerScheduledTask<SimpleTask>, _> =
+                StableBTreeMap::new(VectorMemory::default());
+            let sequence = StableCell::new(VectorMemory::default(), 0).unwrap();
+            let mut scheduler = Scheduler::new(map, sequence);
+            scheduler.set_pause_storage(StableCell::new(memory.clone(), 0).unwrap());
+            assert!(!scheduler.is_paused());
+            scheduler.pause();
+
+            let map: StableBTreeMap<u64, InnerScheduledTask<SimpleTask>, _> =
+                StableBTreeMap::new(VectorMemory::default());
+            let sequence = StableCell::new(VectorMemory::default(), 0).unwrap();
+            let mut restored = Scheduler::new(map, sequence);
+            restored.set_pause_storage(StableCell::new(memory, 0).unwrap());
+            assert!(restored.is_paused());
+        }
+    }
+
     mod test_failure_and_retry {
 
         use std::collections::HashMap;
@@ -896,6 +1583,115 @@ mod test {
                 .await;
         }
 
+        #[tokio::test]
+        async fn test_task_info_tracks_retries_and_failure() {
+            let local = tokio::task::LocalSet::new();
+            local
+                .run_until(async move {
+                    let map = StableBTreeMap::new(VectorMemory::default());
+                    let sequence = StableCell::new(VectorMemory::default(), 0).unwrap();
+                    let scheduler = Scheduler::new(map, sequence);
+                    let id = random();
+
+                    let task_id = scheduler.append_task(
+                        (
+                            SimpleTask::StepOne { id, fails: 2 },
+                            TaskOptions::new()
+                                .with_max_retries_policy(1)
+                                .with_fixed_backoff_policy(0),
+                        )
+                            .into(),
+                    );
+
+                    let info = scheduler.task_info(task_id).unwrap();
+                    assert!(matches!(info.status, TaskStatus::Waiting { .. }));
+                    assert!(!info.is_retrying());
+                    assert_eq!(info.next_execution_timestamp_secs, Some(0));
+                    assert_eq!(scheduler.pending_tasks_info(), vec![info]);
+
+                    scheduler.run(()).unwrap();
+                    tokio::time::sleep(Duration::from_millis(25)).await;
+
+                    let info = scheduler.task_info(task_id).unwrap();
+                    assert!(info.is_retrying());
+                    assert_eq!(info.failures, 1);
+
+                    scheduler.run(()).unwrap();
+                    tokio::time::sleep(Duration::from_millis(25)).await;
+
+                    let info = scheduler.task_info(task_id).unwrap();
+                    assert!(info.is_finished());
+                    assert!(matches!(info.status, TaskStatus::Failed { .. }));
+                    assert_eq!(info.failures, 2);
+                    assert_eq!(info.next_execution_timestamp_secs, None);
+                    assert!(scheduler.pending_tasks_info().is_empty());
+                    assert_eq!(scheduler.finished_tasks_info(), vec![info]);
+                })
+                .await;
+        }
+
+        #[derive(Default, Clone)]
+        struct RecordingHooks {
+            calls: Arc<Mutex<Vec<String>>>,
+        }
+
+        impl TaskHooks<SimpleTask> for RecordingHooks {
+            fn on_success(&self, task_id: u64, _task: &SimpleTask, _duration: Duration) {
+                self.calls.lock().push(format!("{task_id} - success"));
+            }
+
+            fn on_failure(
+                &self,
+                task_id: u64,
+                _task: &SimpleTask,
+                _duration: Duration,
+                error: &SchedulerError,
+                will_retry: bool,
+            ) {
+                self.calls
+                    .lock()
+                    .push(format!("{task_id} - failure: {error}, retry: {will_retry}"));
+            }
+        }
+
+        #[tokio::test]
+        async fn test_should_call_task_hooks() {
+            let local = tokio::task::LocalSet::new();
+            local
+                .run_until(async move {
+                    let map = StableBTreeMap::new(VectorMemory::default());
+                    let sequence = StableCell::new(VectorMemory::default(), 0).unwrap();
+                    let mut scheduler = Scheduler::new(map, sequence);
+                    let hooks = RecordingHooks::default();
+                    scheduler.set_task_hooks(hooks.clone());
+
+                    let id = random();
+                    let task_id = scheduler.append_task(
+                        (
+                            SimpleTask::StepOne { id, fails: 1 },
+                            TaskOptions::new()
+                                .with_max_retries_policy(1)
+                                .with_fixed_backoff_policy(0),
+                        )
+                            .into(),
+                    );
+
+                    for _ in 0..2 {
+                        scheduler.run(()).unwrap();
+                        tokio::time::sleep(Duration::from_millis(25)).await;
+                    }
+
+                    assert_eq!(
+                        *hooks.calls.lock(),
+                        vec![
+                            format!("{task_id} - failure: TaskExecutionFailed: , retry: true"),
+                            format!("{task_id} - success"),
+                        ]
+                    );
+                })
+                .await;
+        }
+
         #[tokio::test]
         async fn test_should_call_error_cb() {
             use std::sync::atomic::AtomicBool;
@@ -1319,6 +2115,139 @@ mod test {
         }
     }
 
+    mod test_cancellation {
+        use std::future::Future;
+        use std::pin::Pin;
+        use std::time::Duration;
+
+        use candid::Deserialize;
+        use ic_stable_structures::{StableBTreeMap, StableCell, VectorMemory};
+        use tokio::sync::Notify;
+
+        use super::*;
+
+        thread_local! {
+            static COMPLETE: Arc<Notify> = Arc::new(Notify::new());
+        }
+
+        /// Task failing once it's notified to complete
+        #[derive(Serialize, Deserialize, Debug, Clone)]
+        struct AwaitingTask {}
+
+        impl Task for AwaitingTask {
+            type Ctx = ();
+
+            fn execute(
+                &self,
+                _context: Self::Ctx,
+                _task_scheduler: Box<dyn 'static + TaskScheduler<Self>>,
+            ) -> Pin<Box<dyn Future<Output = Result<(), SchedulerError>>>> {
+                let complete = COMPLETE.with(|v| v.clone());
+                Box::pin(async move {
+                    complete.notified().await;
+                    Err(SchedulerError::TaskExecutionFailed("".to_string()))
+                })
+            }
+        }
+
+        #[tokio::test]
+        async fn should_cancel_waiting_task() {
+            let local = tokio::task::LocalSet::new();
+            local
+                .run_until(async move {
+                    let map = StableBTreeMap::new(VectorMemory::default());
+                    let sequence = StableCell::new(VectorMemory::default(), 0).unwrap();
+                    let mut scheduler = Scheduler::new(map, sequence);
+                    let cancelled = Arc::new(Mutex::new(vec![]));
+                    let cancelled_t = cancelled.clone();
+                    scheduler.on_completion_callback(move |task| {
+                        if let TaskStatus::Cancelled { .. } = task.status {
+                            cancelled_t.lock().push(task.id);
+                        }
+                    });
+
+                    let id = scheduler.append_task(AwaitingTask {}.into());
+
+                    assert!(scheduler.cancel(id));
+                    assert!(!scheduler.cancel(id));
+                    assert!(!scheduler.cancel(42));
+
+                    assert!(scheduler.get_task(id).is_none());
+                    assert_eq!(*cancelled.lock(), vec![id]);
+                    assert_eq!(scheduler.run(()).unwrap(), 0);
+                })
+                .await;
+        }
+
+        #[tokio::test]
+        async fn should_cancel_tasks_by_tag() {
+            let local = tokio::task::LocalSet::new();
+            local
+                .run_until(async move {
+                    let map = StableBTreeMap::new(VectorMemory::default());
+                    let sequence = StableCell::new(VectorMemory::default(), 0).unwrap();
+                    let scheduler = Scheduler::new(map, sequence);
+
+                    let first = scheduler
+                        .append_task((AwaitingTask {}, TaskOptions::new().with_tag("a")).into());
+                    let second = scheduler
+                        .append_task((AwaitingTask {}, TaskOptions::new().with_tag("b")).into());
+                    let third = scheduler
+                        .append_task((AwaitingTask {}, TaskOptions::new().with_tag("a")).into());
+                    let untagged = scheduler.append_task(AwaitingTask {}.into());
+
+                    assert_eq!(scheduler.cancel_by_tag("a"), vec![first, third]);
+                    assert!(scheduler.cancel_by_tag("c").is_empty());
+
+                    assert!(scheduler.get_task(first).is_none());
+                    assert!(scheduler.get_task(second).is_some());
+                    assert!(scheduler.get_task(third).is_none());
+                    assert!(scheduler.get_task(untagged).is_some());
+                })
+                .await;
+        }
+
+        #[tokio::test]
+        async fn should_drop_running_task_after_execution() {
+            let local = tokio::task::LocalSet::new();
+            local
+                .run_until(async move {
+                    let map = StableBTreeMap::new(VectorMemory::default());
+                    let sequence = StableCell::new(VectorMemory::default(), 0).unwrap();
+                    let scheduler = Scheduler::new(map, sequence);
+
+                    let id = scheduler.append_task(
+                        (
+                            AwaitingTask {},
+                            TaskOptions::new()
+                                .with_max_retries_policy(3)
+                                .with_fixed_backoff_policy(0),
+                        )
+                            .into(),
+                    );
+                    scheduler.run(()).unwrap();
+                    tokio::time::sleep(Duration::from_millis(25)).await;
+
+                    assert!(scheduler.cancel(id));
+                    assert!(matches!(
+                        scheduler.get_task(id).unwrap().status,
+                        TaskStatus::Running { .. }
+                    ));
+
+                    COMPLETE.with(|v| v.notify_one());
+                    tokio::time::sleep(Duration::from_millis(25)).await;
+
+                    // The failed execution is not retried
+                    assert!(scheduler.get_task(id).is_none());
+                    assert!(matches!(
+                        scheduler.task_info(id).unwrap().status,
+                        TaskStatus::Cancelled { .. }
+                    ));
+                })
+                .await;
+        }
+    }
+
     mod test_find_id {
         use ic_stable_structures::{StableBTreeMap, StableCell, VectorMemory};
 
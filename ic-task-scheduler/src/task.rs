@@ -92,22 +92,210 @@ impl<T: Task> InnerScheduledTask<T> {
     pub fn id(&self) -> u64 {
         self.id
     }
+
+    /// Returns the execution state of the task
+    pub fn execution_info(&self) -> TaskExecutionInfo {
+        let next_execution_timestamp_secs = match self.status {
+            TaskStatus::Waiting { .. } => Some(self.options.execute_after_timestamp_in_secs),
+            _ => None,
+        };
+
+        TaskExecutionInfo {
+            id: self.id,
+            status: self.status.clone(),
+            failures: self.options.failures,
+            next_execution_timestamp_secs,
+        }
+    }
 }
 
+/// The execution state of a task, without the task payload.
+#[derive(CandidType, Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct TaskExecutionInfo {
+    /// The task id
+    pub id: u64,
+    /// The current status of the task
+    pub status: TaskStatus,
+    /// The number of failed executions of the task
+    pub failures: u32,
+    /// The timestamp in seconds after which the task will be executed, if the task is waiting
+    pub next_execution_timestamp_secs: Option<u64>,
+}
+
+impl TaskExecutionInfo {
+    /// Returns true if the task is waiting to be executed again after a failure
+    pub fn is_retrying(&self) -> bool {
+        matches!(self.status, TaskStatus::Waiting { .. }) && self.failures > 0
+    }
+
+    /// Returns true if the task is not in the scheduler anymore
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self.status,
+            TaskStatus::Completed { .. }
+                | TaskStatus::Failed { .. }
+                | TaskStatus::TimeoutOrPanic { .. }
+                | TaskStatus::Cancelled { .. }
+        )
+    }
+}
+
+/// Value written in place of the task id at the beginning of the versioned task encoding.
+/// Tasks stored before the encoding was versioned start with the task id, which never reaches
+/// this value.
+const VERSIONED_ENCODING_MARKER: u64 = u64::MAX;
+
+/// Current version of the task encoding.
+const ENCODING_VERSION: u8 = 3;
+
+/// Length of the marker and version header of the versioned task encoding.
+const ENCODING_HEADER_LEN: usize = 9;
+
 impl<T: 'static + Task + Serialize + DeserializeOwned> Storable for InnerScheduledTask<T> {
     fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
-        bincode::serialize(self)
+        bincode::serialize(&(VERSIONED_ENCODING_MARKER, ENCODING_VERSION, self))
             .expect("failed to serialize ScheduledTask")
             .into()
     }
 
     fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
-        bincode::deserialize(&bytes).expect("failed to deserialize ScheduledTask")
+        const ERROR: &str = "failed to deserialize ScheduledTask";
+
+        match bincode::deserialize::<(u64, u8)>(&bytes) {
+            Ok((VERSIONED_ENCODING_MARKER, version)) => {
+                let body = &bytes[ENCODING_HEADER_LEN..];
+                match version {
+                    1 => bincode::deserialize::<legacy::InnerScheduledTaskV1<T>>(body)
+                        .expect(ERROR)
+                        .into(),
+                    2 => bincode::deserialize::<legacy::InnerScheduledTaskV2<T>>(body)
+                        .expect(ERROR)
+                        .into(),
+                    _ => bincode::deserialize(body).expect(ERROR),
+                }
+            }
+            _ => bincode::deserialize::<legacy::InnerScheduledTaskV0<T>>(&bytes)
+                .expect(ERROR)
+                .into(),
+        }
     }
 
     const BOUND: Bound = Bound::Unbounded;
 }
 
+/// Layouts of the tasks stored by the previous versions of the scheduler.
+mod legacy {
+    use serde::{Deserialize, Serialize};
+
+    use super::{InnerScheduledTask, Task, TaskOptions, TaskStatus};
+    use crate::retry::RetryStrategy;
+
+    /// Task options stored before the encoding was versioned.
+    #[derive(Serialize, Deserialize)]
+    pub struct TaskOptionsV0 {
+        pub failures: u32,
+        pub execute_after_timestamp_in_secs: u64,
+        pub retry_strategy: RetryStrategy,
+    }
+
+    /// Task stored before the encoding was versioned.
+    #[derive(Serialize, Deserialize)]
+    pub struct InnerScheduledTaskV0<T> {
+        pub id: u64,
+        pub task: T,
+        pub options: TaskOptionsV0,
+        pub status: TaskStatus,
+    }
+
+    impl<T: Task> From<InnerScheduledTaskV0<T>> for InnerScheduledTask<T> {
+        fn from(task: InnerScheduledTaskV0<T>) -> Self {
+            Self {
+                id: task.id,
+                task: task.task,
+                options: TaskOptions {
+                    failures: task.options.failures,
+                    execute_after_timestamp_in_secs: task.options.execute_after_timestamp_in_secs,
+                    retry_strategy: task.options.retry_strategy,
+                    ..Default::default()
+                },
+                status: task.status,
+            }
+        }
+    }
+
+    /// Task options stored by the version 1 of the encoding.
+    #[derive(Serialize, Deserialize)]
+    pub struct TaskOptionsV1 {
+        pub failures: u32,
+        pub execute_after_timestamp_in_secs: u64,
+        pub retry_strategy: RetryStrategy,
+        pub tag: Option<String>,
+    }
+
+    /// Task stored by the version 1 of the encoding.
+    #[derive(Serialize, Deserialize)]
+    pub struct InnerScheduledTaskV1<T> {
+        pub id: u64,
+        pub task: T,
+        pub options: TaskOptionsV1,
+        pub status: TaskStatus,
+    }
+
+    impl<T: Task> From<InnerScheduledTaskV1<T>> for InnerScheduledTask<T> {
+        fn from(task: InnerScheduledTaskV1<T>) -> Self {
+            Self {
+                id: task.id,
+                task: task.task,
+                options: TaskOptions {
+                    failures: task.options.failures,
+                    execute_after_timestamp_in_secs: task.options.execute_after_timestamp_in_secs,
+                    retry_strategy: task.options.retry_strategy,
+                    tag: task.options.tag,
+                    ..Default::default()
+                },
+                status: task.status,
+            }
+        }
+    }
+
+    /// Task options stored by the version 2 of the encoding.
+    #[derive(Serialize, Deserialize)]
+    pub struct TaskOptionsV2 {
+        pub failures: u32,
+        pub execute_after_timestamp_in_secs: u64,
+        pub retry_strategy: RetryStrategy,
+        pub tag: Option<String>,
+        pub execution_timeout_secs: Option<u64>,
+    }
+
+    /// Task stored by the version 2 of the encoding.
+    #[derive(Serialize, Deserialize)]
+    pub struct InnerScheduledTaskV2<T> {
+        pub id: u64,
+        pub task: T,
+        pub options: TaskOptionsV2,
+        pub status: TaskStatus,
+    }
+
+    impl<T: Task> From<InnerScheduledTaskV2<T>> for InnerScheduledTask<T> {
+        fn from(task: InnerScheduledTaskV2<T>) -> Self {
+            Self {
+                id: task.id,
+                task: task.task,
+                options: TaskOptions {
+                    failures: task.options.failures,
+                    execute_after_timestamp_in_secs: task.options.execute_after_timestamp_in_secs,
+                    retry_strategy: task.options.retry_strategy,
+                    tag: task.options.tag,
+                    execution_timeout_secs: task.options.execution_timeout_secs,
+                    ..Default::default()
+                },
+                status: task.status,
+            }
+        }
+    }
+}
+
 /// The status of a task in the scheduler
 #[derive(CandidType, Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
 pub enum TaskStatus {
@@ -126,6 +314,8 @@ pub enum TaskStatus {
     },
     /// The task has been running for long time. It could be stuck or panicking
     TimeoutOrPanic { timestamp_secs: u64 },
+    /// The task was cancelled before its execution completed
+    Cancelled { timestamp_secs: u64 },
 }
 
 impl TaskStatus {
@@ -162,6 +352,11 @@ impl TaskStatus {
         Self::TimeoutOrPanic { timestamp_secs }
     }
 
+    /// Creates a new TaskStatus::Cancelled with the given timestamp in seconds
+    pub fn cancelled(timestamp_secs: u64) -> Self {
+        Self::Cancelled { timestamp_secs }
+    }
+
     /// Returns the timestamp of the status
     pub fn timestamp_secs(&self) -> u64 {
         match self {
@@ -171,6 +366,7 @@ impl TaskStatus {
             TaskStatus::TimeoutOrPanic { timestamp_secs } => *timestamp_secs,
             TaskStatus::Failed { timestamp_secs, .. } => *timestamp_secs,
             TaskStatus::Scheduled { timestamp_secs, .. } => *timestamp_secs,
+            TaskStatus::Cancelled { timestamp_secs } => *timestamp_secs,
         }
     }
 }
@@ -181,6 +377,54 @@ pub struct TaskOptions {
     pub(crate) failures: u32,
     pub(crate) execute_after_timestamp_in_secs: u64,
     pub(crate) retry_strategy: RetryStrategy,
+    pub(crate) tag: Option<String>,
+    pub(crate) execution_timeout_secs: Option<u64>,
+    pub(crate) recurrence: Option<Recurrence>,
+}
+
+/// Recurrence of a task executed at a fixed interval.
+///
+/// The recurrence is stored with the task, so a recurring task keeps being executed after a
+/// canister upgrade if the scheduler uses stable memory storage.
+#[derive(CandidType, Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Recurrence {
+    /// The interval in seconds between the start of an execution and the next one
+    pub interval_secs: u64,
+    /// The number of executions left, including the next one. `None` if the task is executed
+    /// forever.
+    pub remaining_executions: Option<u32>,
+}
+
+impl Recurrence {
+    /// Creates a recurrence executing the task every `interval_secs` seconds forever.
+    pub fn forever(interval_secs: u64) -> Self {
+        Self {
+            interval_secs,
+            remaining_executions: None,
+        }
+    }
+
+    /// Creates a recurrence executing the task every `interval_secs` seconds, `executions` times.
+    /// The task is executed at least once.
+    pub fn times(interval_secs: u64, executions: u32) -> Self {
+        Self {
+            interval_secs,
+            remaining_executions: Some(executions),
+        }
+    }
+
+    /// Returns the recurrence after a successful execution, or `None` if there are no
+    /// executions left.
+    pub(crate) fn next(self) -> Option<Self> {
+        match self.remaining_executions {
+            None => Some(self),
+            Some(remaining) if remaining > 1 => Some(Self {
+                remaining_executions: Some(remaining - 1),
+                ..self
+            }),
+            Some(_) => None,
+        }
+    }
 }
 
 impl TaskOptions {
@@ -220,6 +464,41 @@ impl TaskOptions {
         self.execute_after_timestamp_in_secs = execute_after_timestamp_in_secs;
         self
     }
+
+    /// Set the tag of the task. All the tasks with the same tag can be cancelled at once with
+    /// `Scheduler::cancel_by_tag`. Default is no tag.
+    pub fn with_tag(mut self, tag: impl Into<String>) -> Self {
+        self.tag = Some(tag.into());
+        self
+    }
+
+    /// Returns the tag of the task
+    pub fn tag(&self) -> Option<&str> {
+        self.tag.as_deref()
+    }
+
+    /// Set the max duration of a task execution. If the execution is still in progress after
+    /// `secs` seconds, it is aborted at the next await point and considered failed with
+    /// `SchedulerError::DeadlineExceeded`, so it is retried according to the retry policy.
+    /// Default is no limit.
+    pub fn with_execution_timeout_secs(mut self, secs: u64) -> Self {
+        self.execution_timeout_secs = Some(secs);
+        self
+    }
+
+    /// Set the recurrence of the task. After every successful execution, the task is executed
+    /// again after the recurrence interval, until there are no executions left. A failed
+    /// execution is retried according to the retry policy, and if it fails permanently the
+    /// recurrence stops. Default is no recurrence.
+    pub fn with_recurrence(mut self, recurrence: Recurrence) -> Self {
+        self.recurrence = Some(recurrence);
+        self
+    }
+
+    /// Returns the recurrence of the task
+    pub fn recurrence(&self) -> Option<&Recurrence> {
+        self.recurrence.as_ref()
+    }
 }
 
 #[cfg(test)]
@@ -316,5 +595,137 @@ mod test {
 
             assert_eq!(task, deserialized);
         }
+
+        {
+            let task = InnerScheduledTask {
+                id: 0,
+                task: TestTask {},
+                options: TaskOptions::new()
+                    .with_tag("order-1")
+                    .with_execution_timeout_secs(30)
+                    .with_recurrence(Recurrence::times(60, 10)),
+                status: TaskStatus::Cancelled {
+                    timestamp_secs: 21230,
+                },
+            };
+
+            let serialized = task.to_bytes();
+            let deserialized = InnerScheduledTask::<TestTask>::from_bytes(serialized);
+
+            assert_eq!(task, deserialized);
+        }
+    }
+
+    #[test]
+    fn test_storable_task_before_versioning() {
+        let task = legacy::InnerScheduledTaskV0 {
+            id: 42,
+            task: TestTask {},
+            options: legacy::TaskOptionsV0 {
+                failures: 2,
+                execute_after_timestamp_in_secs: 100,
+                retry_strategy: RetryStrategy::default(),
+            },
+            status: TaskStatus::Waiting { timestamp_secs: 10 },
+        };
+
+        let serialized = bincode::serialize(&task).unwrap();
+        let deserialized = InnerScheduledTask::<TestTask>::from_bytes(serialized.into());
+
+        assert_eq!(
+            deserialized,
+            InnerScheduledTask {
+                id: 42,
+                task: TestTask {},
+                options: TaskOptions {
+                    failures: 2,
+                    execute_after_timestamp_in_secs: 100,
+                    ..Default::default()
+                },
+                status: TaskStatus::Waiting { timestamp_secs: 10 },
+            }
+        );
+    }
+
+    #[test]
+    fn test_storable_task_version_1() {
+        let task = legacy::InnerScheduledTaskV1 {
+            id: 42,
+            task: TestTask {},
+            options: legacy::TaskOptionsV1 {
+                failures: 2,
+                execute_after_timestamp_in_secs: 100,
+                retry_strategy: RetryStrategy::default(),
+                tag: Some("order-1".to_string()),
+            },
+            status: TaskStatus::Waiting { timestamp_secs: 10 },
+        };
+
+        let serialized = bincode::serialize(&(VERSIONED_ENCODING_MARKER, 1u8, task)).unwrap();
+        let deserialized = InnerScheduledTask::<TestTask>::from_bytes(serialized.into());
+
+        assert_eq!(
+            deserialized,
+            InnerScheduledTask {
+                id: 42,
+                task: TestTask {},
+                options: TaskOptions {
+                    failures: 2,
+                    execute_after_timestamp_in_secs: 100,
+                    ..Default::default()
+                }
+                .with_tag("order-1"),
+                status: TaskStatus::Waiting { timestamp_secs: 10 },
+            }
+        );
+    }
+
+    #[test]
+    fn test_storable_task_version_2() {
+        let task = legacy::InnerScheduledTaskV2 {
+            id: 42,
+            task: TestTask {},
+            options: legacy::TaskOptionsV2 {
+                failures: 2,
+                execute_after_timestamp_in_secs: 100,
+                retry_strategy: RetryStrategy::default(),
+                tag: Some("order-1".to_string()),
+                execution_timeout_secs: Some(30),
+            },
+            status: TaskStatus::Waiting { timestamp_secs: 10 },
+        };
+
+        let serialized = bincode::serialize(&(VERSIONED_ENCODING_MARKER, 2u8, task)).unwrap();
+        let deserialized = InnerScheduledTask::<TestTask>::from_bytes(serialized.into());
+
+        assert_eq!(
+            deserialized,
+            InnerScheduledTask {
+                id: 42,
+                task: TestTask {},
+                options: TaskOptions {
+                    failures: 2,
+                    execute_after_timestamp_in_secs: 100,
+                    ..Default::default()
+                }
+                .with_tag("order-1")
+                .with_execution_timeout_secs(30),
+                status: TaskStatus::Waiting { timestamp_secs: 10 },
+            }
+        );
+    }
+
+    #[test]
+    fn test_recurrence_next() {
+        assert_eq!(
+            Recurrence::forever(10).next(),
+            Some(Recurrence::forever(10))
+        );
+        assert_eq!(
+            Recurrence::times(10, 3).next(),
+            Some(Recurrence::times(10, 2))
+        );
+        assert_eq!(Recurrence::times(10, 1).next(), None);
+        assert_eq!(Recurrence::times(10, 0).next(), None);
     }
 }
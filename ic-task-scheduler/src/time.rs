@@ -1,3 +1,20 @@
+/// returns the timestamp in nanoseconds
+#[inline]
+pub fn time_nanos() -> u64 {
+    #[cfg(not(target_family = "wasm"))]
+    {
+        std::time::SystemTime::now()
+            .duration_since(std::time::SystemTime::UNIX_EPOCH)
+            .expect("get current timestamp error")
+            .as_nanos() as u64
+    }
+
+    #[cfg(target_family = "wasm")]
+    {
+        ic_kit::ic::time()
+    }
+}
+
 /// returns the timestamp in seconds
 #[inline]
 pub fn time_secs() -> u64 {
@@ -166,5 +166,6 @@ fn save_state_cb(task: InnerScheduledTask<DummyTask>) {
             });
         }
         TaskStatus::Scheduled { .. } => {}
+        TaskStatus::Cancelled { .. } => {}
     };
 }
@@ -7,7 +7,7 @@ use std::time::Duration;
 
 use candid::{CandidType, Encode, Principal};
 use ic_canister_client::PocketIcClient;
-use ic_exports::pocket_ic::{init_pocket_ic, PocketIc};
+use ic_exports::pocket_ic::{advance_time_and_tick, init_pocket_ic, PocketIc};
 use ic_kit::mock_principals::alice;
 use ic_task_scheduler::scheduler::TaskScheduler;
 use ic_task_scheduler::task::{InnerScheduledTask, Task};
@@ -62,10 +62,7 @@ impl PocketIcTestContext {
     }
 
     pub async fn run_scheduler(&self) {
-        self.client()
-            .advance_time(Duration::from_millis(5000))
-            .await;
-        self.client().tick().await;
+        advance_time_and_tick(self.client(), Duration::from_millis(5000)).await;
     }
 }
 